@@ -23,7 +23,7 @@ class CodeExecutionResponse(BaseModel):
     output: str
     error: str
     execution_time: float
-    status: str  # "success", "error", "timeout"
+    status: str  # "success", "compile_error", "runtime_error", "timeout", ...
 
 class CodeValidationRequest(BaseModel):
     code: str
@@ -392,7 +392,8 @@ class AssignmentService:
         execution_stats = {
             "total": len(submissions),
             "success": len([s for s in submissions if s.execution_status == "success"]),
-            "error": len([s for s in submissions if s.execution_status == "error"]),
+            # compile_error, runtime_error, memory_limit, ... all count as errors
+            "error": len([s for s in submissions if s.execution_status not in ("success", "timeout", "pending")]),
             "timeout": len([s for s in submissions if s.execution_status == "timeout"]),
             "pending": len([s for s in submissions if s.execution_status == "pending"])
         }
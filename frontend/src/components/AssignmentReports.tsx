@@ -200,7 +200,7 @@ export default function AssignmentReports() {
       case 'success':
         return submission.execution_status === 'success';
       case 'error':
-        return submission.execution_status === 'error';
+        return !['success', 'timeout', 'pending'].includes(submission.execution_status);
       case 'timeout':
         return submission.execution_status === 'timeout';
       default:
@@ -0,0 +1,10 @@
+// Generates the gRPC service from proto/executor.proto. protox compiles the
+// proto in-process, so building does not need protoc installed.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/executor.proto");
+    let descriptors = protox::compile(["proto/executor.proto"], ["proto"])?;
+    tonic_build::configure()
+        .build_client(false)
+        .compile_fds(descriptors)?;
+    Ok(())
+}
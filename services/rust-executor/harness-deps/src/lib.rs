@@ -0,0 +1 @@
+// Placeholder so the dependency list above can be vendored
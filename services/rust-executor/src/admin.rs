@@ -0,0 +1,236 @@
+use crate::RustExecutor;
+use warp::http::StatusCode;
+use warp::reply::{Json, WithStatus};
+
+fn admin_error(message: &str, status: StatusCode) -> WithStatus<Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": message })),
+        status,
+    )
+}
+
+// Compares without short-circuiting so response timing says nothing about
+// how much of the token matched
+pub(crate) fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub enum AdminAuthError {
+    // ADMIN_TOKEN is not set
+    Disabled,
+    Invalid,
+}
+
+impl AdminAuthError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            AdminAuthError::Disabled => "Admin endpoints are disabled (ADMIN_TOKEN is not set)",
+            AdminAuthError::Invalid => "Invalid or missing admin token",
+        }
+    }
+}
+
+impl RustExecutor {
+    // Checks an `Authorization: Bearer <ADMIN_TOKEN>` value, from a header
+    // or gRPC metadata
+    pub fn check_admin(&self, authorization: Option<&str>) -> Result<(), AdminAuthError> {
+        let Some(expected) = self.admin_token.as_deref() else {
+            return Err(AdminAuthError::Disabled);
+        };
+        let provided = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or("");
+        if tokens_match(expected, provided.trim()) {
+            Ok(())
+        } else {
+            Err(AdminAuthError::Invalid)
+        }
+    }
+
+    // Checks the `Authorization: Bearer <ADMIN_TOKEN>` header of an admin
+    // request, returning the reply to send when it is not allowed
+    pub fn authorize_admin(&self, authorization: Option<&str>) -> Result<(), WithStatus<Json>> {
+        self.check_admin(authorization).map_err(|e| {
+            let status = match e {
+                AdminAuthError::Disabled => StatusCode::NOT_FOUND,
+                AdminAuthError::Invalid => StatusCode::UNAUTHORIZED,
+            };
+            admin_error(e.message(), status)
+        })
+    }
+
+    // The requester's role, which only a request carrying the admin token
+    // can claim: the backend vouches for its user's X-Requester-Role, or
+    // acts as "admin" itself when it names none. Anyone can send the header,
+    // so without the token it is ignored.
+    pub fn authenticated_role(
+        &self,
+        authorization: Option<&str>,
+        role: Option<&str>,
+    ) -> Option<String> {
+        self.check_admin(authorization).ok()?;
+        Some(role.unwrap_or("admin").to_string())
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    params(("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")),
+    responses(
+        (status = 200, description = "Configuration reloaded; the body has the new limits"),
+        (status = 400, description = "Config failed to load; current limits kept", body = ErrorResponse),
+        (status = 401, body = ErrorResponse)
+    )
+)]
+pub async fn reload_config(
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let reply = match executor.reload_config() {
+        Ok(config) => {
+            println!("Configuration reloaded");
+            warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "reloaded": true, "config": *config })),
+                StatusCode::OK,
+            )
+        }
+        Err(e) => admin_error(&e, StatusCode::BAD_REQUEST),
+    };
+    Ok(reply)
+}
+
+// Reloads the configuration whenever the process receives SIGHUP
+pub async fn reload_on_sighup(executor: RustExecutor) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            eprintln!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        match executor.reload_config() {
+            Ok(_) => println!("Configuration reloaded"),
+            Err(e) => eprintln!("Configuration reload failed, keeping current limits: {}", e),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/tenants/{id}/usage",
+    params(
+        ("id" = String, Path, description = "Tenant id as sent in X-Tenant-Id"),
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, description = "Usage and limits for the current UTC day and month"),
+        (status = 401, body = ErrorResponse)
+    )
+)]
+pub async fn tenant_usage(
+    tenant_id: String,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    Ok(warp::reply::with_status(
+        warp::reply::json(&executor.tenant_usage(&tenant_id)),
+        StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/executions",
+    params(("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")),
+    responses(
+        (status = 200, description = "Executions currently compiling or running, oldest first"),
+        (status = 401, body = ErrorResponse)
+    )
+)]
+pub async fn running_executions(
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "executions": executor.running_executions(),
+        })),
+        StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/executions/{id}/kill",
+    params(
+        ("id" = u64, Path, description = "Execution id from GET /admin/executions"),
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, description = "Execution killed; it finishes with status \"killed\""),
+        (status = 401, body = ErrorResponse),
+        (status = 404, description = "No running execution has this id", body = ErrorResponse)
+    )
+)]
+pub async fn kill_execution(
+    id: u64,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    if !executor.kill_execution(id) {
+        return Ok(admin_error(
+            &format!("No running execution with id {}", id),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    println!("Execution {} killed by an administrator", id);
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "killed": [id] })),
+        StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/kill-all",
+    params(("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")),
+    responses(
+        (status = 200, description = "Every running execution killed; the body lists their ids"),
+        (status = 401, body = ErrorResponse)
+    )
+)]
+pub async fn kill_all_executions(
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let killed = executor.kill_all_executions();
+    println!("{} executions killed by an administrator", killed.len());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "killed": killed })),
+        StatusCode::OK,
+    ))
+}
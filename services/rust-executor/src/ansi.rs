@@ -0,0 +1,59 @@
+// What happens to ANSI escape sequences (colors, cursor movement) in the
+// program's output
+#[derive(Clone, Copy, Default)]
+pub enum AnsiMode {
+    // Removed, for frontends that show plain text
+    #[default]
+    Strip,
+    // Returned as written, for frontends that render them
+    Preserve,
+}
+
+impl AnsiMode {
+    pub fn parse(mode: Option<&str>) -> Result<Self, String> {
+        match mode {
+            None | Some("strip") => Ok(AnsiMode::Strip),
+            Some("preserve") => Ok(AnsiMode::Preserve),
+            Some(other) => Err(format!("Unsupported ansi mode '{}'", other)),
+        }
+    }
+}
+
+// Removes CSI sequences (ESC [ ... final byte), OSC sequences (ESC ] ...
+// terminated by BEL or ESC \) and two-character escapes
+pub fn strip(text: &str) -> String {
+    if !text.contains('\u{1b}') {
+        return text.to_string();
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // Parameter and intermediate bytes, then one final byte
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
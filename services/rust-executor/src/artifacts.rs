@@ -0,0 +1,705 @@
+use crate::error_reporting::{self, ErrorContext};
+use crate::readiness::{ActiveExecution, KILLED_MESSAGE};
+use crate::reaper::temp_dir;
+use crate::status::StatusError;
+use crate::storage::ObjectStorage;
+use crate::{
+    check_cpu_time_limit, decode_input, history, parse_output_encoding, ChildSetup,
+    CodeExecutionRequest, CodeExecutionResponse, ExecutionStatus, RunLimits, RustExecutor,
+};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tempfile::TempDir;
+use utoipa::ToSchema;
+
+const DEFAULT_ARTIFACT_TTL_SECONDS: u64 = 3600;
+const DEFAULT_MAX_ARTIFACTS: usize = 100;
+const DEFAULT_OUTPUT_THRESHOLD_KB: usize = 256;
+const DEFAULT_ARTIFACT_CACHE_MAX_MB: u64 = 1024;
+
+// A compiled submission kept for later runs. Only the binary is kept; the
+// project and its target directory are removed once it is copied out.
+pub struct Artifact {
+    _dir: TempDir,
+    executable: PathBuf,
+    // Environment granted by the compile request, used unless a run
+    // supplies its own
+    env: Vec<(String, String)>,
+    // Limits of the compile request, used unless a run asks for its own
+    timeout_seconds: u64,
+    cpu_time_limit: Option<u64>,
+    // X-Requester-Id of the compile request, for DELETE /users/{id}/data
+    owner: Option<String>,
+    created: Instant,
+}
+
+// Stored next to the binary as artifacts/{id}/meta.json. It is written
+// last, so an artifact without it was never completely uploaded.
+#[derive(Serialize, Deserialize)]
+struct ArtifactMetadata {
+    env: Vec<(String, String)>,
+    #[serde(rename = "timeoutSeconds")]
+    timeout_seconds: u64,
+    #[serde(rename = "cpuTimeLimit", default)]
+    cpu_time_limit: Option<u64>,
+    #[serde(rename = "requesterId", default)]
+    requester_id: Option<String>,
+    // Unix seconds; the TTL counts from here on every instance
+    #[serde(rename = "createdAt")]
+    created_at: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+// Artifacts a requester compiled are listed under owners/{hash}/{artifact id}
+// so they can be found without reading every artifact's metadata; the id is
+// hashed to keep it out of object keys
+fn owner_prefix(requester_id: &str) -> String {
+    let hash: String = Sha256::digest(requester_id.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("owners/{}/", hash)
+}
+
+fn random_id(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// An artifact in the store with what eviction needs to know
+struct CacheEntry {
+    artifact: Arc<Artifact>,
+    // Of the binary, the only file kept
+    size: u64,
+    last_used: Instant,
+}
+
+// Hit and miss counts of lookups in the store, and what it holds, for
+// /metrics
+#[derive(Default)]
+pub struct ArtifactCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+// Binaries built by /compile, keyed by artifact id. Entries expire after
+// ARTIFACT_TTL_SECONDS. Beyond MAX_ARTIFACTS, or when the binaries would
+// take more than ARTIFACT_CACHE_MAX_MB of disk, the least recently run is
+// dropped.
+//
+// With S3_BUCKET set, binaries are also uploaded to object storage, so an
+// artifact survives restarts and can be run on any instance sharing the
+// bucket; the garbage collector deletes it there once it expires. Run
+// output above S3_OUTPUT_THRESHOLD_KB is uploaded too and returned as a
+// link.
+pub struct ArtifactStore {
+    ttl: Duration,
+    max_artifacts: usize,
+    max_bytes: u64,
+    artifacts: Mutex<HashMap<String, CacheEntry>>,
+    storage: Option<ObjectStorage>,
+    output_threshold_bytes: usize,
+    // Lookups answered from memory, and those that were not (loaded from
+    // object storage or not found)
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ArtifactStore {
+    pub fn new() -> Self {
+        let ttl_seconds = env::var("ARTIFACT_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_ARTIFACT_TTL_SECONDS);
+        let max_artifacts = env::var("MAX_ARTIFACTS")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .filter(|&max| max > 0)
+            .unwrap_or(DEFAULT_MAX_ARTIFACTS);
+        let max_mb = env::var("ARTIFACT_CACHE_MAX_MB")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .filter(|&max| max > 0)
+            .unwrap_or(DEFAULT_ARTIFACT_CACHE_MAX_MB);
+        let storage = ObjectStorage::from_env().unwrap_or_else(|e| {
+            eprintln!("Artifact storage disabled: {}", e);
+            None
+        });
+        let output_threshold_kb = env::var("S3_OUTPUT_THRESHOLD_KB")
+            .ok()
+            .and_then(|kb| kb.parse().ok())
+            .unwrap_or(DEFAULT_OUTPUT_THRESHOLD_KB);
+        Self {
+            ttl: Duration::from_secs(ttl_seconds),
+            max_artifacts,
+            max_bytes: max_mb * 1024 * 1024,
+            artifacts: Mutex::new(HashMap::new()),
+            storage,
+            output_threshold_bytes: output_threshold_kb * 1024,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn has_object_storage(&self) -> bool {
+        self.storage.is_some()
+    }
+
+    pub fn stats(&self) -> ArtifactCacheStats {
+        let artifacts = self.artifacts.lock().unwrap();
+        ArtifactCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: artifacts.len() as u64,
+            bytes: artifacts.values().map(|entry| entry.size).sum(),
+        }
+    }
+
+    fn insert(&self, artifact_id: String, artifact: Artifact) -> Arc<Artifact> {
+        let size = fs::metadata(&artifact.executable).map_or(0, |metadata| metadata.len());
+        let artifact = Arc::new(artifact);
+        let mut artifacts = self.artifacts.lock().unwrap();
+        artifacts.retain(|_, entry| entry.artifact.created.elapsed() < self.ttl);
+        // A binary larger than the whole budget is still kept, alone
+        let mut bytes: u64 = artifacts.values().map(|entry| entry.size).sum();
+        while artifacts.len() >= self.max_artifacts || bytes + size > self.max_bytes {
+            let least_recently_used = artifacts
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone());
+            let Some(evicted) = least_recently_used.and_then(|id| artifacts.remove(&id)) else {
+                break;
+            };
+            bytes -= evicted.size;
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        artifacts.insert(
+            artifact_id,
+            CacheEntry {
+                artifact: artifact.clone(),
+                size,
+                last_used: Instant::now(),
+            },
+        );
+        artifact
+    }
+
+    // Runs hold their own reference, so an artifact that expires or is
+    // evicted mid-run is only deleted once the run finishes
+    async fn get(&self, artifact_id: &str) -> Option<Arc<Artifact>> {
+        {
+            let mut artifacts = self.artifacts.lock().unwrap();
+            artifacts.retain(|_, entry| entry.artifact.created.elapsed() < self.ttl);
+            if let Some(entry) = artifacts.get_mut(artifact_id) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                entry.last_used = Instant::now();
+                return Some(entry.artifact.clone());
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // Ids are alphanumeric; anything else is not ours and must not end
+        // up in an object key
+        let storage = self.storage.as_ref()?;
+        if !artifact_id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        match self.download(storage, artifact_id).await {
+            Ok(artifact) => Some(self.insert(artifact_id.to_string(), artifact?)),
+            Err(e) => {
+                eprintln!("Failed to download artifact {}: {}", artifact_id, e);
+                None
+            }
+        }
+    }
+
+    async fn upload(&self, artifact_id: &str, artifact: &Artifact) -> Result<(), String> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        let executable = fs::read(&artifact.executable)
+            .map_err(|e| format!("Failed to read artifact: {}", e))?;
+        storage
+            .put(&format!("artifacts/{}/main", artifact_id), &executable)
+            .await?;
+        let metadata = ArtifactMetadata {
+            env: artifact.env.clone(),
+            timeout_seconds: artifact.timeout_seconds,
+            cpu_time_limit: artifact.cpu_time_limit,
+            requester_id: artifact.owner.clone(),
+            created_at: unix_now(),
+        };
+        let metadata = serde_json::to_vec(&metadata).map_err(|e| e.to_string())?;
+        if let Some(owner) = &artifact.owner {
+            storage
+                .put(&format!("{}{}", owner_prefix(owner), artifact_id), &[])
+                .await?;
+        }
+        storage
+            .put(&format!("artifacts/{}/meta.json", artifact_id), &metadata)
+            .await
+    }
+
+    // Removes every artifact the requester compiled, with its run output,
+    // here and in object storage. Returns how many there were. Copies other
+    // instances loaded from storage stay in their memory until they expire,
+    // but can no longer be loaded anywhere else.
+    pub async fn delete_owned_by(&self, requester_id: &str) -> Result<usize, String> {
+        let mut deleted: Vec<String> = {
+            let mut artifacts = self.artifacts.lock().unwrap();
+            let owned: Vec<String> = artifacts
+                .iter()
+                .filter(|(_, entry)| entry.artifact.owner.as_deref() == Some(requester_id))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in &owned {
+                artifacts.remove(id);
+            }
+            owned
+        };
+        if let Some(storage) = &self.storage {
+            let prefix = owner_prefix(requester_id);
+            for marker in storage.list(&prefix).await? {
+                let artifact_id = marker.trim_start_matches(&prefix).to_string();
+                for key in storage.list(&format!("artifacts/{}/", artifact_id)).await? {
+                    storage.delete(&key).await?;
+                }
+                storage.delete(&marker).await?;
+                if !deleted.contains(&artifact_id) {
+                    deleted.push(artifact_id);
+                }
+            }
+        }
+        Ok(deleted.len())
+    }
+
+    // Ok(None) when the artifact was never uploaded or has expired
+    async fn download(
+        &self,
+        storage: &ObjectStorage,
+        artifact_id: &str,
+    ) -> Result<Option<Artifact>, String> {
+        let Some(metadata) = storage
+            .get(&format!("artifacts/{}/meta.json", artifact_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let metadata: ArtifactMetadata = serde_json::from_slice(&metadata)
+            .map_err(|e| format!("Invalid artifact metadata: {}", e))?;
+        let age = Duration::from_secs(unix_now().saturating_sub(metadata.created_at));
+        if age >= self.ttl {
+            return Ok(None);
+        }
+        let Some(executable) = storage
+            .get(&format!("artifacts/{}/main", artifact_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let dir = temp_dir().map_err(|e| format!("Failed to store artifact: {}", e))?;
+        let path = dir.path().join("main");
+        fs::write(&path, executable)
+            .and_then(|_| fs::set_permissions(&path, fs::Permissions::from_mode(0o755)))
+            .map_err(|e| format!("Failed to store artifact: {}", e))?;
+        Ok(Some(Artifact {
+            _dir: dir,
+            executable: path,
+            env: metadata.env,
+            timeout_seconds: metadata.timeout_seconds,
+            cpu_time_limit: metadata.cpu_time_limit,
+            owner: metadata.requester_id,
+            created: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+        }))
+    }
+
+    // Uploads output above the threshold and returns a link to it, valid
+    // for as long as the artifact. None when it fits inline or the upload
+    // failed, in which case the output is returned in full.
+    async fn upload_output(&self, artifact_id: &str, output: &[u8]) -> Option<String> {
+        let storage = self.storage.as_ref()?;
+        if output.len() <= self.output_threshold_bytes {
+            return None;
+        }
+        let key = format!("artifacts/{}/outputs/{}", artifact_id, random_id(16));
+        let uploaded = match storage.put(&key, output).await {
+            Ok(()) => storage.presigned_url(&key, self.ttl).await,
+            Err(e) => Err(e),
+        };
+        uploaded
+            .map_err(|e| eprintln!("Failed to upload output of {}: {}", artifact_id, e))
+            .ok()
+    }
+
+    // Drops expired artifacts from memory; their binaries are deleted once
+    // no run holds them. Returns how many there were and their binaries'
+    // size.
+    pub fn remove_expired(&self) -> (u64, u64) {
+        let mut artifacts = self.artifacts.lock().unwrap();
+        let expired: Vec<String> = artifacts
+            .iter()
+            .filter(|(_, entry)| entry.artifact.created.elapsed() >= self.ttl)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut bytes = 0;
+        for id in &expired {
+            if let Some(entry) = artifacts.remove(id) {
+                bytes += entry.size;
+            }
+        }
+        (expired.len() as u64, bytes)
+    }
+
+    // Deletes expired artifacts from object storage with their run output
+    // and owner marker. Artifacts without metadata are mid-upload or failed
+    // to upload and are left alone. Returns how many were deleted and the
+    // size of their objects.
+    pub async fn remove_expired_from_storage(&self) -> Result<(u64, u64), String> {
+        let Some(storage) = &self.storage else {
+            return Ok((0, 0));
+        };
+        let mut objects: HashMap<String, Vec<(String, u64)>> = HashMap::new();
+        for (key, size) in storage.list_sizes("artifacts/").await? {
+            let Some(artifact_id) = key
+                .strip_prefix("artifacts/")
+                .and_then(|rest| rest.split('/').next())
+            else {
+                continue;
+            };
+            objects
+                .entry(artifact_id.to_string())
+                .or_default()
+                .push((key, size));
+        }
+
+        let (mut deleted, mut bytes) = (0, 0);
+        for (artifact_id, mut keys) in objects {
+            let Some(metadata) = storage
+                .get(&format!("artifacts/{}/meta.json", artifact_id))
+                .await?
+            else {
+                continue;
+            };
+            let Ok(metadata) = serde_json::from_slice::<ArtifactMetadata>(&metadata) else {
+                continue;
+            };
+            if unix_now().saturating_sub(metadata.created_at) < self.ttl.as_secs() {
+                continue;
+            }
+            if let Some(owner) = &metadata.requester_id {
+                storage
+                    .delete(&format!("{}{}", owner_prefix(owner), artifact_id))
+                    .await?;
+            }
+            // meta.json last, so a sweep cut short is finished by the next
+            keys.sort_by_key(|(key, _)| key.ends_with("/meta.json"));
+            for (key, size) in keys {
+                storage.delete(&key).await?;
+                bytes += size;
+            }
+            deleted += 1;
+        }
+        Ok((deleted, bytes))
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RunArtifactRequest {
+    #[serde(rename = "inputData")]
+    input_data: Option<String>,
+    // "utf8" (default) or "base64"
+    #[serde(rename = "inputEncoding")]
+    input_encoding: Option<String>,
+    // Command-line arguments for the program
+    args: Option<Vec<String>>,
+    // Seconds; defaults to the timeout the artifact was compiled with
+    timeout: Option<u64>,
+    // CPU seconds; defaults to the limit the artifact was compiled with
+    #[serde(rename = "cpuTimeLimit")]
+    cpu_time_limit: Option<u64>,
+    // Replaces the environment given at compile time; filtered through the
+    // allowlist
+    env: Option<HashMap<String, String>>,
+    // "utf8" (default, lossy), "base64", or "auto"
+    #[serde(rename = "outputEncoding")]
+    output_encoding: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompileResponse {
+    // Set when compilation succeeded; pass it to /run/{artifactId}
+    #[serde(rename = "artifactId")]
+    artifact_id: Option<String>,
+    // Seconds until the artifact is discarded
+    #[serde(rename = "expiresIn")]
+    expires_in: Option<u64>,
+    // Compiler diagnostics when compilation failed
+    error: String,
+    #[serde(rename = "compileTimeMs")]
+    compile_time_ms: Option<f64>,
+    status: ExecutionStatus,
+}
+
+impl CompileResponse {
+    fn error(message: String, status: ExecutionStatus) -> Self {
+        Self {
+            artifact_id: None,
+            expires_in: None,
+            error: message,
+            compile_time_ms: None,
+            status,
+        }
+    }
+}
+
+impl RustExecutor {
+    // Builds a submission the same way /execute does and keeps the binary
+    pub async fn compile_artifact(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+    ) -> CompileResponse {
+        let active = match self.start_execution("compile", requester_id) {
+            Ok(active) => active,
+            Err(e) => return CompileResponse::error(e.message, e.status),
+        };
+        let code_hash = history::code_hash(&req);
+        let response = self.build_artifact(req, requester_id, &active).await;
+        if response.status == ExecutionStatus::InternalError {
+            let context = ErrorContext {
+                kind: "compile",
+                execution_id: active.id(),
+                phase: active.phase(),
+                code_hash: &code_hash,
+                requester_id,
+            };
+            error_reporting::report_internal_error(&response.error, &context);
+        }
+        response
+    }
+
+    async fn build_artifact(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+        active: &ActiveExecution,
+    ) -> CompileResponse {
+        let prepared = match self.prepare_execution(req) {
+            Ok(prepared) => prepared,
+            Err(e) => return CompileResponse::error(e.message, e.status),
+        };
+
+        active.set_phase("compiling");
+        let compile_start = Instant::now();
+        let compiled = active.until_killed(self.compile(prepared.temp_dir.path(), prepared.build));
+        let executable = match compiled.await {
+            Some(Ok(path)) => path,
+            Some(Err(outcome)) => {
+                return CompileResponse {
+                    compile_time_ms: Some(compile_start.elapsed().as_secs_f64() * 1000.0),
+                    ..CompileResponse::error(outcome.stderr, outcome.status)
+                };
+            }
+            None => {
+                return CompileResponse::error(KILLED_MESSAGE.to_string(), ExecutionStatus::Killed)
+            }
+        };
+        let compile_time_ms = compile_start.elapsed().as_secs_f64() * 1000.0;
+
+        let stored = temp_dir().and_then(|dir| {
+            let stored_executable = dir.path().join("main");
+            fs::copy(&executable, &stored_executable)?;
+            Ok((dir, stored_executable))
+        });
+        let (dir, executable) = match stored {
+            Ok(stored) => stored,
+            Err(e) => {
+                return CompileResponse::error(
+                    format!("Failed to store artifact: {}", e),
+                    ExecutionStatus::InternalError,
+                )
+            }
+        };
+
+        let artifact_id = random_id(24);
+        let artifact = self.artifacts.insert(
+            artifact_id.clone(),
+            Artifact {
+                _dir: dir,
+                executable,
+                env: prepared.env,
+                timeout_seconds: prepared.timeout_seconds,
+                cpu_time_limit: prepared.cpu_time_limit,
+                owner: requester_id.map(str::to_string),
+                created: Instant::now(),
+            },
+        );
+        // The artifact still works on this instance if the upload fails
+        if let Err(e) = self.artifacts.upload(&artifact_id, &artifact).await {
+            eprintln!("Failed to upload artifact {}: {}", artifact_id, e);
+        }
+        CompileResponse {
+            artifact_id: Some(artifact_id),
+            expires_in: Some(self.artifacts.ttl().as_secs()),
+            error: String::new(),
+            compile_time_ms: Some(compile_time_ms),
+            status: ExecutionStatus::Success,
+        }
+    }
+
+    // Runs a stored binary; None when the artifact does not exist or expired
+    pub async fn run_artifact(
+        &self,
+        artifact_id: &str,
+        req: RunArtifactRequest,
+    ) -> Option<CodeExecutionResponse> {
+        let artifact = self.artifacts.get(artifact_id).await?;
+        let start_time = Instant::now();
+        let active = match self.start_execution("run", None) {
+            Ok(active) => active,
+            Err(e) => {
+                return Some(CodeExecutionResponse::error(
+                    e.status,
+                    e.message,
+                    start_time.elapsed().as_secs_f64(),
+                ))
+            }
+        };
+        let config = self.config();
+
+        let prepared = decode_input(req.input_data, req.input_encoding.as_deref())
+            .map_err(StatusError::from)
+            .and_then(|input| {
+                let output_encoding = parse_output_encoding(req.output_encoding)?;
+                check_cpu_time_limit(&config, req.cpu_time_limit)?;
+                let env = match &req.env {
+                    Some(requested_env) => Self::allowed_child_env(&config, Some(requested_env))
+                        .map_err(|e| StatusError::new(ExecutionStatus::SecurityViolation, e))?,
+                    None => artifact.env.clone(),
+                };
+                Ok((input, output_encoding, env))
+            });
+        let (input, output_encoding, env) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                return Some(CodeExecutionResponse::rejected(
+                    e,
+                    start_time.elapsed().as_secs_f64(),
+                ))
+            }
+        };
+        let limits = RunLimits {
+            wall_seconds: req
+                .timeout
+                .filter(|&t| t <= config.max_request_timeout)
+                .unwrap_or(artifact.timeout_seconds),
+            cpu_seconds: req.cpu_time_limit.or(artifact.cpu_time_limit),
+            deadline: None,
+        };
+
+        let mut outcome = self
+            .run_executable(
+                &artifact.executable,
+                ChildSetup::default(),
+                &req.args.unwrap_or_default(),
+                input.as_deref(),
+                limits,
+                &env,
+                &active,
+            )
+            .await;
+
+        let output_url = self
+            .artifacts
+            .upload_output(artifact_id, &outcome.stdout)
+            .await;
+        if output_url.is_some() {
+            outcome
+                .stdout
+                .truncate(self.artifacts.output_threshold_bytes);
+        }
+        let mut response = CodeExecutionResponse {
+            output_url,
+            toolchain_version: self.toolchain.project_rustc.clone(),
+            ..outcome.into_response(&output_encoding, start_time.elapsed().as_secs_f64())
+        };
+        response.limit_lines(config.max_output_lines);
+        Some(response)
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/compile",
+    request_body = CodeExecutionRequest,
+    params(
+        ("X-Requester-Id" = Option<String>, Header, description = "Owner of the artifact, whose data DELETE /users/{id}/data removes")
+    ),
+    responses((status = 200, description = "Build result; artifactId is set on success", body = CompileResponse))
+)]
+pub async fn compile(
+    req: CodeExecutionRequest,
+    requester_id: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(
+        &executor
+            .compile_artifact(req, requester_id.as_deref())
+            .await,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/run/{artifactId}",
+    request_body = RunArtifactRequest,
+    params(("artifactId" = String, Path, description = "Artifact id returned by /compile")),
+    responses(
+        (status = 200, body = CodeExecutionResponse),
+        (status = 404, description = "Unknown or expired artifact", body = ErrorResponse)
+    )
+)]
+pub async fn run(
+    artifact_id: String,
+    req: RunArtifactRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = match executor.run_artifact(&artifact_id, req).await {
+        Some(result) => {
+            warp::reply::with_status(warp::reply::json(&result), warp::http::StatusCode::OK)
+        }
+        None => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "Artifact not found or expired" })),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
+    };
+    Ok(reply)
+}
@@ -0,0 +1,269 @@
+use crate::judge::{JudgeRequest, JudgeResponse, OutputComparison, TestCase, TestGroup};
+use crate::{CodeExecutionRequest, ExecutionStatus, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+// Share of a limit a test of the reference solution may use before it is
+// flagged: students' solutions are rarely faster than the reference
+const LIMIT_HEADROOM: f64 = 0.5;
+
+#[derive(Default, Deserialize, ToSchema)]
+pub struct AssignmentLimits {
+    // Seconds per test run, as `timeout` in /execute
+    timeout: Option<u64>,
+    #[serde(rename = "cpuTimeLimit")]
+    cpu_time_limit: Option<u64>,
+    #[serde(rename = "compileTimeout")]
+    compile_timeout: Option<u64>,
+}
+
+// An assignment as it will be judged: what students' code is wrapped in,
+// a solution known to be correct, and the tests and limits it is graded
+// with
+#[derive(Deserialize, ToSchema)]
+pub struct AssignmentBundle {
+    // The harness, with the same placeholder as `template` in /execute
+    template: Option<String>,
+    #[serde(rename = "referenceSolution")]
+    reference_solution: String,
+    tests: Vec<TestCase>,
+    #[serde(default)]
+    groups: Vec<TestGroup>,
+    #[serde(default)]
+    limits: AssignmentLimits,
+    // As in /judge
+    comparison: Option<OutputComparison>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DryRunProblem {
+    // "error": students would be graded wrongly; "warning": likely a mistake
+    severity: &'static str,
+    // The test concerned, if any
+    test: Option<String>,
+    message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DryRunResponse {
+    // No errors among the problems
+    valid: bool,
+    problems: Vec<DryRunProblem>,
+    // The reference solution judged against the tests, hidden ones shown,
+    // with per-test timings
+    judge: JudgeResponse,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+}
+
+fn error(test: Option<&str>, message: String) -> DryRunProblem {
+    DryRunProblem {
+        severity: "error",
+        test: test.map(str::to_string),
+        message,
+    }
+}
+
+fn warning(test: Option<&str>, message: String) -> DryRunProblem {
+    DryRunProblem {
+        severity: "warning",
+        test: test.map(str::to_string),
+        message,
+    }
+}
+
+// What can be told from the bundle alone. Tests are named as the judge
+// names them.
+fn check_bundle(bundle: &AssignmentBundle, max_request_timeout: u64) -> Vec<DryRunProblem> {
+    let mut problems = Vec::new();
+    if let Some(timeout) = bundle
+        .limits
+        .timeout
+        .filter(|&timeout| timeout > max_request_timeout)
+    {
+        problems.push(error(
+            None,
+            format!(
+                "timeout {}s exceeds the {}s maximum; runs would use the default instead",
+                timeout, max_request_timeout
+            ),
+        ));
+    }
+    let names: Vec<String> = bundle
+        .tests
+        .iter()
+        .enumerate()
+        .map(|(index, test)| {
+            test.name
+                .clone()
+                .unwrap_or_else(|| format!("test {}", index + 1))
+        })
+        .collect();
+    let mut seen_names = HashSet::new();
+    let mut seen_inputs: HashMap<&str, &str> = HashMap::new();
+    for (test, name) in bundle.tests.iter().zip(&names) {
+        if !seen_names.insert(name.as_str()) {
+            problems.push(warning(
+                Some(name),
+                "Another test has the same name".to_string(),
+            ));
+        }
+        match seen_inputs.get(test.input.as_str()) {
+            Some(first) => problems.push(warning(Some(name), format!("Same input as '{}'", first))),
+            None => {
+                seen_inputs.insert(&test.input, name);
+            }
+        }
+        match test.expected_output.as_deref() {
+            // The bundle's referenceSolution is what is judged, so nothing
+            // provides it
+            None => problems.push(error(
+                Some(name),
+                "No expectedOutput, so submissions cannot be judged on this test".to_string(),
+            )),
+            Some(expected) if expected.trim().is_empty() => {
+                problems.push(warning(Some(name), "expectedOutput is empty".to_string()))
+            }
+            Some(_) => {}
+        }
+    }
+    for group in &bundle.groups {
+        if !bundle
+            .tests
+            .iter()
+            .any(|test| test.group.as_deref() == Some(group.name.as_str()))
+        {
+            problems.push(warning(
+                None,
+                format!("Group '{}' has no tests", group.name),
+            ));
+        }
+    }
+    if bundle.tests.iter().all(|test| test.hidden == Some(true)) {
+        problems.push(warning(
+            None,
+            "Every test is hidden; students see no sample".to_string(),
+        ));
+    }
+    problems
+}
+
+impl RustExecutor {
+    // Judges the reference solution as students' submissions will be, and
+    // reports whatever in the bundle would grade them wrongly: tests the
+    // reference fails, tests close to their limits, limits the service
+    // would not honour and tests that look like copy-paste mistakes
+    pub async fn dry_run_assignment(&self, mut bundle: AssignmentBundle) -> DryRunResponse {
+        let start_time = Instant::now();
+        let config = self.config();
+        let mut problems = check_bundle(&bundle, config.max_request_timeout);
+        let timeout = bundle
+            .limits
+            .timeout
+            .filter(|&timeout| timeout <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+        let cpu_time_limit = bundle.limits.cpu_time_limit;
+
+        // The professor sees every test's output. Tests without an
+        // expectedOutput are reported above and would stop the judge from
+        // checking the others; the rest keep the names they were reported
+        // under.
+        for (index, test) in bundle.tests.iter_mut().enumerate() {
+            test.hidden = None;
+            test.name
+                .get_or_insert_with(|| format!("test {}", index + 1));
+        }
+        bundle.tests.retain(|test| test.expected_output.is_some());
+        let request = JudgeRequest {
+            submission: CodeExecutionRequest {
+                code: bundle.reference_solution,
+                template: bundle.template,
+                timeout: bundle.limits.timeout,
+                cpu_time_limit,
+                compile_timeout: bundle.limits.compile_timeout,
+                ..Default::default()
+            },
+            tests: bundle.tests,
+            groups: bundle.groups,
+            comparison: bundle.comparison,
+            reference_solution: None,
+            generated_inputs: None,
+        };
+        let judge = self.judge_submission(request).await;
+
+        if judge.status != ExecutionStatus::Success {
+            problems.push(error(
+                None,
+                format!(
+                    "The reference solution could not be judged ({}): {}",
+                    judge.status.as_str(),
+                    judge.error
+                ),
+            ));
+        }
+        for test in &judge.tests {
+            let name = Some(test.name.as_str());
+            if !test.passed {
+                problems.push(error(
+                    name,
+                    if test.status.is_success() {
+                        "The reference solution's output differs from expectedOutput".to_string()
+                    } else {
+                        format!(
+                            "The reference solution fails with status {}",
+                            test.status.as_str()
+                        )
+                    },
+                ));
+                continue;
+            }
+            if let Some(run_time_ms) = test
+                .run_time_ms
+                .filter(|&ms| ms > timeout as f64 * 1000.0 * LIMIT_HEADROOM)
+            {
+                problems.push(warning(
+                    name,
+                    format!(
+                        "The reference solution takes {:.0}ms of the {}s timeout",
+                        run_time_ms, timeout
+                    ),
+                ));
+            }
+            if let (Some(cpu_time_ms), Some(limit)) = (test.cpu_time_ms, cpu_time_limit) {
+                if cpu_time_ms as f64 > limit as f64 * 1000.0 * LIMIT_HEADROOM {
+                    problems.push(warning(
+                        name,
+                        format!(
+                            "The reference solution uses {}ms of the {}s CPU time limit",
+                            cpu_time_ms, limit
+                        ),
+                    ));
+                }
+            }
+        }
+
+        DryRunResponse {
+            valid: !problems.iter().any(|problem| problem.severity == "error"),
+            problems,
+            judge,
+            execution_time: start_time.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/assignments/dryrun",
+    request_body = AssignmentBundle,
+    responses((status = 200, description = "Problems found in the assignment; valid is false when any is an error", body = DryRunResponse))
+)]
+pub async fn dry_run(
+    bundle: AssignmentBundle,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(
+        &executor.dry_run_assignment(bundle).await,
+    ))
+}
@@ -0,0 +1,310 @@
+use crate::{history, queue, CodeExecutionRequest, CodeExecutionResponse, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tokio_postgres::{Client, NoTls};
+use utoipa::{IntoParams, ToSchema};
+use warp::http::StatusCode;
+
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+const MAX_QUERY_LIMIT: i64 = 1000;
+
+// Rows can be added but never changed: the triggers reject UPDATE, DELETE
+// and TRUNCATE, so removing records takes dropping them on purpose. The one
+// exception is erasing a user's records on request, which deletes them in a
+// transaction that sets rust_executor.erasure.
+const CREATE_TABLE_SQL: &str = "
+CREATE TABLE IF NOT EXISTS rust_execution_audit (
+    id BIGSERIAL PRIMARY KEY,
+    requester_id TEXT,
+    code_hash TEXT NOT NULL,
+    limits TEXT NOT NULL,
+    status TEXT NOT NULL,
+    exit_code INTEGER,
+    execution_time_ms DOUBLE PRECISION NOT NULL,
+    instance_id TEXT NOT NULL,
+    requested_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS rust_execution_audit_requester_idx
+    ON rust_execution_audit (requester_id, requested_at);
+CREATE INDEX IF NOT EXISTS rust_execution_audit_code_hash_idx
+    ON rust_execution_audit (code_hash);
+CREATE OR REPLACE FUNCTION rust_execution_audit_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' AND current_setting('rust_executor.erasure', true) = 'on' THEN
+        RETURN OLD;
+    END IF;
+    RAISE EXCEPTION 'rust_execution_audit is append-only';
+END;
+$$ LANGUAGE plpgsql;
+DROP TRIGGER IF EXISTS rust_execution_audit_no_update ON rust_execution_audit;
+CREATE TRIGGER rust_execution_audit_no_update
+    BEFORE UPDATE OR DELETE ON rust_execution_audit
+    FOR EACH ROW EXECUTE FUNCTION rust_execution_audit_append_only();
+DROP TRIGGER IF EXISTS rust_execution_audit_no_truncate ON rust_execution_audit;
+CREATE TRIGGER rust_execution_audit_no_truncate
+    BEFORE TRUNCATE ON rust_execution_audit
+    FOR EACH STATEMENT EXECUTE FUNCTION rust_execution_audit_append_only();
+";
+
+// Who ran what and when, for academic-integrity investigations. Unlike the
+// execution history it keeps no output, covers every execution whatever
+// its entry point (HTTP, gRPC or the job queue), and is queried through an
+// admin endpoint. Stored in PostgreSQL at AUDIT_LOG_DATABASE_URL.
+pub struct AuditLog {
+    database_url: String,
+    client: Mutex<Option<Client>>,
+}
+
+// Taken when the request arrives, completed with its outcome
+pub struct AuditEntry {
+    requester_id: Option<String>,
+    code_hash: String,
+    limits: String,
+    // Unix seconds
+    requested_at: f64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    user: Option<String>,
+    #[serde(rename = "codeHash")]
+    code_hash: Option<String>,
+    // RFC 3339 timestamps bounding requestedAt, e.g. 2024-05-01T00:00:00Z
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditRecord {
+    id: i64,
+    #[serde(rename = "requesterId")]
+    requester_id: Option<String>,
+    // SHA-256 of the code and source files, as in /executions
+    #[serde(rename = "codeHash")]
+    code_hash: String,
+    // The limits and build settings the request asked for
+    #[schema(value_type = Object)]
+    limits: serde_json::Value,
+    status: String,
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+    #[serde(rename = "executionTimeMs")]
+    execution_time_ms: f64,
+    // The replica that ran it (see /info)
+    #[serde(rename = "instanceId")]
+    instance_id: String,
+    #[serde(rename = "requestedAt")]
+    requested_at: String,
+    #[serde(rename = "completedAt")]
+    completed_at: String,
+}
+
+impl AuditEntry {
+    pub fn new(req: &CodeExecutionRequest, requester_id: Option<&str>) -> Self {
+        let limits = serde_json::json!({
+            "timeout": req.timeout,
+            "cpuTimeLimit": req.cpu_time_limit,
+            "compileTimeout": req.compile_timeout,
+            "profile": req.profile,
+            "optLevel": req.opt_level,
+            "overflowChecks": req.overflow_checks,
+            "deterministic": req.deterministic,
+            "forbidUnsafe": req.forbid_unsafe,
+            "strict": req.strict,
+            "auditSyscalls": req.audit_syscalls,
+        });
+        Self {
+            requester_id: requester_id.map(str::to_string),
+            code_hash: history::code_hash(req),
+            limits: limits.to_string(),
+            requested_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |now| now.as_secs_f64()),
+        }
+    }
+}
+
+fn database_error(e: tokio_postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db_error) => format!("Audit database error: {}", db_error.message()),
+        None => format!("Audit database error: {}", e),
+    }
+}
+
+impl AuditLog {
+    pub fn new(database_url: String) -> Self {
+        Self {
+            database_url,
+            client: Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> Result<Client, String> {
+        let (client, connection) = tokio_postgres::connect(&self.database_url, NoTls)
+            .await
+            .map_err(|e| format!("Failed to connect to audit database: {}", e))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("Audit database connection closed: {}", e);
+            }
+        });
+        client
+            .batch_execute(CREATE_TABLE_SQL)
+            .await
+            .map_err(|e| format!("Failed to create audit table: {}", e))?;
+        Ok(client)
+    }
+
+    async fn client(&self) -> Result<MappedMutexGuard<'_, Client>, String> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().is_none_or(Client::is_closed) {
+            *client = Some(self.connect().await?);
+        }
+        Ok(MutexGuard::map(client, |client| {
+            client.as_mut().expect("client was just connected")
+        }))
+    }
+
+    pub async fn record(
+        &self,
+        entry: AuditEntry,
+        result: &CodeExecutionResponse,
+    ) -> Result<(), String> {
+        let execution_time_ms = result.execution_time * 1000.0;
+        self.client()
+            .await?
+            .execute(
+                "INSERT INTO rust_execution_audit (requester_id, code_hash, limits, status, \
+                     exit_code, execution_time_ms, instance_id, requested_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, to_timestamp($8))",
+                &[
+                    &entry.requester_id,
+                    &entry.code_hash,
+                    &entry.limits,
+                    &result.status.as_str(),
+                    &result.exit_code,
+                    &execution_time_ms,
+                    &queue::instance_id(),
+                    &entry.requested_at,
+                ],
+            )
+            .await
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    // Erases the requester's records, for DELETE /users/{id}/data. Returns
+    // how many were deleted.
+    pub async fn delete_for(&self, requester_id: &str) -> Result<u64, String> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await.map_err(database_error)?;
+        transaction
+            .batch_execute("SET LOCAL rust_executor.erasure = 'on'")
+            .await
+            .map_err(database_error)?;
+        let deleted = transaction
+            .execute(
+                "DELETE FROM rust_execution_audit WHERE requester_id = $1",
+                &[&requester_id],
+            )
+            .await
+            .map_err(database_error)?;
+        transaction.commit().await.map_err(database_error)?;
+        Ok(deleted)
+    }
+
+    pub async fn query(&self, query: AuditLogQuery) -> Result<Vec<AuditRecord>, String> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT);
+        let rows = self
+            .client()
+            .await?
+            .query(
+                "SELECT id, requester_id, code_hash, limits, status, exit_code, \
+                         execution_time_ms, instance_id, \
+                         to_char(requested_at AT TIME ZONE 'UTC', \
+                                 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"'), \
+                         to_char(completed_at AT TIME ZONE 'UTC', \
+                                 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"') \
+                         FROM rust_execution_audit \
+                         WHERE ($1::text IS NULL OR requester_id = $1) \
+                           AND ($2::text IS NULL OR code_hash = $2) \
+                           AND ($3::text IS NULL OR requested_at >= $3::text::timestamptz) \
+                           AND ($4::text IS NULL OR requested_at < $4::text::timestamptz) \
+                         ORDER BY requested_at DESC, id DESC \
+                         LIMIT $5",
+                &[
+                    &query.user,
+                    &query.code_hash,
+                    &query.since,
+                    &query.until,
+                    &limit,
+                ],
+            )
+            .await
+            .map_err(database_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AuditRecord {
+                id: row.get(0),
+                requester_id: row.get(1),
+                code_hash: row.get(2),
+                limits: serde_json::from_str(row.get(3)).unwrap_or_default(),
+                status: row.get(4),
+                exit_code: row.get(5),
+                execution_time_ms: row.get(6),
+                instance_id: row.get(7),
+                requested_at: row.get(8),
+                completed_at: row.get(9),
+            })
+            .collect())
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(
+        AuditLogQuery,
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, description = "Matching audit records, newest first, under `entries`", body = [AuditRecord]),
+        (status = 401, body = ErrorResponse),
+        (status = 404, description = "Audit log is not enabled", body = ErrorResponse),
+        (status = 502, body = ErrorResponse)
+    )
+)]
+pub async fn query_audit_log(
+    query: AuditLogQuery,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let reply = match &executor.audit_log {
+        Some(audit_log) => match audit_log.query(query).await {
+            Ok(entries) => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "entries": entries })),
+                StatusCode::OK,
+            ),
+            Err(e) => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": e })),
+                StatusCode::BAD_GATEWAY,
+            ),
+        },
+        None => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "Audit log is not enabled" })),
+            StatusCode::NOT_FOUND,
+        ),
+    };
+    Ok(reply)
+}
@@ -0,0 +1,346 @@
+use crate::ansi::AnsiMode;
+use crate::diagnostics::Diagnostic;
+use crate::readiness::{ActiveExecution, KILLED_MESSAGE};
+use crate::{
+    lint_verdict, output_files, panic_report, syscall_audit, telemetry, ChildSetup,
+    CodeExecutionRequest, CodeExecutionResponse, ExecutionStatus, PreparedExecution, RunOutcome,
+    RustExecutor, StatusError,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Language used when a request does not name one
+pub const DEFAULT_LANGUAGE: &str = "rust";
+
+// A way of turning a submission into a result, in four steps. Admission,
+// tracking, quotas, auditing and the HTTP and gRPC layers sit around it, so
+// a backend for another language, or another way of running Rust (such as
+// WASM instead of native binaries), only implements these and registers
+// itself in ExecutorRegistry::new.
+#[tonic::async_trait]
+pub trait Executor: Send + Sync {
+    // Whatever the later steps need: sources on disk, limits, settings
+    type Prepared: Send + Sync;
+    // A build ready to run
+    type Compiled: Send;
+
+    // Validates the request and lays out the sources; errors are returned
+    // to the client as they are, under their status
+    fn prepare(
+        &self,
+        service: &RustExecutor,
+        req: CodeExecutionRequest,
+    ) -> Result<Self::Prepared, StatusError>;
+
+    // Builds the program. A failed build is returned as the final response,
+    // usually through `report`.
+    async fn compile(
+        &self,
+        service: &RustExecutor,
+        prepared: &mut Self::Prepared,
+        active: &ActiveExecution,
+    ) -> Result<Self::Compiled, CodeExecutionResponse>;
+
+    async fn run(
+        &self,
+        service: &RustExecutor,
+        prepared: &Self::Prepared,
+        compiled: Self::Compiled,
+        active: &ActiveExecution,
+    ) -> RunOutcome;
+
+    // Turns the outcome into the response. executionTime is filled in
+    // afterwards.
+    fn report(
+        &self,
+        service: &RustExecutor,
+        prepared: &Self::Prepared,
+        outcome: RunOutcome,
+    ) -> CodeExecutionResponse;
+}
+
+// The four steps run in order, as one object the registry can hold
+#[tonic::async_trait]
+pub trait Backend: Send + Sync {
+    async fn execute(
+        &self,
+        service: &RustExecutor,
+        req: CodeExecutionRequest,
+        active: &ActiveExecution,
+    ) -> CodeExecutionResponse;
+}
+
+#[tonic::async_trait]
+impl<E: Executor> Backend for E {
+    async fn execute(
+        &self,
+        service: &RustExecutor,
+        req: CodeExecutionRequest,
+        active: &ActiveExecution,
+    ) -> CodeExecutionResponse {
+        let start_time = Instant::now();
+        // Compilation and the run are traced as spans of their own
+        let mut response = match self.prepare(service, req) {
+            Ok(mut prepared) => {
+                service.phase_successes.record("preparing");
+                let compile = self.compile(service, &mut prepared, active);
+                match telemetry::in_span("compile", compile).await.0 {
+                    Ok(compiled) => {
+                        service.phase_successes.record("compiling");
+                        let run = self.run(service, &prepared, compiled, active);
+                        let outcome = telemetry::in_span("run", run).await.0;
+                        let response = self.report(service, &prepared, outcome);
+                        if service_did_its_part(response.status) {
+                            service.phase_successes.record("running");
+                        }
+                        response
+                    }
+                    Err(response) => {
+                        if response.status == ExecutionStatus::CompileError {
+                            service.phase_successes.record("compiling");
+                        }
+                        response
+                    }
+                }
+            }
+            Err(e) => CodeExecutionResponse::rejected(e, 0.0),
+        };
+        response.execution_time = start_time.elapsed().as_secs_f64();
+        response.toolchain_version = service.toolchain.project_rustc.clone();
+        response
+    }
+}
+
+// Whether a run ended without the service failing it, as /health counts
+fn service_did_its_part(status: ExecutionStatus) -> bool {
+    !matches!(
+        status,
+        ExecutionStatus::InternalError | ExecutionStatus::Killed
+    )
+}
+
+// Backends by the `language` of the request
+pub struct ExecutorRegistry {
+    backends: BTreeMap<&'static str, Arc<dyn Backend>>,
+}
+
+impl ExecutorRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            backends: BTreeMap::new(),
+        };
+        registry.register(DEFAULT_LANGUAGE, NativeRust);
+        registry
+    }
+
+    pub fn register(&mut self, language: &'static str, backend: impl Backend + 'static) {
+        self.backends.insert(language, Arc::new(backend));
+    }
+
+    pub fn get(&self, language: Option<&str>) -> Result<Arc<dyn Backend>, String> {
+        let language = language.unwrap_or(DEFAULT_LANGUAGE);
+        self.backends.get(language).cloned().ok_or_else(|| {
+            format!(
+                "Unsupported language '{}', expected one of: {}",
+                language,
+                self.languages().join(", ")
+            )
+        })
+    }
+
+    pub fn languages(&self) -> Vec<&'static str> {
+        self.backends.keys().copied().collect()
+    }
+}
+
+// Rust built with cargo and run as a native binary
+pub struct NativeRust;
+
+pub struct NativePrepared {
+    execution: PreparedExecution,
+    // Whether the request set RUST_BACKTRACE itself, which keeps
+    // backtraces in the error text
+    backtrace_requested: bool,
+    trace_log: Option<PathBuf>,
+    // From the lint pass, returned with the result
+    lint_warnings: Vec<Diagnostic>,
+}
+
+pub struct NativeBinary {
+    executable: PathBuf,
+    compile_time: Duration,
+}
+
+fn killed_response() -> CodeExecutionResponse {
+    CodeExecutionResponse {
+        ..CodeExecutionResponse::error(ExecutionStatus::Killed, KILLED_MESSAGE.to_string(), 0.0)
+    }
+}
+
+#[tonic::async_trait]
+impl Executor for NativeRust {
+    type Prepared = NativePrepared;
+    type Compiled = NativeBinary;
+
+    fn prepare(
+        &self,
+        service: &RustExecutor,
+        req: CodeExecutionRequest,
+    ) -> Result<NativePrepared, StatusError> {
+        let mut execution = service.prepare_execution(req)?;
+        // Backtraces feed the panic report
+        let backtrace_requested = execution
+            .env
+            .iter()
+            .any(|(name, _)| name == "RUST_BACKTRACE");
+        if !backtrace_requested {
+            execution
+                .env
+                .push(("RUST_BACKTRACE".to_string(), "1".to_string()));
+        }
+        let trace_log = execution
+            .audit_syscalls
+            .then(|| execution.temp_dir.path().join(syscall_audit::TRACE_LOG));
+        Ok(NativePrepared {
+            execution,
+            backtrace_requested,
+            trace_log,
+            lint_warnings: Vec::new(),
+        })
+    }
+
+    async fn compile(
+        &self,
+        service: &RustExecutor,
+        prepared: &mut NativePrepared,
+        active: &ActiveExecution,
+    ) -> Result<NativeBinary, CodeExecutionResponse> {
+        let execution = &prepared.execution;
+        active.set_phase("compiling");
+        if execution.needs_lint_pass() {
+            let diagnostics = match active.until_killed(service.lint_pass(execution)).await {
+                Some(Ok(diagnostics)) => diagnostics,
+                Some(Err(outcome)) => return Err(outcome.into_response("utf8", 0.0)),
+                None => return Err(killed_response()),
+            };
+            match lint_verdict(diagnostics, execution.strict) {
+                Ok(warnings) => prepared.lint_warnings = warnings,
+                Err((message, diagnostics)) => {
+                    return Err(CodeExecutionResponse {
+                        diagnostics,
+                        ..CodeExecutionResponse::error(ExecutionStatus::CompileError, message, 0.0)
+                    });
+                }
+            }
+        }
+
+        let execution = &prepared.execution;
+        let compile_start = Instant::now();
+        let compiled = active
+            .until_killed(service.compile(execution.temp_dir.path(), execution.build))
+            .await;
+        let compile_time = compile_start.elapsed();
+        let failed = match compiled {
+            Some(Ok(executable)) => {
+                return Ok(NativeBinary {
+                    executable,
+                    compile_time,
+                })
+            }
+            // Code that tries unsafe under forbidUnsafe fails to compile
+            // with the lint's name in the error
+            Some(Err(outcome))
+                if execution.forbid_unsafe
+                    && outcome.status == ExecutionStatus::CompileError
+                    && outcome.stderr.contains("unsafe_code") =>
+            {
+                RunOutcome {
+                    status: ExecutionStatus::SecurityViolation,
+                    ..outcome
+                }
+            }
+            Some(Err(outcome)) => outcome,
+            None => RunOutcome::killed(),
+        };
+        Err(self.report(
+            service,
+            prepared,
+            RunOutcome {
+                compile_time,
+                ..failed
+            },
+        ))
+    }
+
+    async fn run(
+        &self,
+        service: &RustExecutor,
+        prepared: &NativePrepared,
+        compiled: NativeBinary,
+        active: &ActiveExecution,
+    ) -> RunOutcome {
+        let execution = &prepared.execution;
+        let setup = ChildSetup {
+            working_dir: Some(execution.temp_dir.path()),
+            trace_log: prepared.trace_log.as_deref(),
+            keep_stdin_open: execution.close_stdin == Some(false),
+        };
+        let outcome = service
+            .run_executable(
+                &compiled.executable,
+                setup,
+                &[],
+                execution.input.as_deref(),
+                execution.limits(),
+                &execution.env,
+                active,
+            )
+            .await;
+        RunOutcome {
+            compile_time: compiled.compile_time,
+            ..outcome
+        }
+    }
+
+    fn report(
+        &self,
+        service: &RustExecutor,
+        prepared: &NativePrepared,
+        outcome: RunOutcome,
+    ) -> CodeExecutionResponse {
+        let execution = &prepared.execution;
+        let config = service.config();
+        let mut response = CodeExecutionResponse {
+            compile_time_ms: Some(outcome.compile_time.as_secs_f64() * 1000.0),
+            diagnostics: prepared.lint_warnings.clone(),
+            ..outcome.into_response(&execution.output_encoding, 0.0)
+        };
+        if let AnsiMode::Strip = execution.ansi {
+            response.strip_ansi();
+        }
+        if response.run_time_ms.is_some() {
+            response.panic = panic_report::parse(&response.error, execution.user_lines);
+            // A failed allocation prints a backtrace too
+            let backtraced =
+                response.panic.is_some() || response.status == ExecutionStatus::MemoryLimit;
+            if backtraced && !prepared.backtrace_requested {
+                response.error = panic_report::strip_backtraces(&response.error);
+                response.output_events =
+                    panic_report::strip_backtrace_events(response.output_events);
+            }
+            response.output_files = output_files::collect(
+                execution.temp_dir.path(),
+                &execution.data_file_names,
+                config.max_output_files_kb,
+            );
+            response.syscall_audit = prepared
+                .trace_log
+                .as_deref()
+                .and_then(syscall_audit::summarize);
+        }
+        response.limit_lines(config.max_output_lines);
+        response
+    }
+}
@@ -0,0 +1,264 @@
+use crate::readiness::KILLED_MESSAGE;
+use crate::status::StatusError;
+use crate::{BuildSettings, ChildSetup, ExecutionStatus, RunLimits, RunOutcome, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+const MAX_INPUTS: usize = 20;
+
+#[derive(Deserialize, ToSchema)]
+pub struct BehaviorDiffRequest {
+    // e.g. the last passing attempt, or the reference solution
+    #[serde(rename = "codeA")]
+    code_a: String,
+    #[serde(rename = "codeB")]
+    code_b: String,
+    // stdin for each case; one run without input when omitted
+    inputs: Option<Vec<String>>,
+    // Wall-clock seconds per run
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CaseRun {
+    output: String,
+    error: String,
+    status: ExecutionStatus,
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+}
+
+impl From<RunOutcome> for CaseRun {
+    fn from(outcome: RunOutcome) -> Self {
+        Self {
+            output: String::from_utf8_lossy(&outcome.stdout).into_owned(),
+            error: outcome.stderr,
+            status: outcome.status,
+            exit_code: outcome.exit_code,
+        }
+    }
+}
+
+// One line of the output diff: "equal", "removed" (only in A) or "added"
+// (only in B)
+#[derive(Serialize, ToSchema)]
+pub struct DiffLine {
+    op: &'static str,
+    text: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CaseDiff {
+    input: Option<String>,
+    a: CaseRun,
+    b: CaseRun,
+    // Same output, status and exit code
+    identical: bool,
+    // Line diff of the outputs, A to B; empty when they are equal
+    diff: Vec<DiffLine>,
+    // Set when the outputs had more lines than max_output_lines and only
+    // the first ones were compared
+    truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BehaviorDiffResponse {
+    cases: Vec<CaseDiff>,
+    // Every case is identical
+    identical: bool,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    status: ExecutionStatus,
+}
+
+impl BehaviorDiffResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            cases: Vec::new(),
+            identical: false,
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+// Longest-common-subsequence line diff
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<DiffLine> {
+    // common[i][j]: length of the LCS of a[i..] and b[j..]
+    let mut common = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_string(),
+    };
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            diff.push(line("equal", a[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            diff.push(line("removed", a[i]));
+            i += 1;
+        } else {
+            diff.push(line("added", b[j]));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|text| line("removed", text)));
+    diff.extend(b[j..].iter().map(|text| line("added", text)));
+    diff
+}
+
+impl RustExecutor {
+    // Runs both versions on every input and diffs what they print
+    pub async fn diff_behavior(&self, req: BehaviorDiffRequest) -> BehaviorDiffResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("diff", None) {
+            Ok(active) => active,
+            Err(e) => return BehaviorDiffResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        let code_size_kb = (req.code_a.len() + req.code_b.len()) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return BehaviorDiffResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) =
+            self.check_unstable_features([req.code_a.as_str(), req.code_b.as_str()].into_iter())
+        {
+            return BehaviorDiffResponse::error(e, start_time);
+        }
+        let inputs: Vec<Option<String>> = match req.inputs {
+            Some(inputs) if inputs.len() > MAX_INPUTS => {
+                return BehaviorDiffResponse::error(
+                    format!("At most {} inputs are allowed", MAX_INPUTS),
+                    start_time,
+                );
+            }
+            Some(inputs) if !inputs.is_empty() => inputs.into_iter().map(Some).collect(),
+            _ => vec![None],
+        };
+
+        active.set_phase("compiling");
+        let build = BuildSettings::default();
+        let built = active
+            .until_killed(async {
+                let a = self.build_for_comparison("A", &req.code_a, build).await?;
+                let b = self.build_for_comparison("B", &req.code_b, build).await?;
+                Ok::<_, StatusError>((a, b))
+            })
+            .await;
+        let ((dir_a, executable_a), (dir_b, executable_b)) = match built {
+            Some(Ok(built)) => built,
+            Some(Err(e)) => return BehaviorDiffResponse::error(e, start_time),
+            None => return BehaviorDiffResponse::killed(start_time),
+        };
+
+        let mut cases = Vec::new();
+        for input in inputs {
+            let mut runs = Vec::new();
+            for (dir, executable) in [(&dir_a, &executable_a), (&dir_b, &executable_b)] {
+                let setup = ChildSetup {
+                    working_dir: Some(dir.path()),
+                    trace_log: None,
+                    keep_stdin_open: false,
+                };
+                let outcome = self
+                    .run_executable(
+                        executable,
+                        setup,
+                        &[],
+                        input.as_deref().map(str::as_bytes),
+                        RunLimits::wall_clock(execution_timeout),
+                        &[],
+                        &active,
+                    )
+                    .await;
+                if active.is_killed() {
+                    return BehaviorDiffResponse::killed(start_time);
+                }
+                runs.push(CaseRun::from(outcome));
+            }
+            let (Some(b), Some(a)) = (runs.pop(), runs.pop()) else {
+                continue;
+            };
+
+            let lines_a: Vec<&str> = a.output.lines().collect();
+            let lines_b: Vec<&str> = b.output.lines().collect();
+            let max_lines = config.max_output_lines;
+            let truncated = lines_a.len() > max_lines || lines_b.len() > max_lines;
+            let diff = if a.output == b.output {
+                Vec::new()
+            } else {
+                diff_lines(
+                    &lines_a[..lines_a.len().min(max_lines)],
+                    &lines_b[..lines_b.len().min(max_lines)],
+                )
+            };
+            cases.push(CaseDiff {
+                input,
+                identical: a.output == b.output
+                    && a.status == b.status
+                    && a.exit_code == b.exit_code,
+                a,
+                b,
+                diff,
+                truncated,
+            });
+        }
+
+        BehaviorDiffResponse {
+            identical: cases.iter().all(|case| case.identical),
+            cases,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: ExecutionStatus::Success,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/diff",
+    request_body = BehaviorDiffRequest,
+    responses((status = 200, body = BehaviorDiffResponse))
+)]
+pub async fn diff(
+    req: BehaviorDiffRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.diff_behavior(req).await;
+    Ok(warp::reply::json(&result))
+}
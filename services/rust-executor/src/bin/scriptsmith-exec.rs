@@ -0,0 +1,183 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rust_executor::{CodeExecutionRequest, ExecutionStatus, ExecutorConfig, RustExecutor};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage:
+  scriptsmith-exec run <file.rs> [--stdin <file>|-] [--timeout <seconds>]
+                       [--cpu-time-limit <seconds>] [--release] [--strict] [--json]
+  scriptsmith-exec validate <file.rs> [--json]
+
+Runs a submission the way POST /execute does, with the same wrapping,
+limits and configuration (CONFIG_FILE and the environment), but without
+the HTTP server. Without --json the program's output goes to stdout, its
+errors to stderr, and the exit code is the program's, or 1 when it did
+not compile or was stopped.";
+
+struct Options {
+    command: String,
+    file: String,
+    stdin: Option<String>,
+    timeout: Option<u64>,
+    cpu_time_limit: Option<u64>,
+    release: bool,
+    strict: bool,
+    json: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let command = args.next().ok_or("Missing command")?;
+    if command != "run" && command != "validate" {
+        return Err(format!("Unknown command '{}'", command));
+    }
+    let mut options = Options {
+        command,
+        file: String::new(),
+        stdin: None,
+        timeout: None,
+        cpu_time_limit: None,
+        release: false,
+        strict: false,
+        json: false,
+    };
+    let seconds = |flag: &str, value: Option<String>| -> Result<Option<u64>, String> {
+        let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} expects whole seconds, got '{}'", flag, value))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdin" => options.stdin = Some(args.next().ok_or("--stdin needs a file or -")?),
+            "--timeout" => options.timeout = seconds("--timeout", args.next())?,
+            "--cpu-time-limit" => {
+                options.cpu_time_limit = seconds("--cpu-time-limit", args.next())?
+            }
+            "--release" => options.release = true,
+            "--strict" => options.strict = true,
+            "--json" => options.json = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option '{}'", flag)),
+            file if options.file.is_empty() => options.file = file.to_string(),
+            extra => return Err(format!("Unexpected argument '{}'", extra)),
+        }
+    }
+    if options.file.is_empty() {
+        return Err("Missing the source file".to_string());
+    }
+    Ok(options)
+}
+
+// "-" reads the input from this process's stdin
+fn read_input(source: &str) -> Result<Vec<u8>, String> {
+    if source == "-" {
+        let mut input = Vec::new();
+        io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        return Ok(input);
+    }
+    fs::read(source).map_err(|e| format!("Failed to read {}: {}", source, e))
+}
+
+fn print_json(value: &impl serde::Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_default()
+    );
+}
+
+async fn run(options: Options) -> Result<ExitCode, String> {
+    let code = fs::read_to_string(&options.file)
+        .map_err(|e| format!("Failed to read {}: {}", options.file, e))?;
+    let config = ExecutorConfig::load()?;
+    let mut executor = RustExecutor::new(config);
+    executor.detect_toolchain().await;
+
+    if options.command == "validate" {
+        let result = executor.validate_syntax(code).await;
+        if options.json {
+            print_json(&result);
+        } else {
+            for message in result.errors.iter().chain(&result.warnings) {
+                eprintln!("{}", message);
+            }
+        }
+        return Ok(if result.is_valid {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+
+    // Binary input is passed through as base64, as the API would take it
+    let (input_data, input_encoding) = match options.stdin.as_deref().map(read_input) {
+        Some(Ok(input)) => match String::from_utf8(input) {
+            Ok(text) => (Some(text), None),
+            Err(e) => (
+                Some(BASE64.encode(e.into_bytes())),
+                Some("base64".to_string()),
+            ),
+        },
+        Some(Err(e)) => return Err(e),
+        None => (None, None),
+    };
+    let result = executor
+        .execute(CodeExecutionRequest {
+            code,
+            input_data,
+            input_encoding,
+            timeout: options.timeout,
+            cpu_time_limit: options.cpu_time_limit,
+            profile: options.release.then(|| "release".to_string()),
+            strict: options.strict.then_some(true),
+            ..Default::default()
+        })
+        .await;
+
+    if options.json {
+        print_json(&result);
+    } else {
+        if !result.output.is_empty() {
+            println!("{}", result.output);
+        }
+        if !result.error.is_empty() {
+            eprintln!("{}", result.error);
+        }
+    }
+    let _ = io::stdout().flush();
+    Ok(match (result.status, result.exit_code) {
+        (ExecutionStatus::Success, _) => ExitCode::SUCCESS,
+        (_, Some(code)) if code > 0 && code < 256 => ExitCode::from(code as u8),
+        _ => ExitCode::FAILURE,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    if env::args()
+        .nth(1)
+        .is_some_and(|arg| arg == "--help" || arg == "-h" || arg == "help")
+    {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(options).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+const BWRAP: &str = "bwrap";
+
+// config.sandbox values: programs run as plain processes in their own
+// process group, or additionally inside bubblewrap
+pub const PROCESS_GROUP: &str = "process-group";
+pub const SANDBOX_BWRAP: &str = "bwrap";
+
+// The host's system directories, visible read-only; /bin and the like are
+// recreated as symlinks on merged-/usr systems
+const SYSTEM_DIRS: [&str; 4] = ["/bin", "/lib", "/lib64", "/sbin"];
+
+// Runs `inner` inside bwrap: fresh user, pid, network, ipc, uts and cgroup
+// namespaces without capabilities, a root holding only /usr and the other
+// system directories read-only, its own /proc and /dev and an empty tmpfs
+// /tmp. The working directory is the one writable place; a binary outside
+// it (and /usr) is mounted read-only. Works unprivileged wherever
+// unprivileged user namespaces are enabled.
+pub fn wrap(inner: &Command, executable_path: &Path, working_dir: Option<&Path>) -> Command {
+    let mut cmd = Command::new(BWRAP);
+    cmd.args([
+        "--unshare-all",
+        "--die-with-parent",
+        "--cap-drop",
+        "ALL",
+        "--hostname",
+        "sandbox",
+        "--ro-bind",
+        "/usr",
+        "/usr",
+    ]);
+    for dir in SYSTEM_DIRS {
+        match fs::read_link(dir) {
+            Ok(target) => cmd.arg("--symlink").arg(target).arg(dir),
+            Err(_) => cmd.args(["--ro-bind-try", dir, dir]),
+        };
+    }
+    cmd.args([
+        "--ro-bind-try",
+        "/etc/ld.so.cache",
+        "/etc/ld.so.cache",
+        "--proc",
+        "/proc",
+        "--dev",
+        "/dev",
+        "--tmpfs",
+        "/tmp",
+    ]);
+    match working_dir {
+        Some(dir) => cmd.arg("--bind").arg(dir).arg(dir).arg("--chdir").arg(dir),
+        None => cmd.args(["--chdir", "/"]),
+    };
+    if !working_dir.is_some_and(|dir| executable_path.starts_with(dir))
+        && !executable_path.starts_with("/usr")
+    {
+        cmd.arg("--ro-bind")
+            .arg(executable_path)
+            .arg(executable_path);
+    }
+    cmd.arg("--")
+        .arg(inner.get_program())
+        .args(inner.get_args());
+    cmd
+}
+
+pub fn wraps(cmd: &Command) -> bool {
+    cmd.get_program() == BWRAP
+}
+
+// bwrap exits with 128 + the signal when the program is killed by one;
+// turned back into that signal so limits and crashes are recognised. A
+// program exiting with such a code itself is mistaken for a signal.
+pub fn program_status(status: ExitStatus) -> ExitStatus {
+    match status.code() {
+        Some(code) if code > 128 && code - 128 < 65 => ExitStatus::from_raw(code - 128),
+        _ => status,
+    }
+}
+
+// Whether bwrap can start a sandbox on this host
+pub fn check() -> Result<(), String> {
+    let probe = wrap(&Command::new("true"), Path::new("/usr/bin/true"), None)
+        .output()
+        .map_err(|e| format!("sandbox is bwrap but bwrap cannot be started: {}", e))?;
+    if !probe.status.success() {
+        return Err(format!(
+            "sandbox is bwrap but bwrap fails: {}",
+            String::from_utf8_lossy(&probe.stderr).trim()
+        ));
+    }
+    Ok(())
+}
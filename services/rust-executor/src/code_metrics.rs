@@ -0,0 +1,250 @@
+use crate::RustExecutor;
+use serde::{Deserialize, Serialize};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{BinOp, Expr};
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CodeMetricsRequest {
+    code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FunctionMetrics {
+    name: String,
+    line: usize,
+    #[serde(rename = "linesOfCode")]
+    lines_of_code: usize,
+    // McCabe: 1 + branches (if, match arms past the first, loops, ?, && and ||)
+    #[serde(rename = "cyclomaticComplexity")]
+    cyclomatic_complexity: usize,
+    #[serde(rename = "maxNestingDepth")]
+    max_nesting_depth: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CodeMetricsResponse {
+    // Lines that are neither blank nor only a comment
+    #[serde(rename = "linesOfCode")]
+    lines_of_code: usize,
+    #[serde(rename = "totalLines")]
+    total_lines: usize,
+    #[serde(rename = "commentLines")]
+    comment_lines: usize,
+    #[serde(rename = "functionCount")]
+    function_count: usize,
+    // Sum over all functions
+    #[serde(rename = "cyclomaticComplexity")]
+    cyclomatic_complexity: usize,
+    #[serde(rename = "maxCyclomaticComplexity")]
+    max_cyclomatic_complexity: usize,
+    // Deepest nesting of blocks, loops, branches and closures
+    #[serde(rename = "maxNestingDepth")]
+    max_nesting_depth: usize,
+    #[serde(rename = "unsafeBlockCount")]
+    unsafe_block_count: usize,
+    functions: Vec<FunctionMetrics>,
+}
+
+// Counts lines by their first non-blank characters; block comments are
+// tracked across lines but a line mixing code and comment counts as code
+fn count_lines(code: &str) -> (usize, usize, usize) {
+    let (mut code_lines, mut comment_lines) = (0, 0);
+    let mut in_block_comment = false;
+    for line in code.lines() {
+        let line = line.trim();
+        if in_block_comment {
+            comment_lines += 1;
+            in_block_comment = !line.contains("*/");
+        } else if line.starts_with("//") {
+            comment_lines += 1;
+        } else if line.starts_with("/*") {
+            comment_lines += 1;
+            in_block_comment = !line.contains("*/");
+        } else if !line.is_empty() {
+            code_lines += 1;
+        }
+    }
+    (code_lines, code.lines().count(), comment_lines)
+}
+
+#[derive(Default)]
+struct FunctionVisitor {
+    complexity: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl FunctionVisitor {
+    fn nested(&mut self, visit_inner: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        visit_inner(self);
+        self.depth -= 1;
+    }
+}
+
+impl<'ast> Visit<'ast> for FunctionVisitor {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            // An `else if` chain is one level of nesting, not one per branch
+            Expr::If(if_expr) => {
+                self.complexity += 1;
+                self.nested(|v| {
+                    v.visit_expr(&if_expr.cond);
+                    v.visit_block(&if_expr.then_branch);
+                });
+                match &if_expr.else_branch {
+                    Some((_, branch)) if matches!(**branch, Expr::If(_)) => self.visit_expr(branch),
+                    Some((_, branch)) => self.nested(|v| visit::visit_expr(v, branch)),
+                    None => {}
+                }
+            }
+            Expr::While(_) | Expr::ForLoop(_) => {
+                self.complexity += 1;
+                self.nested(|v| visit::visit_expr(v, expr));
+            }
+            Expr::Loop(_) => self.nested(|v| visit::visit_expr(v, expr)),
+            Expr::Match(m) => {
+                self.complexity += m.arms.len().saturating_sub(1);
+                self.nested(|v| visit::visit_expr(v, expr));
+            }
+            Expr::Closure(_) | Expr::Block(_) | Expr::Unsafe(_) | Expr::Async(_) => {
+                self.nested(|v| visit::visit_expr(v, expr));
+            }
+            Expr::Try(_) => {
+                self.complexity += 1;
+                visit::visit_expr(self, expr);
+            }
+            Expr::Binary(b) if matches!(b.op, BinOp::And(_) | BinOp::Or(_)) => {
+                self.complexity += 1;
+                visit::visit_expr(self, expr);
+            }
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+
+    // Nested functions are measured on their own
+    fn visit_item_fn(&mut self, _: &'ast syn::ItemFn) {}
+}
+
+#[derive(Default)]
+struct MetricsCollector {
+    functions: Vec<FunctionMetrics>,
+    unsafe_blocks: usize,
+    // 1 when the code was wrapped in `fn main() {` to parse it
+    line_offset: usize,
+}
+
+impl MetricsCollector {
+    fn measure(&mut self, name: String, span: proc_macro2::Span, block: &syn::Block) {
+        let mut visitor = FunctionVisitor::default();
+        visitor.visit_block(block);
+        self.functions.push(FunctionMetrics {
+            name,
+            line: span.start().line.saturating_sub(self.line_offset).max(1),
+            lines_of_code: span.end().line - span.start().line + 1,
+            cyclomatic_complexity: 1 + visitor.complexity,
+            max_nesting_depth: visitor.max_depth,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for MetricsCollector {
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.measure(item.sig.ident.to_string(), item.span(), &item.block);
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.measure(item.sig.ident.to_string(), item.span(), &item.block);
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        if let Some(block) = &item.default {
+            self.measure(item.sig.ident.to_string(), item.span(), block);
+        }
+        visit::visit_trait_item_fn(self, item);
+    }
+
+    fn visit_expr_unsafe(&mut self, expr: &'ast syn::ExprUnsafe) {
+        self.unsafe_blocks += 1;
+        visit::visit_expr_unsafe(self, expr);
+    }
+}
+
+fn compute_metrics(code: &str) -> Result<CodeMetricsResponse, String> {
+    // Snippets are measured as the body of main, the way /execute runs them
+    let (file, line_offset) = match syn::parse_file(code) {
+        Ok(file) => (file, 0),
+        Err(e) => (
+            syn::parse_file(&format!("fn main() {{\n{}\n}}", code))
+                .map_err(|_| format!("Failed to parse code: {}", e))?,
+            1,
+        ),
+    };
+
+    let mut collector = MetricsCollector {
+        line_offset,
+        ..Default::default()
+    };
+    collector.visit_file(&file);
+
+    let (lines_of_code, total_lines, comment_lines) = count_lines(code);
+    let functions = collector.functions;
+    Ok(CodeMetricsResponse {
+        lines_of_code,
+        total_lines,
+        comment_lines,
+        function_count: functions.len(),
+        cyclomatic_complexity: functions.iter().map(|f| f.cyclomatic_complexity).sum(),
+        max_cyclomatic_complexity: functions
+            .iter()
+            .map(|f| f.cyclomatic_complexity)
+            .max()
+            .unwrap_or(0),
+        max_nesting_depth: functions
+            .iter()
+            .map(|f| f.max_nesting_depth)
+            .max()
+            .unwrap_or(0),
+        unsafe_block_count: collector.unsafe_blocks,
+        functions,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/metrics/code",
+    request_body = CodeMetricsRequest,
+    responses(
+        (status = 200, body = CodeMetricsResponse),
+        (status = 400, description = "Code too large or not parseable", body = ErrorResponse)
+    )
+)]
+pub async fn code_metrics(
+    req: CodeMetricsRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let max_code_size_kb = executor.config().max_code_size_kb;
+    let code_size_kb = req.code.len() as f64 / 1024.0;
+    let result = if code_size_kb > max_code_size_kb as f64 {
+        Err(format!(
+            "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+            code_size_kb, max_code_size_kb
+        ))
+    } else {
+        compute_metrics(&req.code)
+    };
+    let reply = match result {
+        Ok(metrics) => warp::reply::with_status(warp::reply::json(&metrics), StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            StatusCode::BAD_REQUEST,
+        ),
+    };
+    Ok(reply)
+}
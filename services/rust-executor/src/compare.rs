@@ -0,0 +1,363 @@
+use crate::project::write_project;
+use crate::readiness::KILLED_MESSAGE;
+use crate::reaper::temp_dir;
+use crate::status::StatusError;
+use crate::wrapping::{self, WrapMode};
+use crate::{BuildProfile, BuildSettings, ChildSetup, ExecutionStatus, RunLimits, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+use tempfile::TempDir;
+use utoipa::ToSchema;
+
+const COMPARE_CARGO_TOML: &str = r#"[package]
+name = "rust_compare"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+"#;
+
+const DEFAULT_RUNS: u32 = 10;
+const MAX_RUNS: u32 = 50;
+const DEFAULT_WARMUP_RUNS: u32 = 1;
+const MAX_WARMUP_RUNS: u32 = 10;
+const MAX_INPUTS: usize = 10;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CompareRequest {
+    #[serde(rename = "codeA")]
+    code_a: String,
+    #[serde(rename = "codeB")]
+    code_b: String,
+    // stdin for each measured case; one run without input when omitted
+    inputs: Option<Vec<String>>,
+    // Timed runs per submission and input, default 10
+    runs: Option<u32>,
+    // Untimed runs before them, default 1
+    #[serde(rename = "warmupRuns")]
+    warmup_runs: Option<u32>,
+    // "release" (default) or "dev"; both submissions use the same profile
+    profile: Option<String>,
+    // Wall-clock seconds per run
+    timeout: Option<u64>,
+}
+
+// Wall-clock run times of one submission on one input, in milliseconds
+#[derive(Serialize, ToSchema)]
+pub struct TimingStats {
+    runs: usize,
+    #[serde(rename = "meanMs")]
+    mean_ms: f64,
+    #[serde(rename = "medianMs")]
+    median_ms: f64,
+    #[serde(rename = "stdDevMs")]
+    std_dev_ms: f64,
+    #[serde(rename = "minMs")]
+    min_ms: f64,
+    #[serde(rename = "maxMs")]
+    max_ms: f64,
+}
+
+impl TimingStats {
+    fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        // The middle sample, or the mean of the two middle ones
+        let median = (sorted[(n - 1) / 2] + sorted[n / 2]) / 2.0;
+        // Sample standard deviation; zero for a single run
+        let variance = if n > 1 {
+            sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        Self {
+            runs: n,
+            mean_ms: mean,
+            median_ms: median,
+            std_dev_ms: variance.sqrt(),
+            min_ms: sorted[0],
+            max_ms: sorted[n - 1],
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InputComparison {
+    input: Option<String>,
+    a: TimingStats,
+    b: TimingStats,
+    // Whether both printed the same output; timings of programs that do
+    // different work say little
+    #[serde(rename = "outputsMatch")]
+    outputs_match: bool,
+    // meanMs of B minus meanMs of A; negative when B is faster
+    #[serde(rename = "meanDifferenceMs")]
+    mean_difference_ms: f64,
+    // 95% confidence interval of that difference (Welch, normal
+    // approximation)
+    #[serde(rename = "confidenceInterval95")]
+    confidence_interval_95: [f64; 2],
+    // The interval excludes zero
+    significant: bool,
+    // medianMs of A divided by medianMs of B; above 1 when B is faster
+    speedup: f64,
+}
+
+impl InputComparison {
+    fn new(input: Option<String>, a: &[f64], b: &[f64], outputs_match: bool) -> Self {
+        let (a, b) = (TimingStats::new(a), TimingStats::new(b));
+        let difference = b.mean_ms - a.mean_ms;
+        let standard_error =
+            (a.std_dev_ms.powi(2) / a.runs as f64 + b.std_dev_ms.powi(2) / b.runs as f64).sqrt();
+        let margin = 1.96 * standard_error;
+        let interval = [difference - margin, difference + margin];
+        Self {
+            input,
+            outputs_match,
+            mean_difference_ms: difference,
+            confidence_interval_95: interval,
+            significant: interval[0] > 0.0 || interval[1] < 0.0,
+            speedup: if b.median_ms > 0.0 {
+                a.median_ms / b.median_ms
+            } else {
+                0.0
+            },
+            a,
+            b,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompareResponse {
+    comparisons: Vec<InputComparison>,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    status: ExecutionStatus,
+}
+
+impl CompareResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            comparisons: Vec::new(),
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+// Outcome of one side's measured runs on one input
+struct Samples {
+    times_ms: Vec<f64>,
+    output: Vec<u8>,
+}
+
+impl RustExecutor {
+    // Builds a submission into its own temp project
+    pub(crate) async fn build_for_comparison(
+        &self,
+        label: &str,
+        code: &str,
+        build: BuildSettings,
+    ) -> Result<(TempDir, PathBuf), StatusError> {
+        let temp_dir = temp_dir().map_err(|e| {
+            StatusError::internal(format!("Failed to create temp directory: {}", e))
+        })?;
+        let mut main_rs = wrapping::wrap(code, WrapMode::Auto);
+        let forbid_unsafe = self.forbids_unsafe(std::iter::once(code)).map_err(|e| {
+            StatusError::new(e.status, format!("Submission {}: {}", label, e.message))
+        })?;
+        if forbid_unsafe {
+            main_rs = wrapping::forbid_unsafe(&main_rs);
+        }
+        let cargo_toml = format!("{}{}", COMPARE_CARGO_TOML, build.cargo_toml_section());
+        write_project(temp_dir.path(), &cargo_toml, &[("src/main.rs", &main_rs)])
+            .map_err(StatusError::internal)?;
+        match self.compile(temp_dir.path(), build).await {
+            Ok(executable) => Ok((temp_dir, executable)),
+            Err(outcome) => Err(StatusError::new(
+                outcome.status,
+                format!("Submission {}: {}", label, outcome.stderr),
+            )),
+        }
+    }
+
+    // Compiles both submissions with the same settings and times them on
+    // the same inputs. Runs of A and B alternate so load on the machine
+    // affects both alike.
+    pub async fn compare_submissions(&self, req: CompareRequest) -> CompareResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("compare", None) {
+            Ok(active) => active,
+            Err(e) => return CompareResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        let code_size_kb = (req.code_a.len() + req.code_b.len()) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return CompareResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) =
+            self.check_unstable_features([req.code_a.as_str(), req.code_b.as_str()].into_iter())
+        {
+            return CompareResponse::error(e, start_time);
+        }
+        let runs = req.runs.unwrap_or(DEFAULT_RUNS);
+        if runs == 0 || runs > MAX_RUNS {
+            return CompareResponse::error(
+                format!("runs must be between 1 and {}", MAX_RUNS),
+                start_time,
+            );
+        }
+        let warmup_runs = req.warmup_runs.unwrap_or(DEFAULT_WARMUP_RUNS);
+        if warmup_runs > MAX_WARMUP_RUNS {
+            return CompareResponse::error(
+                format!("warmupRuns must be at most {}", MAX_WARMUP_RUNS),
+                start_time,
+            );
+        }
+        let inputs: Vec<Option<String>> = match req.inputs {
+            Some(inputs) if inputs.len() > MAX_INPUTS => {
+                return CompareResponse::error(
+                    format!("At most {} inputs are allowed", MAX_INPUTS),
+                    start_time,
+                );
+            }
+            Some(inputs) if !inputs.is_empty() => inputs.into_iter().map(Some).collect(),
+            _ => vec![None],
+        };
+        let profile = match BuildProfile::parse(req.profile.as_deref().or(Some("release"))) {
+            Ok(profile) => profile,
+            Err(e) => return CompareResponse::error(e, start_time),
+        };
+        let build = BuildSettings {
+            profile,
+            ..BuildSettings::default()
+        };
+
+        active.set_phase("compiling");
+        let built = active
+            .until_killed(async {
+                let a = self.build_for_comparison("A", &req.code_a, build).await?;
+                let b = self.build_for_comparison("B", &req.code_b, build).await?;
+                Ok::<_, StatusError>((a, b))
+            })
+            .await;
+        let ((dir_a, executable_a), (dir_b, executable_b)) = match built {
+            Some(Ok(built)) => built,
+            Some(Err(e)) => return CompareResponse::error(e, start_time),
+            None => return CompareResponse::killed(start_time),
+        };
+
+        let mut comparisons = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let mut samples = [
+                Samples {
+                    times_ms: Vec::new(),
+                    output: Vec::new(),
+                },
+                Samples {
+                    times_ms: Vec::new(),
+                    output: Vec::new(),
+                },
+            ];
+            for round in 0..warmup_runs + runs {
+                let sides = [(&dir_a, &executable_a), (&dir_b, &executable_b)];
+                for (side, (dir, executable)) in sides.into_iter().enumerate() {
+                    let setup = ChildSetup {
+                        working_dir: Some(dir.path()),
+                        trace_log: None,
+                        keep_stdin_open: false,
+                    };
+                    let outcome = self
+                        .run_executable(
+                            executable,
+                            setup,
+                            &[],
+                            input.as_deref().map(str::as_bytes),
+                            RunLimits::wall_clock(execution_timeout),
+                            &[],
+                            &active,
+                        )
+                        .await;
+                    if active.is_killed() {
+                        return CompareResponse::killed(start_time);
+                    }
+                    if !outcome.status.is_success() {
+                        return CompareResponse::error(
+                            StatusError::new(
+                                outcome.status,
+                                format!(
+                                    "Submission {} failed on input {} ({}): {}",
+                                    ["A", "B"][side],
+                                    index + 1,
+                                    outcome.status,
+                                    outcome.stderr
+                                ),
+                            ),
+                            start_time,
+                        );
+                    }
+                    if round >= warmup_runs {
+                        let run_time = outcome.run_time.unwrap_or_default();
+                        samples[side].times_ms.push(run_time.as_secs_f64() * 1000.0);
+                        samples[side].output = outcome.stdout;
+                    }
+                }
+            }
+            let [a, b] = samples;
+            comparisons.push(InputComparison::new(
+                input,
+                &a.times_ms,
+                &b.times_ms,
+                a.output == b.output,
+            ));
+        }
+
+        CompareResponse {
+            comparisons,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: ExecutionStatus::Success,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/compare",
+    request_body = CompareRequest,
+    responses((status = 200, body = CompareResponse))
+)]
+pub async fn compare(
+    req: CompareRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.compare_submissions(req).await;
+    Ok(warp::reply::json(&result))
+}
@@ -0,0 +1,538 @@
+use crate::project::write_project;
+use crate::reaper::temp_dir;
+use crate::wrapping::{self, WrapMode};
+use crate::RustExecutor;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const MAX_ANALYZER_SESSIONS: usize = 20;
+const ANALYZER_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// Starting up includes loading the standard library sources
+const ANALYZER_START_TIMEOUT: Duration = Duration::from_secs(30);
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_COMPLETION_ITEMS: usize = 100;
+const MAX_SESSION_ID_LEN: usize = 64;
+
+const COMPLETION_CARGO_TOML: &str = r#"[package]
+name = "rust_complete"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+"#;
+
+pub type AnalyzerSessions = Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<AnalyzerSession>>>>>;
+
+type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+// A rust-analyzer process spoken to over stdio. Messages to it go through a
+// channel so the reader can answer the server's own requests.
+struct LanguageServer {
+    _process: tokio::process::Child,
+    outgoing: mpsc::UnboundedSender<Value>,
+    pending: PendingRequests,
+    next_id: u64,
+}
+
+impl LanguageServer {
+    // Nothing in the project is built or run: cargo check, build scripts and
+    // proc macros are all turned off
+    async fn start(project_path: &Path) -> Result<Self, String> {
+        let mut process = tokio::process::Command::new("rust-analyzer")
+            .current_dir(project_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("rust-analyzer is not available: {}", e))?;
+        let (Some(mut stdin), Some(stdout)) = (process.stdin.take(), process.stdout.take()) else {
+            return Err("Failed to open rust-analyzer pipes".to_string());
+        };
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                let body = message.to_string();
+                let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+                if stdin.write_all(frame.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let pending = PendingRequests::default();
+        tokio::spawn(read_messages(stdout, pending.clone(), outgoing.clone()));
+
+        let mut server = Self {
+            _process: process,
+            outgoing,
+            pending,
+            next_id: 1,
+        };
+        let root_uri = file_uri(project_path);
+        let initialize = server.request(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root_uri,
+                "capabilities": {
+                    "textDocument": {
+                        "completion": {
+                            "completionItem": {
+                                "snippetSupport": false,
+                                "documentationFormat": ["plaintext"],
+                            },
+                        },
+                    },
+                },
+                "initializationOptions": {
+                    "checkOnSave": false,
+                    "cargo": { "buildScripts": { "enable": false } },
+                    "procMacro": { "enable": false },
+                },
+            }),
+            ANALYZER_START_TIMEOUT,
+        );
+        initialize.await?;
+        server.notify("initialized", json!({}));
+        Ok(server)
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        limit: Duration,
+    ) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+
+        let response = tokio::time::timeout(limit, rx).await;
+        self.pending.lock().unwrap().remove(&id);
+        let mut response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err("rust-analyzer exited".to_string()),
+            Err(_) => return Err(format!("rust-analyzer did not answer {}", method)),
+        };
+        if let Some(message) = response["error"]["message"].as_str() {
+            return Err(format!("rust-analyzer: {}", message));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+// Routes responses to their pending request and answers requests from the
+// server (configuration, progress tokens) with empty results
+async fn read_messages(
+    stdout: tokio::process::ChildStdout,
+    pending: PendingRequests,
+    outgoing: mpsc::UnboundedSender<Value>,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut content_length = None;
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(length) = header.strip_prefix("Content-Length:") {
+                content_length = length.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(content_length) = content_length else {
+            return;
+        };
+        let mut body = vec![0; content_length];
+        if reader.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        let Ok(message) = serde_json::from_slice::<Value>(&body) else {
+            continue;
+        };
+
+        match (message["id"].as_u64(), message["method"].as_str()) {
+            (Some(id), None) => {
+                if let Some(tx) = pending.lock().unwrap().remove(&id) {
+                    let _ = tx.send(message);
+                }
+            }
+            (_, Some(method)) if !message["id"].is_null() => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let _ = outgoing.send(json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "result": result,
+                }));
+            }
+            // Notifications such as diagnostics and progress are not used
+            _ => {}
+        }
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+// A rust-analyzer instance and the project it analyzes, kept for one editor
+// session so completions after the first do not pay for startup
+pub struct AnalyzerSession {
+    // Declared first so the process is stopped before its directory goes
+    server: LanguageServer,
+    project_dir: TempDir,
+    // Version of src/main.rs last sent; 0 before it was opened
+    version: i32,
+    last_used: Instant,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CompletionRequest {
+    // Chosen by the client, e.g. one per open editor; requests with the same
+    // id share a warm rust-analyzer instance
+    #[serde(rename = "sessionId")]
+    session_id: String,
+    code: String,
+    // Cursor position in `code`, 1-based; `column` counts characters
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompletionItem {
+    label: String,
+    // e.g. "function", "method", "struct", "keyword"
+    kind: Option<String>,
+    // Type or signature, e.g. "fn(&self) -> usize"
+    detail: Option<String>,
+    // Replaces the partial word before the cursor
+    #[serde(rename = "insertText")]
+    insert_text: String,
+    documentation: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompletionResponse {
+    // In rust-analyzer's ranking order, at most 100
+    items: Vec<CompletionItem>,
+    // More items exist; typing further narrows the list
+    #[serde(rename = "isIncomplete")]
+    is_incomplete: bool,
+}
+
+pub enum CompletionError {
+    Invalid(String),
+    Unavailable(String),
+}
+
+impl CompletionError {
+    fn status(&self) -> StatusCode {
+        match self {
+            CompletionError::Invalid(_) => StatusCode::BAD_REQUEST,
+            CompletionError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            CompletionError::Invalid(message) | CompletionError::Unavailable(message) => message,
+        }
+    }
+}
+
+// LSP CompletionItemKind
+fn kind_name(kind: u64) -> Option<&'static str> {
+    let name = match kind {
+        1 => "text",
+        2 => "method",
+        3 => "function",
+        4 => "constructor",
+        5 => "field",
+        6 => "variable",
+        7 => "class",
+        8 => "interface",
+        9 => "module",
+        10 => "property",
+        11 => "unit",
+        12 => "value",
+        13 => "enum",
+        14 => "keyword",
+        15 => "snippet",
+        16 => "color",
+        17 => "file",
+        18 => "reference",
+        19 => "folder",
+        20 => "enumMember",
+        21 => "constant",
+        22 => "struct",
+        23 => "event",
+        24 => "operator",
+        25 => "typeParameter",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn completion_item(item: &Value) -> Option<CompletionItem> {
+    let label = item["label"].as_str()?.to_string();
+    let insert_text = item["textEdit"]["newText"]
+        .as_str()
+        .or_else(|| item["insertText"].as_str())
+        .unwrap_or(&label)
+        .to_string();
+    let documentation = match &item["documentation"] {
+        Value::String(text) => Some(text.clone()),
+        other => other["value"].as_str().map(str::to_string),
+    };
+    Some(CompletionItem {
+        kind: item["kind"]
+            .as_u64()
+            .and_then(kind_name)
+            .map(str::to_string),
+        detail: item["detail"].as_str().map(str::to_string),
+        insert_text,
+        documentation,
+        label,
+    })
+}
+
+// UTF-16 offset of a 1-based character column, as LSP positions count
+fn utf16_column(line: &str, column: usize) -> usize {
+    line.chars().take(column - 1).map(char::len_utf16).sum()
+}
+
+impl RustExecutor {
+    async fn analyzer_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Arc<tokio::sync::Mutex<AnalyzerSession>>, CompletionError> {
+        {
+            let mut sessions = self.analyzer_sessions.lock().unwrap();
+            if let Some(session) = sessions.get(session_id) {
+                return Ok(session.clone());
+            }
+            // Sessions currently completing are locked and are never reclaimed
+            sessions.retain(|_, session| match session.try_lock() {
+                Ok(session) => session.last_used.elapsed() < ANALYZER_IDLE_TIMEOUT,
+                Err(_) => true,
+            });
+            if sessions.len() >= MAX_ANALYZER_SESSIONS {
+                return Err(CompletionError::Unavailable(format!(
+                    "Too many active completion sessions (limit {})",
+                    MAX_ANALYZER_SESSIONS
+                )));
+            }
+        }
+
+        let project_dir = temp_dir().map_err(|e| {
+            CompletionError::Unavailable(format!("Failed to create temp directory: {}", e))
+        })?;
+        write_project(
+            project_dir.path(),
+            COMPLETION_CARGO_TOML,
+            &[("src/main.rs", "fn main() {}\n")],
+        )
+        .map_err(CompletionError::Unavailable)?;
+        let server = LanguageServer::start(project_dir.path())
+            .await
+            .map_err(CompletionError::Unavailable)?;
+
+        let session = Arc::new(tokio::sync::Mutex::new(AnalyzerSession {
+            server,
+            project_dir,
+            version: 0,
+            last_used: Instant::now(),
+        }));
+        // A concurrent first request may have started one as well; keep the
+        // one already registered
+        Ok(self
+            .analyzer_sessions
+            .lock()
+            .unwrap()
+            .entry(session_id.to_string())
+            .or_insert(session)
+            .clone())
+    }
+
+    pub async fn complete(
+        &self,
+        req: CompletionRequest,
+    ) -> Result<CompletionResponse, CompletionError> {
+        if req.session_id.is_empty() || req.session_id.len() > MAX_SESSION_ID_LEN {
+            return Err(CompletionError::Invalid(format!(
+                "sessionId must be 1 to {} characters",
+                MAX_SESSION_ID_LEN
+            )));
+        }
+        let max_code_size_kb = self.config().max_code_size_kb;
+        let code_size_kb = req.code.len() as f64 / 1024.0;
+        if code_size_kb > max_code_size_kb as f64 {
+            return Err(CompletionError::Invalid(format!(
+                "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                code_size_kb, max_code_size_kb
+            )));
+        }
+        let Some(cursor_line) = req
+            .line
+            .checked_sub(1)
+            .and_then(|index| req.code.split('\n').nth(index))
+        else {
+            return Err(CompletionError::Invalid(format!(
+                "line {} is outside the code",
+                req.line
+            )));
+        };
+        if req.column == 0 || req.column > cursor_line.chars().count() + 1 {
+            return Err(CompletionError::Invalid(format!(
+                "column {} is outside line {}",
+                req.column, req.line
+            )));
+        }
+
+        // Analyzed as it would be compiled, so the prelude imports resolve
+        let program = wrapping::wrap(&req.code, WrapMode::Auto);
+        let line = Self::user_line_offset(&program, &req.code) + req.line - 1;
+        let character = utf16_column(cursor_line, req.column);
+
+        let session = self.analyzer_session(&req.session_id).await?;
+        let mut session = session.lock().await;
+        session.last_used = Instant::now();
+        let main_rs = session.project_dir.path().join("src").join("main.rs");
+        let uri = file_uri(&main_rs);
+        session.version += 1;
+        if session.version == 1 {
+            session.server.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "rust",
+                        "version": session.version,
+                        "text": program,
+                    },
+                }),
+            );
+        } else {
+            session.server.notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": uri, "version": session.version },
+                    "contentChanges": [{ "text": program }],
+                }),
+            );
+        }
+
+        let result = session
+            .server
+            .request(
+                "textDocument/completion",
+                json!({
+                    "textDocument": { "uri": uri },
+                    "position": { "line": line, "character": character },
+                }),
+                COMPLETION_TIMEOUT,
+            )
+            .await
+            .map_err(CompletionError::Unavailable)?;
+
+        // Either a bare list of items or a CompletionList
+        let (mut raw_items, is_incomplete) = match result {
+            Value::Array(items) => (items, false),
+            Value::Object(mut list) => (
+                list.remove("items")
+                    .and_then(|items| match items {
+                        Value::Array(items) => Some(items),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+                list.get("isIncomplete").and_then(Value::as_bool) == Some(true),
+            ),
+            _ => (Vec::new(), false),
+        };
+        raw_items.sort_by(|a, b| {
+            let key = |item: &Value| {
+                item["sortText"]
+                    .as_str()
+                    .or_else(|| item["label"].as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            key(a).cmp(&key(b))
+        });
+        let is_incomplete = is_incomplete || raw_items.len() > MAX_COMPLETION_ITEMS;
+        let items = raw_items
+            .iter()
+            .take(MAX_COMPLETION_ITEMS)
+            .filter_map(completion_item)
+            .collect();
+        Ok(CompletionResponse {
+            items,
+            is_incomplete,
+        })
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/complete",
+    request_body = CompletionRequest,
+    responses(
+        (status = 200, body = CompletionResponse),
+        (status = 400, description = "Invalid cursor position or code too large", body = ErrorResponse),
+        (status = 503, description = "rust-analyzer unavailable or too many sessions", body = ErrorResponse)
+    )
+)]
+pub async fn complete(
+    req: CompletionRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = match executor.complete(req).await {
+        Ok(completions) => {
+            warp::reply::with_status(warp::reply::json(&completions), StatusCode::OK)
+        }
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e.message() })),
+            e.status(),
+        ),
+    };
+    Ok(reply)
+}
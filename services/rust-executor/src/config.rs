@@ -0,0 +1,242 @@
+use crate::bwrap;
+use crate::quota::TenantQuotaConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+// Limits read from the TOML file named by CONFIG_FILE, with environment
+// variables taking precedence over the file. Reloaded on SIGHUP and
+// POST /admin/reload; a config that fails to load leaves the current one in
+// place.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutorConfig {
+    // Default run timeout in seconds
+    pub max_execution_time: u64,
+    // Largest per-request `timeout` a client may ask for
+    pub max_request_timeout: u64,
+    // RLIMIT_DATA of every user program; going past it ends the run with
+    // status "memory_limit"
+    pub max_memory_mb: u32,
+    pub max_code_size_kb: u32,
+    pub max_source_files: usize,
+    // Combined decoded size of a request's dataFiles
+    pub max_data_files_kb: u32,
+    // What a program may write to stdout, and to stderr; going past it
+    // stops the program with status "output_limit"
+    pub max_output_kb: u32,
+    // Combined size of the output files returned with a response
+    pub max_output_files_kb: u32,
+    // Lines of `output` and `error` returned; longer output keeps its first
+    // and last lines around an omission marker
+    pub max_output_lines: usize,
+    // Default build timeout in seconds, separate from the run timeout
+    pub compile_timeout: u64,
+    // Largest per-request `compileTimeout` a client may ask for
+    pub max_compile_timeout: u64,
+    // Executions allowed in flight before /ready reports not ready
+    pub max_active_executions: usize,
+    // Host pressure at which new executions are rejected with status
+    // "capacity" rather than accepted and left to time out; 0 disables a
+    // check. The load is the 1-minute load average divided by the CPUs.
+    pub max_load_per_cpu: f64,
+    pub min_free_memory_mb: u64,
+    // On the volume holding the temp directories
+    pub min_free_disk_mb: u64,
+    // Inject `#![forbid(unsafe_code)]` into every execution request, whatever
+    // its forbidUnsafe says
+    pub forbid_unsafe_code: bool,
+    // How programs are isolated: "process-group" runs them as ordinary
+    // processes, "bwrap" inside bubblewrap (see bwrap.rs), which needs no
+    // root, only unprivileged user namespaces
+    pub sandbox: String,
+    // Variable names (or PREFIX_* patterns) a request may set for the child
+    pub allowed_env_vars: Vec<String>,
+    // Crates a request may list in `dependencies`, by name, with the
+    // version requirement they are built at. Each must be vendored (see
+    // submission-deps/); none are allowed by default.
+    pub allowed_crates: BTreeMap<String, String>,
+    // Unstable features a submission may enable with `#![feature(...)]` when
+    // projects build with a nightly toolchain; none by default
+    pub allowed_features: Vec<String>,
+    // Roles allowed to queue jobs with priority "high". The role is the
+    // X-Requester-Role header of a request carrying the admin token, or
+    // "admin" for the token alone; other requests queue "high" as normal.
+    pub high_priority_roles: Vec<String>,
+    // PEM certificate chain and private key. When both are set the HTTP
+    // listener serves HTTPS; replaced files are picked up without a restart.
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub tenant_quotas: TenantQuotaConfig,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            max_execution_time: 30,
+            max_request_timeout: 60,
+            max_memory_mb: 128,
+            max_code_size_kb: 50,
+            max_source_files: 32,
+            max_data_files_kb: 1024,
+            max_output_kb: 8192,
+            max_output_files_kb: 1024,
+            max_output_lines: 1000,
+            compile_timeout: 30,
+            max_compile_timeout: 120,
+            max_active_executions: 8,
+            max_load_per_cpu: 4.0,
+            min_free_memory_mb: 256,
+            min_free_disk_mb: 512,
+            forbid_unsafe_code: false,
+            sandbox: bwrap::PROCESS_GROUP.to_string(),
+            allowed_env_vars: ["APP_*", "STUDENT_*", "RUST_BACKTRACE", "RUST_LOG"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            allowed_crates: BTreeMap::new(),
+            allowed_features: Vec::new(),
+            high_priority_roles: vec!["admin".to_string()],
+            tls_cert_path: None,
+            tls_key_path: None,
+            tenant_quotas: TenantQuotaConfig::default(),
+        }
+    }
+}
+
+fn env_override<T: std::str::FromStr>(name: &str, target: &mut T) -> Result<(), String> {
+    if let Ok(value) = env::var(name) {
+        *target = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: '{}'", name, value))?;
+    }
+    Ok(())
+}
+
+impl ExecutorConfig {
+    pub fn load() -> Result<Self, String> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) if !path.is_empty() => {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+                toml::from_str(&content)
+                    .map_err(|e| format!("Invalid config file {}: {}", path, e))?
+            }
+            _ => ExecutorConfig::default(),
+        };
+
+        env_override("MAX_EXECUTION_TIME", &mut config.max_execution_time)?;
+        env_override("MAX_REQUEST_TIMEOUT", &mut config.max_request_timeout)?;
+        env_override("MAX_MEMORY_MB", &mut config.max_memory_mb)?;
+        env_override("MAX_CODE_SIZE_KB", &mut config.max_code_size_kb)?;
+        env_override("MAX_SOURCE_FILES", &mut config.max_source_files)?;
+        env_override("MAX_DATA_FILES_KB", &mut config.max_data_files_kb)?;
+        env_override("MAX_OUTPUT_KB", &mut config.max_output_kb)?;
+        env_override("MAX_OUTPUT_FILES_KB", &mut config.max_output_files_kb)?;
+        env_override("MAX_OUTPUT_LINES", &mut config.max_output_lines)?;
+        env_override("COMPILE_TIMEOUT", &mut config.compile_timeout)?;
+        env_override("MAX_COMPILE_TIMEOUT", &mut config.max_compile_timeout)?;
+        env_override("MAX_ACTIVE_EXECUTIONS", &mut config.max_active_executions)?;
+        env_override("MAX_LOAD_PER_CPU", &mut config.max_load_per_cpu)?;
+        env_override("MIN_FREE_MEMORY_MB", &mut config.min_free_memory_mb)?;
+        env_override("MIN_FREE_DISK_MB", &mut config.min_free_disk_mb)?;
+        env_override("FORBID_UNSAFE_CODE", &mut config.forbid_unsafe_code)?;
+        env_override("SANDBOX", &mut config.sandbox)?;
+        if let Ok(names) = env::var("ALLOWED_ENV_VARS") {
+            config.allowed_env_vars = names
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+        // name=version pairs, e.g. "rand=0.8,itertools=0.13"
+        if let Ok(crates) = env::var("ALLOWED_CRATES") {
+            config.allowed_crates = crates
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    entry
+                        .split_once('=')
+                        .map(|(name, version)| {
+                            (name.trim().to_string(), version.trim().to_string())
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid value for ALLOWED_CRATES: '{}' is not name=version",
+                                entry
+                            )
+                        })
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Ok(features) = env::var("ALLOWED_FEATURES") {
+            config.allowed_features = features
+                .split(',')
+                .map(|feature| feature.trim().to_string())
+                .filter(|feature| !feature.is_empty())
+                .collect();
+        }
+        if let Ok(roles) = env::var("HIGH_PRIORITY_ROLES") {
+            config.high_priority_roles = roles
+                .split(',')
+                .map(|role| role.trim().to_string())
+                .filter(|role| !role.is_empty())
+                .collect();
+        }
+        if let Ok(path) = env::var("TLS_CERT_PATH") {
+            config.tls_cert_path = Some(path).filter(|path| !path.is_empty());
+        }
+        if let Ok(path) = env::var("TLS_KEY_PATH") {
+            config.tls_key_path = Some(path).filter(|path| !path.is_empty());
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("max_execution_time", self.max_execution_time),
+            ("max_request_timeout", self.max_request_timeout),
+            ("max_memory_mb", self.max_memory_mb as u64),
+            ("max_code_size_kb", self.max_code_size_kb as u64),
+            ("max_source_files", self.max_source_files as u64),
+            ("max_data_files_kb", self.max_data_files_kb as u64),
+            ("max_output_kb", self.max_output_kb as u64),
+            ("max_output_files_kb", self.max_output_files_kb as u64),
+            ("max_output_lines", self.max_output_lines as u64),
+            ("compile_timeout", self.compile_timeout),
+            ("max_compile_timeout", self.max_compile_timeout),
+            ("max_active_executions", self.max_active_executions as u64),
+        ];
+        for (name, value) in positive {
+            if value == 0 {
+                return Err(format!("{} must be greater than zero", name));
+            }
+        }
+        if !(0.0..f64::INFINITY).contains(&self.max_load_per_cpu) {
+            return Err("max_load_per_cpu must be zero or a positive number".to_string());
+        }
+        if self.max_execution_time > self.max_request_timeout {
+            return Err("max_execution_time cannot exceed max_request_timeout".to_string());
+        }
+        if self.compile_timeout > self.max_compile_timeout {
+            return Err("compile_timeout cannot exceed max_compile_timeout".to_string());
+        }
+        if ![bwrap::PROCESS_GROUP, bwrap::SANDBOX_BWRAP].contains(&self.sandbox.as_str()) {
+            return Err(format!(
+                "sandbox must be \"{}\" or \"{}\", not \"{}\"",
+                bwrap::PROCESS_GROUP,
+                bwrap::SANDBOX_BWRAP,
+                self.sandbox
+            ));
+        }
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            return Err("tls_cert_path and tls_key_path must be set together".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,606 @@
+use crate::project::{build_executable, write_project};
+use crate::readiness::KILLED_MESSAGE;
+use crate::reaper::temp_dir;
+use crate::status::StatusError;
+use crate::wrapping::{self, WrapMode};
+use crate::{ChildSetup, ExecutionStatus, RunLimits, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+const COVERAGE_CARGO_TOML: &str = r#"[package]
+name = "rust_coverage"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+"#;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CodeCoverageRequest {
+    code: String,
+    inputs: Option<Vec<String>>,
+    tests: Option<String>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LineCoverage {
+    line: usize,
+    count: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CoverageRun {
+    input: Option<String>,
+    output: String,
+    error: String,
+    status: ExecutionStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CodeCoverageResponse {
+    lines: Vec<LineCoverage>,
+    #[serde(rename = "linesCovered")]
+    lines_covered: usize,
+    #[serde(rename = "linesTotal")]
+    lines_total: usize,
+    #[serde(rename = "coveragePercent")]
+    coverage_percent: f64,
+    // llvm-cov's JSON export (`llvm-cov export -format=text`) for the
+    // submitted code: line numbers are the user's, and regions, branches and
+    // functions of the wrapper and the tests are dropped. Their summaries
+    // would count those too, so they are left out.
+    #[serde(rename = "llvmCov")]
+    #[schema(value_type = Object)]
+    llvm_cov: serde_json::Value,
+    runs: Vec<CoverageRun>,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    // The first run that failed decides it
+    status: ExecutionStatus,
+}
+
+impl CodeCoverageResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            lines: vec![],
+            lines_covered: 0,
+            lines_total: 0,
+            coverage_percent: 0.0,
+            llvm_cov: serde_json::Value::Null,
+            runs: vec![],
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+impl RustExecutor {
+    pub async fn collect_coverage(&self, req: CodeCoverageRequest) -> CodeCoverageResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("coverage", None) {
+            Ok(active) => active,
+            Err(e) => return CodeCoverageResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        let code_size_kb =
+            (req.code.len() + req.tests.as_deref().map_or(0, str::len)) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return CodeCoverageResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) = self
+            .check_unstable_features(std::iter::once(req.code.as_str()).chain(req.tests.as_deref()))
+        {
+            return CodeCoverageResponse::error(e, start_time);
+        }
+
+        let temp_dir = match temp_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                return CodeCoverageResponse::error(
+                    StatusError::internal(format!("Failed to create temp directory: {}", e)),
+                    start_time,
+                );
+            }
+        };
+        let project_path = temp_dir.path();
+
+        // Student tests are compiled into the same crate so they can reach
+        // private items, exactly like an inline `mod tests`
+        let forbid_unsafe = match self
+            .forbids_unsafe(std::iter::once(req.code.as_str()).chain(req.tests.as_deref()))
+        {
+            Ok(forbid_unsafe) => forbid_unsafe,
+            Err(e) => return CodeCoverageResponse::error(e, start_time),
+        };
+        let mut main_rs = wrapping::wrap(&req.code, WrapMode::Auto);
+        if forbid_unsafe {
+            main_rs = wrapping::forbid_unsafe(&main_rs);
+        }
+        let line_offset = Self::user_line_offset(&main_rs, &req.code);
+        if let Some(tests) = &req.tests {
+            main_rs.push_str(&format!(
+                "\n\n#[cfg(test)]\nmod student_tests {{\n    use super::*;\n\n{}\n}}\n",
+                tests
+            ));
+        }
+
+        if let Err(e) = write_project(
+            project_path,
+            COVERAGE_CARGO_TOML,
+            &[("src/main.rs", &main_rs)],
+        ) {
+            return CodeCoverageResponse::error(StatusError::internal(e), start_time);
+        }
+
+        let mut objects = Vec::new();
+        let mut runs = Vec::new();
+        let inputs = req.inputs.unwrap_or_default();
+
+        if !inputs.is_empty() || req.tests.is_none() {
+            active.set_phase("compiling");
+            let built = active
+                .until_killed(build_instrumented(
+                    project_path,
+                    &["build", "--bin", "main"],
+                ))
+                .await;
+            let executable = match built {
+                Some(Ok(path)) => path,
+                Some(Err(e)) => return CodeCoverageResponse::error(e, start_time),
+                None => return CodeCoverageResponse::killed(start_time),
+            };
+
+            let run_inputs: Vec<Option<String>> = if inputs.is_empty() {
+                vec![None]
+            } else {
+                inputs.into_iter().map(Some).collect()
+            };
+            for (index, input) in run_inputs.into_iter().enumerate() {
+                let profile_file = project_path.join(format!("coverage-run-{}-%p.profraw", index));
+                let outcome = self
+                    .run_executable(
+                        &executable,
+                        ChildSetup::default(),
+                        &[],
+                        input.as_deref().map(str::as_bytes),
+                        RunLimits::wall_clock(execution_timeout),
+                        &[(
+                            "LLVM_PROFILE_FILE".to_string(),
+                            profile_file.display().to_string(),
+                        )],
+                        &active,
+                    )
+                    .await;
+                if active.is_killed() {
+                    return CodeCoverageResponse::killed(start_time);
+                }
+                runs.push(CoverageRun {
+                    input,
+                    output: outcome.stdout_text(),
+                    error: outcome.stderr,
+                    status: outcome.status,
+                });
+            }
+            objects.push(executable);
+        }
+
+        if req.tests.is_some() {
+            active.set_phase("compiling");
+            let built = active
+                .until_killed(build_instrumented(
+                    project_path,
+                    &["test", "--no-run", "--bin", "main"],
+                ))
+                .await;
+            let test_executable = match built {
+                Some(Ok(path)) => path,
+                Some(Err(e)) => return CodeCoverageResponse::error(e, start_time),
+                None => return CodeCoverageResponse::killed(start_time),
+            };
+            let profile_file = project_path.join("coverage-tests-%p.profraw");
+            let outcome = self
+                .run_executable(
+                    &test_executable,
+                    ChildSetup::default(),
+                    &[],
+                    None,
+                    RunLimits::wall_clock(execution_timeout),
+                    &[(
+                        "LLVM_PROFILE_FILE".to_string(),
+                        profile_file.display().to_string(),
+                    )],
+                    &active,
+                )
+                .await;
+            if active.is_killed() {
+                return CodeCoverageResponse::killed(start_time);
+            }
+            runs.push(CoverageRun {
+                input: None,
+                output: outcome.stdout_text(),
+                error: outcome.stderr,
+                status: outcome.status,
+            });
+            objects.push(test_executable);
+        }
+
+        let exported = async {
+            let profdata = merge_profiles(project_path).await?;
+            let lcov = export(project_path, &profdata, &objects, "lcov").await?;
+            let json = export(project_path, &profdata, &objects, "text").await?;
+            let json = serde_json::from_slice(&json)
+                .map_err(|e| format!("llvm-cov produced invalid JSON: {}", e))?;
+            Ok::<_, String>((line_counts(&lcov), json))
+        };
+        let (line_counts, mut llvm_cov) = match exported.await {
+            Ok(exported) => exported,
+            Err(e) => return CodeCoverageResponse::error(StatusError::internal(e), start_time),
+        };
+
+        // Drop instrumented lines that belong to the wrapper or the tests
+        let user_lines = req.code.lines().count();
+        let main_rs_path = project_path.join("src").join("main.rs");
+        remap_export(
+            &mut llvm_cov,
+            &main_rs_path.display().to_string(),
+            line_offset,
+            user_lines,
+        );
+        let lines: Vec<LineCoverage> = line_counts
+            .into_iter()
+            .filter(|(line, _)| *line > line_offset && *line <= line_offset + user_lines)
+            .map(|(line, count)| LineCoverage {
+                line: line - line_offset,
+                count,
+            })
+            .collect();
+
+        let lines_total = lines.len();
+        let lines_covered = lines.iter().filter(|l| l.count > 0).count();
+        let coverage_percent = if lines_total == 0 {
+            0.0
+        } else {
+            lines_covered as f64 * 100.0 / lines_total as f64
+        };
+        let status = runs
+            .iter()
+            .map(|run| run.status)
+            .find(|status| !status.is_success())
+            .unwrap_or(ExecutionStatus::Success);
+
+        CodeCoverageResponse {
+            lines,
+            lines_covered,
+            lines_total,
+            coverage_percent,
+            llvm_cov,
+            runs,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status,
+        }
+    }
+}
+
+async fn build_instrumented(
+    project_path: &Path,
+    cargo_args: &[&str],
+) -> Result<PathBuf, StatusError> {
+    build_executable(
+        project_path,
+        cargo_args,
+        Some("-C instrument-coverage"),
+        Duration::from_secs(60),
+    )
+    .await
+}
+
+// Merges the raw profiles the runs left in the project
+async fn merge_profiles(project_path: &Path) -> Result<PathBuf, String> {
+    let profraw_files: Vec<PathBuf> = fs::read_dir(project_path)
+        .map_err(|e| format!("Failed to read coverage profiles: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "profraw"))
+        .collect();
+    if profraw_files.is_empty() {
+        return Err("Program produced no coverage data".to_string());
+    }
+
+    let profdata = project_path.join("coverage.profdata");
+    let merge = tokio::process::Command::new(llvm_tool("llvm-profdata").await)
+        .arg("merge")
+        .arg("-sparse")
+        .args(&profraw_files)
+        .arg("-o")
+        .arg(&profdata)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute llvm-profdata: {}", e))?;
+    if !merge.status.success() {
+        return Err(format!(
+            "llvm-profdata failed: {}",
+            String::from_utf8_lossy(&merge.stderr)
+        ));
+    }
+    Ok(profdata)
+}
+
+// Coverage of src/main.rs in one of llvm-cov's export formats: "lcov", or
+// "text" for JSON
+async fn export(
+    project_path: &Path,
+    profdata: &Path,
+    objects: &[PathBuf],
+    format: &str,
+) -> Result<Vec<u8>, String> {
+    let mut cmd = tokio::process::Command::new(llvm_tool("llvm-cov").await);
+    cmd.arg("export")
+        .arg(format!("-format={}", format))
+        .arg(format!("-instr-profile={}", profdata.display()));
+    for (index, object) in objects.iter().enumerate() {
+        if index > 0 {
+            cmd.arg("-object");
+        }
+        cmd.arg(object);
+    }
+    cmd.arg(project_path.join("src").join("main.rs"));
+
+    let export = cmd
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute llvm-cov: {}", e))?;
+    if !export.status.success() {
+        return Err(format!(
+            "llvm-cov failed: {}",
+            String::from_utf8_lossy(&export.stderr)
+        ));
+    }
+    Ok(export.stdout)
+}
+
+// Per-line execution counts from the lcov export
+fn line_counts(lcov: &[u8]) -> BTreeMap<usize, u64> {
+    let mut counts = BTreeMap::new();
+    for line in String::from_utf8_lossy(lcov).lines() {
+        if let Some(data) = line.strip_prefix("DA:") {
+            let mut parts = data.split(',');
+            if let (Some(Ok(line_no)), Some(Ok(count))) = (
+                parts.next().map(str::parse::<usize>),
+                parts.next().map(str::parse::<u64>),
+            ) {
+                *counts.entry(line_no).or_insert(0) += count;
+            }
+        }
+    }
+    counts
+}
+
+// Keeps the entries of a list of llvm-cov arrays (segments, regions,
+// branches) that start in the user's code, shifting the line numbers at
+// `line_fields` to the user's. Entries with a file id at `file_field` must
+// be in the function's own file, id 0.
+fn remap_entries(
+    entries: Option<&mut serde_json::Value>,
+    line_fields: &[usize],
+    file_field: Option<usize>,
+    line_offset: usize,
+    user_lines: usize,
+) {
+    let Some(serde_json::Value::Array(entries)) = entries else {
+        return;
+    };
+    let in_user_code = |line: u64| {
+        let line = line as usize;
+        line > line_offset && line <= line_offset + user_lines
+    };
+    entries.retain(|entry| {
+        file_field.is_none_or(|field| entry[field] == 0)
+            && entry
+                .get(line_fields[0])
+                .and_then(serde_json::Value::as_u64)
+                .is_some_and(in_user_code)
+    });
+    for entry in entries.iter_mut() {
+        for &field in line_fields {
+            if let Some(line) = entry.get(field).and_then(serde_json::Value::as_u64) {
+                entry[field] = (line.saturating_sub(line_offset as u64)).into();
+            }
+        }
+    }
+}
+
+// Rewrites llvm-cov's JSON export to the user's coordinates, with the
+// project's temp path shown as src/main.rs; see CodeCoverageResponse::llvm_cov
+fn remap_export(
+    export: &mut serde_json::Value,
+    main_rs_path: &str,
+    line_offset: usize,
+    user_lines: usize,
+) {
+    let Some(serde_json::Value::Array(data)) = export.get_mut("data") else {
+        return;
+    };
+    for data in data {
+        if let Some(data) = data.as_object_mut() {
+            data.remove("totals");
+        }
+        if let Some(serde_json::Value::Array(files)) = data.get_mut("files") {
+            files.retain(|file| file["filename"] == main_rs_path);
+            for file in files {
+                file["filename"] = "src/main.rs".into();
+                if let Some(file) = file.as_object_mut() {
+                    file.remove("summary");
+                    file.remove("expansions");
+                    file.remove("mcdc_records");
+                }
+                // [line, col, count, hasCount, isRegionEntry, isGapRegion]
+                remap_entries(
+                    file.get_mut("segments"),
+                    &[0],
+                    None,
+                    line_offset,
+                    user_lines,
+                );
+                // [lineStart, colStart, lineEnd, colEnd, ...]
+                remap_entries(
+                    file.get_mut("branches"),
+                    &[0, 2],
+                    None,
+                    line_offset,
+                    user_lines,
+                );
+            }
+        }
+        if let Some(serde_json::Value::Array(functions)) = data.get_mut("functions") {
+            // A function's regions point into its filenames by index; only
+            // those of its first file, where it is defined, are the user's
+            functions.retain(|function| function["filenames"][0] == main_rs_path);
+            for function in functions.iter_mut() {
+                function["filenames"][0] = "src/main.rs".into();
+                // [lineStart, colStart, lineEnd, colEnd, count, fileId, ...]
+                remap_entries(
+                    function.get_mut("regions"),
+                    &[0, 2],
+                    Some(5),
+                    line_offset,
+                    user_lines,
+                );
+                // [..., count, falseCount, fileId, ...]
+                remap_entries(
+                    function.get_mut("branches"),
+                    &[0, 2],
+                    Some(6),
+                    line_offset,
+                    user_lines,
+                );
+            }
+            functions.retain(|function| {
+                function["regions"]
+                    .as_array()
+                    .is_some_and(|regions| !regions.is_empty())
+            });
+        }
+    }
+}
+
+// The llvm tools shipped with the toolchain (llvm-tools-preview) match the
+// profile format rustc emits, so prefer them over whatever is on PATH
+async fn llvm_tool(name: &str) -> PathBuf {
+    if let Ok(output) = tokio::process::Command::new("rustc")
+        .arg("--print")
+        .arg("target-libdir")
+        .output()
+        .await
+    {
+        let libdir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        if let Some(candidate) = libdir.parent().map(|p| p.join("bin").join(name)) {
+            if candidate.exists() {
+                return candidate;
+            }
+        }
+    }
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MAIN_RS: &str = "/tmp/project/src/main.rs";
+
+    #[test]
+    fn line_counts_sum_lcov_lines() {
+        let lcov = b"SF:main.rs\nDA:3,1\nDA:4,0\nDA:3,2\nend_of_record\n";
+        let counts = line_counts(lcov);
+        assert_eq!(counts.get(&3), Some(&3));
+        assert_eq!(counts.get(&4), Some(&0));
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn remap_export_keeps_only_user_lines() {
+        // User code on lines 3..=5 of the generated main.rs
+        let mut export = json!({
+            "type": "llvm.coverage.json.export",
+            "data": [{
+                "files": [
+                    {
+                        "filename": MAIN_RS,
+                        "segments": [[1, 1, 1, true, true, false], [3, 5, 2, true, true, false], [6, 1, 0, false, false, false]],
+                        "branches": [[4, 8, 4, 13, 2, 1, 0, 0, 4]],
+                        "summary": {"lines": {"count": 9}}
+                    },
+                    {"filename": "/rustc/library/std/src/macros.rs", "segments": []}
+                ],
+                "functions": [
+                    {
+                        "name": "main::f",
+                        "count": 2,
+                        "filenames": [MAIN_RS, "/rustc/library/std/src/macros.rs"],
+                        "regions": [[3, 1, 5, 2, 2, 0, 0, 0], [10, 1, 12, 2, 2, 1, 0, 0]],
+                        "branches": []
+                    },
+                    {
+                        "name": "main::main",
+                        "count": 1,
+                        "filenames": [MAIN_RS],
+                        "regions": [[7, 1, 9, 2, 1, 0, 0, 0]],
+                        "branches": []
+                    },
+                    {
+                        "name": "std::rt::lang_start",
+                        "count": 1,
+                        "filenames": ["/rustc/library/std/src/rt.rs"],
+                        "regions": [[3, 1, 4, 2, 1, 0, 0, 0]]
+                    }
+                ],
+                "totals": {"lines": {"count": 9}}
+            }]
+        });
+
+        remap_export(&mut export, MAIN_RS, 2, 3);
+
+        let data = &export["data"][0];
+        assert!(data.get("totals").is_none());
+        let files = data["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["filename"], "src/main.rs");
+        assert!(files[0].get("summary").is_none());
+        assert_eq!(files[0]["segments"], json!([[1, 5, 2, true, true, false]]));
+        assert_eq!(files[0]["branches"], json!([[2, 8, 2, 13, 2, 1, 0, 0, 4]]));
+        let functions = data["functions"].as_array().unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0]["name"], "main::f");
+        assert_eq!(functions[0]["filenames"][0], "src/main.rs");
+        assert_eq!(functions[0]["regions"], json!([[1, 1, 3, 2, 2, 0, 0, 0]]));
+    }
+}
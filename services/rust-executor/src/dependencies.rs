@@ -0,0 +1,81 @@
+use crate::config::ExecutorConfig;
+use crate::status::{ExecutionStatus, StatusError};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CRATE_VENDOR_DIR: &str = "/opt/crate-vendor";
+
+// Where the image vendors the crates of submission-deps/ (CRATE_VENDOR_DIR).
+// Submissions with dependencies build from it with cargo offline, so
+// executions never reach crates.io.
+pub fn vendor_dir() -> PathBuf {
+    PathBuf::from(env::var("CRATE_VENDOR_DIR").unwrap_or_else(|_| DEFAULT_CRATE_VENDOR_DIR.into()))
+}
+
+// `cargo vendor` names a crate's directory after it, with the version
+// appended when several versions are vendored
+fn is_vendored(vendor_dir: &Path, name: &str) -> bool {
+    let versioned = format!("{}-", name);
+    fs::read_dir(vendor_dir).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            file_name == name
+                || file_name
+                    .strip_prefix(&versioned)
+                    .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        })
+    })
+}
+
+// The [dependencies] entries for a request's `dependencies`, at the
+// versions allowed_crates fixes. An empty string when there are none.
+pub fn cargo_dependencies(
+    config: &ExecutorConfig,
+    requested: &[String],
+    deterministic: bool,
+) -> Result<String, StatusError> {
+    let requested: BTreeSet<&str> = requested.iter().map(|name| name.trim()).collect();
+    if requested.is_empty() {
+        return Ok(String::new());
+    }
+    let vendor_dir = vendor_dir();
+    let mut entries = String::new();
+    for name in requested {
+        let Some(version) = config.allowed_crates.get(name) else {
+            let allowed: Vec<&str> = config.allowed_crates.keys().map(String::as_str).collect();
+            return Err(StatusError::new(
+                ExecutionStatus::SecurityViolation,
+                if allowed.is_empty() {
+                    format!("Crate '{}' is not allowed: no dependencies are", name)
+                } else {
+                    format!(
+                        "Crate '{}' is not allowed, expected one of: {}",
+                        name,
+                        allowed.join(", ")
+                    )
+                },
+            ));
+        };
+        // The seeded shim is a module named rand, which the crate would
+        // clash with
+        if deterministic && name == "rand" {
+            return Err(
+                "deterministic provides its own seeded rand; remove rand from dependencies"
+                    .to_string()
+                    .into(),
+            );
+        }
+        if !is_vendored(&vendor_dir, name) {
+            return Err(StatusError::internal(format!(
+                "Crate '{}' is allowed but not vendored in {}",
+                name,
+                vendor_dir.display()
+            )));
+        }
+        entries.push_str(&format!("{} = \"{}\"\n", name, version));
+    }
+    Ok(entries)
+}
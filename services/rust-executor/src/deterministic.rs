@@ -0,0 +1,661 @@
+// Deterministic execution: std::time is redirected to a virtual clock and a
+// seeded, rand-compatible RNG module is provided so that repeated runs of
+// the same program produce byte-identical output.
+
+use proc_macro2::{LineColumn, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::visit::{self, Visit};
+use syn::{ItemUse, Macro, Path, PathSegment, UseTree};
+
+const TIME_SHIM: &str = r#"
+#[allow(dead_code)]
+mod __scriptsmith_time {
+    pub use std::time::*;
+    use std::ops::{Add, AddAssign, Sub, SubAssign};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Every clock reading advances the virtual clock by one millisecond,
+    // starting from 2024-01-01T00:00:00Z
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+    const START_SECS: u64 = 1_704_067_200;
+
+    fn tick() -> Duration {
+        Duration::from_millis(TICKS.fetch_add(1, Ordering::SeqCst))
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn now() -> Instant {
+            Instant(tick())
+        }
+        pub fn elapsed(&self) -> Duration {
+            Instant::now().0.saturating_sub(self.0)
+        }
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.0.checked_sub(earlier.0)
+        }
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_add(duration).map(Instant)
+        }
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_sub(duration).map(Instant)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+        fn add(self, rhs: Duration) -> Instant {
+            Instant(self.0 + rhs)
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, rhs: Duration) {
+            self.0 += rhs;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+        fn sub(self, rhs: Duration) -> Instant {
+            Instant(self.0 - rhs)
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, rhs: Duration) {
+            self.0 -= rhs;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+        fn sub(self, rhs: Instant) -> Duration {
+            self.duration_since(rhs)
+        }
+    }
+
+    // Wraps the real SystemTime so SystemTimeError keeps working
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct SystemTime(std::time::SystemTime);
+
+    pub const UNIX_EPOCH: SystemTime = SystemTime(std::time::UNIX_EPOCH);
+
+    impl SystemTime {
+        pub const UNIX_EPOCH: SystemTime = UNIX_EPOCH;
+
+        pub fn now() -> SystemTime {
+            SystemTime(std::time::UNIX_EPOCH + Duration::from_secs(START_SECS) + tick())
+        }
+        pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+            self.0.duration_since(earlier.0)
+        }
+        pub fn elapsed(&self) -> Result<Duration, SystemTimeError> {
+            SystemTime::now().duration_since(*self)
+        }
+        pub fn checked_add(&self, duration: Duration) -> Option<SystemTime> {
+            self.0.checked_add(duration).map(SystemTime)
+        }
+        pub fn checked_sub(&self, duration: Duration) -> Option<SystemTime> {
+            self.0.checked_sub(duration).map(SystemTime)
+        }
+    }
+
+    impl Add<Duration> for SystemTime {
+        type Output = SystemTime;
+        fn add(self, rhs: Duration) -> SystemTime {
+            SystemTime(self.0 + rhs)
+        }
+    }
+
+    impl Sub<Duration> for SystemTime {
+        type Output = SystemTime;
+        fn sub(self, rhs: Duration) -> SystemTime {
+            SystemTime(self.0 - rhs)
+        }
+    }
+}
+"#;
+
+// A small subset of the `rand` 0.8 API backed by SplitMix64, so programs
+// written against `rand` compile without the crate and replay identically
+const RAND_SHIM: &str = r#"
+#[allow(dead_code)]
+mod rand {
+    use std::cell::Cell;
+    use std::ops::{Range, RangeInclusive};
+
+    pub const SEED: u64 = __SCRIPTSMITH_SEED__;
+
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(SEED);
+    }
+
+    fn next_u64() -> u64 {
+        STATE.with(|state| {
+            let next = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+            state.set(next);
+            let mut z = next;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        })
+    }
+
+    fn next_f64() -> f64 {
+        (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub trait Standard: Sized {
+        fn sample() -> Self;
+    }
+
+    macro_rules! standard_int {
+        ($($t:ty),*) => {$(
+            impl Standard for $t {
+                fn sample() -> Self {
+                    next_u64() as $t
+                }
+            }
+        )*};
+    }
+    standard_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+    impl Standard for u128 {
+        fn sample() -> Self {
+            ((next_u64() as u128) << 64) | next_u64() as u128
+        }
+    }
+
+    impl Standard for i128 {
+        fn sample() -> Self {
+            u128::sample() as i128
+        }
+    }
+
+    impl Standard for bool {
+        fn sample() -> Self {
+            next_u64() & 1 == 1
+        }
+    }
+
+    impl Standard for f64 {
+        fn sample() -> Self {
+            next_f64()
+        }
+    }
+
+    impl Standard for f32 {
+        fn sample() -> Self {
+            next_f64() as f32
+        }
+    }
+
+    impl Standard for char {
+        fn sample() -> Self {
+            char::from(b'a' + (next_u64() % 26) as u8)
+        }
+    }
+
+    pub trait SampleRange<T> {
+        fn sample_single(self) -> T;
+    }
+
+    macro_rules! range_int {
+        ($($t:ty => $u:ty),*) => {$(
+            impl SampleRange<$t> for Range<$t> {
+                fn sample_single(self) -> $t {
+                    assert!(self.start < self.end, "cannot sample empty range");
+                    let span = self.end.wrapping_sub(self.start) as $u as u64;
+                    self.start.wrapping_add((next_u64() % span) as $t)
+                }
+            }
+            impl SampleRange<$t> for RangeInclusive<$t> {
+                fn sample_single(self) -> $t {
+                    let (start, end) = self.into_inner();
+                    assert!(start <= end, "cannot sample empty range");
+                    let span = (end.wrapping_sub(start) as $u as u64).wrapping_add(1);
+                    if span == 0 {
+                        return next_u64() as $t;
+                    }
+                    start.wrapping_add((next_u64() % span) as $t)
+                }
+            }
+        )*};
+    }
+    range_int!(
+        u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+        i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize
+    );
+
+    macro_rules! range_float {
+        ($($t:ty),*) => {$(
+            impl SampleRange<$t> for Range<$t> {
+                fn sample_single(self) -> $t {
+                    self.start + (self.end - self.start) * next_f64() as $t
+                }
+            }
+            impl SampleRange<$t> for RangeInclusive<$t> {
+                fn sample_single(self) -> $t {
+                    let (start, end) = self.into_inner();
+                    start + (end - start) * next_f64() as $t
+                }
+            }
+        )*};
+    }
+    range_float!(f32, f64);
+
+    pub trait Rng {
+        fn gen<T: Standard>(&mut self) -> T {
+            T::sample()
+        }
+        fn gen_range<T, R: SampleRange<T>>(&mut self, range: R) -> T {
+            range.sample_single()
+        }
+        fn gen_bool(&mut self, p: f64) -> bool {
+            next_f64() < p
+        }
+    }
+
+    pub trait RngCore {
+        fn next_u32(&mut self) -> u32 {
+            next_u64() as u32
+        }
+        fn next_u64(&mut self) -> u64 {
+            next_u64()
+        }
+    }
+
+    pub trait SeedableRng: Sized {
+        fn seed_from_u64(seed: u64) -> Self;
+        fn from_entropy() -> Self {
+            Self::seed_from_u64(SEED)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct ThreadRng;
+
+    impl Rng for ThreadRng {}
+    impl RngCore for ThreadRng {}
+
+    // Seeding is ignored on purpose: the request's seed is authoritative
+    impl SeedableRng for ThreadRng {
+        fn seed_from_u64(_seed: u64) -> Self {
+            ThreadRng
+        }
+    }
+
+    pub fn thread_rng() -> ThreadRng {
+        ThreadRng
+    }
+
+    pub fn random<T: Standard>() -> T {
+        T::sample()
+    }
+
+    pub mod rngs {
+        pub type ThreadRng = super::ThreadRng;
+        pub type StdRng = super::ThreadRng;
+        pub type SmallRng = super::ThreadRng;
+    }
+
+    pub mod seq {
+        use super::Rng;
+
+        pub trait SliceRandom {
+            type Item;
+            fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R);
+            fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&Self::Item>;
+        }
+
+        impl<T> SliceRandom for [T] {
+            type Item = T;
+
+            fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+                for i in (1..self.len()).rev() {
+                    let j = rng.gen_range(0..=i);
+                    self.swap(i, j);
+                }
+            }
+
+            fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+                if self.is_empty() {
+                    None
+                } else {
+                    Some(&self[rng.gen_range(0..self.len())])
+                }
+            }
+        }
+    }
+
+    pub mod prelude {
+        pub use super::seq::SliceRandom;
+        pub use super::{random, thread_rng, Rng, RngCore, SeedableRng};
+    }
+}
+"#;
+
+const SHIM_PATH: &str = "crate::__scriptsmith_time";
+
+// A replacement of the source between two positions
+struct Edit {
+    start: LineColumn,
+    end: LineColumn,
+    text: String,
+}
+
+// Where a token stream starts and ends in the source
+fn token_range(tokens: TokenStream) -> Option<(LineColumn, LineColumn)> {
+    let mut tokens = tokens.into_iter();
+    let first = tokens.next()?;
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    Some((first.span().start(), last.span().end()))
+}
+
+// The imports of a use tree, each as its path and the name it binds
+fn flatten_use(tree: &UseTree, prefix: &mut Vec<String>, imports: &mut Vec<(Vec<String>, String)>) {
+    match tree {
+        UseTree::Path(path) => {
+            prefix.push(path.ident.to_string());
+            flatten_use(&path.tree, prefix, imports);
+            prefix.pop();
+        }
+        UseTree::Name(name) if name.ident == "self" => {
+            imports.push((prefix.clone(), prefix.last().cloned().unwrap_or_default()));
+        }
+        UseTree::Name(name) => {
+            let mut path = prefix.clone();
+            path.push(name.ident.to_string());
+            imports.push((path, name.ident.to_string()));
+        }
+        UseTree::Rename(rename) => {
+            let mut path = prefix.clone();
+            if rename.ident != "self" {
+                path.push(rename.ident.to_string());
+            }
+            imports.push((path, rename.rename.to_string()));
+        }
+        UseTree::Glob(_) => {
+            let mut path = prefix.clone();
+            path.push("*".to_string());
+            imports.push((path, String::new()));
+        }
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                flatten_use(tree, prefix, imports);
+            }
+        }
+    }
+}
+
+fn is_std_time(first: &str, second: &str) -> bool {
+    first == "std" && second == "time"
+}
+
+// Collects the edits pointing std::time at the shim, wherever it is named
+// as a path: in expressions and types, in use declarations (grouped ones
+// included) and in macro arguments. Literals and comments are not paths,
+// so they keep their text.
+#[derive(Default)]
+struct ClockRedirect {
+    edits: Vec<Edit>,
+}
+
+impl ClockRedirect {
+    // `std :: time` (optionally after `::`) in a macro's tokens
+    fn scan_tokens(&mut self, tokens: TokenStream) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let is_ident = |i: usize, name: &str| matches!(tokens.get(i), Some(TokenTree::Ident(ident)) if ident == name);
+        let is_colon =
+            |i: usize| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == ':');
+        let mut i = 0;
+        while i < tokens.len() {
+            if let TokenTree::Group(group) = &tokens[i] {
+                self.scan_tokens(group.stream());
+            } else if is_ident(i, "std")
+                && is_colon(i + 1)
+                && is_colon(i + 2)
+                && is_ident(i + 3, "time")
+            {
+                let leading_colons = i >= 2 && is_colon(i - 1) && is_colon(i - 2);
+                let start = if leading_colons { i - 2 } else { i };
+                self.edits.push(Edit {
+                    start: tokens[start].span().start(),
+                    end: tokens[i + 3].span().end(),
+                    text: SHIM_PATH.to_string(),
+                });
+                i += 4;
+                continue;
+            }
+            i += 1;
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ClockRedirect {
+    fn visit_path(&mut self, path: &'ast Path) {
+        let segments: Vec<&PathSegment> = path.segments.iter().take(2).collect();
+        if let [first, second] = segments[..] {
+            if is_std_time(&first.ident.to_string(), &second.ident.to_string()) {
+                let start = match &path.leading_colon {
+                    Some(colons) => colons.spans[0].start(),
+                    None => first.ident.span().start(),
+                };
+                self.edits.push(Edit {
+                    start,
+                    end: second.ident.span().end(),
+                    text: SHIM_PATH.to_string(),
+                });
+            }
+        }
+        visit::visit_path(self, path);
+    }
+
+    // A use tree can name std::time in pieces (`std::{time::Instant, io}`),
+    // so the tree is rewritten as a whole: into a group of the same
+    // imports, those of std::time taken from the shim
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        let mut imports = Vec::new();
+        flatten_use(&item.tree, &mut Vec::new(), &mut imports);
+        if !imports
+            .iter()
+            .any(|(path, _)| path.len() >= 2 && is_std_time(&path[0], &path[1]))
+        {
+            return;
+        }
+        let leading = if item.leading_colon.is_some() {
+            "::"
+        } else {
+            ""
+        };
+        let rendered: Vec<String> = imports
+            .into_iter()
+            .map(|(path, name)| {
+                if path.len() >= 2 && is_std_time(&path[0], &path[1]) {
+                    let rest: Vec<&str> = path[2..].iter().map(String::as_str).collect();
+                    match rest.last() {
+                        None => format!("{} as {}", SHIM_PATH, name),
+                        Some(last) if *last != name && *last != "*" => {
+                            format!("{}::{} as {}", SHIM_PATH, rest.join("::"), name)
+                        }
+                        Some(_) => format!("{}::{}", SHIM_PATH, rest.join("::")),
+                    }
+                } else {
+                    let path = format!("{}{}", leading, path.join("::"));
+                    match path.rsplit("::").next() {
+                        Some(last) if last != name && last != "*" => {
+                            format!("{} as {}", path, name)
+                        }
+                        _ => path,
+                    }
+                }
+            })
+            .collect();
+        let Some((tree_start, end)) = token_range(item.tree.to_token_stream()) else {
+            return;
+        };
+        let start = match &item.leading_colon {
+            Some(colons) => colons.spans[0].start(),
+            None => tree_start,
+        };
+        self.edits.push(Edit {
+            start,
+            end,
+            text: format!("{{{}}}", rendered.join(", ")),
+        });
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        self.scan_tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+}
+
+// Byte offset of a line/column position; columns count characters
+fn byte_offset(line_starts: &[usize], source: &str, position: LineColumn) -> usize {
+    let line_start = line_starts[position.line - 1];
+    source[line_start..]
+        .char_indices()
+        .nth(position.column)
+        .map_or(source.len(), |(offset, _)| line_start + offset)
+}
+
+// Points every use of std::time in `source` at the shim. Edits keep the
+// line count, so diagnostics still point at the user's lines. A source that
+// does not parse is left alone for the compiler to report.
+fn redirect_clock(source: &str) -> String {
+    let Ok(file) = syn::parse_file(source) else {
+        return source.to_string();
+    };
+    let mut redirect = ClockRedirect::default();
+    redirect.visit_file(&file);
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut edits: Vec<(usize, usize, String)> = redirect
+        .edits
+        .into_iter()
+        .map(|edit| {
+            let start = byte_offset(&line_starts, source, edit.start);
+            let end = byte_offset(&line_starts, source, edit.end);
+            let newlines = source[start..end].matches('\n').count();
+            (
+                start,
+                end,
+                format!("{}{}", edit.text, "\n".repeat(newlines)),
+            )
+        })
+        .collect();
+    edits.sort_by_key(|(start, _, _)| *start);
+
+    let mut redirected = String::with_capacity(source.len());
+    let mut copied = 0;
+    for (start, end, text) in edits {
+        // Overlapping edits cannot happen for valid paths; keep the first
+        if start < copied {
+            continue;
+        }
+        redirected.push_str(&source[copied..start]);
+        redirected.push_str(&text);
+        copied = end;
+    }
+    redirected.push_str(&source[copied..]);
+    redirected
+}
+
+// Rewrites a generated main.rs for deterministic execution. The shims are
+// appended at the end so user line numbers are unaffected.
+pub fn instrument(source: &str, seed: u64) -> String {
+    let mut instrumented = redirect_clock(source);
+
+    instrumented.push('\n');
+    instrumented.push_str(TIME_SHIM);
+    if !source.contains("mod rand") {
+        instrumented.push_str(&RAND_SHIM.replace("__SCRIPTSMITH_SEED__", &format!("{}u64", seed)));
+    }
+    instrumented
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_redirected() {
+        let source = "fn main() {\n    let t = std::time::Instant::now();\n    let d: ::std::time::Duration = t.elapsed();\n}\n";
+        assert_eq!(
+            redirect_clock(source),
+            "fn main() {\n    let t = crate::__scriptsmith_time::Instant::now();\n    let d: crate::__scriptsmith_time::Duration = t.elapsed();\n}\n"
+        );
+    }
+
+    #[test]
+    fn literals_and_comments_are_kept() {
+        let source = "// reads std::time\nfn main() {\n    println!(\"uses std::time\");\n}\n";
+        assert_eq!(redirect_clock(source), source);
+    }
+
+    #[test]
+    fn macro_arguments_are_redirected() {
+        let source = "fn main() {\n    println!(\"{:?}\", std::time::SystemTime::now());\n}\n";
+        assert_eq!(
+            redirect_clock(source),
+            "fn main() {\n    println!(\"{:?}\", crate::__scriptsmith_time::SystemTime::now());\n}\n"
+        );
+    }
+
+    #[test]
+    fn grouped_imports_are_split_out() {
+        assert_eq!(
+            redirect_clock("use std::{time::Instant, collections::HashMap};\n"),
+            "use {crate::__scriptsmith_time::Instant, std::collections::HashMap};\n"
+        );
+        assert_eq!(
+            redirect_clock("use std::{self, time, io::Read as _};\n"),
+            "use {std, crate::__scriptsmith_time as time, std::io::Read as _};\n"
+        );
+        assert_eq!(
+            redirect_clock("pub use ::std::time::{self as clock, Duration as D, *};\n"),
+            "pub use {crate::__scriptsmith_time as clock, crate::__scriptsmith_time::Duration as D, crate::__scriptsmith_time::*};\n"
+        );
+    }
+
+    #[test]
+    fn unrelated_imports_are_untouched() {
+        let source = "use std::{io, collections::HashMap};\nuse core::time::Duration;\n";
+        assert_eq!(redirect_clock(source), source);
+    }
+
+    #[test]
+    fn line_numbers_are_preserved() {
+        let source =
+            "use std::{\n    time::Instant,\n    io,\n};\nfn main() { let _ = Instant::now(); }\n";
+        let redirected = redirect_clock(source);
+        assert_eq!(redirected.lines().count(), source.lines().count());
+        assert_eq!(
+            redirected.lines().nth(4),
+            Some("fn main() { let _ = Instant::now(); }")
+        );
+        assert!(redirected.starts_with("use {crate::__scriptsmith_time::Instant, std::io}"));
+    }
+
+    #[test]
+    fn instrumented_program_parses_with_the_shims() {
+        let instrumented = instrument(
+            "use std::{time::{Duration, Instant}, io};\nfn main() { let _ = Instant::now() + Duration::from_secs(1); }\n",
+            7,
+        );
+        assert!(syn::parse_file(&instrumented).is_ok());
+        assert!(!instrumented.lines().next().unwrap().contains("std::time"));
+    }
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+// A rustc diagnostic from `cargo --message-format=json`, with its location
+// mapped back to the user's code
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Diagnostic {
+    // "error" or "warning"
+    #[serde(rename = "severity")]
+    pub level: String,
+    pub message: String,
+    // e.g. "E0308" or "unused_variables"
+    pub code: Option<String>,
+    // 1-based, in the user's code; missing when the diagnostic has no span
+    // inside it. The end column is exclusive.
+    #[serde(rename = "startLine")]
+    pub line: Option<usize>,
+    #[serde(rename = "startColumn")]
+    pub column: Option<usize>,
+    #[serde(rename = "endLine")]
+    pub end_line: Option<usize>,
+    #[serde(rename = "endColumn")]
+    pub end_column: Option<usize>,
+    // Fixes rustc proposes, e.g. "consider borrowing here"
+    pub suggestions: Vec<Suggestion>,
+}
+
+// Replace the text between start and end with `replacement`; an empty range
+// is an insertion
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Suggestion {
+    pub message: String,
+    pub replacement: String,
+    #[serde(rename = "startLine")]
+    pub start_line: usize,
+    #[serde(rename = "startColumn")]
+    pub start_column: usize,
+    #[serde(rename = "endLine")]
+    pub end_line: usize,
+    #[serde(rename = "endColumn")]
+    pub end_column: usize,
+}
+
+// Where a span sits in the user's code: start and end line and column
+struct UserSpan {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+// None when the span starts outside the user's code; an end past it is
+// pulled back to the start
+fn map_span(span: &Value, line_offset: usize, user_lines: usize) -> Option<UserSpan> {
+    let to_user_line = |line: usize| {
+        line.checked_sub(line_offset)
+            .filter(|&l| l >= 1 && l <= user_lines)
+    };
+    let start_line = to_user_line(span["line_start"].as_u64()? as usize)?;
+    let start_column = span["column_start"].as_u64()? as usize;
+    let end = span["line_end"]
+        .as_u64()
+        .and_then(|line| to_user_line(line as usize))
+        .zip(span["column_end"].as_u64().map(|column| column as usize));
+    let (end_line, end_column) = end.unwrap_or((start_line, start_column));
+    Some(UserSpan {
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+    })
+}
+
+impl Diagnostic {
+    // One line per diagnostic, e.g.
+    // "error[E0308]: mismatched types (line 3, column 18)"
+    pub fn summary(&self) -> String {
+        let mut summary = self.level.clone();
+        if let Some(code) = &self.code {
+            summary.push_str(&format!("[{}]", code));
+        }
+        summary.push_str(": ");
+        summary.push_str(&self.message);
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                summary.push_str(&format!(" (line {}, column {})", line, column))
+            }
+            (Some(line), None) => summary.push_str(&format!(" (line {})", line)),
+            _ => {}
+        }
+        summary
+    }
+}
+
+// Errors and warnings from cargo's JSON messages on stdout. `line_offset` is
+// the number of generated lines before the user's code and `user_lines` the
+// length of that code. Warnings outside the user's code (unused wrapper
+// imports) and summary lines like "aborting due to 2 previous errors" are
+// dropped.
+pub fn parse_cargo_messages(
+    stdout: &str,
+    line_offset: usize,
+    user_lines: usize,
+) -> Vec<Diagnostic> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|msg| msg["reason"] == "compiler-message")
+        .filter_map(|msg| parse_message(&msg["message"], line_offset, user_lines))
+        .collect()
+}
+
+fn parse_message(message: &Value, line_offset: usize, user_lines: usize) -> Option<Diagnostic> {
+    let level = match message["level"].as_str()? {
+        "error" | "error: internal compiler error" => "error",
+        "warning" => "warning",
+        _ => return None,
+    };
+    let text = message["message"].as_str()?.to_string();
+    let spans = message["spans"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+    if spans.is_empty() && (level == "warning" || text.starts_with("aborting due to")) {
+        return None;
+    }
+
+    let primary = spans
+        .iter()
+        .find(|span| span["is_primary"] == true)
+        .or_else(|| spans.first());
+    let location = primary.and_then(|span| map_span(span, line_offset, user_lines));
+    if location.is_none() && level == "warning" {
+        return None;
+    }
+
+    // Suggestions are children ("help: consider ...") whose spans carry the
+    // replacement text
+    let suggestions = message["children"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+        .iter()
+        .flat_map(|child| {
+            let child_message = child["message"].as_str().unwrap_or_default();
+            child["spans"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(move |span| {
+                    let replacement = span["suggested_replacement"].as_str()?;
+                    let span = map_span(span, line_offset, user_lines)?;
+                    Some(Suggestion {
+                        message: child_message.to_string(),
+                        replacement: replacement.to_string(),
+                        start_line: span.start_line,
+                        start_column: span.start_column,
+                        end_line: span.end_line,
+                        end_column: span.end_column,
+                    })
+                })
+        })
+        .collect();
+
+    Some(Diagnostic {
+        level: level.to_string(),
+        message: text,
+        code: message["code"]["code"].as_str().map(str::to_string),
+        line: location.as_ref().map(|span| span.start_line),
+        column: location.as_ref().map(|span| span.start_column),
+        end_line: location.as_ref().map(|span| span.end_line),
+        end_column: location.as_ref().map(|span| span.end_column),
+        suggestions,
+    })
+}
+
+// One rustc suggestion as edits to byte ranges of the user's code; a
+// suggestion touching several places has several edits
+pub struct Fix {
+    // The diagnostic, e.g. "unused variable: `x`"
+    pub message: String,
+    // e.g. "if this is intentional, prefix it with an underscore"
+    pub suggestion: String,
+    // (start, end, replacement)
+    pub edits: Vec<(usize, usize, String)>,
+}
+
+// Suggestions rustc marks MachineApplicable, the ones `cargo fix` applies.
+// `byte_offset` is where the user's code starts in the generated file and
+// `code_len` its length; suggestions that edit the wrapper are dropped.
+pub fn machine_applicable_fixes(stdout: &str, byte_offset: usize, code_len: usize) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+    let messages = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|msg| msg["reason"] == "compiler-message");
+    for msg in messages {
+        let message = &msg["message"];
+        let children = message["children"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        for child in children {
+            let spans: Vec<&Value> = child["spans"]
+                .as_array()
+                .map(|spans| {
+                    spans
+                        .iter()
+                        .filter(|span| span["suggestion_applicability"] == "MachineApplicable")
+                        .collect()
+                })
+                .unwrap_or_default();
+            if spans.is_empty() {
+                continue;
+            }
+            let edits: Option<Vec<_>> = spans
+                .iter()
+                .map(|span| {
+                    let start = (span["byte_start"].as_u64()? as usize).checked_sub(byte_offset)?;
+                    let end = (span["byte_end"].as_u64()? as usize).checked_sub(byte_offset)?;
+                    let replacement = span["suggested_replacement"].as_str()?;
+                    (end <= code_len).then(|| (start, end, replacement.to_string()))
+                })
+                .collect();
+            if let Some(edits) = edits {
+                fixes.push(Fix {
+                    message: message["message"].as_str().unwrap_or_default().to_string(),
+                    suggestion: child["message"].as_str().unwrap_or_default().to_string(),
+                    edits,
+                });
+            }
+        }
+    }
+    fixes
+}
@@ -0,0 +1,61 @@
+use crate::telemetry;
+use sentry::protocol::Value;
+use sentry::{ClientInitGuard, ClientOptions, Level};
+use std::env;
+
+/// Reports internal errors and panics to Sentry when SENTRY_DSN is set,
+/// tagged with SENTRY_ENVIRONMENT if given. Events are sent until the
+/// returned guard is dropped, which flushes the ones still queued.
+pub fn init() -> Option<ClientInitGuard> {
+    let dsn = env::var("SENTRY_DSN").ok().filter(|dsn| !dsn.is_empty())?;
+    let guard = sentry::init((
+        dsn,
+        ClientOptions {
+            release: sentry::release_name!(),
+            environment: env::var("SENTRY_ENVIRONMENT").ok().map(Into::into),
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        println!("Reporting internal errors to Sentry");
+        Some(guard)
+    } else {
+        eprintln!("Error reporting disabled: SENTRY_DSN is invalid");
+        None
+    }
+}
+
+// What was going on when an execution failed with status "internal_error".
+// Students only see the error string; the event carries enough to find the
+// execution again.
+pub struct ErrorContext<'a> {
+    // Which endpoint started it: "execute", "compile", ...
+    pub kind: &'static str,
+    // Registry id, as listed by /admin/executions
+    pub execution_id: u64,
+    // "preparing", "compiling" or "running"
+    pub phase: &'static str,
+    // As in /executions
+    pub code_hash: &'a str,
+    pub requester_id: Option<&'a str>,
+}
+
+// Does nothing unless init enabled Sentry
+pub fn report_internal_error(message: &str, context: &ErrorContext) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("kind", context.kind);
+            scope.set_tag("phase", context.phase);
+            scope.set_extra("executionId", Value::from(context.execution_id));
+            scope.set_extra("codeHash", Value::from(context.code_hash));
+            if let Some(requester_id) = context.requester_id {
+                scope.set_extra("requesterId", Value::from(requester_id));
+            }
+            // Links the event to the request's trace
+            if let Some(trace_id) = telemetry::current_trace_id() {
+                scope.set_tag("trace_id", trace_id);
+            }
+        },
+        || sentry::capture_message(message, Level::Error),
+    );
+}
@@ -0,0 +1,109 @@
+use crate::RustExecutor;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const EXPLAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// `rustc --explain` output keyed by error code. The text only changes with
+// the toolchain, so entries never expire.
+pub type ExplanationCache = Arc<Mutex<HashMap<String, String>>>;
+
+#[derive(Serialize, ToSchema)]
+pub struct ExplanationResponse {
+    // Normalized to the form rustc prints, e.g. "E0308"
+    code: String,
+    // Markdown, as printed by rustc
+    explanation: String,
+}
+
+enum ExplainError {
+    UnknownCode,
+    Failed(String),
+}
+
+// Accepts "E0308" as well as "e0308" or "0308"
+fn normalize_error_code(code: &str) -> Option<String> {
+    let digits = code
+        .strip_prefix('E')
+        .or_else(|| code.strip_prefix('e'))
+        .unwrap_or(code);
+    (digits.len() == 4 && digits.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| format!("E{}", digits))
+}
+
+impl RustExecutor {
+    async fn explain_error_code(&self, code: &str) -> Result<String, ExplainError> {
+        if let Some(explanation) = self.explanations.lock().unwrap().get(code) {
+            return Ok(explanation.clone());
+        }
+
+        let output = timeout(
+            EXPLAIN_TIMEOUT,
+            tokio::process::Command::new("rustc")
+                .arg("--explain")
+                .arg(code)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| ExplainError::Failed("rustc --explain timed out".to_string()))?
+        .map_err(|e| ExplainError::Failed(format!("Failed to run rustc: {}", e)))?;
+        if !output.status.success() {
+            return Err(ExplainError::UnknownCode);
+        }
+
+        let explanation = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        self.explanations
+            .lock()
+            .unwrap()
+            .insert(code.to_string(), explanation.clone());
+        Ok(explanation)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/explain/{error_code}",
+    params(("error_code" = String, Path, description = "Compiler error code such as E0308")),
+    responses(
+        (status = 200, body = ExplanationResponse),
+        (status = 400, description = "Not an error code", body = ErrorResponse),
+        (status = 404, description = "rustc has no explanation for this code", body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    )
+)]
+pub async fn explain(
+    error_code: String,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let error = |message: String, status| {
+        warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": message })),
+            status,
+        )
+    };
+
+    let Some(code) = normalize_error_code(&error_code) else {
+        return Ok(error(
+            format!("'{}' is not an error code like E0308", error_code),
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+    let reply = match executor.explain_error_code(&code).await {
+        Ok(explanation) => warp::reply::with_status(
+            warp::reply::json(&ExplanationResponse { code, explanation }),
+            StatusCode::OK,
+        ),
+        Err(ExplainError::UnknownCode) => error(
+            format!("No explanation available for {}", code),
+            StatusCode::NOT_FOUND,
+        ),
+        Err(ExplainError::Failed(e)) => error(e, StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    Ok(reply)
+}
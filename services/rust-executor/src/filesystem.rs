@@ -0,0 +1,20 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// statvfs(3) of the filesystem holding `path`, or None when it cannot be read
+pub fn stat(path: &Path) -> Option<libc::statvfs> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the struct it is given
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat)
+}
+
+// Space left for unprivileged writers on the filesystem holding `path`
+pub fn free_mb(path: &Path) -> Option<u64> {
+    let stat = stat(path)?;
+    Some(stat.f_bavail * stat.f_frsize / (1024 * 1024))
+}
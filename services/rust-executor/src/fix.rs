@@ -0,0 +1,146 @@
+use crate::diagnostics;
+use crate::RustExecutor;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct FixRequest {
+    code: String,
+}
+
+// One edit made to the submitted code
+#[derive(Serialize, ToSchema)]
+pub struct AppliedFix {
+    // The diagnostic it fixes, e.g. "unused variable: `x`"
+    message: String,
+    // rustc's help text, e.g. "if this is intentional, prefix it with an
+    // underscore"
+    suggestion: String,
+    replacement: String,
+    // Replaced range in the submitted code, 1-based; the end column is
+    // exclusive and an empty range is an insertion
+    #[serde(rename = "startLine")]
+    start_line: usize,
+    #[serde(rename = "startColumn")]
+    start_column: usize,
+    #[serde(rename = "endLine")]
+    end_line: usize,
+    #[serde(rename = "endColumn")]
+    end_column: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FixResponse {
+    // The submitted code with every applied fix; unchanged when none applied
+    code: String,
+    fixes: Vec<AppliedFix>,
+    // Set when the code could not be checked; compile errors are not an
+    // error here
+    error: Option<String>,
+}
+
+impl FixResponse {
+    fn error(code: String, message: String) -> Self {
+        Self {
+            code,
+            fixes: Vec::new(),
+            error: Some(message),
+        }
+    }
+}
+
+// 1-based line and character column of a byte offset
+fn position(code: &str, offset: usize) -> (usize, usize) {
+    let before = &code[..offset];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+impl RustExecutor {
+    // Applies rustc's machine-applicable suggestions in one pass, like a
+    // single round of `cargo fix`. Suggestions that overlap one already
+    // taken are skipped; some only show up once others are applied, so
+    // calling /fix again on the result can find more.
+    pub async fn fix_code(&self, req: FixRequest) -> FixResponse {
+        let code = req.code;
+        let max_code_size_kb = self.config().max_code_size_kb;
+        let code_size_kb = code.len() as f64 / 1024.0;
+        if code_size_kb > max_code_size_kb as f64 {
+            let message = format!(
+                "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                code_size_kb, max_code_size_kb
+            );
+            return FixResponse::error(code, message);
+        }
+
+        let check = match self.check_code(&code).await {
+            Ok(check) => check,
+            Err(e) => return FixResponse::error(code, e),
+        };
+
+        let mut taken: Vec<(usize, usize, String)> = Vec::new();
+        let mut fixes = Vec::new();
+        for fix in
+            diagnostics::machine_applicable_fixes(&check.stdout, check.byte_offset, code.len())
+        {
+            // Edits at the same start also count, which drops suggestions
+            // reported more than once
+            let overlaps = fix.edits.iter().any(|(start, end, _)| {
+                taken.iter().any(|(taken_start, taken_end, _)| {
+                    (start < taken_end && taken_start < end) || start == taken_start
+                })
+            });
+            let on_char_boundaries = fix.edits.iter().all(|(start, end, _)| {
+                start <= end && code.is_char_boundary(*start) && code.is_char_boundary(*end)
+            });
+            if overlaps || !on_char_boundaries {
+                continue;
+            }
+            for (start, end, replacement) in fix.edits {
+                let (start_line, start_column) = position(&code, start);
+                let (end_line, end_column) = position(&code, end);
+                fixes.push(AppliedFix {
+                    message: fix.message.clone(),
+                    suggestion: fix.suggestion.clone(),
+                    replacement: replacement.clone(),
+                    start_line,
+                    start_column,
+                    end_line,
+                    end_column,
+                });
+                taken.push((start, end, replacement));
+            }
+        }
+
+        // Applied back to front so earlier offsets stay valid
+        taken.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut fixed = code;
+        for (start, end, replacement) in taken.iter().rev() {
+            fixed.replace_range(*start..*end, replacement);
+        }
+        fixes.sort_by_key(|fix| (fix.start_line, fix.start_column));
+
+        FixResponse {
+            code: fixed,
+            fixes,
+            error: None,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/fix",
+    request_body = FixRequest,
+    responses((status = 200, body = FixResponse))
+)]
+pub async fn fix(
+    req: FixRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.fix_code(req).await;
+    Ok(warp::reply::json(&result))
+}
@@ -0,0 +1,78 @@
+use crate::RustExecutor;
+use std::env;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const DEFAULT_GC_INTERVAL_SECONDS: u64 = 600;
+
+// Deletes what the service has persisted once its retention is over, every
+// GC_INTERVAL_SECONDS:
+// - compiled artifacts past ARTIFACT_TTL_SECONDS, in memory and in object
+//   storage, with their run output
+// - execution history older than HISTORY_RETENTION_DAYS (unset or 0 keeps
+//   it forever)
+// - entries of the job listing index whose jobs have expired; Redis itself
+//   drops jobs JOB_RESULT_TTL_SECONDS after they finish
+// The audit log is append-only and is never collected.
+pub async fn collect_garbage_periodically(executor: RustExecutor) {
+    let interval = env::var("GC_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(DEFAULT_GC_INTERVAL_SECONDS);
+    let history_retention_days = env::var("HISTORY_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|&days| days > 0);
+
+    let mut ticks = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticks.tick().await;
+        let metrics = &executor.metrics;
+
+        let (mut artifacts, mut bytes) = executor.artifacts.remove_expired();
+        match executor.artifacts.remove_expired_from_storage().await {
+            Ok((stored, stored_bytes)) => {
+                artifacts += stored;
+                bytes += stored_bytes;
+            }
+            Err(e) => eprintln!("Failed to collect stored artifacts: {}", e),
+        }
+        metrics
+            .artifacts_reclaimed
+            .fetch_add(artifacts, Ordering::Relaxed);
+        metrics
+            .artifact_bytes_reclaimed
+            .fetch_add(bytes, Ordering::Relaxed);
+        if artifacts > 0 {
+            println!("Removed {} expired artifacts ({} bytes)", artifacts, bytes);
+        }
+
+        if let (Some(history), Some(days)) = (&executor.history, history_retention_days) {
+            match history.delete_older_than(days).await {
+                Ok(rows) => {
+                    metrics
+                        .history_rows_deleted
+                        .fetch_add(rows, Ordering::Relaxed);
+                    if rows > 0 {
+                        println!("Removed {} executions older than {} days", rows, days);
+                    }
+                }
+                Err(e) => eprintln!("Failed to collect execution history: {}", e),
+            }
+        }
+
+        if let Some(queue) = &executor.job_queue {
+            match queue.prune_index().await {
+                Ok(entries) => {
+                    metrics
+                        .job_index_entries_pruned
+                        .fetch_add(entries, Ordering::Relaxed);
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        metrics.gc_runs.fetch_add(1, Ordering::Relaxed);
+    }
+}
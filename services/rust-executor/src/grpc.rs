@@ -0,0 +1,598 @@
+use crate::admin::AdminAuthError;
+use crate::bwrap;
+use crate::diagnostics::Diagnostic;
+use crate::interactive::{forward_output, OUTPUT_DRAIN_TIMEOUT};
+use crate::lints::LintConfig;
+use crate::memory_limit::{self, OomWatch};
+use crate::output_files::OutputFile;
+use crate::queue::{JobListQuery, JobStatusResponse, PriorityError};
+use crate::readiness::KILLED_MESSAGE;
+use crate::rules::ConstructRules;
+use crate::rusage::ProcessGroup;
+use crate::syscall_audit::SyscallAudit;
+use crate::{
+    write_stdin, AbortOnDrop, ChildSetup, CodeExecutionRequest, CodeExecutionResponse, DataFile,
+    ExecutionStatus, OptLevel, RustExecutor, SourceFile,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use proto::execution_event::Event;
+use proto::rust_executor_server::{RustExecutor as RustExecutorService, RustExecutorServer};
+use proto::{
+    ExecuteRequest, ExecuteResponse, ExecutionEvent, ExitEvent, JobReference, JobStatus,
+    ListJobsRequest, ListJobsResponse, ValidateRequest, ValidateResponse,
+};
+use std::net::SocketAddr;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("scriptsmith.rust_executor.v1");
+}
+
+type EventSender = mpsc::Sender<Result<ExecutionEvent, Status>>;
+
+impl From<OutputFile> for proto::OutputFile {
+    fn from(file: OutputFile) -> Self {
+        Self {
+            name: file.name,
+            size: file.size,
+            content: file
+                .content_base64
+                .and_then(|content| BASE64.decode(content).ok()),
+        }
+    }
+}
+
+impl From<SyscallAudit> for proto::SyscallAudit {
+    fn from(audit: SyscallAudit) -> Self {
+        Self {
+            counts: audit
+                .counts
+                .into_iter()
+                .map(|(category, count)| (category, count as u64))
+                .collect(),
+            files: audit
+                .files
+                .into_iter()
+                .map(|file| proto::FileAccess {
+                    path: file.path,
+                    syscalls: file.syscalls,
+                })
+                .collect(),
+            network: audit.network,
+            executed: audit.executed,
+        }
+    }
+}
+
+impl From<Diagnostic> for proto::Diagnostic {
+    fn from(diagnostic: Diagnostic) -> Self {
+        let position = |value: Option<usize>| value.map(|value| value as u32);
+        Self {
+            severity: diagnostic.level,
+            message: diagnostic.message,
+            code: diagnostic.code,
+            start_line: position(diagnostic.line),
+            start_column: position(diagnostic.column),
+            end_line: position(diagnostic.end_line),
+            end_column: position(diagnostic.end_column),
+            suggestions: diagnostic
+                .suggestions
+                .into_iter()
+                .map(|suggestion| proto::Suggestion {
+                    message: suggestion.message,
+                    replacement: suggestion.replacement,
+                    start_line: suggestion.start_line as u32,
+                    start_column: suggestion.start_column as u32,
+                    end_line: suggestion.end_line as u32,
+                    end_column: suggestion.end_column as u32,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<proto::ConstructRules> for ConstructRules {
+    fn from(rules: proto::ConstructRules) -> Self {
+        let list = |names: Vec<String>| (!names.is_empty()).then_some(names);
+        Self {
+            forbidden_loops: list(rules.forbidden_loops),
+            forbidden_methods: list(rules.forbidden_methods),
+            forbidden_macros: list(rules.forbidden_macros),
+            forbidden_paths: list(rules.forbidden_paths),
+            require_recursion: Some(rules.require_recursion),
+        }
+    }
+}
+
+impl From<proto::LintConfig> for LintConfig {
+    fn from(lints: proto::LintConfig) -> Self {
+        let list = |names: Vec<String>| (!names.is_empty()).then_some(names);
+        Self {
+            allow: list(lints.allow),
+            warn: list(lints.warn),
+            deny: list(lints.deny),
+            clippy_config: (!lints.clippy_config.is_empty()).then(|| {
+                lints
+                    .clippy_config
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = serde_json::from_str(&value)
+                            .unwrap_or(serde_json::Value::String(value));
+                        (key, value)
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl From<ExecuteRequest> for CodeExecutionRequest {
+    fn from(req: ExecuteRequest) -> Self {
+        Self {
+            language: req.language,
+            code: req.code,
+            files: (!req.files.is_empty()).then(|| {
+                req.files
+                    .into_iter()
+                    .map(|file| SourceFile {
+                        path: file.path,
+                        content: file.content,
+                    })
+                    .collect()
+            }),
+            data_files: (!req.data_files.is_empty()).then(|| {
+                req.data_files
+                    .into_iter()
+                    .map(|file| DataFile {
+                        name: file.name,
+                        content_base64: BASE64.encode(file.content),
+                    })
+                    .collect()
+            }),
+            dependencies: (!req.dependencies.is_empty()).then_some(req.dependencies),
+            input_data: req.input_data,
+            close_stdin: req.close_stdin,
+            timeout: req.timeout,
+            cpu_time_limit: req.cpu_time_limit,
+            compile_timeout: req.compile_timeout,
+            deterministic: req.deterministic,
+            seed: req.seed,
+            env: (!req.env.is_empty()).then_some(req.env),
+            input_encoding: req.input_encoding,
+            output_encoding: req.output_encoding,
+            priority: req.priority,
+            profile: req.profile,
+            opt_level: req.opt_level.map(OptLevel::Name),
+            overflow_checks: req.overflow_checks,
+            wrap_mode: req.wrap_mode,
+            template: req.template,
+            required_signatures: (!req.required_signatures.is_empty())
+                .then_some(req.required_signatures),
+            rules: req.rules.map(ConstructRules::from),
+            forbid_unsafe: req.forbid_unsafe,
+            strict: req.strict,
+            lints: req.lints.map(LintConfig::from),
+            ansi: req.ansi,
+            audit_syscalls: req.audit_syscalls,
+        }
+    }
+}
+
+impl From<CodeExecutionResponse> for ExecuteResponse {
+    fn from(result: CodeExecutionResponse) -> Self {
+        Self {
+            output: result.output,
+            output_encoding: result.output_encoding,
+            error: result.error,
+            execution_time: result.execution_time,
+            status: result.status.as_str().to_string(),
+            peak_memory_kb: result.peak_memory_kb,
+            user_cpu_ms: result.user_cpu_ms,
+            system_cpu_ms: result.system_cpu_ms,
+            exit_code: result.exit_code,
+            compile_time_ms: result.compile_time_ms,
+            run_time_ms: result.run_time_ms,
+            limit_exceeded: result.limit_exceeded,
+            truncated_by_timeout: result.truncated_by_timeout,
+            memory_limit_kb: result.memory_limit_kb,
+            toolchain_version: result.toolchain_version,
+            output_files: result
+                .output_files
+                .into_iter()
+                .map(proto::OutputFile::from)
+                .collect(),
+            output_events: result
+                .output_events
+                .into_iter()
+                .map(|event| proto::OutputEvent {
+                    stream: event.stream,
+                    timestamp: event.timestamp,
+                    text: event.text,
+                })
+                .collect(),
+            syscall_audit: result.syscall_audit.map(proto::SyscallAudit::from),
+            diagnostics: result
+                .diagnostics
+                .into_iter()
+                .map(proto::Diagnostic::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<JobStatusResponse> for JobStatus {
+    fn from(job: JobStatusResponse) -> Self {
+        Self {
+            job_id: job.job_id,
+            status: job.status,
+            result: job.result.map(ExecuteResponse::from),
+            submitted_by: job.submitted_by,
+            worker: job.worker,
+            queued_at: job.queued_at,
+            started_at: job.started_at,
+            completed_at: job.completed_at,
+            submitter: job.submitter,
+        }
+    }
+}
+
+impl From<ListJobsRequest> for JobListQuery {
+    fn from(req: ListJobsRequest) -> Self {
+        Self {
+            status: req.status,
+            submitter: req.submitter,
+            page: req.page.map(|page| page as usize),
+            page_size: req.page_size.map(|page_size| page_size as usize),
+        }
+    }
+}
+
+fn event(event: Event) -> ExecutionEvent {
+    ExecutionEvent { event: Some(event) }
+}
+
+async fn send_exit(
+    tx: &EventSender,
+    status: ExecutionStatus,
+    exit_code: Option<i32>,
+    error: String,
+    start_time: Instant,
+) {
+    let _ = tx
+        .send(Ok(event(Event::Exit(ExitEvent {
+            status: status.to_string(),
+            exit_code,
+            error,
+            execution_time: start_time.elapsed().as_secs_f64(),
+        }))))
+        .await;
+}
+
+// Metadata entries stand in for the HTTP headers of the same name
+fn metadata<T>(request: &Request<T>, key: &str) -> Option<String> {
+    request
+        .metadata()
+        .get(key)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+// As the replies of RustExecutor::authorize_admin
+fn admin_status(e: AdminAuthError) -> Status {
+    match e {
+        AdminAuthError::Disabled => Status::unimplemented(e.message()),
+        AdminAuthError::Invalid => Status::unauthenticated(e.message()),
+    }
+}
+
+// Runs a request like /execute but reports output while the program runs
+#[allow(clippy::result_large_err)]
+async fn stream_execution(executor: RustExecutor, req: CodeExecutionRequest, tx: EventSender) {
+    let start_time = Instant::now();
+    let active = match executor.start_execution("stream", None) {
+        Ok(active) => active,
+        Err(e) => return send_exit(&tx, e.status, None, e.message, start_time).await,
+    };
+    let prepared = match executor.prepare_execution(req) {
+        Ok(prepared) => prepared,
+        Err(e) => return send_exit(&tx, e.status, None, e.message, start_time).await,
+    };
+
+    active.set_phase("compiling");
+    let _ = tx
+        .send(Ok(event(Event::Status("compiling".to_string()))))
+        .await;
+    let compiled = active.until_killed(executor.compile(prepared.temp_dir.path(), prepared.build));
+    let executable_path = match compiled.await {
+        Some(Ok(path)) => path,
+        None => {
+            let error = KILLED_MESSAGE.to_string();
+            return send_exit(&tx, ExecutionStatus::Killed, None, error, start_time).await;
+        }
+        Some(Err(outcome)) => {
+            return send_exit(&tx, outcome.status, None, outcome.stderr, start_time).await
+        }
+    };
+
+    let mut cmd = executor.child_command(
+        &executable_path,
+        ChildSetup {
+            working_dir: Some(prepared.temp_dir.path()),
+            trace_log: None,
+            keep_stdin_open: false,
+        },
+        &prepared.env,
+        prepared.cpu_time_limit,
+    );
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let oom = OomWatch::start();
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let message = format!("Failed to spawn process: {}", e);
+            return send_exit(
+                &tx,
+                ExecutionStatus::InternalError,
+                None,
+                message,
+                start_time,
+            )
+            .await;
+        }
+    };
+    let process = ProcessGroup::new(child.id()).in_bwrap(bwrap::wraps(&cmd));
+    active.set_phase("running");
+    active.set_pid(Some(process.pid()));
+    let _ = tx
+        .send(Ok(event(Event::Status("running".to_string()))))
+        .await;
+
+    let mut forwarders = Vec::new();
+    if let Some(stdout) = child
+        .stdout
+        .take()
+        .and_then(|pipe| ChildStdout::from_std(pipe).ok())
+    {
+        forwarders.push(tokio::spawn(forward_output(stdout, tx.clone(), |data| {
+            Ok(event(Event::Stdout(data.to_vec())))
+        })));
+    }
+    if let Some(stderr) = child
+        .stderr
+        .take()
+        .and_then(|pipe| ChildStderr::from_std(pipe).ok())
+    {
+        forwarders.push(tokio::spawn(forward_output(stderr, tx.clone(), |data| {
+            Ok(event(Event::Stderr(data.to_vec())))
+        })));
+    }
+    // Written from a separate task so a program that never reads its input
+    // cannot block the stream
+    let stdin_writer = child
+        .stdin
+        .take()
+        .and_then(|pipe| ChildStdin::from_std(pipe).ok())
+        .map(|stdin| {
+            tokio::spawn(write_stdin(
+                stdin,
+                prepared.input.clone(),
+                None,
+                prepared.close_stdin == Some(false),
+            ))
+        });
+    let _stdin_writer = AbortOnDrop(stdin_writer);
+
+    let timeout_seconds = prepared.timeout_seconds;
+    let waiter = process.wait();
+    tokio::pin!(waiter);
+    let wait_result = tokio::select! {
+        status = tokio::time::timeout(Duration::from_secs(timeout_seconds), &mut waiter) => status,
+        // The client cancelled the call; dropping the process kills it
+        _ = tx.closed() => return,
+    };
+
+    let (status, exit_code, error) = match wait_result {
+        Ok(Ok(_)) if active.is_killed() => {
+            (ExecutionStatus::Killed, None, KILLED_MESSAGE.to_string())
+        }
+        Ok(Ok((status, _))) if status.success() => {
+            (ExecutionStatus::Success, status.code(), String::new())
+        }
+        Ok(Ok((status, usage))) if prepared.limits().cpu_time_exceeded(status, &usage) => (
+            ExecutionStatus::Timeout,
+            None,
+            prepared.limits().cpu_time_message(),
+        ),
+        Ok(Ok((status, _))) if oom.exceeded(status, None) => (
+            ExecutionStatus::MemoryLimit,
+            None,
+            memory_limit::message(executor.config().max_memory_mb),
+        ),
+        Ok(Ok((status, _))) if crate::stack_overflowed(status, None) => (
+            ExecutionStatus::StackOverflow,
+            None,
+            crate::STACK_OVERFLOW_MESSAGE.to_string(),
+        ),
+        Ok(Ok((status, _))) => (ExecutionStatus::RuntimeError, status.code(), String::new()),
+        Ok(Err(e)) => (
+            ExecutionStatus::InternalError,
+            None,
+            format!("Process error: {}", e),
+        ),
+        Err(_) => {
+            process.kill();
+            let _ = waiter.await;
+            (
+                ExecutionStatus::Timeout,
+                None,
+                format!("Code execution timed out after {} seconds", timeout_seconds),
+            )
+        }
+    };
+    active.set_pid(None);
+
+    let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, async {
+        for forwarder in forwarders {
+            let _ = forwarder.await;
+        }
+    })
+    .await;
+    send_exit(&tx, status, exit_code, error, start_time).await;
+}
+
+pub struct GrpcService {
+    executor: RustExecutor,
+}
+
+#[tonic::async_trait]
+impl RustExecutorService for GrpcService {
+    async fn execute(
+        &self,
+        request: Request<ExecuteRequest>,
+    ) -> Result<Response<ExecuteResponse>, Status> {
+        // As the X-Requester-Id and X-Tenant-Id headers of /execute
+        let requester_id = metadata(&request, "x-requester-id");
+        let tenant_id = metadata(&request, "x-tenant-id");
+        let result = self
+            .executor
+            .execute_for_tenant(
+                request.into_inner().into(),
+                requester_id.as_deref(),
+                tenant_id.as_deref(),
+            )
+            .await;
+        Ok(Response::new(result.into()))
+    }
+
+    type ExecuteStreamStream = ReceiverStream<Result<ExecutionEvent, Status>>;
+
+    async fn execute_stream(
+        &self,
+        request: Request<ExecuteRequest>,
+    ) -> Result<Response<Self::ExecuteStreamStream>, Status> {
+        // Counted against the tenant's executions quota, as the HTTP routes
+        // other than /execute are
+        if let Some(tenant_id) = metadata(&request, "x-tenant-id") {
+            self.executor
+                .reserve_tenant_execution(&tenant_id)
+                .map_err(Status::resource_exhausted)?;
+        }
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(stream_execution(
+            self.executor.clone(),
+            request.into_inner().into(),
+            tx,
+        ));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<ValidateRequest>,
+    ) -> Result<Response<ValidateResponse>, Status> {
+        let (result, _) = self
+            .executor
+            .validate_cached(request.into_inner().code)
+            .await;
+        Ok(Response::new(ValidateResponse {
+            is_valid: result.is_valid,
+            errors: result.errors,
+            warnings: result.warnings,
+            diagnostics: result
+                .diagnostics
+                .into_iter()
+                .map(proto::Diagnostic::from)
+                .collect(),
+        }))
+    }
+
+    async fn submit_job(
+        &self,
+        request: Request<ExecuteRequest>,
+    ) -> Result<Response<JobReference>, Status> {
+        let queue = self
+            .executor
+            .job_queue
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Job queue is not enabled"))?;
+        // As the headers of POST /jobs
+        let role = self.executor.authenticated_role(
+            metadata(&request, "authorization").as_deref(),
+            metadata(&request, "x-requester-role").as_deref(),
+        );
+        let submitter = metadata(&request, "x-requester-id");
+        let req: CodeExecutionRequest = request.into_inner().into();
+        let priority = self
+            .executor
+            .job_priority(req.priority.as_deref(), role.as_deref())
+            .map_err(|e| match e {
+                PriorityError::Invalid(message) => Status::invalid_argument(message),
+                PriorityError::NotAllowed(message) => Status::permission_denied(message),
+            })?;
+        let (job_id, stdin_token) = queue
+            .enqueue(&req, priority, submitter.as_deref())
+            .await
+            .map_err(Status::unavailable)?;
+        Ok(Response::new(JobReference {
+            job_id,
+            stdin_token,
+        }))
+    }
+
+    async fn get_job(&self, request: Request<JobReference>) -> Result<Response<JobStatus>, Status> {
+        let queue = self
+            .executor
+            .job_queue
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Job queue is not enabled"))?;
+        let job = queue
+            .status(&request.into_inner().job_id)
+            .await
+            .map_err(Status::unavailable)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        Ok(Response::new(job.into()))
+    }
+
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsResponse>, Status> {
+        // Every submitter's jobs, as GET /jobs
+        self.executor
+            .check_admin(metadata(&request, "authorization").as_deref())
+            .map_err(admin_status)?;
+        let queue = self
+            .executor
+            .job_queue
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Job queue is not enabled"))?;
+        let query: JobListQuery = request.into_inner().into();
+        query.validate().map_err(Status::invalid_argument)?;
+        let jobs = queue.list(&query).await.map_err(Status::unavailable)?;
+        Ok(Response::new(ListJobsResponse {
+            jobs: jobs.jobs.into_iter().map(JobStatus::from).collect(),
+            page: jobs.page as u32,
+            page_size: jobs.page_size as u32,
+            has_more: jobs.has_more,
+        }))
+    }
+}
+
+pub async fn serve(executor: RustExecutor, addr: SocketAddr) {
+    println!("Rust executor gRPC service running on {}", addr);
+    if let Err(e) = tonic::transport::Server::builder()
+        .add_service(RustExecutorServer::new(GrpcService { executor }))
+        .serve(addr)
+        .await
+    {
+        eprintln!("gRPC server stopped: {}", e);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::load_shedding::HostSample;
+use crate::warmup::WarmupStatus;
+use crate::RustExecutor;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+// When each step of an execution last worked, for /health. A step works
+// when the service did its part, whatever the code did: a compile error
+// comes from a working compiler and a panic from a working run.
+#[derive(Default)]
+pub struct PhaseSuccesses {
+    preparing: AtomicU64,
+    compiling: AtomicU64,
+    running: AtomicU64,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as u64)
+}
+
+impl PhaseSuccesses {
+    // One of "preparing", "compiling" or "running"
+    pub fn record(&self, phase: &'static str) {
+        let last = match phase {
+            "preparing" => &self.preparing,
+            "compiling" => &self.compiling,
+            "running" => &self.running,
+            _ => return,
+        };
+        last.store(unix_millis(), Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LastSuccess {
+        let at = |last: &AtomicU64| Some(last.load(Ordering::Relaxed)).filter(|&at| at > 0);
+        LastSuccess {
+            preparing: at(&self.preparing),
+            compiling: at(&self.compiling),
+            running: at(&self.running),
+        }
+    }
+}
+
+// Unix milliseconds; null until the step has worked once since startup
+#[derive(Serialize, ToSchema)]
+pub struct LastSuccess {
+    preparing: Option<u64>,
+    compiling: Option<u64>,
+    running: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    // "healthy", or "degraded" when up but impaired (see degradedReasons)
+    status: &'static str,
+    service: &'static str,
+    #[serde(rename = "degradedReasons")]
+    degraded_reasons: Vec<String>,
+    // Jobs waiting in Redis across priorities; null without REDIS_URL or
+    // when Redis cannot be reached
+    #[serde(rename = "queueDepth")]
+    queue_depth: Option<u64>,
+    #[serde(rename = "inFlightExecutions")]
+    in_flight_executions: usize,
+    #[serde(rename = "maxActiveExecutions")]
+    max_active_executions: usize,
+    // rust-analyzer instances kept warm for /complete
+    #[serde(rename = "warmPoolSize")]
+    warm_pool_size: usize,
+    // Free space on the volume holding the temp directories
+    #[serde(rename = "freeDiskMB")]
+    free_disk_mb: Option<u64>,
+    #[serde(rename = "lastSuccess")]
+    last_success: LastSuccess,
+}
+
+impl RustExecutor {
+    // Unlike /ready, a busy instance is not degraded: only what keeps
+    // executions from working once they are admitted counts
+    pub async fn health(&self) -> HealthResponse {
+        let config = self.config();
+        let mut degraded_reasons = Vec::new();
+
+        if let WarmupStatus::Failed(e) = &*self.warmup.read().unwrap() {
+            degraded_reasons.push(format!("warm-up compilation failed: {}", e));
+        }
+
+        let queue_depth = match &self.job_queue {
+            Some(queue) => match queue.depth().await {
+                Ok(depth) => Some(depth),
+                Err(e) => {
+                    degraded_reasons.push(e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let free_disk_mb = HostSample::take().free_disk_mb;
+        if let Some(free) = free_disk_mb {
+            if free < config.min_free_disk_mb {
+                degraded_reasons.push(format!(
+                    "{}MB of disk free, below {}MB",
+                    free, config.min_free_disk_mb
+                ));
+            }
+        }
+
+        HealthResponse {
+            status: if degraded_reasons.is_empty() {
+                "healthy"
+            } else {
+                "degraded"
+            },
+            service: "rust-executor",
+            degraded_reasons,
+            queue_depth,
+            in_flight_executions: self.running_executions.lock().unwrap().len(),
+            max_active_executions: config.max_active_executions,
+            warm_pool_size: self.analyzer_sessions.lock().unwrap().len(),
+            free_disk_mb,
+            last_success: self.phase_successes.snapshot(),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, description = "Service is up, possibly degraded", body = HealthResponse))
+)]
+pub async fn health(executor: RustExecutor) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&executor.health().await))
+}
@@ -0,0 +1,332 @@
+use crate::{CodeExecutionRequest, CodeExecutionResponse, ExecutionStatus};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tokio_postgres::{Client, NoTls};
+use utoipa::{IntoParams, ToSchema};
+
+// Output and error text are cut to this many bytes before being stored
+const MAX_STORED_TEXT_BYTES: usize = 4096;
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+const MAX_QUERY_LIMIT: i64 = 1000;
+
+const CREATE_TABLE_SQL: &str = "
+CREATE TABLE IF NOT EXISTS rust_executions (
+    id BIGSERIAL PRIMARY KEY,
+    code_hash TEXT NOT NULL,
+    requester_id TEXT,
+    status TEXT NOT NULL,
+    compilation_failed BOOLEAN NOT NULL,
+    execution_time_ms DOUBLE PRECISION NOT NULL,
+    output TEXT NOT NULL,
+    error TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS rust_executions_requester_created_idx
+    ON rust_executions (requester_id, created_at);
+CREATE INDEX IF NOT EXISTS rust_executions_created_idx
+    ON rust_executions (created_at);
+";
+
+// Execution history stored in PostgreSQL. The connection is opened lazily
+// and re-opened after it drops, so the executor starts even when the
+// database is not reachable yet.
+pub struct ExecutionHistory {
+    database_url: String,
+    client: Mutex<Option<Client>>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExecutionHistoryQuery {
+    user: Option<String>,
+    // RFC 3339 timestamp, e.g. 2024-05-01T00:00:00Z
+    since: Option<String>,
+    status: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ExecutionRecord {
+    id: i64,
+    #[serde(rename = "codeHash")]
+    code_hash: String,
+    #[serde(rename = "requesterId")]
+    requester_id: Option<String>,
+    status: String,
+    #[serde(rename = "compilationFailed")]
+    compilation_failed: bool,
+    #[serde(rename = "executionTimeMs")]
+    execution_time_ms: f64,
+    output: String,
+    error: String,
+    #[serde(rename = "createdAt")]
+    created_at: String,
+}
+
+// Identifies the submitted program independently of input and settings
+// Whether the two digits at `range` are a number within [min, max]
+fn field(value: &str, range: std::ops::Range<usize>, min: u32, max: u32) -> bool {
+    value
+        .get(range)
+        .filter(|digits| digits.len() == 2 && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+        .is_some_and(|number| (min..=max).contains(&number))
+}
+
+// Whether `value` is an RFC 3339 timestamp, such as 2024-05-01T00:00:00Z or
+// 2024-05-01T08:30:00.25+02:00. PostgreSQL would reject anything else only
+// once the query runs.
+fn is_rfc3339(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || !value.is_char_boundary(19)
+        || !value[..4].bytes().all(|b| b.is_ascii_digit())
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return false;
+    }
+    if !(field(value, 5..7, 1, 12)
+        && field(value, 8..10, 1, 31)
+        && field(value, 11..13, 0, 23)
+        && field(value, 14..16, 0, 59)
+        && field(value, 17..19, 0, 60))
+    {
+        return false;
+    }
+    let mut rest = &value[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return false;
+        }
+        rest = &fraction[digits..];
+    }
+    match rest.as_bytes() {
+        [b'Z' | b'z'] => true,
+        [b'+' | b'-', _, _, b':', _, _] => field(rest, 1..3, 0, 23) && field(rest, 4..6, 0, 59),
+        _ => false,
+    }
+}
+
+impl ExecutionHistoryQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.since {
+            Some(since) if !is_rfc3339(since) => Err(format!(
+                "since must be an RFC 3339 timestamp such as 2024-05-01T00:00:00Z, not '{}'",
+                since
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn code_hash(req: &CodeExecutionRequest) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.code.as_bytes());
+    for file in req.files.iter().flatten() {
+        hasher.update([0]);
+        hasher.update(file.path.as_bytes());
+        hasher.update([0]);
+        hasher.update(file.content.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn truncate(text: &str) -> String {
+    if text.len() <= MAX_STORED_TEXT_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_STORED_TEXT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
+}
+
+// tokio-postgres only says "db error" for server-side errors
+fn database_error(e: tokio_postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db_error) => format!("History database error: {}", db_error.message()),
+        None => format!("History database error: {}", e),
+    }
+}
+
+impl ExecutionHistory {
+    pub fn new(database_url: String) -> Self {
+        Self {
+            database_url,
+            client: Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> Result<Client, String> {
+        let (client, connection) = tokio_postgres::connect(&self.database_url, NoTls)
+            .await
+            .map_err(|e| format!("Failed to connect to history database: {}", e))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("History database connection closed: {}", e);
+            }
+        });
+        client
+            .batch_execute(CREATE_TABLE_SQL)
+            .await
+            .map_err(|e| format!("Failed to create history table: {}", e))?;
+        Ok(client)
+    }
+
+    async fn client(&self) -> Result<MappedMutexGuard<'_, Client>, String> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().is_none_or(Client::is_closed) {
+            *client = Some(self.connect().await?);
+        }
+        Ok(MutexGuard::map(client, |client| {
+            client.as_mut().expect("client was just connected")
+        }))
+    }
+
+    pub async fn record(
+        &self,
+        code_hash: &str,
+        requester_id: Option<&str>,
+        result: &CodeExecutionResponse,
+    ) -> Result<(), String> {
+        let compilation_failed = result.status == ExecutionStatus::CompileError
+            || result.limit_exceeded.as_deref() == Some("compileTime");
+        let execution_time_ms = result.execution_time * 1000.0;
+        let output = truncate(&result.output);
+        let error = truncate(&result.error);
+        self.client()
+            .await?
+            .execute(
+                "INSERT INTO rust_executions (code_hash, requester_id, status, \
+                     compilation_failed, execution_time_ms, output, error) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                &[
+                    &code_hash,
+                    &requester_id,
+                    &result.status.as_str(),
+                    &compilation_failed,
+                    &execution_time_ms,
+                    &output,
+                    &error,
+                ],
+            )
+            .await
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    // Returns how many executions were deleted
+    pub async fn delete_for(&self, requester_id: &str) -> Result<u64, String> {
+        self.client()
+            .await?
+            .execute(
+                "DELETE FROM rust_executions WHERE requester_id = $1",
+                &[&requester_id],
+            )
+            .await
+            .map_err(database_error)
+    }
+
+    // Deletes executions recorded more than `days` ago. Returns how many
+    // there were.
+    pub async fn delete_older_than(&self, days: u32) -> Result<u64, String> {
+        self.client()
+            .await?
+            .execute(
+                "DELETE FROM rust_executions \
+                 WHERE created_at < now() - make_interval(days => $1)",
+                &[&(days as i32)],
+            )
+            .await
+            .map_err(database_error)
+    }
+
+    pub async fn query(
+        &self,
+        query: ExecutionHistoryQuery,
+    ) -> Result<Vec<ExecutionRecord>, String> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT);
+        let rows = self
+            .client()
+            .await?
+            .query(
+                "SELECT id, code_hash, requester_id, status, compilation_failed, \
+                         execution_time_ms, output, error, \
+                         to_char(created_at AT TIME ZONE 'UTC', \
+                                 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"') \
+                         FROM rust_executions \
+                         WHERE ($1::text IS NULL OR requester_id = $1) \
+                           AND ($2::text IS NULL OR created_at >= $2::text::timestamptz) \
+                           AND ($3::text IS NULL OR status = $3) \
+                         ORDER BY created_at DESC, id DESC \
+                         LIMIT $4",
+                &[&query.user, &query.since, &query.status, &limit],
+            )
+            .await
+            .map_err(database_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ExecutionRecord {
+                id: row.get(0),
+                code_hash: row.get(1),
+                requester_id: row.get(2),
+                status: row.get(3),
+                compilation_failed: row.get(4),
+                execution_time_ms: row.get(5),
+                output: row.get(6),
+                error: row.get(7),
+                created_at: row.get(8),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339_timestamps_are_accepted() {
+        for value in [
+            "2024-05-01T00:00:00Z",
+            "2024-05-01t23:59:60z",
+            "2024-05-01 08:30:00.25+02:00",
+            "2024-12-31T12:00:00.123456-11:30",
+        ] {
+            assert!(is_rfc3339(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn other_timestamps_are_rejected() {
+        for value in [
+            "",
+            "yesterday",
+            "2024-05-01",
+            "2024-05-01T00:00:00",
+            "2024-13-01T00:00:00Z",
+            "2024-05-01T24:00:00Z",
+            "2024-05-01T00:00:00.Z",
+            "2024-05-01T00:00:00+0200",
+            "2024-05-01T00:00:00+02:00 ",
+            "2024-05-01T00:00:0éZ",
+        ] {
+            assert!(!is_rfc3339(value), "{}", value);
+        }
+    }
+}
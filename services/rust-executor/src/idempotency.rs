@@ -0,0 +1,107 @@
+use crate::{CodeExecutionRequest, CodeExecutionResponse};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+const DEFAULT_IDEMPOTENCY_TTL_SECONDS: u64 = 3600;
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+type CachedResult = Arc<OnceCell<CodeExecutionResponse>>;
+
+struct Entry {
+    fingerprint: String,
+    created: Instant,
+    result: CachedResult,
+}
+
+// Results of /execute requests sent with an Idempotency-Key header. A retry
+// with the same key and body gets the stored result; a retry that arrives
+// while the first attempt is still running waits for it instead of
+// executing again.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+pub enum IdempotencyError {
+    InvalidKey,
+    KeyReused,
+}
+
+impl IdempotencyError {
+    pub fn message(&self) -> String {
+        match self {
+            IdempotencyError::InvalidKey => format!(
+                "Idempotency-Key must be 1-{} characters",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            ),
+            IdempotencyError::KeyReused => {
+                "Idempotency-Key was already used with a different request".to_string()
+            }
+        }
+    }
+}
+
+// Field order in serde_json objects is sorted, so equal requests always
+// produce the same fingerprint
+fn fingerprint(req: &CodeExecutionRequest) -> String {
+    let body = serde_json::to_value(req)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl IdempotencyCache {
+    pub fn new() -> Self {
+        let ttl_seconds = env::var("IDEMPOTENCY_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_IDEMPOTENCY_TTL_SECONDS);
+        Self {
+            ttl: Duration::from_secs(ttl_seconds),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Returns the slot holding the result for this key. Keys are scoped to
+    // the requester so one client cannot read another client's results.
+    pub fn slot(
+        &self,
+        key: &str,
+        requester_id: Option<&str>,
+        req: &CodeExecutionRequest,
+    ) -> Result<CachedResult, IdempotencyError> {
+        if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(IdempotencyError::InvalidKey);
+        }
+        let scoped_key = format!("{}\u{0}{}", requester_id.unwrap_or(""), key);
+        let fingerprint = fingerprint(req);
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.created.elapsed() < self.ttl);
+        let entry = entries.entry(scoped_key).or_insert_with(|| Entry {
+            fingerprint: fingerprint.clone(),
+            created: Instant::now(),
+            result: CachedResult::default(),
+        });
+        if entry.fingerprint != fingerprint {
+            return Err(IdempotencyError::KeyReused);
+        }
+        Ok(entry.result.clone())
+    }
+
+    // Drops the requester's stored results; returns how many there were
+    pub fn forget(&self, requester_id: &str) -> usize {
+        let prefix = format!("{}\u{0}", requester_id);
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|key, _| !key.starts_with(&prefix));
+        before - entries.len()
+    }
+}
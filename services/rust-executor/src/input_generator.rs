@@ -0,0 +1,355 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const MAX_INPUTS: usize = 1000;
+// Of all the inputs together
+const MAX_OUTPUT_KB: usize = 4096;
+const DEFAULT_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+// More digits than an f64 holds are zeros anyway
+const MAX_DECIMALS: usize = 20;
+
+#[derive(Clone, Copy, Deserialize, ToSchema)]
+pub struct ValueRange {
+    min: i64,
+    max: i64,
+}
+
+// One line of an input
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LineSpec {
+    // An integer in [min, max]; later lines can refer to it by name
+    Int {
+        min: i64,
+        max: i64,
+        name: Option<String>,
+    },
+    // A number in [min, max] printed with `decimals` digits (default 6, at
+    // most 20)
+    Float {
+        min: f64,
+        max: f64,
+        decimals: Option<usize>,
+    },
+    // Integers in [min, max], space-separated. Their number is drawn from
+    // `length`, or is the value of an earlier named int (`lengthOf`, such
+    // as an "n" line).
+    Array {
+        length: Option<ValueRange>,
+        #[serde(rename = "lengthOf")]
+        length_of: Option<String>,
+        min: i64,
+        max: i64,
+    },
+    // Characters drawn from `alphabet` (default a-z), as many as for an
+    // array
+    String {
+        length: Option<ValueRange>,
+        #[serde(rename = "lengthOf")]
+        length_of: Option<String>,
+        alphabet: Option<String>,
+    },
+    // Printed as is
+    Literal {
+        value: String,
+    },
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GenerateInputsRequest {
+    // Inputs to generate
+    count: usize,
+    // Generates the same inputs again; random when absent
+    seed: Option<u64>,
+    lines: Vec<LineSpec>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GenerateInputsResponse {
+    // To regenerate these inputs
+    pub seed: u64,
+    pub inputs: Vec<String>,
+}
+
+fn check_range(min: i64, max: i64, what: &str) -> Result<(), String> {
+    if min > max {
+        return Err(format!("{}: min {} is greater than max {}", what, min, max));
+    }
+    Ok(())
+}
+
+fn length(
+    rng: &mut StdRng,
+    (length, length_of): (&Option<ValueRange>, &Option<String>),
+    named: &HashMap<&str, i64>,
+    what: &str,
+) -> Result<usize, String> {
+    let value = match (length, length_of) {
+        (Some(range), None) => {
+            check_range(range.min, range.max, what)?;
+            rng.gen_range(range.min..=range.max)
+        }
+        (None, Some(name)) => *named
+            .get(name.as_str())
+            .ok_or_else(|| format!("{}: lengthOf names no earlier int '{}'", what, name))?,
+        _ => return Err(format!("{}: set either length or lengthOf", what)),
+    };
+    let value =
+        usize::try_from(value).map_err(|_| format!("{}: negative length {}", what, value))?;
+    if value > MAX_OUTPUT_KB * 1024 {
+        return Err(format!("Inputs exceed {}KB", MAX_OUTPUT_KB));
+    }
+    Ok(value)
+}
+
+fn generate_input(rng: &mut StdRng, lines: &[LineSpec]) -> Result<String, String> {
+    let mut named = HashMap::new();
+    let mut input = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        let what = format!("Line {}", index + 1);
+        match line {
+            LineSpec::Int { min, max, name } => {
+                check_range(*min, *max, &what)?;
+                let value = rng.gen_range(*min..=*max);
+                if let Some(name) = name {
+                    named.insert(name.as_str(), value);
+                }
+                input.push_str(&value.to_string());
+            }
+            LineSpec::Float { min, max, decimals } => {
+                // rand panics on a range whose width overflows, such as
+                // -1e308..1e308
+                if !(min <= max && (max - min).is_finite()) {
+                    return Err(format!("{}: invalid range {}..{}", what, min, max));
+                }
+                let decimals = decimals.unwrap_or(6);
+                if decimals > MAX_DECIMALS {
+                    return Err(format!(
+                        "{}: decimals must be at most {}",
+                        what, MAX_DECIMALS
+                    ));
+                }
+                let value = rng.gen_range(*min..=*max);
+                input.push_str(&format!("{:.*}", decimals, value));
+            }
+            LineSpec::Array {
+                length: array_length,
+                length_of,
+                min,
+                max,
+            } => {
+                check_range(*min, *max, &what)?;
+                let n = length(rng, (array_length, length_of), &named, &what)?;
+                for i in 0..n {
+                    if i > 0 {
+                        input.push(' ');
+                    }
+                    input.push_str(&rng.gen_range(*min..=*max).to_string());
+                    if input.len() > MAX_OUTPUT_KB * 1024 {
+                        return Err(format!("Inputs exceed {}KB", MAX_OUTPUT_KB));
+                    }
+                }
+            }
+            LineSpec::String {
+                length: string_length,
+                length_of,
+                alphabet,
+            } => {
+                let alphabet: Vec<char> = alphabet
+                    .as_deref()
+                    .unwrap_or(DEFAULT_ALPHABET)
+                    .chars()
+                    .collect();
+                if alphabet.is_empty() {
+                    return Err(format!("{}: alphabet is empty", what));
+                }
+                let n = length(rng, (string_length, length_of), &named, &what)?;
+                input.extend((0..n).map(|_| alphabet[rng.gen_range(0..alphabet.len())]));
+            }
+            LineSpec::Literal { value } => input.push_str(value),
+        }
+        input.push('\n');
+        if input.len() > MAX_OUTPUT_KB * 1024 {
+            return Err(format!("Inputs exceed {}KB", MAX_OUTPUT_KB));
+        }
+    }
+    Ok(input)
+}
+
+// The first of the inputs /generate-inputs gives for this seed
+pub fn generate_one(seed: u64, lines: &[LineSpec]) -> Result<String, String> {
+    generate_input(&mut StdRng::seed_from_u64(seed), lines)
+}
+
+// The same seed and spec give the same inputs for as long as the service
+// builds with the same rand release
+pub fn generate_inputs(req: &GenerateInputsRequest) -> Result<GenerateInputsResponse, String> {
+    if req.count == 0 || req.count > MAX_INPUTS {
+        return Err(format!("count must be between 1 and {}", MAX_INPUTS));
+    }
+    let seed = req.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut inputs = Vec::with_capacity(req.count);
+    let mut total = 0;
+    for _ in 0..req.count {
+        let input = generate_input(&mut rng, &req.lines)?;
+        total += input.len();
+        if total > MAX_OUTPUT_KB * 1024 {
+            return Err(format!("Inputs exceed {}KB", MAX_OUTPUT_KB));
+        }
+        inputs.push(input);
+    }
+    Ok(GenerateInputsResponse { seed, inputs })
+}
+
+#[utoipa::path(
+    post,
+    path = "/generate-inputs",
+    request_body = GenerateInputsRequest,
+    responses(
+        (status = 200, body = GenerateInputsResponse),
+        (status = 400, description = "Invalid spec, or the inputs would be too large", body = ErrorResponse)
+    )
+)]
+pub async fn generate(req: GenerateInputsRequest) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = match generate_inputs(&req) {
+        Ok(generated) => warp::reply::with_status(warp::reply::json(&generated), StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            StatusCode::BAD_REQUEST,
+        ),
+    };
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(spec: serde_json::Value) -> Vec<LineSpec> {
+        serde_json::from_value(spec).unwrap()
+    }
+
+    fn request(count: usize, seed: Option<u64>, spec: serde_json::Value) -> GenerateInputsRequest {
+        GenerateInputsRequest {
+            count,
+            seed,
+            lines: lines(spec),
+        }
+    }
+
+    fn spec() -> serde_json::Value {
+        serde_json::json!([
+            { "type": "int", "min": 1, "max": 10, "name": "n" },
+            { "type": "array", "lengthOf": "n", "min": -5, "max": 5 },
+            { "type": "float", "min": 0.0, "max": 1.0, "decimals": 3 },
+            { "type": "string", "length": { "min": 2, "max": 4 }, "alphabet": "xy" },
+            { "type": "literal", "value": "end" }
+        ])
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_inputs_again() {
+        let first = generate_inputs(&request(20, Some(42), spec())).unwrap();
+        let again = generate_inputs(&request(20, Some(42), spec())).unwrap();
+        assert_eq!(first.seed, 42);
+        assert_eq!(first.inputs, again.inputs);
+        let other = generate_inputs(&request(20, Some(43), spec())).unwrap();
+        assert_ne!(first.inputs, other.inputs);
+    }
+
+    #[test]
+    fn generate_one_gives_the_first_input_of_its_seed() {
+        let inputs = generate_inputs(&request(3, Some(7), spec()))
+            .unwrap()
+            .inputs;
+        assert_eq!(generate_one(7, &lines(spec())).unwrap(), inputs[0]);
+    }
+
+    #[test]
+    fn lines_follow_their_spec() {
+        for input in generate_inputs(&request(50, Some(1), spec()))
+            .unwrap()
+            .inputs
+        {
+            let lines: Vec<&str> = input.lines().collect();
+            assert_eq!(lines.len(), 5);
+            let n: usize = lines[0].parse().unwrap();
+            assert!((1..=10).contains(&n));
+            let array: Vec<i64> = lines[1]
+                .split(' ')
+                .map(|value| value.parse().unwrap())
+                .collect();
+            assert_eq!(array.len(), n);
+            assert!(array.iter().all(|value| (-5..=5).contains(value)));
+            let (whole, fraction) = lines[2].split_once('.').unwrap();
+            assert_eq!((whole, fraction.len()), ("0", 3));
+            assert!((2..=4).contains(&lines[3].len()));
+            assert!(lines[3].chars().all(|c| c == 'x' || c == 'y'));
+            assert_eq!(lines[4], "end");
+        }
+    }
+
+    #[test]
+    fn float_ranges_that_overflow_are_rejected() {
+        let wide = lines(serde_json::json!([{ "type": "float", "min": -1e308, "max": 1e308 }]));
+        assert!(generate_one(0, &wide).is_err());
+        let reversed = lines(serde_json::json!([{ "type": "float", "min": 2.0, "max": 1.0 }]));
+        assert!(generate_one(0, &reversed).is_err());
+        let point = lines(serde_json::json!([{ "type": "float", "min": 1.5, "max": 1.5 }]));
+        assert_eq!(generate_one(0, &point).unwrap(), "1.500000\n");
+    }
+
+    #[test]
+    fn decimals_are_capped() {
+        let spec = |decimals: usize| {
+            lines(serde_json::json!([
+                { "type": "float", "min": 0.0, "max": 1.0, "decimals": decimals }
+            ]))
+        };
+        assert!(generate_one(0, &spec(MAX_DECIMALS)).is_ok());
+        assert!(generate_one(0, &spec(MAX_DECIMALS + 1)).is_err());
+        assert!(generate_one(0, &spec(1_000_000_000)).is_err());
+    }
+
+    #[test]
+    fn output_size_is_capped() {
+        let array = serde_json::json!([
+            { "type": "array", "length": { "min": 3_000_000, "max": 3_000_000 }, "min": 0, "max": 9 }
+        ]);
+        assert!(generate_inputs(&request(1, Some(0), array)).is_err());
+        let string = serde_json::json!([
+            { "type": "string", "length": { "min": 5_000_000, "max": 5_000_000 } }
+        ]);
+        assert!(generate_inputs(&request(1, Some(0), string)).is_err());
+        let per_input = serde_json::json!([
+            { "type": "string", "length": { "min": 8192, "max": 8192 } }
+        ]);
+        assert!(generate_inputs(&request(MAX_INPUTS, Some(0), per_input)).is_err());
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        assert!(generate_inputs(&request(0, None, spec())).is_err());
+        assert!(generate_inputs(&request(MAX_INPUTS + 1, None, spec())).is_err());
+        let cases = [
+            serde_json::json!([{ "type": "int", "min": 2, "max": 1 }]),
+            serde_json::json!([{ "type": "array", "lengthOf": "n", "min": 0, "max": 1 }]),
+            serde_json::json!([{ "type": "array", "min": 0, "max": 1 }]),
+            serde_json::json!([
+                { "type": "int", "min": -3, "max": -1, "name": "n" },
+                { "type": "string", "lengthOf": "n" }
+            ]),
+            serde_json::json!([
+                { "type": "string", "length": { "min": 1, "max": 1 }, "alphabet": "" }
+            ]),
+        ];
+        for case in cases {
+            assert!(generate_one(0, &lines(case)).is_err());
+        }
+    }
+}
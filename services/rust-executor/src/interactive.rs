@@ -0,0 +1,369 @@
+use crate::bwrap;
+use crate::memory_limit::{self, OomWatch};
+use crate::readiness::KILLED_MESSAGE;
+use crate::rusage::{ProcessGroup, ResourceUsage};
+use crate::{ChildSetup, CodeExecutionRequest, ExecutionStatus, RustExecutor};
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout};
+use tokio::sync::mpsc;
+use warp::ws::{Message, WebSocket};
+
+// How long to keep forwarding output after the process has exited, in case a
+// detached grandchild still holds the pipes open
+pub(crate) const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Messages a client may send after the initial execution request
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    Stdin { data: String },
+    Eof,
+    Kill,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage {
+    Status {
+        status: String,
+    },
+    Stdout {
+        data: String,
+    },
+    Stderr {
+        data: String,
+    },
+    Exit {
+        status: ExecutionStatus,
+        #[serde(rename = "exitCode")]
+        exit_code: Option<i32>,
+        error: String,
+        #[serde(rename = "executionTime")]
+        execution_time: f64,
+    },
+}
+
+type SocketSink = SplitSink<WebSocket, Message>;
+
+enum SessionEnd {
+    Exited(std::process::ExitStatus, ResourceUsage),
+    TimedOut,
+    // Stopped with the client's "kill" frame
+    Killed,
+    Failed(String),
+}
+
+async fn send(tx: &mut SocketSink, message: &ServerMessage) -> bool {
+    match serde_json::to_string(message) {
+        Ok(text) => tx.send(Message::text(text)).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+async fn send_exit(
+    tx: &mut SocketSink,
+    status: ExecutionStatus,
+    error: String,
+    start_time: Instant,
+) {
+    send(
+        tx,
+        &ServerMessage::Exit {
+            status,
+            exit_code: None,
+            error,
+            execution_time: start_time.elapsed().as_secs_f64(),
+        },
+    )
+    .await;
+}
+
+// Forwards a child's output pipe as it arrives, each chunk sent as the
+// message `message` makes of it, until the pipe closes or the receiver is gone
+pub(crate) async fn forward_output<R: AsyncRead + Unpin, M>(
+    mut reader: R,
+    events: mpsc::Sender<M>,
+    message: impl Fn(&[u8]) -> M,
+) {
+    let mut buffer = [0u8; 4096];
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if events.send(message(&buffer[..n])).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+impl RustExecutor {
+    // Interactive execution over a WebSocket. The first text frame is a
+    // regular execution request; afterwards the client streams stdin
+    // ({"type":"stdin","data":...}, {"type":"eof"}, {"type":"kill"}) and
+    // receives stdout/stderr frames until a final "exit" frame. stdin stays
+    // open until the "eof" frame unless the request sets closeStdin.
+    pub async fn interactive_session(self, socket: WebSocket) {
+        let (mut tx, mut rx) = socket.split();
+        let start_time = Instant::now();
+
+        let req = loop {
+            match rx.next().await {
+                Some(Ok(msg)) if msg.is_text() => {
+                    break serde_json::from_str::<CodeExecutionRequest>(msg.to_str().unwrap_or(""))
+                }
+                Some(Ok(msg)) if msg.is_close() => return,
+                Some(Ok(_)) => continue,
+                _ => return,
+            }
+        };
+        let req = match req {
+            Ok(req) => req,
+            Err(e) => {
+                send_exit(
+                    &mut tx,
+                    ExecutionStatus::InvalidRequest,
+                    format!("Invalid execution request: {}", e),
+                    start_time,
+                )
+                .await;
+                return;
+            }
+        };
+
+        let active = match self.start_execution("interactive", None) {
+            Ok(active) => active,
+            Err(e) => {
+                send_exit(&mut tx, e.status, e.message, start_time).await;
+                return;
+            }
+        };
+        let prepared = match self.prepare_execution(req) {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                send_exit(&mut tx, e.status, e.message, start_time).await;
+                return;
+            }
+        };
+
+        active.set_phase("compiling");
+        send(
+            &mut tx,
+            &ServerMessage::Status {
+                status: "compiling".to_string(),
+            },
+        )
+        .await;
+        let compiled = active.until_killed(self.compile(prepared.temp_dir.path(), prepared.build));
+        let executable_path = match compiled.await {
+            Some(Ok(path)) => path,
+            None => {
+                let error = KILLED_MESSAGE.to_string();
+                send_exit(&mut tx, ExecutionStatus::Killed, error, start_time).await;
+                return;
+            }
+            Some(Err(outcome)) => {
+                send_exit(&mut tx, outcome.status, outcome.stderr, start_time).await;
+                return;
+            }
+        };
+
+        let mut cmd = self.child_command(
+            &executable_path,
+            ChildSetup {
+                working_dir: Some(prepared.temp_dir.path()),
+                trace_log: None,
+                keep_stdin_open: false,
+            },
+            &prepared.env,
+            prepared.cpu_time_limit,
+        );
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let oom = OomWatch::start();
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                send_exit(
+                    &mut tx,
+                    ExecutionStatus::InternalError,
+                    format!("Failed to spawn process: {}", e),
+                    start_time,
+                )
+                .await;
+                return;
+            }
+        };
+        // Dropping the process when the socket goes away kills the program
+        let process = ProcessGroup::new(child.id()).in_bwrap(bwrap::wraps(&cmd));
+        active.set_phase("running");
+        active.set_pid(Some(process.pid()));
+        send(
+            &mut tx,
+            &ServerMessage::Status {
+                status: "running".to_string(),
+            },
+        )
+        .await;
+
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        if let Some(stdout) = child
+            .stdout
+            .take()
+            .and_then(|pipe| ChildStdout::from_std(pipe).ok())
+        {
+            tokio::spawn(forward_output(stdout, event_tx.clone(), |data| {
+                ServerMessage::Stdout { data: text(data) }
+            }));
+        }
+        if let Some(stderr) = child
+            .stderr
+            .take()
+            .and_then(|pipe| ChildStderr::from_std(pipe).ok())
+        {
+            tokio::spawn(forward_output(stderr, event_tx.clone(), |data| {
+                ServerMessage::Stderr { data: text(data) }
+            }));
+        }
+        drop(event_tx);
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .and_then(|pipe| ChildStdin::from_std(pipe).ok());
+        if let (Some(input), Some(pipe)) = (prepared.input.as_deref(), stdin.as_mut()) {
+            let _ = pipe.write_all(input).await;
+        }
+        // Open for stdin frames unless the request asked otherwise
+        if prepared.close_stdin == Some(true) {
+            stdin = None;
+        }
+
+        let deadline = tokio::time::sleep(Duration::from_secs(prepared.timeout_seconds));
+        tokio::pin!(deadline);
+        let waiter = process.wait();
+        tokio::pin!(waiter);
+
+        let end = loop {
+            tokio::select! {
+                status = &mut waiter => break match status {
+                    Ok((status, usage)) => SessionEnd::Exited(status, usage),
+                    Err(e) => SessionEnd::Failed(format!("Process error: {}", e)),
+                },
+                _ = &mut deadline => {
+                    process.kill();
+                    let _ = (&mut waiter).await;
+                    break SessionEnd::TimedOut;
+                }
+                Some(event) = event_rx.recv() => {
+                    if !send(&mut tx, &event).await {
+                        return;
+                    }
+                }
+                msg = rx.next() => match msg {
+                    Some(Ok(msg)) if msg.is_text() => {
+                        match serde_json::from_str::<ClientMessage>(msg.to_str().unwrap_or("")) {
+                            Ok(ClientMessage::Stdin { data }) => {
+                                if let Some(pipe) = stdin.as_mut() {
+                                    if pipe.write_all(data.as_bytes()).await.is_err()
+                                        || pipe.flush().await.is_err()
+                                    {
+                                        stdin = None;
+                                    }
+                                }
+                            }
+                            Ok(ClientMessage::Eof) => stdin = None,
+                            Ok(ClientMessage::Kill) => {
+                                process.kill();
+                                let _ = (&mut waiter).await;
+                                break SessionEnd::Killed;
+                            }
+                            Err(_) => {}
+                        }
+                    }
+                    Some(Ok(msg)) if msg.is_close() => return,
+                    Some(Ok(_)) => {}
+                    _ => return,
+                },
+            }
+        };
+        active.set_pid(None);
+
+        // Flush whatever the program printed right before exiting
+        let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, async {
+            while let Some(event) = event_rx.recv().await {
+                if !send(&mut tx, &event).await {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        let (status, exit_code, error) = match end {
+            SessionEnd::Exited(..) if active.is_killed() => {
+                (ExecutionStatus::Killed, None, KILLED_MESSAGE.to_string())
+            }
+            SessionEnd::Exited(status, _) if status.success() => {
+                (ExecutionStatus::Success, status.code(), String::new())
+            }
+            SessionEnd::Exited(status, usage)
+                if prepared.limits().cpu_time_exceeded(status, &usage) =>
+            {
+                (
+                    ExecutionStatus::Timeout,
+                    None,
+                    prepared.limits().cpu_time_message(),
+                )
+            }
+            SessionEnd::Exited(status, _) if oom.exceeded(status, None) => (
+                ExecutionStatus::MemoryLimit,
+                None,
+                memory_limit::message(self.config().max_memory_mb),
+            ),
+            SessionEnd::Exited(status, _) if crate::stack_overflowed(status, None) => (
+                ExecutionStatus::StackOverflow,
+                None,
+                crate::STACK_OVERFLOW_MESSAGE.to_string(),
+            ),
+            SessionEnd::Exited(status, _) => {
+                (ExecutionStatus::RuntimeError, status.code(), String::new())
+            }
+            SessionEnd::TimedOut => (
+                ExecutionStatus::Timeout,
+                None,
+                format!(
+                    "Code execution timed out after {} seconds",
+                    prepared.timeout_seconds
+                ),
+            ),
+            SessionEnd::Killed => (
+                ExecutionStatus::Killed,
+                None,
+                "Execution killed by client".to_string(),
+            ),
+            SessionEnd::Failed(e) => (ExecutionStatus::InternalError, None, e),
+        };
+        send(
+            &mut tx,
+            &ServerMessage::Exit {
+                status,
+                exit_code,
+                error,
+                execution_time: start_time.elapsed().as_secs_f64(),
+            },
+        )
+        .await;
+        let _ = tx.close().await;
+    }
+}
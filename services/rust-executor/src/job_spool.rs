@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+const DEFAULT_JOB_SPOOL_DIR: &str = "/var/lib/rust-executor/jobs";
+
+// A job a worker has taken off the queue and not yet finished
+#[derive(Serialize, Deserialize)]
+pub struct SpooledJob {
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+    // The priority list it was taken from, to put it back in
+    #[serde(rename = "queueKey")]
+    pub queue_key: String,
+}
+
+// One file per job in flight on this instance, under
+// JOB_SPOOL_DIR/<instance id>. Each file is written and synced before the
+// job runs and removed once its result is stored, so whatever is left after
+// a crash or a deploy is exactly the work that was cut short. The directory
+// has to outlive the process (a volume, not the container's /tmp) for jobs
+// to be recovered after a redeploy.
+pub struct JobSpool {
+    dir: PathBuf,
+}
+
+impl JobSpool {
+    // None when the directory cannot be created; jobs then run without a
+    // record and a worker that dies mid-job leaves them "running"
+    pub fn open(instance_id: &str) -> Option<Self> {
+        let root = env::var("JOB_SPOOL_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or_else(|| DEFAULT_JOB_SPOOL_DIR.to_string());
+        let dir = PathBuf::from(root).join(instance_id);
+        match fs::create_dir_all(&dir) {
+            Ok(()) => Some(Self { dir }),
+            Err(e) => {
+                eprintln!(
+                    "Job spool {} unavailable, interrupted jobs will not be recovered: {}",
+                    dir.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn path(&self, job_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", job_id))
+    }
+
+    pub fn record(&self, job: &SpooledJob) -> Result<(), String> {
+        let content =
+            serde_json::to_vec(job).map_err(|e| format!("Failed to encode job: {}", e))?;
+        let mut file = fs::File::create(self.path(&job.job_id))
+            .map_err(|e| format!("Failed to spool job {}: {}", job.job_id, e))?;
+        file.write_all(&content)
+            .and_then(|()| file.sync_all())
+            .map_err(|e| format!("Failed to spool job {}: {}", job.job_id, e))
+    }
+
+    pub fn remove(&self, job_id: &str) {
+        if let Err(e) = fs::remove_file(self.path(job_id)) {
+            eprintln!("Failed to remove spooled job {}: {}", job_id, e);
+        }
+    }
+
+    // Jobs left behind by a previous run of this instance; unreadable files
+    // are skipped and left in place for inspection
+    pub fn leftover(&self) -> Vec<SpooledJob> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                let content = fs::read(entry.path()).ok()?;
+                match serde_json::from_slice(&content) {
+                    Ok(job) => Some(job),
+                    Err(e) => {
+                        eprintln!("Skipping unreadable spooled job {:?}: {}", entry.path(), e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,826 @@
+use crate::ansi::{self, AnsiMode};
+use crate::input_generator::{self, GenerateInputsRequest};
+use crate::readiness::KILLED_MESSAGE;
+use crate::status::StatusError;
+use crate::{
+    lint_verdict, omit_middle_lines, ChildSetup, CodeExecutionRequest, ExecutionStatus,
+    RustExecutor,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+const MAX_TESTS: usize = 100;
+const MAX_GROUPS: usize = 20;
+// Group of tests that name none
+const DEFAULT_GROUP: &str = "default";
+
+#[derive(Deserialize, ToSchema)]
+pub struct TestCase {
+    // Shown in the results; defaults to "test N"
+    pub name: Option<String>,
+    #[serde(default)]
+    pub input: String,
+    // Required unless the request has a referenceSolution, whose output
+    // is expected instead
+    #[serde(rename = "expectedOutput")]
+    pub expected_output: Option<String>,
+    // Share of its group's points, relative to the other tests in the
+    // group; default 1
+    weight: Option<f64>,
+    // Name of an entry in `groups`; tests without one form the "default"
+    // group
+    pub group: Option<String>,
+    // Only whether it passed is reported: input, expected and actual output
+    // and errors are left out of the response
+    pub hidden: Option<bool>,
+}
+
+// A set of tests scored together, e.g. the sample tests shown to students,
+// the hidden tests, or one subtask
+#[derive(Deserialize, ToSchema)]
+pub struct TestGroup {
+    pub name: String,
+    // Points the group is worth; defaults to the sum of its tests' weights.
+    // 0 for groups that should not count, such as samples.
+    points: Option<f64>,
+    // "partial" (default): points in proportion to the weight of the passed
+    // tests; "all": the group's points only when every test in it passes,
+    // as for subtasks
+    scoring: Option<String>,
+}
+
+// How a test's output is compared with its expected output
+#[derive(Deserialize, ToSchema)]
+pub struct OutputComparison {
+    // "exact" (default): the same text, trailing whitespace aside;
+    // "numeric": the same whitespace-separated tokens line by line, where
+    // tokens that are both numbers only need to be within a tolerance.
+    // Integers are compared exactly rather than as floats.
+    mode: Option<String>,
+    // A number passes when it is within either tolerance of the expected
+    // one; defaults 1e-9 absolute and 0 relative
+    #[serde(rename = "absoluteTolerance")]
+    absolute_tolerance: Option<f64>,
+    // Relative to the larger magnitude of the two
+    #[serde(rename = "relativeTolerance")]
+    relative_tolerance: Option<f64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct JudgeRequest {
+    // The submission and its build and run settings, as for /execute;
+    // inputData is ignored in favour of each test's input
+    #[serde(flatten)]
+    pub submission: CodeExecutionRequest,
+    pub tests: Vec<TestCase>,
+    #[serde(default)]
+    pub groups: Vec<TestGroup>,
+    // Exact comparison when absent
+    pub comparison: Option<OutputComparison>,
+    // Built with the submission's profile, opt-level, overflow checks and
+    // limits, but none of its files, template or checks, and run on the
+    // tests that have no expectedOutput to provide it
+    #[serde(rename = "referenceSolution")]
+    pub reference_solution: Option<String>,
+    // Tests added after `tests`, named "generated N", with inputs as
+    // /generate-inputs makes them; needs a referenceSolution
+    #[serde(rename = "generatedInputs")]
+    pub generated_inputs: Option<GenerateInputsRequest>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TestResult {
+    pub name: String,
+    group: String,
+    pub passed: bool,
+    // The run's status; a successful run still fails the test when its
+    // output differs
+    pub status: ExecutionStatus,
+    weight: f64,
+    // Points this test earned within its group
+    score: f64,
+    // Resource usage of this run, as in /execute responses, reported for
+    // hidden tests too so limits can be tuned. null when the program never
+    // ran.
+    #[serde(rename = "runTimeMs")]
+    pub run_time_ms: Option<f64>,
+    #[serde(rename = "peakMemoryKB")]
+    peak_memory_kb: Option<u64>,
+    #[serde(rename = "cpuTimeMs")]
+    pub cpu_time_ms: Option<u64>,
+    // null when the program never ran or was killed by a signal, and for
+    // hidden tests: a program could report their input through its exit code
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+    // With status "timeout": "wallClock" or "cpuTime"; null for hidden tests
+    #[serde(rename = "limitExceeded")]
+    limit_exceeded: Option<String>,
+    hidden: bool,
+    // null for hidden tests
+    input: Option<String>,
+    #[serde(rename = "expectedOutput")]
+    pub expected_output: Option<String>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GroupResult {
+    name: String,
+    score: f64,
+    #[serde(rename = "maxScore")]
+    max_score: f64,
+    passed: usize,
+    total: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JudgeResponse {
+    // Sum of the group scores
+    score: f64,
+    #[serde(rename = "maxScore")]
+    max_score: f64,
+    groups: Vec<GroupResult>,
+    pub tests: Vec<TestResult>,
+    // Compilation errors, or why the request was rejected
+    pub error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    #[serde(rename = "compileTimeMs")]
+    pub compile_time_ms: Option<f64>,
+    // "success" once every test ran, whatever the score
+    pub status: ExecutionStatus,
+    // Seed of the generatedInputs, to judge with the same inputs again
+    #[serde(rename = "generatedSeed")]
+    generated_seed: Option<u64>,
+}
+
+impl JudgeResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            score: 0.0,
+            max_score: 0.0,
+            groups: Vec::new(),
+            tests: Vec::new(),
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            compile_time_ms: None,
+            status: error.status,
+            generated_seed: None,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scoring {
+    Partial,
+    All,
+}
+
+impl Scoring {
+    fn parse(scoring: Option<&str>) -> Result<Self, String> {
+        match scoring {
+            None | Some("partial") => Ok(Scoring::Partial),
+            Some("all") => Ok(Scoring::All),
+            Some(other) => Err(format!(
+                "Unknown scoring '{}': expected \"partial\" or \"all\"",
+                other
+            )),
+        }
+    }
+}
+
+const DEFAULT_ABSOLUTE_TOLERANCE: f64 = 1e-9;
+// A relative default would let a wrong answer pass once the expected value
+// is large enough
+const DEFAULT_RELATIVE_TOLERANCE: f64 = 0.0;
+
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    Exact,
+    Numeric { absolute: f64, relative: f64 },
+}
+
+impl Comparison {
+    pub fn parse(comparison: Option<&OutputComparison>) -> Result<Self, String> {
+        let Some(comparison) = comparison else {
+            return Ok(Comparison::Exact);
+        };
+        let tolerance = |name: &str, value: Option<f64>, default: f64| match value {
+            Some(value) if !value.is_finite() || value < 0.0 => {
+                Err(format!("{} must be a non-negative number", name))
+            }
+            value => Ok(value.unwrap_or(default)),
+        };
+        match comparison.mode.as_deref() {
+            None | Some("exact") => Ok(Comparison::Exact),
+            Some("numeric") => Ok(Comparison::Numeric {
+                absolute: tolerance(
+                    "absoluteTolerance",
+                    comparison.absolute_tolerance,
+                    DEFAULT_ABSOLUTE_TOLERANCE,
+                )?,
+                relative: tolerance(
+                    "relativeTolerance",
+                    comparison.relative_tolerance,
+                    DEFAULT_RELATIVE_TOLERANCE,
+                )?,
+            }),
+            Some(other) => Err(format!(
+                "Unknown comparison mode '{}': expected \"exact\" or \"numeric\"",
+                other
+            )),
+        }
+    }
+
+    pub fn matches(self, output: &str, expected: &str) -> bool {
+        match self {
+            Comparison::Exact => outputs_match(output, expected),
+            Comparison::Numeric { absolute, relative } => {
+                let (output, expected) = (output_lines(output), output_lines(expected));
+                output.len() == expected.len()
+                    && output.iter().zip(&expected).all(|(line, expected_line)| {
+                        let tokens: Vec<&str> = line.split_whitespace().collect();
+                        let expected_tokens: Vec<&str> = expected_line.split_whitespace().collect();
+                        tokens.len() == expected_tokens.len()
+                            && tokens
+                                .iter()
+                                .zip(&expected_tokens)
+                                .all(|(token, expected)| {
+                                    tokens_match(token, expected, absolute, relative)
+                                })
+                    })
+            }
+        }
+    }
+}
+
+// The exact difference of two integer tokens and the larger magnitude. As
+// f64, 10000000000000001 and 10000000000000000 are the same number.
+fn integer_difference(token: &str, expected: &str) -> Option<(u128, u128)> {
+    if let (Ok(value), Ok(expected)) = (token.parse::<i128>(), expected.parse::<i128>()) {
+        let magnitude = value.unsigned_abs().max(expected.unsigned_abs());
+        return Some((value.abs_diff(expected), magnitude));
+    }
+    let (value, expected) = (token.parse::<u128>().ok()?, expected.parse::<u128>().ok()?);
+    Some((value.abs_diff(expected), value.max(expected)))
+}
+
+// Tokens that are not both numbers must be equal
+fn tokens_match(token: &str, expected: &str, absolute: f64, relative: f64) -> bool {
+    if token == expected {
+        return true;
+    }
+    if let Some((difference, magnitude)) = integer_difference(token, expected) {
+        let difference = difference as f64;
+        return difference == 0.0
+            || difference <= absolute
+            || difference <= relative * magnitude as f64;
+    }
+    let (Ok(value), Ok(expected)) = (token.parse::<f64>(), expected.parse::<f64>()) else {
+        return false;
+    };
+    let difference = (value - expected).abs();
+    difference <= absolute || difference <= relative * value.abs().max(expected.abs())
+}
+
+// A group as scored: the declared ones in request order, then "default"
+// if any test has no group
+struct ScoredGroup {
+    name: String,
+    points: Option<f64>,
+    scoring: Scoring,
+}
+
+fn scored_groups(groups: &[TestGroup], tests: &[TestCase]) -> Result<Vec<ScoredGroup>, String> {
+    if groups.len() > MAX_GROUPS {
+        return Err(format!("At most {} groups are allowed", MAX_GROUPS));
+    }
+    let mut scored: Vec<ScoredGroup> = Vec::with_capacity(groups.len() + 1);
+    for group in groups {
+        if scored.iter().any(|g| g.name == group.name) {
+            return Err(format!("Duplicate group '{}'", group.name));
+        }
+        if group
+            .points
+            .is_some_and(|points| !(0.0..).contains(&points))
+        {
+            return Err(format!("Group '{}' has negative points", group.name));
+        }
+        scored.push(ScoredGroup {
+            name: group.name.clone(),
+            points: group.points,
+            scoring: Scoring::parse(group.scoring.as_deref())?,
+        });
+    }
+    for (index, test) in tests.iter().enumerate() {
+        if test.weight.is_some_and(|weight| !(0.0..).contains(&weight)) {
+            return Err(format!("Test {} has a negative weight", index + 1));
+        }
+        match &test.group {
+            Some(name) if !scored.iter().any(|g| &g.name == name) => {
+                return Err(format!("Test {} names unknown group '{}'", index + 1, name));
+            }
+            Some(_) => {}
+            None if !scored.iter().any(|g| g.name == DEFAULT_GROUP) => {
+                scored.push(ScoredGroup {
+                    name: DEFAULT_GROUP.to_string(),
+                    points: None,
+                    scoring: Scoring::Partial,
+                });
+            }
+            None => {}
+        }
+    }
+    Ok(scored)
+}
+
+// A field of a test result, withheld when the test is hidden
+fn shown<T>(hidden: bool, value: T) -> Option<T> {
+    (!hidden).then_some(value)
+}
+
+// Judges compare what is printed, not how it is laid out at the end of
+// lines: trailing whitespace and trailing blank lines are ignored
+fn output_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn outputs_match(output: &str, expected: &str) -> bool {
+    output_lines(output) == output_lines(expected)
+}
+
+// Fills in each test's score and returns the per-group totals
+fn score_groups(groups: &[ScoredGroup], tests: &mut [TestResult]) -> Vec<GroupResult> {
+    groups
+        .iter()
+        .map(|group| {
+            let members: Vec<usize> = (0..tests.len())
+                .filter(|&i| tests[i].group == group.name)
+                .collect();
+            let total_weight: f64 = members.iter().map(|&i| tests[i].weight).sum();
+            let max_score = group.points.unwrap_or(total_weight);
+            let passed = members.iter().filter(|&&i| tests[i].passed).count();
+            let all_passed = passed == members.len();
+            // With all weights zero the tests count equally
+            let share = |i: usize| {
+                if total_weight > 0.0 {
+                    tests[i].weight / total_weight
+                } else {
+                    1.0 / members.len() as f64
+                }
+            };
+            let shares: Vec<f64> = members.iter().map(|&i| share(i)).collect();
+            let mut score = 0.0;
+            for (&i, share) in members.iter().zip(shares) {
+                let earned = match group.scoring {
+                    _ if !tests[i].passed => 0.0,
+                    Scoring::Partial => max_score * share,
+                    Scoring::All if all_passed => max_score * share,
+                    Scoring::All => 0.0,
+                };
+                tests[i].score = earned;
+                score += earned;
+            }
+            GroupResult {
+                name: group.name.clone(),
+                score,
+                max_score,
+                passed,
+                total: members.len(),
+            }
+        })
+        .collect()
+}
+
+impl RustExecutor {
+    /// Compiles the submission once, runs it on every test and scores the
+    /// results by group, as POST /judge does. Build the request from its
+    /// JSON form with serde.
+    pub async fn judge_submission(&self, req: JudgeRequest) -> JudgeResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("judge", None) {
+            Ok(active) => active,
+            Err(e) => return JudgeResponse::error(e, start_time),
+        };
+        let JudgeRequest {
+            mut submission,
+            mut tests,
+            groups,
+            comparison,
+            reference_solution,
+            generated_inputs,
+        } = req;
+
+        let mut generated_seed = None;
+        if let Some(spec) = &generated_inputs {
+            if reference_solution.is_none() {
+                return JudgeResponse::error(
+                    "generatedInputs needs a referenceSolution to provide their expected output"
+                        .to_string(),
+                    start_time,
+                );
+            }
+            let generated = match input_generator::generate_inputs(spec) {
+                Ok(generated) => generated,
+                Err(e) => return JudgeResponse::error(e, start_time),
+            };
+            generated_seed = Some(generated.seed);
+            tests.extend(
+                generated
+                    .inputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, input)| TestCase {
+                        name: Some(format!("generated {}", index + 1)),
+                        input,
+                        expected_output: None,
+                        weight: None,
+                        group: None,
+                        hidden: None,
+                    }),
+            );
+        }
+        if tests.is_empty() || tests.len() > MAX_TESTS {
+            return JudgeResponse::error(
+                format!("Between 1 and {} tests are required", MAX_TESTS),
+                start_time,
+            );
+        }
+        let groups = match scored_groups(&groups, &tests) {
+            Ok(groups) => groups,
+            Err(e) => return JudgeResponse::error(e, start_time),
+        };
+        let comparison = match Comparison::parse(comparison.as_ref()) {
+            Ok(comparison) => comparison,
+            Err(e) => return JudgeResponse::error(e, start_time),
+        };
+        if reference_solution.is_none() {
+            if let Some(index) = tests.iter().position(|test| test.expected_output.is_none()) {
+                return JudgeResponse::error(
+                    format!(
+                        "Test {} has no expectedOutput and there is no referenceSolution",
+                        index + 1
+                    ),
+                    start_time,
+                );
+            }
+        }
+        submission.input_data = None;
+        // The rest of the submission is the student's: its files could
+        // replace the reference's main.rs and its rules or lints reject it
+        let reference = reference_solution.map(|code| CodeExecutionRequest {
+            code,
+            timeout: submission.timeout,
+            cpu_time_limit: submission.cpu_time_limit,
+            compile_timeout: submission.compile_timeout,
+            profile: submission.profile.clone(),
+            opt_level: submission.opt_level.clone(),
+            overflow_checks: submission.overflow_checks,
+            ..CodeExecutionRequest::default()
+        });
+        let prepared = match self.prepare_execution(submission) {
+            Ok(prepared) => prepared,
+            Err(e) => return JudgeResponse::error(e, start_time),
+        };
+
+        active.set_phase("compiling");
+        if prepared.needs_lint_pass() {
+            match active.until_killed(self.lint_pass(&prepared)).await {
+                Some(Ok(diagnostics)) => {
+                    if let Err((message, _)) = lint_verdict(diagnostics, prepared.strict) {
+                        return JudgeResponse::error(
+                            StatusError::new(ExecutionStatus::CompileError, message),
+                            start_time,
+                        );
+                    }
+                }
+                Some(Err(outcome)) => {
+                    return JudgeResponse::error(
+                        StatusError::new(outcome.status, outcome.stderr),
+                        start_time,
+                    )
+                }
+                None => return JudgeResponse::killed(start_time),
+            }
+        }
+        let compile_start = Instant::now();
+        let compiled = active
+            .until_killed(self.compile(prepared.temp_dir.path(), prepared.build))
+            .await;
+        let compile_time_ms = Some(compile_start.elapsed().as_secs_f64() * 1000.0);
+        let executable = match compiled {
+            Some(Ok(executable)) => executable,
+            Some(Err(outcome)) => {
+                return JudgeResponse {
+                    compile_time_ms,
+                    ..JudgeResponse::error(
+                        StatusError::new(outcome.status, outcome.stderr),
+                        start_time,
+                    )
+                };
+            }
+            None => return JudgeResponse::killed(start_time),
+        };
+
+        // Built after the submission, so one that does not compile is
+        // reported without waiting for the reference
+        let reference = match reference {
+            Some(reference) => {
+                let prepared = match self.prepare_execution(reference) {
+                    Ok(prepared) => prepared,
+                    Err(e) => {
+                        return JudgeResponse::error(
+                            StatusError::new(
+                                ExecutionStatus::InvalidRequest,
+                                format!("referenceSolution: {}", e.message),
+                            ),
+                            start_time,
+                        )
+                    }
+                };
+                let compiled = active
+                    .until_killed(self.compile(prepared.temp_dir.path(), prepared.build))
+                    .await;
+                match compiled {
+                    Some(Ok(executable)) => Some((prepared, executable)),
+                    Some(Err(outcome)) => {
+                        return JudgeResponse::error(
+                            StatusError::new(
+                                ExecutionStatus::InvalidRequest,
+                                format!(
+                                    "The reference solution does not build: {}",
+                                    outcome.stderr
+                                ),
+                            ),
+                            start_time,
+                        )
+                    }
+                    None => return JudgeResponse::killed(start_time),
+                }
+            }
+            None => None,
+        };
+
+        let max_output_lines = self.config().max_output_lines;
+        let mut results = Vec::with_capacity(tests.len());
+        for (index, test) in tests.into_iter().enumerate() {
+            let name = test.name.unwrap_or_else(|| format!("test {}", index + 1));
+            let expected_output = match (test.expected_output, &reference) {
+                (Some(expected_output), _) => expected_output,
+                (None, Some((reference, reference_executable))) => {
+                    let setup = ChildSetup {
+                        working_dir: Some(reference.temp_dir.path()),
+                        trace_log: None,
+                        keep_stdin_open: false,
+                    };
+                    let outcome = self
+                        .run_executable(
+                            reference_executable,
+                            setup,
+                            &[],
+                            Some(test.input.as_bytes()),
+                            reference.limits(),
+                            &reference.env,
+                            &active,
+                        )
+                        .await;
+                    if active.is_killed() {
+                        return JudgeResponse::killed(start_time);
+                    }
+                    if !outcome.status.is_success() {
+                        return JudgeResponse::error(
+                            StatusError::new(
+                                ExecutionStatus::InvalidRequest,
+                                format!(
+                                    "The reference solution fails on '{}' with status {}: {}",
+                                    name,
+                                    outcome.status.as_str(),
+                                    outcome.stderr
+                                ),
+                            ),
+                            start_time,
+                        );
+                    }
+                    let output = String::from_utf8_lossy(&outcome.stdout).into_owned();
+                    // Treated like the submission's output it is compared with
+                    match prepared.ansi {
+                        AnsiMode::Strip => ansi::strip(&output),
+                        _ => output,
+                    }
+                }
+                // Rejected above
+                (None, None) => String::new(),
+            };
+            let setup = ChildSetup {
+                working_dir: Some(prepared.temp_dir.path()),
+                trace_log: None,
+                keep_stdin_open: false,
+            };
+            let outcome = self
+                .run_executable(
+                    &executable,
+                    setup,
+                    &[],
+                    Some(test.input.as_bytes()),
+                    prepared.limits(),
+                    &prepared.env,
+                    &active,
+                )
+                .await;
+            if active.is_killed() {
+                return JudgeResponse::killed(start_time);
+            }
+            let hidden = test.hidden.unwrap_or(false);
+            let mut output = String::from_utf8_lossy(&outcome.stdout).into_owned();
+            let mut error = outcome.stderr;
+            if let AnsiMode::Strip = prepared.ansi {
+                output = ansi::strip(&output);
+                error = ansi::strip(&error);
+            }
+            let passed =
+                outcome.status.is_success() && comparison.matches(&output, &expected_output);
+            // Redacted here rather than by the caller, so nothing about a
+            // hidden test ever leaves the executor
+            results.push(TestResult {
+                name,
+                group: test.group.unwrap_or_else(|| DEFAULT_GROUP.to_string()),
+                passed,
+                status: outcome.status,
+                weight: test.weight.unwrap_or(1.0),
+                score: 0.0,
+                run_time_ms: outcome
+                    .run_time
+                    .map(|run_time| run_time.as_secs_f64() * 1000.0),
+                peak_memory_kb: outcome.usage.map(|usage| usage.peak_memory_kb),
+                cpu_time_ms: outcome
+                    .usage
+                    .map(|usage| usage.user_cpu_ms + usage.system_cpu_ms),
+                exit_code: outcome.exit_code.and_then(|code| shown(hidden, code)),
+                limit_exceeded: outcome
+                    .limit_exceeded
+                    .and_then(|limit| shown(hidden, limit.as_str().to_string())),
+                hidden,
+                input: shown(hidden, test.input),
+                expected_output: shown(hidden, expected_output),
+                output: shown(hidden, omit_middle_lines(&output, max_output_lines)),
+                error: shown(hidden, omit_middle_lines(&error, max_output_lines)),
+            });
+        }
+
+        let groups = score_groups(&groups, &mut results);
+        JudgeResponse {
+            score: groups.iter().map(|group| group.score).sum(),
+            max_score: groups.iter().map(|group| group.max_score).sum(),
+            groups,
+            tests: results,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            compile_time_ms,
+            status: ExecutionStatus::Success,
+            generated_seed,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/judge",
+    request_body = JudgeRequest,
+    responses((status = 200, body = JudgeResponse))
+)]
+pub async fn judge(
+    req: JudgeRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.judge_submission(req).await;
+    Ok(warp::reply::json(&result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(absolute: Option<f64>, relative: Option<f64>) -> OutputComparison {
+        OutputComparison {
+            mode: Some("numeric".to_string()),
+            absolute_tolerance: absolute,
+            relative_tolerance: relative,
+        }
+    }
+
+    fn defaults() -> Comparison {
+        Comparison::parse(Some(&numeric(None, None))).unwrap()
+    }
+
+    #[test]
+    fn comparison_defaults_to_exact() {
+        assert!(matches!(Comparison::parse(None), Ok(Comparison::Exact)));
+        let exact = OutputComparison {
+            mode: Some("exact".to_string()),
+            absolute_tolerance: None,
+            relative_tolerance: None,
+        };
+        assert!(matches!(
+            Comparison::parse(Some(&exact)),
+            Ok(Comparison::Exact)
+        ));
+    }
+
+    #[test]
+    fn numeric_defaults_have_no_relative_tolerance() {
+        let Comparison::Numeric { absolute, relative } = defaults() else {
+            panic!("expected a numeric comparison");
+        };
+        assert_eq!((absolute, relative), (1e-9, 0.0));
+    }
+
+    #[test]
+    fn invalid_comparisons_are_rejected() {
+        assert!(Comparison::parse(Some(&numeric(Some(-1.0), None))).is_err());
+        assert!(Comparison::parse(Some(&numeric(None, Some(f64::NAN)))).is_err());
+        assert!(Comparison::parse(Some(&numeric(Some(f64::INFINITY), None))).is_err());
+        let unknown = OutputComparison {
+            mode: Some("fuzzy".to_string()),
+            absolute_tolerance: None,
+            relative_tolerance: None,
+        };
+        assert!(Comparison::parse(Some(&unknown)).is_err());
+    }
+
+    #[test]
+    fn integers_are_compared_exactly() {
+        assert!(!tokens_match(
+            "10000000000000001",
+            "10000000000000000",
+            0.0,
+            0.0
+        ));
+        assert!(!tokens_match(
+            "10000000000000001",
+            "10000000000000000",
+            1e-9,
+            0.0
+        ));
+        assert!(!tokens_match(
+            "340282366920938463463374607431768211455",
+            "340282366920938463463374607431768211454",
+            0.0,
+            0.0
+        ));
+        assert!(!tokens_match("-9", "9", 0.0, 0.0));
+        assert!(tokens_match("+7", "7", 0.0, 0.0));
+        assert!(tokens_match("007", "7", 0.0, 0.0));
+    }
+
+    #[test]
+    fn integers_within_a_given_tolerance_pass() {
+        assert!(tokens_match("11", "10", 1.0, 0.0));
+        assert!(!tokens_match("12", "10", 1.0, 0.0));
+        assert!(tokens_match("1000001", "1000000", 0.0, 1e-6));
+    }
+
+    #[test]
+    fn large_wrong_answers_fail_with_the_defaults() {
+        let comparison = defaults();
+        assert!(!comparison.matches("1000000001\n", "1000000000\n"));
+        assert!(!comparison.matches("1.0000001e20\n", "1e20\n"));
+    }
+
+    #[test]
+    fn floats_are_compared_within_the_tolerances() {
+        assert!(tokens_match("0.1000000001", "0.1", 1e-9, 0.0));
+        assert!(!tokens_match("0.10001", "0.1", 1e-9, 0.0));
+        assert!(tokens_match("1.0001e10", "1e10", 0.0, 1e-4));
+        assert!(tokens_match("2.5", "2.50", 0.0, 0.0));
+        assert!(!tokens_match("NaN", "1", 1.0, 1.0));
+    }
+
+    #[test]
+    fn other_tokens_must_be_equal() {
+        assert!(tokens_match("YES", "YES", 0.0, 0.0));
+        assert!(!tokens_match("YES", "yes", 1.0, 1.0));
+        assert!(!tokens_match("1", "one", 1.0, 1.0));
+    }
+
+    #[test]
+    fn numeric_comparison_goes_line_by_line() {
+        let comparison = defaults();
+        assert!(comparison.matches("1 2.0000000001\n3  \n\n", "1 2\n3\n"));
+        assert!(!comparison.matches("1 2 3\n", "1 2\n"));
+        assert!(!comparison.matches("1\n2\n", "1 2\n"));
+    }
+}
@@ -0,0 +1,3147 @@
+//! The Rust executor as a library: compiles and runs untrusted Rust
+//! submissions under time, CPU and output limits, checks them against
+//! assignment rules, and grades them against test cases.
+//!
+//! The `rust-executor` binary serves [`routes`] over HTTP. Services that
+//! grade trusted code in-process can build a [`RustExecutor`] and call it
+//! directly:
+//!
+//! ```no_run
+//! # async fn grade() {
+//! use rust_executor::{CodeExecutionRequest, ExecutorConfig, RustExecutor};
+//!
+//! let mut executor = RustExecutor::new(ExecutorConfig::default());
+//! executor.detect_toolchain().await;
+//! let result = executor
+//!     .execute(CodeExecutionRequest {
+//!         code: "println!(\"{}\", 6 * 7);".to_string(),
+//!         ..Default::default()
+//!     })
+//!     .await;
+//! assert_eq!(result.output, "42");
+//! # }
+//! ```
+
+mod admin;
+mod ansi;
+mod artifacts;
+mod assignments;
+mod audit;
+mod backend;
+mod behavior_diff;
+mod bwrap;
+mod code_metrics;
+mod compare;
+mod completion;
+mod config;
+mod coverage;
+mod dependencies;
+mod deterministic;
+mod diagnostics;
+mod error_reporting;
+mod explain;
+mod filesystem;
+mod fix;
+mod gc;
+mod grpc;
+mod health;
+mod history;
+mod idempotency;
+mod input_generator;
+mod interactive;
+mod job_spool;
+mod judge;
+mod library_tests;
+mod lints;
+mod load_shedding;
+mod memory_limit;
+mod metrics;
+mod mutation;
+mod openapi;
+mod output_events;
+mod output_files;
+mod panic_report;
+mod preflight;
+mod project;
+mod properties;
+mod queue;
+mod quota;
+mod readiness;
+mod reaper;
+mod rejection;
+mod repl;
+mod rules;
+mod rusage;
+mod sandbox_user;
+mod selftest;
+mod signatures;
+mod similarity;
+mod status;
+mod std_usage;
+mod storage;
+mod stress;
+mod syscall_audit;
+mod telemetry;
+mod tls;
+mod toolchain_pin;
+mod unstable_features;
+mod user_data;
+mod v1;
+mod validation_cache;
+mod warmup;
+mod wrapping;
+
+use ansi::AnsiMode;
+use artifacts::ArtifactStore;
+use audit::{AuditEntry, AuditLog};
+use backend::ExecutorRegistry;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use completion::AnalyzerSessions;
+pub use config::ExecutorConfig;
+use coverage::CodeCoverageRequest;
+pub use diagnostics::{Diagnostic, Suggestion};
+use error_reporting::ErrorContext;
+use explain::ExplanationCache;
+use health::PhaseSuccesses;
+use history::{ExecutionHistory, ExecutionHistoryQuery};
+use idempotency::IdempotencyCache;
+pub use lints::LintConfig;
+use load_shedding::LoadShedder;
+use metrics::ServiceMetrics;
+use mutation::MutationTestRequest;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::KeyValue;
+use output_events::{LineRecorder, OutputEvent};
+use output_files::OutputFile;
+use panic_report::{PanicReport, UserLines};
+use queue::{JobListQuery, JobQueue, JobStdinRequest, PriorityError, StdinError};
+use quota::{ExecutionCost, TenantUsageTable};
+use readiness::{ActiveExecution, ExecutionRegistry, StdinFeed, ToolchainVersions, KILLED_MESSAGE};
+use repl::{ReplEvalRequest, ReplSessions};
+pub use rules::ConstructRules;
+use rusage::{ProcessGroup, ResourceUsage};
+use serde::{Deserialize, Serialize};
+pub use status::ExecutionStatus;
+pub(crate) use status::StatusError;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use syscall_audit::SyscallAudit;
+use tempfile::TempDir;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Notify;
+use tokio::time::timeout;
+use utoipa::{OpenApi, ToSchema};
+use validation_cache::ValidationCache;
+use warmup::{WarmupState, WarmupStatus};
+use warp::{Filter, Reply};
+use wrapping::WrapMode;
+
+pub use error_reporting::init as init_error_reporting;
+pub use grpc::serve as serve_grpc;
+pub use judge::{JudgeRequest, JudgeResponse};
+pub use telemetry::{init as init_telemetry, serve as serve_http, shutdown as shutdown_telemetry};
+pub use tls::serve as serve_tls;
+
+/// An extra source file of a submission, e.g. `src/parser.rs`
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// A file the program can open, written next to Cargo.toml; the program runs
+/// with the project directory as its working directory
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+pub struct DataFile {
+    /// Relative path, e.g. "input.txt" or "data/grid.txt"
+    pub name: String,
+    #[serde(rename = "contentBase64")]
+    pub content_base64: String,
+}
+
+/// A submission to compile and run, the body of POST /execute. Only `code`
+/// is required; everything else falls back to the configured defaults.
+#[derive(Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct CodeExecutionRequest {
+    /// Backend to run the code with; "rust" (default) is the only one built
+    /// in. /info lists them under `languages`.
+    pub language: Option<String>,
+    #[serde(default)]
+    pub code: String,
+    /// Additional modules, e.g. src/lib.rs or src/parser.rs. A src/main.rs
+    /// entry here replaces `code` as the entry point.
+    pub files: Option<Vec<SourceFile>>,
+    /// Input files for file-I/O assignments, e.g.
+    /// std::fs::read_to_string("input.txt")
+    #[serde(rename = "dataFiles")]
+    pub data_files: Option<Vec<DataFile>>,
+    /// Crates to build with, by name, from the configured allowlist (/info
+    /// lists it under `dependencies` with the versions used). They are
+    /// built offline from sources vendored into the image.
+    pub dependencies: Option<Vec<String>>,
+    #[serde(rename = "inputData")]
+    pub input_data: Option<String>,
+    /// Whether stdin is closed once `inputData` has been written, so a
+    /// program reading until EOF finishes (default true). False keeps it
+    /// open: WebSocket sessions (where false is the default) and jobs then
+    /// take further input until the client sends EOF, and elsewhere a
+    /// program reading past its input waits until a time limit.
+    #[serde(rename = "closeStdin")]
+    pub close_stdin: Option<bool>,
+    /// Wall-clock seconds
+    pub timeout: Option<u64>,
+    /// CPU seconds, enforced independently of `timeout`: a program blocked on
+    /// input uses up wall-clock time but no CPU time
+    #[serde(rename = "cpuTimeLimit")]
+    pub cpu_time_limit: Option<u64>,
+    /// Seconds the build may take, up to the configured max_compile_timeout;
+    /// `timeout` only covers the run. Submissions heavy on generics may need
+    /// longer than the default compile_timeout.
+    #[serde(rename = "compileTimeout")]
+    pub compile_timeout: Option<u64>,
+    /// Virtual clock and seeded `rand` shim for reproducible output
+    pub deterministic: Option<bool>,
+    pub seed: Option<u64>,
+    /// Environment for the executed binary, filtered through the allowlist
+    pub env: Option<HashMap<String, String>>,
+    /// "utf8" (default) or "base64"
+    #[serde(rename = "inputEncoding")]
+    pub input_encoding: Option<String>,
+    /// "utf8" (default, lossy), "base64", or "auto" (base64 only when stdout
+    /// is not valid UTF-8)
+    #[serde(rename = "outputEncoding")]
+    pub output_encoding: Option<String>,
+    /// Queue priority for /jobs: "high", "normal" (default) or "low". Workers
+    /// always take the highest priority job waiting. "high" needs a role
+    /// vouched for with the admin token (see high_priority_roles).
+    pub priority: Option<String>,
+    /// Cargo profile to build with: "dev" (default, faster to compile, debug
+    /// assertions on) or "release" (optimized, for benchmarks and grading)
+    pub profile: Option<String>,
+    /// opt-level override on top of `profile`: 0-3 or "s"
+    #[serde(rename = "optLevel")]
+    pub opt_level: Option<OptLevel>,
+    /// Integer overflow panics when true, wraps when false; unset keeps the
+    /// profile's default (on for dev, off for release)
+    #[serde(rename = "overflowChecks")]
+    pub overflow_checks: Option<bool>,
+    /// "auto" (default) decides from the code whether it needs a generated
+    /// `main`; "wrap" always places it inside one; "raw" compiles it exactly
+    /// as submitted, without the standard imports
+    #[serde(rename = "wrapMode")]
+    pub wrap_mode: Option<String>,
+    /// Replaces the generated wrapper: src/main.rs is this text with the
+    /// code in place of its single {{STUDENT_CODE}} placeholder, so an
+    /// assignment can surround the submission with a prelude, hidden helper
+    /// functions and its own `main`. Cannot be combined with wrapMode.
+    pub template: Option<String>,
+    /// Functions the code must define at its top level, e.g.
+    /// `pub fn sort(v: &mut Vec<i32>)`. Checked before compiling, so a
+    /// missing or mismatched function is reported as such rather than as
+    /// errors in the template or harness that calls it.
+    #[serde(rename = "requiredSignatures")]
+    pub required_signatures: Option<Vec<String>>,
+    /// Constructs the assignment forbids or requires, e.g. no `for` loops,
+    /// no `.clone()`, or recursion. A violation fails the submission before
+    /// it is compiled.
+    pub rules: Option<ConstructRules>,
+    /// Compiles the program with `#![forbid(unsafe_code)]`, rejecting code
+    /// that tries to allow it again. Always on when the executor is
+    /// configured with forbid_unsafe_code.
+    #[serde(rename = "forbidUnsafe")]
+    pub forbid_unsafe: Option<bool>,
+    /// Fails the submission when rustc warns about anything in the user's
+    /// code, as if built with `-D warnings`; the warnings are returned in
+    /// `diagnostics`. Warnings about the generated wrapper are ignored.
+    pub strict: Option<bool>,
+    /// Lint levels and clippy.toml values for this execution. Denied lints
+    /// firing in the user's code fail the submission; other warnings are
+    /// returned in `diagnostics`.
+    pub lints: Option<LintConfig>,
+    /// "strip" (default) removes ANSI escape sequences such as colors from
+    /// the output and errors; "preserve" keeps them for frontends that
+    /// render them
+    pub ansi: Option<String>,
+    /// Runs the program under strace and returns a summary of the files,
+    /// network and processes it used. Tracing slows the program down, which
+    /// shows in runTimeMs and counts against the time limits.
+    #[serde(rename = "auditSyscalls")]
+    pub audit_syscalls: Option<bool>,
+}
+
+/// An opt-level: `0`-`3`, or `"s"` to optimize for size
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+#[serde(untagged)]
+pub enum OptLevel {
+    Number(u8),
+    Name(String),
+}
+
+/// The result of an execution. `status` says how it ended; `error` is the
+/// text to show for it.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct CodeExecutionResponse {
+    pub output: String,
+    /// Encoding actually used for `output`: "utf8" or "base64"
+    #[serde(rename = "outputEncoding")]
+    pub output_encoding: String,
+    pub error: String,
+    /// Total wall-clock seconds, compilation included
+    #[serde(rename = "executionTime")]
+    pub execution_time: f64,
+    pub status: ExecutionStatus,
+    /// null when compilation was not reached
+    #[serde(rename = "compileTimeMs")]
+    pub compile_time_ms: Option<f64>,
+    /// null when the program never ran
+    #[serde(rename = "runTimeMs")]
+    pub run_time_ms: Option<f64>,
+    /// Resource usage of the program as reported by wait4; null when it
+    /// never ran
+    #[serde(rename = "peakMemoryKB")]
+    pub peak_memory_kb: Option<u64>,
+    #[serde(rename = "userCpuMs")]
+    pub user_cpu_ms: Option<u64>,
+    #[serde(rename = "systemCpuMs")]
+    pub system_cpu_ms: Option<u64>,
+    /// The program's exit code; null when it never ran or was killed by a
+    /// signal
+    #[serde(rename = "exitCode")]
+    pub exit_code: Option<i32>,
+    /// Download link for the full output when it was too large to return
+    /// inline; `output` then only holds its beginning
+    #[serde(rename = "outputUrl")]
+    pub output_url: Option<String>,
+    /// With status "timeout", the limit that stopped it: "compileTime" when
+    /// the build ran out of time, "wallClock" or "cpuTime" when the program
+    /// did. "memory" with status "memory_limit".
+    #[serde(rename = "limitExceeded")]
+    pub limit_exceeded: Option<String>,
+    /// With status "memory_limit", the limit the program ran into;
+    /// `peakMemoryKB` is how much it was using
+    #[serde(rename = "memoryLimitKB")]
+    pub memory_limit_kb: Option<u64>,
+    /// True when a time limit stopped the program; `output` and `error` then
+    /// hold what it wrote up to that point
+    #[serde(rename = "truncatedByTimeout", default)]
+    pub truncated_by_timeout: bool,
+    /// Set when the program panicked; the backtrace is then left out of
+    /// `error` unless the request set RUST_BACKTRACE itself
+    pub panic: Option<PanicReport>,
+    /// Files the program wrote into its working directory, e.g. a CSV or PPM
+    /// it was asked to produce
+    #[serde(rename = "outputFiles", default)]
+    pub output_files: Vec<OutputFile>,
+    /// stdout and stderr line by line in the order they were written, next
+    /// to the separate `output` and `error`; empty when the program never ran
+    #[serde(rename = "outputEvents", default)]
+    pub output_events: Vec<OutputEvent>,
+    /// Set when the request asked for auditSyscalls and the program ran
+    #[serde(rename = "syscallAudit")]
+    pub syscall_audit: Option<SyscallAudit>,
+    /// Warnings and denied lints in the user's code, from the lint pass run
+    /// for `strict` and `lints`
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// `rustc --version` of the compiler that built the program, e.g.
+    /// "rustc 1.82.0 (f6e511eec 2024-10-15)"
+    #[serde(rename = "toolchainVersion", default)]
+    pub toolchain_version: Option<String>,
+}
+
+impl CodeExecutionResponse {
+    // base64 output is left alone: it is returned byte for byte
+    fn strip_ansi(&mut self) {
+        if self.output_encoding == "utf8" {
+            self.output = ansi::strip(&self.output);
+        }
+        self.error = ansi::strip(&self.error);
+        for event in &mut self.output_events {
+            event.text = ansi::strip(&event.text);
+        }
+    }
+
+    // Keeps the first and last lines of long output and error text
+    fn limit_lines(&mut self, max_lines: usize) {
+        if self.output_encoding == "utf8" {
+            self.output = omit_middle_lines(&self.output, max_lines);
+        }
+        self.error = omit_middle_lines(&self.error, max_lines);
+    }
+
+    fn rejected(rejection: StatusError, execution_time: f64) -> Self {
+        Self::error(rejection.status, rejection.message, execution_time)
+    }
+
+    fn error(status: ExecutionStatus, message: String, execution_time: f64) -> Self {
+        Self {
+            output: String::new(),
+            output_encoding: "utf8".to_string(),
+            error: message,
+            execution_time,
+            status,
+            compile_time_ms: None,
+            run_time_ms: None,
+            peak_memory_kb: None,
+            user_cpu_ms: None,
+            system_cpu_ms: None,
+            exit_code: None,
+            output_url: None,
+            limit_exceeded: None,
+            memory_limit_kb: None,
+            truncated_by_timeout: false,
+            panic: None,
+            output_files: Vec::new(),
+            output_events: Vec::new(),
+            syscall_audit: None,
+            diagnostics: Vec::new(),
+            toolchain_version: None,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CodeValidationRequest {
+    code: String,
+}
+
+/// Compiler diagnostics from checking a submission without running it
+#[derive(Clone, Serialize, ToSchema)]
+pub struct CodeValidationResponse {
+    #[serde(rename = "isValid")]
+    pub is_valid: bool,
+    /// One entry per compiler diagnostic, with its line and column in the
+    /// submitted code
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    /// The same diagnostics with their full span in the submitted code and
+    /// rustc's suggested fixes
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Result of `cargo check` on a submission
+struct CheckOutput {
+    // cargo's JSON messages
+    stdout: String,
+    stderr: String,
+    success: bool,
+    // Generated lines and bytes before the user's code
+    line_offset: usize,
+    byte_offset: usize,
+}
+
+// Outcome of compiling and/or running a program. `stderr` carries the
+// executor's own error message when the failure happened before the run.
+struct RunOutcome {
+    stdout: Vec<u8>,
+    stderr: String,
+    status: ExecutionStatus,
+    compile_time: Duration,
+    // From spawn to exit; missing when the program never ran
+    run_time: Option<Duration>,
+    // Missing when the program never ran
+    usage: Option<ResourceUsage>,
+    // Missing when the program never ran or was killed by a signal
+    exit_code: Option<i32>,
+    // Set when the program was stopped for exceeding a limit
+    limit_exceeded: Option<LimitExceeded>,
+    // Both streams interleaved; empty when the program never ran
+    events: Vec<OutputEvent>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LimitExceeded {
+    WallClock,
+    CpuTime,
+    // The build, not the program, ran out of time
+    CompileTime,
+    Memory { limit_kb: u64 },
+    // The caller's deadline (RunLimits::deadline) ended the run before the
+    // program's own limits did
+    TimeBudget,
+}
+
+impl LimitExceeded {
+    fn as_str(self) -> &'static str {
+        match self {
+            LimitExceeded::WallClock => "wallClock",
+            LimitExceeded::CpuTime => "cpuTime",
+            LimitExceeded::CompileTime => "compileTime",
+            LimitExceeded::Memory { .. } => "memory",
+            LimitExceeded::TimeBudget => "timeBudget",
+        }
+    }
+
+    // A time limit cut the program off while it was running
+    fn stopped_program(self) -> bool {
+        matches!(
+            self,
+            LimitExceeded::WallClock | LimitExceeded::CpuTime | LimitExceeded::TimeBudget
+        )
+    }
+
+    fn memory_limit_kb(self) -> Option<u64> {
+        match self {
+            LimitExceeded::Memory { limit_kb } => Some(limit_kb),
+            _ => None,
+        }
+    }
+}
+
+// Time limits for one run of a user program
+#[derive(Clone, Copy)]
+struct RunLimits {
+    wall_seconds: u64,
+    cpu_seconds: Option<u64>,
+    // Kills the program at this instant even within wall_seconds, e.g. at
+    // the end of /stress's time budget
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl RunLimits {
+    fn wall_clock(seconds: u64) -> Self {
+        Self {
+            wall_seconds: seconds,
+            cpu_seconds: None,
+            deadline: None,
+        }
+    }
+
+    // How a run killed at its deadline ended: a deadline before the
+    // wall-clock limit is not the program's fault
+    fn timed_out(&self, deadline_first: bool) -> RunOutcome {
+        if !deadline_first {
+            return self.wall_clock_exceeded();
+        }
+        RunOutcome {
+            limit_exceeded: Some(LimitExceeded::TimeBudget),
+            ..RunOutcome::failed(
+                "The time budget ran out during this run".to_string(),
+                ExecutionStatus::Timeout,
+            )
+        }
+    }
+
+    fn wall_clock_exceeded(&self) -> RunOutcome {
+        RunOutcome {
+            limit_exceeded: Some(LimitExceeded::WallClock),
+            ..RunOutcome::failed(
+                format!(
+                    "Code execution timed out after {} seconds",
+                    self.wall_seconds
+                ),
+                ExecutionStatus::Timeout,
+            )
+        }
+    }
+
+    // Stopped by RLIMIT_CPU: SIGXCPU at the limit, or SIGKILL a second
+    // later when the program ignores SIGXCPU
+    fn cpu_time_exceeded(&self, status: std::process::ExitStatus, usage: &ResourceUsage) -> bool {
+        use std::os::unix::process::ExitStatusExt;
+
+        let Some(cpu_seconds) = self.cpu_seconds else {
+            return false;
+        };
+        match status.signal() {
+            Some(libc::SIGXCPU) => true,
+            Some(libc::SIGKILL) => usage.cpu_seconds() >= cpu_seconds as f64,
+            _ => false,
+        }
+    }
+
+    fn cpu_time_message(&self) -> String {
+        format!(
+            "CPU time limit of {} seconds exceeded",
+            self.cpu_seconds.unwrap_or_default()
+        )
+    }
+}
+
+// How long output is still read after a program is killed at its timeout
+const TIMEOUT_OUTPUT_DRAIN: Duration = Duration::from_secs(1);
+
+fn compile_timeout_message(seconds: u64) -> String {
+    format!("Compilation timed out after {} seconds", seconds)
+}
+
+const STACK_OVERFLOW_MESSAGE: &str = "Stack overflow: the program used up its stack, \
+usually through recursion that never reaches its base case";
+
+// Rust's guard page handler prints "has overflowed its stack" and aborts;
+// streamed runs pass no stderr and are only recognised by the SIGSEGV of an
+// overflow that ran past the guard page
+fn stack_overflowed(status: std::process::ExitStatus, stderr: Option<&str>) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    match status.signal() {
+        Some(libc::SIGSEGV) => true,
+        Some(libc::SIGABRT) => {
+            stderr.is_some_and(|stderr| stderr.contains("has overflowed its stack"))
+        }
+        _ => false,
+    }
+}
+
+impl RunOutcome {
+    fn failed(message: String, status: ExecutionStatus) -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: message,
+            status,
+            compile_time: Duration::ZERO,
+            run_time: None,
+            usage: None,
+            exit_code: None,
+            limit_exceeded: None,
+            events: Vec::new(),
+        }
+    }
+
+    fn killed() -> Self {
+        Self::failed(KILLED_MESSAGE.to_string(), ExecutionStatus::Killed)
+    }
+
+    fn compile_timed_out(seconds: u64) -> Self {
+        Self {
+            limit_exceeded: Some(LimitExceeded::CompileTime),
+            ..Self::failed(compile_timeout_message(seconds), ExecutionStatus::Timeout)
+        }
+    }
+
+    fn stdout_text(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).trim().to_string()
+    }
+
+    // `compile_time_ms` is left unset for runs that did not compile anything
+    fn into_response(self, output_encoding: &str, execution_time: f64) -> CodeExecutionResponse {
+        // Binary output is only returned verbatim when asked for; the default
+        // keeps the historical trimmed, lossy UTF-8 text
+        let (output, output_encoding) = match output_encoding {
+            "base64" => (BASE64.encode(&self.stdout), "base64"),
+            "auto" => match std::str::from_utf8(&self.stdout) {
+                Ok(text) => (text.trim().to_string(), "utf8"),
+                Err(_) => (BASE64.encode(&self.stdout), "base64"),
+            },
+            _ => (self.stdout_text(), "utf8"),
+        };
+        CodeExecutionResponse {
+            output,
+            output_encoding: output_encoding.to_string(),
+            error: self.stderr,
+            execution_time,
+            status: self.status,
+            compile_time_ms: None,
+            run_time_ms: self
+                .run_time
+                .map(|run_time| run_time.as_secs_f64() * 1000.0),
+            peak_memory_kb: self.usage.map(|usage| usage.peak_memory_kb),
+            user_cpu_ms: self.usage.map(|usage| usage.user_cpu_ms),
+            system_cpu_ms: self.usage.map(|usage| usage.system_cpu_ms),
+            exit_code: self.exit_code,
+            output_url: None,
+            limit_exceeded: self.limit_exceeded.map(|limit| limit.as_str().to_string()),
+            memory_limit_kb: self.limit_exceeded.and_then(LimitExceeded::memory_limit_kb),
+            truncated_by_timeout: self
+                .limit_exceeded
+                .is_some_and(LimitExceeded::stopped_program),
+            panic: None,
+            output_files: Vec::new(),
+            output_events: self.events,
+            syscall_audit: None,
+            diagnostics: Vec::new(),
+            toolchain_version: None,
+        }
+    }
+}
+
+// "… N lines omitted …" in place of everything but the first and last
+// max_lines / 2 lines
+fn omit_middle_lines(text: &str, max_lines: usize) -> String {
+    let line_count = text.lines().count();
+    if line_count <= max_lines {
+        return text.to_string();
+    }
+    let tail = max_lines / 2;
+    let head = max_lines - tail;
+    let lines: Vec<&str> = text.lines().collect();
+    let mut limited = lines[..head].join("\n");
+    limited.push_str(&format!(
+        "\n… {} lines omitted …\n",
+        line_count - head - tail
+    ));
+    limited.push_str(&lines[line_count - tail..].join("\n"));
+    limited
+}
+
+fn decode_input(
+    input_data: Option<String>,
+    input_encoding: Option<&str>,
+) -> Result<Option<Vec<u8>>, String> {
+    match (input_encoding, input_data) {
+        (_, None) => Ok(None),
+        (None | Some("utf8"), Some(input)) => Ok(Some(input.into_bytes())),
+        (Some("base64"), Some(input)) => BASE64
+            .decode(input.trim())
+            .map(Some)
+            .map_err(|e| format!("inputData is not valid base64: {}", e)),
+        (Some(other), Some(_)) => Err(format!("Unsupported inputEncoding '{}'", other)),
+    }
+}
+
+// rustc error codes look like "E0308"; lint diagnostics carry the lint
+// name instead
+fn is_lint(diagnostic: &Diagnostic) -> bool {
+    diagnostic.code.as_deref().is_some_and(|code| {
+        !(code.starts_with('E') && code[1..].chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+// What the lint pass means for the submission: the warnings to return with
+// its result, or why it fails, which is a denied lint or, in strict mode,
+// any warning. Compile errors are left to the build, which reports them as
+// usual.
+fn lint_verdict(
+    diagnostics: Vec<Diagnostic>,
+    strict: bool,
+) -> Result<Vec<Diagnostic>, (String, Vec<Diagnostic>)> {
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.level == "error" && !is_lint(diagnostic))
+    {
+        return Ok(Vec::new());
+    }
+    let failing: Vec<&Diagnostic> = diagnostics
+        .iter()
+        .filter(|diagnostic| strict || diagnostic.level == "error")
+        .collect();
+    if failing.is_empty() {
+        return Ok(diagnostics);
+    }
+    let mut message = if strict {
+        format!(
+            "Strict mode: the code has {} warning(s), which are treated as errors",
+            failing.len()
+        )
+    } else {
+        format!("The code breaks {} denied lint(s)", failing.len())
+    };
+    for diagnostic in failing {
+        message.push('\n');
+        message.push_str(&diagnostic.summary());
+    }
+    Err((message, diagnostics))
+}
+
+fn check_cpu_time_limit(
+    config: &ExecutorConfig,
+    cpu_time_limit: Option<u64>,
+) -> Result<(), String> {
+    if cpu_time_limit.is_some_and(|limit| limit == 0 || limit > config.max_request_timeout) {
+        return Err(format!(
+            "cpuTimeLimit must be between 1 and {} seconds",
+            config.max_request_timeout
+        ));
+    }
+    Ok(())
+}
+
+fn check_compile_timeout(
+    config: &ExecutorConfig,
+    compile_timeout: Option<u64>,
+) -> Result<(), String> {
+    if compile_timeout.is_some_and(|limit| limit == 0 || limit > config.max_compile_timeout) {
+        return Err(format!(
+            "compileTimeout must be between 1 and {} seconds",
+            config.max_compile_timeout
+        ));
+    }
+    Ok(())
+}
+
+// Cargo profile a submission is built with
+#[derive(Clone, Copy, Default)]
+enum BuildProfile {
+    #[default]
+    Dev,
+    Release,
+}
+
+impl BuildProfile {
+    fn parse(profile: Option<&str>) -> Result<Self, String> {
+        match profile {
+            None | Some("dev") => Ok(BuildProfile::Dev),
+            Some("release") => Ok(BuildProfile::Release),
+            Some(other) => Err(format!("Unsupported profile '{}'", other)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BuildProfile::Dev => "dev",
+            BuildProfile::Release => "release",
+        }
+    }
+}
+
+// Per-request overrides live in this profile of the generated Cargo.toml,
+// which inherits everything else from the requested one
+const CUSTOM_PROFILE: &str = "submission";
+
+// How a submission is built: a cargo profile and the settings it overrides
+#[derive(Clone, Copy, Default)]
+struct BuildSettings {
+    profile: BuildProfile,
+    // As written into Cargo.toml, e.g. `3` or `"s"`
+    opt_level: Option<&'static str>,
+    overflow_checks: Option<bool>,
+    // Seconds the build may take; the configured compile_timeout when unset
+    compile_timeout: Option<u64>,
+}
+
+impl BuildSettings {
+    fn parse(
+        profile: Option<&str>,
+        opt_level: Option<&OptLevel>,
+        overflow_checks: Option<bool>,
+    ) -> Result<Self, String> {
+        let opt_level = match opt_level {
+            None => None,
+            Some(OptLevel::Number(level)) => Some(level.to_string()),
+            Some(OptLevel::Name(name)) => Some(name.clone()),
+        };
+        let opt_level = match opt_level.as_deref() {
+            None => None,
+            Some("0") => Some("0"),
+            Some("1") => Some("1"),
+            Some("2") => Some("2"),
+            Some("3") => Some("3"),
+            Some("s") => Some("\"s\""),
+            Some(other) => return Err(format!("Unsupported optLevel '{}'", other)),
+        };
+        Ok(Self {
+            profile: BuildProfile::parse(profile)?,
+            opt_level,
+            overflow_checks,
+            compile_timeout: None,
+        })
+    }
+
+    fn compile_seconds(&self, config: &ExecutorConfig) -> u64 {
+        self.compile_timeout.unwrap_or(config.compile_timeout)
+    }
+
+    fn has_overrides(&self) -> bool {
+        self.opt_level.is_some() || self.overflow_checks.is_some()
+    }
+
+    // Profile passed to `cargo build --profile`
+    fn cargo_profile(&self) -> &'static str {
+        if self.has_overrides() {
+            CUSTOM_PROFILE
+        } else {
+            self.profile.name()
+        }
+    }
+
+    // Directory under target/ the binary ends up in
+    fn target_dir(&self) -> &'static str {
+        match self.cargo_profile() {
+            "dev" => "debug",
+            profile => profile,
+        }
+    }
+
+    // Section appended to the generated Cargo.toml; empty without overrides
+    fn cargo_toml_section(&self) -> String {
+        if !self.has_overrides() {
+            return String::new();
+        }
+        let mut section = format!(
+            "\n[profile.{}]\ninherits = \"{}\"\n",
+            CUSTOM_PROFILE,
+            self.profile.name()
+        );
+        if let Some(opt_level) = self.opt_level {
+            section.push_str(&format!("opt-level = {}\n", opt_level));
+        }
+        if let Some(overflow_checks) = self.overflow_checks {
+            section.push_str(&format!("overflow-checks = {}\n", overflow_checks));
+        }
+        section
+    }
+}
+
+fn parse_output_encoding(output_encoding: Option<String>) -> Result<String, String> {
+    let output_encoding = output_encoding.unwrap_or_else(|| "utf8".to_string());
+    if !matches!(output_encoding.as_str(), "utf8" | "base64" | "auto") {
+        return Err(format!("Unsupported outputEncoding '{}'", output_encoding));
+    }
+    Ok(output_encoding)
+}
+
+// Bytes a program may write to each of stdout and stderr. The first
+// stream to go past it wakes the run, which stops the program.
+#[derive(Clone)]
+struct OutputCap {
+    max_bytes: usize,
+    exceeded: Arc<AtomicBool>,
+    full: Arc<Notify>,
+}
+
+impl OutputCap {
+    fn new(max_output_kb: u32) -> Self {
+        Self {
+            max_bytes: max_output_kb as usize * 1024,
+            exceeded: Arc::default(),
+            full: Arc::default(),
+        }
+    }
+
+    fn exceed(&self) {
+        self.exceeded.store(true, Ordering::Relaxed);
+        self.full.notify_one();
+    }
+
+    fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+fn output_limit_message(max_output_kb: u32) -> String {
+    format!(
+        "Output limit of {} KB exceeded: the program was stopped",
+        max_output_kb
+    )
+}
+
+// Everything read from the pipe up to the cap, also split into timed lines
+async fn read_pipe<R: AsyncRead + Unpin>(
+    pipe: Option<R>,
+    mut lines: LineRecorder,
+    cap: OutputCap,
+) -> (Vec<u8>, Vec<OutputEvent>) {
+    let mut output = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let mut buf = [0u8; 8192];
+        while let Ok(n @ 1..) = pipe.read(&mut buf).await {
+            let kept = n.min(cap.max_bytes - output.len());
+            output.extend_from_slice(&buf[..kept]);
+            lines.push(&buf[..kept]);
+            if kept < n {
+                cap.exceed();
+                break;
+            }
+        }
+    }
+    (output, lines.finish())
+}
+
+// Writes the input and then whatever the feed delivers. Returning drops
+// the pipe, which the program reads as EOF; with keep_open and no feed it
+// never returns and is aborted once the program exits.
+async fn write_stdin(
+    mut stdin: tokio::process::ChildStdin,
+    input: Option<Vec<u8>>,
+    feed: Option<StdinFeed>,
+    keep_open: bool,
+) {
+    if let Some(input) = input {
+        if let Err(e) = stdin.write_all(&input).await {
+            eprintln!("Failed to write to stdin: {}", e);
+            return;
+        }
+    }
+    match feed {
+        Some(mut feed) => {
+            while let Some(chunk) = feed.recv().await {
+                if stdin.write_all(&chunk).await.is_err() || stdin.flush().await.is_err() {
+                    return;
+                }
+            }
+        }
+        None if keep_open => std::future::pending().await,
+        None => {}
+    }
+}
+
+// Aborts a background task when the scope that started it ends
+struct AbortOnDrop(Option<tokio::task::JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(task) = &self.0 {
+            task.abort();
+        }
+    }
+}
+
+// A submission written to disk and ready to compile, together with the run
+// settings derived from its request
+struct PreparedExecution {
+    temp_dir: TempDir,
+    input: Option<Vec<u8>>,
+    // As requested; each entry point has its own default
+    close_stdin: Option<bool>,
+    output_encoding: String,
+    timeout_seconds: u64,
+    cpu_time_limit: Option<u64>,
+    env: Vec<(String, String)>,
+    user_lines: UserLines,
+    build: BuildSettings,
+    // Names of the request's dataFiles, left out of the output files
+    data_file_names: Vec<String>,
+    ansi: AnsiMode,
+    audit_syscalls: bool,
+    // Warnings in the user's code fail the submission
+    strict: bool,
+    lints: Option<LintConfig>,
+    // Built with #![forbid(unsafe_code)]
+    forbid_unsafe: bool,
+}
+
+impl PreparedExecution {
+    // A lint pass runs before the build
+    fn needs_lint_pass(&self) -> bool {
+        self.strict || self.lints.is_some()
+    }
+}
+
+impl PreparedExecution {
+    fn limits(&self) -> RunLimits {
+        RunLimits {
+            wall_seconds: self.timeout_seconds,
+            cpu_seconds: self.cpu_time_limit,
+            deadline: None,
+        }
+    }
+}
+
+// Where and how a user binary is started
+#[derive(Clone, Copy, Default)]
+struct ChildSetup<'a> {
+    // Submissions run in their project directory, where their data files
+    // are; without one the service's working directory is inherited
+    working_dir: Option<&'a Path>,
+    // Runs the binary under strace, which writes its trace here
+    trace_log: Option<&'a Path>,
+    // Leaves stdin open after the input has been written rather than
+    // signalling EOF; input fed through the execution is written as well
+    keep_stdin_open: bool,
+}
+
+// The executed binary never inherits the service environment; it only gets
+// this PATH plus whatever the request supplied
+const CHILD_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// The executor service. Cheap to clone: clones share configuration,
+/// caches and running executions.
+pub struct RustExecutor {
+    // Swapped as a whole on reload; requests keep the snapshot they started with
+    config: Arc<RwLock<Arc<ExecutorConfig>>>,
+    // Required as a bearer token by /admin endpoints; unset disables them
+    admin_token: Option<String>,
+    repl_sessions: ReplSessions,
+    // rust-analyzer instances serving /complete, by editor session
+    analyzer_sessions: AnalyzerSessions,
+    // Set when EXECUTION_HISTORY_DATABASE_URL is configured
+    history: Option<Arc<ExecutionHistory>>,
+    // Set when AUDIT_LOG_DATABASE_URL is configured
+    audit_log: Option<Arc<AuditLog>>,
+    idempotency: Arc<IdempotencyCache>,
+    explanations: ExplanationCache,
+    validations: Arc<ValidationCache>,
+    artifacts: Arc<ArtifactStore>,
+    // Set when REDIS_URL is configured
+    job_queue: Option<JobQueue>,
+    // Executions currently compiling or running, listed by
+    // /admin/executions; /ready reports not ready once there are
+    // max_active_executions of them
+    running_executions: ExecutionRegistry,
+    tenant_usage: TenantUsageTable,
+    metrics: Arc<ServiceMetrics>,
+    // When each execution step last worked, for /health
+    phase_successes: Arc<PhaseSuccesses>,
+    // Whether the startup warm-up compilation has finished
+    warmup: WarmupState,
+    toolchain: Arc<ToolchainVersions>,
+    load_shedder: Arc<LoadShedder>,
+    // Backends by request language
+    executors: Arc<ExecutorRegistry>,
+}
+
+impl RustExecutor {
+    /// An executor with `config`. Optional stores (execution history, audit
+    /// log, object storage) and the admin token are read from the
+    /// environment, as the service does.
+    pub fn new(config: ExecutorConfig) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            repl_sessions: ReplSessions::default(),
+            analyzer_sessions: AnalyzerSessions::default(),
+            history: env::var("EXECUTION_HISTORY_DATABASE_URL")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .map(|url| Arc::new(ExecutionHistory::new(url))),
+            audit_log: env::var("AUDIT_LOG_DATABASE_URL")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .map(|url| Arc::new(AuditLog::new(url))),
+            idempotency: Arc::new(IdempotencyCache::new()),
+            explanations: ExplanationCache::default(),
+            validations: Arc::new(ValidationCache::new()),
+            artifacts: Arc::new(ArtifactStore::new()),
+            job_queue: None,
+            running_executions: ExecutionRegistry::default(),
+            tenant_usage: TenantUsageTable::default(),
+            metrics: Arc::new(ServiceMetrics::default()),
+            phase_successes: Arc::new(PhaseSuccesses::default()),
+            warmup: Arc::new(RwLock::new(WarmupStatus::Running)),
+            toolchain: Arc::new(ToolchainVersions::default()),
+            load_shedder: Arc::new(LoadShedder::default()),
+            executors: Arc::new(ExecutorRegistry::new()),
+        }
+    }
+
+    /// Detects rustc, cargo, clippy, strace and rust-analyzer. Features that
+    /// need a missing tool are refused until this has found it.
+    pub async fn detect_toolchain(&mut self) {
+        self.toolchain = Arc::new(ToolchainVersions::detect().await);
+    }
+
+    /// Connects the Redis job queue behind /jobs and the workers
+    pub async fn connect_job_queue(&mut self, redis_url: &str) -> Result<(), String> {
+        self.job_queue = Some(JobQueue::connect(redis_url).await?);
+        Ok(())
+    }
+
+    pub fn has_job_queue(&self) -> bool {
+        self.job_queue.is_some()
+    }
+
+    /// Executes queued jobs until the process stops; fails without a job
+    /// queue
+    pub async fn run_job_workers(self) -> Result<(), String> {
+        let queue = self.job_queue.clone().ok_or("No job queue is connected")?;
+        self.run_worker(queue).await;
+        Ok(())
+    }
+
+    /// Starts the background tasks of a long-running service: reloading the
+    /// configuration on SIGHUP, removing abandoned temp directories,
+    /// deleting persisted data past its retention, and killing and reaping
+    /// processes that outlived their execution
+    pub fn spawn_maintenance(&self) {
+        tokio::spawn(admin::reload_on_sighup(self.clone()));
+        tokio::spawn(reaper::reap_temp_dirs_periodically(self.clone()));
+        tokio::spawn(gc::collect_garbage_periodically(self.clone()));
+        reaper::adopt_orphans();
+        tokio::spawn(reaper::reap_stray_processes_periodically(self.clone()));
+    }
+
+    /// Compiles and runs a submission, as POST /execute does for a request
+    /// without a requester or tenant
+    pub async fn execute(&self, req: CodeExecutionRequest) -> CodeExecutionResponse {
+        self.execute_code(req, None).await
+    }
+
+    /// The configuration in effect; reloads replace it as a whole
+    pub fn config(&self) -> Arc<ExecutorConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    fn reload_config(&self) -> Result<Arc<ExecutorConfig>, String> {
+        let config = Arc::new(ExecutorConfig::load()?);
+        *self.config.write().unwrap() = config.clone();
+        Ok(config)
+    }
+
+    fn is_env_var_allowed(config: &ExecutorConfig, name: &str) -> bool {
+        config
+            .allowed_env_vars
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    }
+
+    // Validates a request and writes its project to a fresh temp directory
+    fn prepare_execution(
+        &self,
+        req: CodeExecutionRequest,
+    ) -> Result<PreparedExecution, StatusError> {
+        let config = self.config();
+        let CodeExecutionRequest {
+            language,
+            mut code,
+            files,
+            data_files,
+            dependencies,
+            input_data,
+            close_stdin,
+            timeout: timeout_override,
+            cpu_time_limit,
+            compile_timeout,
+            deterministic,
+            seed,
+            env: requested_env,
+            input_encoding,
+            output_encoding,
+            priority: _,
+            profile,
+            opt_level,
+            overflow_checks,
+            wrap_mode,
+            template,
+            required_signatures,
+            rules,
+            forbid_unsafe,
+            strict,
+            lints,
+            ansi,
+            audit_syscalls,
+        } = req;
+        // Other backends are only reached through the registry, which the
+        // endpoints built on this native pipeline (/compile, /judge, ...)
+        // do not go through
+        if let Some(language) = language.filter(|language| language != backend::DEFAULT_LANGUAGE) {
+            return Err(format!(
+                "Language '{}' is only supported by /execute and /jobs",
+                language
+            )
+            .into());
+        }
+        let execution_timeout = timeout_override
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+        check_cpu_time_limit(&config, cpu_time_limit)?;
+        check_compile_timeout(&config, compile_timeout)?;
+
+        let input_bytes = decode_input(input_data, input_encoding.as_deref())?;
+        let output_encoding = parse_output_encoding(output_encoding)?;
+        if template.is_some() && wrap_mode.is_some() {
+            return Err("Provide either a template or a wrapMode, not both"
+                .to_string()
+                .into());
+        }
+        let wrap_mode = WrapMode::parse(wrap_mode.as_deref())?;
+        let ansi = AnsiMode::parse(ansi.as_deref())?;
+        let audit_syscalls = audit_syscalls.unwrap_or(false);
+        if audit_syscalls && self.toolchain.strace.is_none() {
+            return Err("auditSyscalls is unavailable: strace is not installed"
+                .to_string()
+                .into());
+        }
+        let build = BuildSettings {
+            compile_timeout,
+            ..BuildSettings::parse(profile.as_deref(), opt_level.as_ref(), overflow_checks)?
+        };
+
+        let mut files = files.unwrap_or_default();
+        Self::validate_source_files(&config, &files)?;
+        if let Some(index) = files.iter().position(|f| f.path == "src/main.rs") {
+            if !code.trim().is_empty() {
+                return Err(
+                    "Provide the entry point either in `code` or as src/main.rs in `files`, not both"
+                        .to_string()
+                        .into(),
+                );
+            }
+            code = files.remove(index).content;
+        }
+
+        // Code that breaks the assignment's constraints fails as if it had
+        // not compiled
+        signatures::verify(&code, &required_signatures.unwrap_or_default())
+            .map_err(|e| StatusError::new(ExecutionStatus::CompileError, e))?;
+        rules::check(&code, &rules.unwrap_or_default())
+            .map_err(|e| StatusError::new(ExecutionStatus::CompileError, e))?;
+        if let Some(lints) = &lints {
+            lints.validate()?;
+            if lints.needs_clippy() && self.toolchain.clippy.is_none() {
+                return Err("clippy lints are unavailable: clippy is not installed"
+                    .to_string()
+                    .into());
+            }
+        }
+        let forbid_unsafe = forbid_unsafe.unwrap_or(false) || config.forbid_unsafe_code;
+        if forbid_unsafe {
+            wrapping::check_unsafe_not_allowed(
+                std::iter::once(code.as_str()).chain(files.iter().map(|f| f.content.as_str())),
+            )
+            .map_err(|e| StatusError::new(ExecutionStatus::SecurityViolation, e))?;
+        }
+        self.check_unstable_features(
+            std::iter::once(code.as_str())
+                .chain(template.as_deref())
+                .chain(files.iter().map(|f| f.content.as_str())),
+        )?;
+        let data_files = Self::decode_data_files(&config, data_files.unwrap_or_default())?;
+        let dependencies = dependencies::cargo_dependencies(
+            &config,
+            &dependencies.unwrap_or_default(),
+            deterministic.unwrap_or(false),
+        )?;
+        let child_env = Self::allowed_child_env(&config, requested_env.as_ref())
+            .map_err(|e| StatusError::new(ExecutionStatus::SecurityViolation, e))?;
+
+        // Validate code size
+        let total_size = code.len()
+            + template.as_ref().map_or(0, String::len)
+            + files.iter().map(|f| f.content.len()).sum::<usize>();
+        let code_size_kb = total_size as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return Err(format!(
+                "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                code_size_kb, config.max_code_size_kb
+            )
+            .into());
+        }
+
+        // Create temporary directory
+        let temp_dir = reaper::temp_dir().map_err(|e| {
+            StatusError::internal(format!("Failed to create temp directory: {}", e))
+        })?;
+
+        // Create Rust project structure
+        let project_path = temp_dir.path();
+        let src_dir = project_path.join("src");
+        fs::create_dir_all(&src_dir)
+            .map_err(|e| StatusError::internal(format!("Failed to create src directory: {}", e)))?;
+
+        // Create Cargo.toml
+        let cargo_toml = r#"[package]
+name = "rust_exec"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+
+[dependencies]
+"#;
+
+        let cargo_toml = format!(
+            "{}{}{}",
+            cargo_toml,
+            dependencies,
+            build.cargo_toml_section()
+        );
+        fs::write(project_path.join("Cargo.toml"), cargo_toml)
+            .map_err(|e| StatusError::internal(format!("Failed to create Cargo.toml: {}", e)))?;
+        toolchain_pin::write(project_path).map_err(StatusError::internal)?;
+        // Only allowlisted crates, and only from the vendored sources
+        if !dependencies.is_empty() {
+            project::write_offline_config(project_path, &dependencies::vendor_dir())
+                .map_err(StatusError::internal)?;
+        }
+        if let Some(lints) = &lints {
+            lints
+                .write_clippy_toml(project_path)
+                .map_err(StatusError::internal)?;
+        }
+
+        // Create restricted code
+        let mut restricted_code = match &template {
+            Some(template) => wrapping::apply_template(template, &code)?,
+            None => wrapping::wrap(&code, wrap_mode),
+        };
+        if deterministic.unwrap_or(false) {
+            restricted_code = deterministic::instrument(&restricted_code, seed.unwrap_or(0));
+        }
+        if forbid_unsafe {
+            restricted_code = wrapping::forbid_unsafe(&restricted_code);
+        }
+        let user_lines = UserLines::new(&restricted_code, &code);
+        let main_rs_path = src_dir.join("main.rs");
+
+        fs::write(main_rs_path, restricted_code)
+            .map_err(|e| StatusError::internal(format!("Failed to write main.rs: {}", e)))?;
+
+        let sources: Vec<(&str, &str)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.content.as_str()))
+            .collect();
+        project::write_sources(project_path, &sources).map_err(StatusError::internal)?;
+        let data_file_names = data_files.iter().map(|(name, _)| name.clone()).collect();
+        let data_files: Vec<(&str, &[u8])> = data_files
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_slice()))
+            .collect();
+        project::write_sources(project_path, &data_files).map_err(StatusError::internal)?;
+
+        Ok(PreparedExecution {
+            temp_dir,
+            input: input_bytes,
+            close_stdin,
+            output_encoding,
+            timeout_seconds: execution_timeout,
+            cpu_time_limit,
+            env: child_env,
+            user_lines,
+            build,
+            data_file_names,
+            ansi,
+            audit_syscalls,
+            strict: strict.unwrap_or(false),
+            lints,
+            forbid_unsafe,
+        })
+    }
+
+    async fn execute_code(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+    ) -> CodeExecutionResponse {
+        self.execute_code_metered(req, requester_id, None).await.0
+    }
+
+    // Like execute_code, also returning the compile time and CPU time spent.
+    // `stdin` is written to the program after its inputData.
+    async fn execute_code_metered(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+        stdin: Option<StdinFeed>,
+    ) -> (CodeExecutionResponse, ExecutionCost) {
+        let audit_entry = self
+            .audit_log
+            .as_ref()
+            .map(|_| AuditEntry::new(&req, requester_id));
+        let (result, cost) = self.run_execution(req, requester_id, stdin).await;
+        // Recorded in the background like the history, but every failure
+        // is logged: a missing audit record is worth noticing
+        if let (Some(audit_log), Some(entry)) = (self.audit_log.clone(), audit_entry) {
+            let recorded = result.clone();
+            tokio::spawn(async move {
+                if let Err(e) = audit_log.record(entry, &recorded).await {
+                    eprintln!("Failed to write audit record: {}", e);
+                }
+            });
+        }
+        (result, cost)
+    }
+
+    async fn run_execution(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+        stdin: Option<StdinFeed>,
+    ) -> (CodeExecutionResponse, ExecutionCost) {
+        let backend = match self.executors.get(req.language.as_deref()) {
+            Ok(backend) => backend,
+            Err(e) => {
+                return (
+                    CodeExecutionResponse::error(ExecutionStatus::InvalidRequest, e, 0.0),
+                    ExecutionCost::default(),
+                )
+            }
+        };
+        let active = match self.start_execution("execute", requester_id) {
+            Ok(active) => active,
+            Err(e) => {
+                return (
+                    CodeExecutionResponse::error(e.status, e.message, 0.0),
+                    ExecutionCost::default(),
+                )
+            }
+        };
+        if let Some(stdin) = stdin {
+            active.attach_stdin(stdin);
+        }
+        let code_hash = history::code_hash(&req);
+        let (response, cx) =
+            telemetry::in_span("execution", backend.execute(self, req, &active)).await;
+        cx.span()
+            .set_attribute(KeyValue::new("execution.status", response.status.as_str()));
+        if response.status == ExecutionStatus::InternalError {
+            let context = ErrorContext {
+                kind: "execute",
+                execution_id: active.id(),
+                phase: active.phase(),
+                code_hash: &code_hash,
+                requester_id,
+            };
+            error_reporting::report_internal_error(&response.error, &context);
+        }
+        let cost = ExecutionCost {
+            compile_seconds: response.compile_time_ms.unwrap_or(0.0) / 1000.0,
+            cpu_seconds: (response.user_cpu_ms.unwrap_or(0) + response.system_cpu_ms.unwrap_or(0))
+                as f64
+                / 1000.0,
+        };
+        (response, cost)
+    }
+
+    fn allowed_child_env(
+        config: &ExecutorConfig,
+        requested_env: Option<&HashMap<String, String>>,
+    ) -> Result<Vec<(String, String)>, String> {
+        let Some(requested_env) = requested_env else {
+            return Ok(Vec::new());
+        };
+        let mut rejected: Vec<&str> = requested_env
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !Self::is_env_var_allowed(config, name))
+            .collect();
+        if !rejected.is_empty() {
+            rejected.sort_unstable();
+            return Err(format!(
+                "Environment variables not allowed: {}",
+                rejected.join(", ")
+            ));
+        }
+        Ok(requested_env
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect())
+    }
+
+    fn validate_source_files(config: &ExecutorConfig, files: &[SourceFile]) -> Result<(), String> {
+        if files.len() > config.max_source_files {
+            return Err(format!(
+                "Too many files ({}), at most {} are allowed",
+                files.len(),
+                config.max_source_files
+            ));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for file in files {
+            let path = Path::new(&file.path);
+            let inside_src = path.starts_with("src")
+                && path
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !inside_src || path.extension().is_none_or(|ext| ext != "rs") {
+                return Err(format!(
+                    "Invalid file path '{}': files must be .rs sources under src/",
+                    file.path
+                ));
+            }
+            if !seen.insert(path) {
+                return Err(format!("Duplicate file path '{}'", file.path));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks names and sizes and decodes the contents. Names must stay out
+    // of the files cargo reads or writes.
+    fn decode_data_files(
+        config: &ExecutorConfig,
+        files: Vec<DataFile>,
+    ) -> Result<Vec<(String, Vec<u8>)>, String> {
+        if files.len() > config.max_source_files {
+            return Err(format!(
+                "Too many data files ({}), at most {} are allowed",
+                files.len(),
+                config.max_source_files
+            ));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let mut decoded = Vec::with_capacity(files.len());
+        let mut total_size = 0;
+        for file in files {
+            let path = Path::new(&file.name);
+            let relative = path.components().next().is_some()
+                && path
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)));
+            let reserved = ["src", "target", "Cargo.toml", "Cargo.lock"]
+                .iter()
+                .any(|reserved| path.starts_with(reserved));
+            if !relative || reserved {
+                return Err(format!(
+                    "Invalid data file name '{}': names must be relative paths outside src/ and target/",
+                    file.name
+                ));
+            }
+            if !seen.insert(file.name.clone()) {
+                return Err(format!("Duplicate data file name '{}'", file.name));
+            }
+            let content = BASE64
+                .decode(file.content_base64.trim())
+                .map_err(|e| format!("Data file '{}' is not valid base64: {}", file.name, e))?;
+            total_size += content.len();
+            decoded.push((file.name, content));
+        }
+
+        let total_size_kb = total_size as f64 / 1024.0;
+        if total_size_kb > config.max_data_files_kb as f64 {
+            return Err(format!(
+                "Data files size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                total_size_kb, config.max_data_files_kb
+            ));
+        }
+        Ok(decoded)
+    }
+
+    // Number of generated lines that precede the user's code, used to map
+    // compiler and runtime line numbers back to what the student wrote
+    fn user_line_offset(generated: &str, user_code: &str) -> usize {
+        generated
+            .find(user_code)
+            .map(|pos| generated[..pos].matches('\n').count())
+            .unwrap_or(0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn compile_and_run(
+        &self,
+        project_path: &Path,
+        trace_log: Option<&Path>,
+        input_data: Option<&[u8]>,
+        limits: RunLimits,
+        envs: &[(String, String)],
+        build: BuildSettings,
+        active: &ActiveExecution,
+    ) -> RunOutcome {
+        active.set_phase("compiling");
+        let compile_start = Instant::now();
+        let compiled = active.until_killed(self.compile(project_path, build)).await;
+        let compile_time = compile_start.elapsed();
+        let Some(compiled) = compiled else {
+            return RunOutcome {
+                compile_time,
+                ..RunOutcome::killed()
+            };
+        };
+        let mut outcome = match compiled {
+            Ok(executable_path) => {
+                let setup = ChildSetup {
+                    working_dir: Some(project_path),
+                    trace_log,
+                    keep_stdin_open: false,
+                };
+                self.run_executable(
+                    &executable_path,
+                    setup,
+                    &[],
+                    input_data,
+                    limits,
+                    envs,
+                    active,
+                )
+                .await
+            }
+            Err(outcome) => outcome,
+        };
+        outcome.compile_time = compile_time;
+        outcome
+    }
+
+    // Diagnostics in the user's code from `cargo check`, or `cargo clippy`
+    // when clippy lints are configured, with the request's lint levels
+    // passed through RUSTFLAGS. Cargo caps lints in dependencies, so the
+    // flags only fail the submission's own crate.
+    async fn lint_pass(&self, prepared: &PreparedExecution) -> Result<Vec<Diagnostic>, RunOutcome> {
+        let project_path = prepared.temp_dir.path();
+        let lints = prepared.lints.as_ref();
+        let subcommand = if lints.is_some_and(LintConfig::needs_clippy) {
+            "clippy"
+        } else {
+            "check"
+        };
+        let compile_seconds = prepared.build.compile_seconds(&self.config());
+        let check = timeout(
+            Duration::from_secs(compile_seconds),
+            tokio::process::Command::new("cargo")
+                .arg(subcommand)
+                .arg("--profile")
+                .arg(prepared.build.cargo_profile())
+                .arg("--bin")
+                .arg("main")
+                .arg("--message-format=json")
+                .current_dir(project_path)
+                .env("CARGO_TARGET_DIR", project_path.join("target"))
+                .env(
+                    "RUSTFLAGS",
+                    lints.map(LintConfig::rustflags).unwrap_or_default(),
+                )
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await;
+        let output = match check {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                return Err(RunOutcome::failed(
+                    format!("Failed to execute cargo {}: {}", subcommand, e),
+                    ExecutionStatus::InternalError,
+                ))
+            }
+            Err(_) => return Err(RunOutcome::compile_timed_out(compile_seconds)),
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let user_lines = prepared.user_lines;
+        let diagnostics =
+            diagnostics::parse_cargo_messages(&stdout, user_lines.offset, user_lines.count);
+        // Reported against clippy.toml, which the build never reads
+        if let Some(config_error) = diagnostics.iter().find(|diagnostic| {
+            diagnostic
+                .message
+                .starts_with("error reading Clippy's configuration file")
+        }) {
+            // The first line names the key; the rest lists every valid one
+            let message = config_error
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches(", expected one of");
+            return Err(RunOutcome::failed(
+                format!("Invalid clippyConfig: {}", message),
+                ExecutionStatus::InvalidRequest,
+            ));
+        }
+        // Failures cargo reports outside the JSON messages
+        if !output.status.success() && diagnostics.is_empty() {
+            return Err(RunOutcome::failed(
+                format!(
+                    "Lint check failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+                ExecutionStatus::CompileError,
+            ));
+        }
+        Ok(diagnostics)
+    }
+
+    // Builds src/main.rs and returns the path of the binary
+    async fn compile(
+        &self,
+        project_path: &Path,
+        build: BuildSettings,
+    ) -> Result<PathBuf, RunOutcome> {
+        let compile_seconds = build.compile_seconds(&self.config());
+        let compile_result = match timeout(
+            Duration::from_secs(compile_seconds),
+            tokio::process::Command::new("cargo")
+                .arg("build")
+                .arg("--profile")
+                .arg(build.cargo_profile())
+                .arg("--bin")
+                .arg("main")
+                .current_dir(project_path)
+                .env("CARGO_TARGET_DIR", project_path.join("target"))
+                // Lets release panic backtraces name the file and line; dev
+                // builds have full debug info already
+                .env("CARGO_PROFILE_RELEASE_DEBUG", "line-tables-only")
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                return Err(RunOutcome::failed(
+                    format!("Failed to execute cargo build: {}", e),
+                    ExecutionStatus::InternalError,
+                ));
+            }
+            Err(_) => {
+                return Err(RunOutcome::compile_timed_out(compile_seconds));
+            }
+        };
+
+        if !compile_result.status.success() {
+            let stderr = String::from_utf8_lossy(&compile_result.stderr);
+            return Err(RunOutcome::failed(
+                format!("Compilation error: {}", stderr),
+                ExecutionStatus::CompileError,
+            ));
+        }
+
+        Ok(project_path
+            .join("target")
+            .join(build.target_dir())
+            .join("main"))
+    }
+
+    // Command for a user binary: started from an empty environment so nothing
+    // from the service leaks into it, and in its own process group so a
+    // timeout or kill also reaches every process it starts. The CPU and
+    // memory limits are rlimits, which each process the program starts
+    // inherits. With SANDBOX_USER set it runs as that user, and with sandbox
+    // "bwrap" inside bubblewrap.
+    fn child_command(
+        &self,
+        executable_path: &Path,
+        setup: ChildSetup,
+        envs: &[(String, String)],
+        cpu_seconds: Option<u64>,
+    ) -> std::process::Command {
+        let mut cmd = match setup.trace_log {
+            Some(log) => syscall_audit::traced_command(executable_path, log),
+            None => std::process::Command::new(executable_path),
+        };
+        if self.config().sandbox == bwrap::SANDBOX_BWRAP {
+            cmd = bwrap::wrap(&cmd, executable_path, setup.working_dir);
+        }
+        cmd.env_clear().env("PATH", CHILD_PATH).process_group(0);
+        if let Some(working_dir) = setup.working_dir {
+            cmd.current_dir(working_dir);
+        }
+        for (key, value) in envs {
+            cmd.env(key, value);
+        }
+        if let Some(cpu_seconds) = cpu_seconds {
+            let limit = libc::rlimit {
+                rlim_cur: cpu_seconds as libc::rlim_t,
+                rlim_max: (cpu_seconds + 1) as libc::rlim_t,
+            };
+            // SAFETY: setrlimit is async-signal-safe and touches no memory
+            // shared with the parent
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::setrlimit(libc::RLIMIT_CPU, &limit) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        let memory = memory_limit::rlimit(self.config().max_memory_mb);
+        // SAFETY: as above
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::setrlimit(libc::RLIMIT_DATA, &memory) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        sandbox_user::apply(&mut cmd, executable_path, setup.working_dir);
+        cmd
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn run_executable(
+        &self,
+        executable_path: &Path,
+        setup: ChildSetup<'_>,
+        args: &[String],
+        input_data: Option<&[u8]>,
+        limits: RunLimits,
+        envs: &[(String, String)],
+        active: &ActiveExecution,
+    ) -> RunOutcome {
+        let mut cmd = self.child_command(executable_path, setup, envs, limits.cpu_seconds);
+        cmd.args(args);
+
+        // Without input the program reads EOF rather than the service's stdin
+        let feed = active.take_stdin();
+        let keep_stdin_open = setup.keep_stdin_open || feed.is_some();
+        cmd.stdin(if input_data.is_some() || keep_stdin_open {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        });
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let oom = memory_limit::OomWatch::start();
+        let max_output_kb = self.config().max_output_kb;
+        let output_cap = OutputCap::new(max_output_kb);
+        // Spawned through std so the child can be reaped with wait4 and its
+        // resource usage collected
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return RunOutcome::failed(
+                    format!("Failed to spawn process: {}", e),
+                    ExecutionStatus::InternalError,
+                )
+            }
+        };
+        let run_start = Instant::now();
+        let process = ProcessGroup::new(child.id()).in_bwrap(bwrap::wraps(&cmd));
+        active.set_phase("running");
+        active.set_pid(Some(process.pid()));
+        let wall_deadline = tokio::time::Instant::now() + Duration::from_secs(limits.wall_seconds);
+        let deadline_first = limits
+            .deadline
+            .is_some_and(|deadline| deadline < wall_deadline);
+        let deadline = match limits.deadline {
+            Some(deadline) if deadline_first => deadline,
+            _ => wall_deadline,
+        };
+
+        let stdout_reader = tokio::spawn(read_pipe(
+            child
+                .stdout
+                .take()
+                .and_then(|pipe| tokio::process::ChildStdout::from_std(pipe).ok()),
+            LineRecorder::new("stdout", run_start),
+            output_cap.clone(),
+        ));
+        let stderr_reader = tokio::spawn(read_pipe(
+            child
+                .stderr
+                .take()
+                .and_then(|pipe| tokio::process::ChildStderr::from_std(pipe).ok()),
+            LineRecorder::new("stderr", run_start),
+            output_cap.clone(),
+        ));
+
+        // Send input if provided; dropping the pipe afterwards closes stdin.
+        // A pipe kept open is held until the program exits, when the writer
+        // is aborted.
+        let stdin_writer = child
+            .stdin
+            .take()
+            .and_then(|pipe| tokio::process::ChildStdin::from_std(pipe).ok())
+            .map(|stdin| {
+                tokio::spawn(write_stdin(
+                    stdin,
+                    input_data.map(<[u8]>::to_vec),
+                    feed,
+                    keep_stdin_open,
+                ))
+            });
+        let _stdin_writer = AbortOnDrop(stdin_writer);
+
+        let waiter = process.wait();
+        tokio::pin!(waiter);
+        let waited = tokio::time::timeout_at(deadline, async {
+            tokio::select! {
+                waited = &mut waiter => waited,
+                _ = output_cap.full.notified() => {
+                    process.kill();
+                    (&mut waiter).await
+                }
+            }
+        })
+        .await;
+        if waited.is_ok() {
+            active.set_pid(None);
+        }
+        let run_time = Some(run_start.elapsed());
+        let (exit_status, usage) = match waited {
+            Ok(Ok((_, usage))) if active.is_killed() => {
+                return RunOutcome {
+                    run_time,
+                    usage: Some(usage),
+                    ..RunOutcome::killed()
+                };
+            }
+            Ok(Ok(exited)) => exited,
+            Ok(Err(e)) => {
+                return RunOutcome::failed(
+                    format!("Process error: {}", e),
+                    ExecutionStatus::InternalError,
+                )
+            }
+            Err(_) => {
+                process.kill();
+                let usage = waiter.await.ok().map(|(_, usage)| usage);
+                active.set_pid(None);
+                let run_time = Some(run_start.elapsed());
+                // What the program wrote before it was killed; its pipes close
+                // with it, unless something outside its group still holds them
+                let drained = tokio::time::timeout(TIMEOUT_OUTPUT_DRAIN, async {
+                    (
+                        stdout_reader.await.unwrap_or_default(),
+                        stderr_reader.await.unwrap_or_default(),
+                    )
+                })
+                .await;
+                let timed_out = limits.timed_out(deadline_first);
+                let Ok(((stdout, stdout_events), (stderr, stderr_events))) = drained else {
+                    return RunOutcome {
+                        run_time,
+                        usage,
+                        ..timed_out
+                    };
+                };
+                let mut captured = String::from_utf8_lossy(&stderr).trim().to_string();
+                if !captured.is_empty() {
+                    captured.push('\n');
+                }
+                captured.push_str(&timed_out.stderr);
+                return RunOutcome {
+                    stdout,
+                    stderr: captured,
+                    run_time,
+                    usage,
+                    events: output_events::interleave(stdout_events, stderr_events),
+                    ..timed_out
+                };
+            }
+        };
+
+        // Processes the program left behind were killed with its group, but
+        // collecting output is still bounded by the same deadline
+        let ((stdout, stdout_events), (stderr, stderr_events)) =
+            match tokio::time::timeout_at(deadline, async {
+                (
+                    stdout_reader.await.unwrap_or_default(),
+                    stderr_reader.await.unwrap_or_default(),
+                )
+            })
+            .await
+            {
+                Ok(output) => output,
+                Err(_) => {
+                    return RunOutcome {
+                        run_time,
+                        usage: Some(usage),
+                        ..limits.timed_out(deadline_first)
+                    };
+                }
+            };
+
+        let mut stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+
+        // Output up to the point the CPU or memory limit was hit is kept
+        let max_memory_mb = self.config().max_memory_mb;
+        let limit_exceeded = if limits.cpu_time_exceeded(exit_status, &usage) {
+            Some(LimitExceeded::CpuTime)
+        } else if oom.exceeded(exit_status, Some(&stderr)) {
+            Some(LimitExceeded::Memory {
+                limit_kb: max_memory_mb as u64 * 1024,
+            })
+        } else {
+            None
+        };
+        let status = if output_cap.exceeded() {
+            if !stderr.is_empty() {
+                stderr.push('\n');
+            }
+            stderr.push_str(&output_limit_message(max_output_kb));
+            ExecutionStatus::OutputLimit
+        } else if exit_status.success() {
+            ExecutionStatus::Success
+        } else if limit_exceeded == Some(LimitExceeded::CpuTime) {
+            if !stderr.is_empty() {
+                stderr.push('\n');
+            }
+            stderr.push_str(&limits.cpu_time_message());
+            ExecutionStatus::Timeout
+        } else if limit_exceeded.is_some() {
+            if !stderr.is_empty() {
+                stderr.push('\n');
+            }
+            stderr.push_str(&memory_limit::message(max_memory_mb));
+            ExecutionStatus::MemoryLimit
+        } else if stack_overflowed(exit_status, Some(&stderr)) {
+            if !stderr.is_empty() {
+                stderr.push('\n');
+            }
+            stderr.push_str(STACK_OVERFLOW_MESSAGE);
+            ExecutionStatus::StackOverflow
+        } else {
+            ExecutionStatus::RuntimeError
+        };
+
+        RunOutcome {
+            stdout,
+            stderr,
+            status,
+            compile_time: Duration::ZERO,
+            run_time,
+            usage: Some(usage),
+            exit_code: exit_status.code(),
+            limit_exceeded,
+            events: output_events::interleave(stdout_events, stderr_events),
+        }
+    }
+
+    // Runs `cargo check` on a submission, generated the way /execute builds
+    // it by default
+    async fn check_code(&self, code: &str) -> Result<CheckOutput, String> {
+        let temp_dir =
+            reaper::temp_dir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+        let project_path = temp_dir.path();
+        let src_dir = project_path.join("src");
+        fs::create_dir_all(&src_dir)
+            .map_err(|e| format!("Failed to create src directory: {}", e))?;
+
+        // Create minimal Cargo.toml
+        let cargo_toml = r#"[package]
+name = "rust_validate"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+"#;
+
+        fs::write(project_path.join("Cargo.toml"), cargo_toml)
+            .map_err(|e| format!("Failed to create Cargo.toml: {}", e))?;
+        toolchain_pin::write(project_path)?;
+
+        let full_code = wrapping::wrap(code, WrapMode::Auto);
+        let line_offset = Self::user_line_offset(&full_code, code);
+        let byte_offset = full_code.find(code).unwrap_or(0);
+        let main_rs_path = src_dir.join("main.rs");
+        fs::write(main_rs_path, &full_code)
+            .map_err(|e| format!("Failed to write main.rs: {}", e))?;
+
+        // Check syntax
+        let check_result = match timeout(
+            Duration::from_secs(10),
+            tokio::process::Command::new("cargo")
+                .arg("check")
+                .arg("--message-format=json")
+                .current_dir(project_path)
+                .output(),
+        )
+        .await
+        {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return Err(format!("Failed to execute cargo check: {}", e)),
+            Err(_) => return Err("Syntax check timed out".to_string()),
+        };
+
+        Ok(CheckOutput {
+            stdout: String::from_utf8_lossy(&check_result.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&check_result.stderr).to_string(),
+            success: check_result.status.success(),
+            line_offset,
+            byte_offset,
+        })
+    }
+
+    /// Checks a submission with the compiler without running it, as POST
+    /// /validate does
+    pub async fn validate_syntax(&self, code: String) -> CodeValidationResponse {
+        let check = match self.check_code(&code).await {
+            Ok(check) => check,
+            Err(e) => {
+                return CodeValidationResponse {
+                    is_valid: false,
+                    errors: vec![e],
+                    warnings: vec![],
+                    diagnostics: vec![],
+                };
+            }
+        };
+
+        let user_lines = code.lines().count().max(1);
+        let diagnostics =
+            diagnostics::parse_cargo_messages(&check.stdout, check.line_offset, user_lines);
+        let (mut errors, mut warnings) = (Vec::new(), Vec::new());
+        for diagnostic in &diagnostics {
+            if diagnostic.level == "error" {
+                errors.push(diagnostic.summary());
+            } else {
+                warnings.push(diagnostic.summary());
+            }
+        }
+        let is_valid = check.success;
+        // Failures cargo reports outside the JSON messages, e.g. a broken
+        // toolchain
+        if !is_valid && errors.is_empty() {
+            errors.push(check.stderr);
+        }
+        CodeValidationResponse {
+            is_valid,
+            errors,
+            warnings,
+            diagnostics,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/execute",
+    request_body = CodeExecutionRequest,
+    params(
+        ("X-Requester-Id" = Option<String>, Header, description = "Recorded in the execution history and audit log"),
+        ("X-Tenant-Id" = Option<String>, Header, description = "Tenant whose quotas the execution counts against"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key and body return the first result")
+    ),
+    responses(
+        (status = 200, body = CodeExecutionResponse),
+        (status = 422, description = "Idempotency-Key reused with a different request", body = ErrorResponse),
+        (status = 429, description = "Tenant quota exhausted (status \"quota_exceeded\")", body = CodeExecutionResponse),
+        (status = 503, description = "Host overloaded (status \"capacity\")", body = CodeExecutionResponse)
+    )
+)]
+async fn execute(
+    req: CodeExecutionRequest,
+    requester_id: Option<String>,
+    tenant_id: Option<String>,
+    idempotency_key: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let code_hash = executor.history.as_ref().map(|_| history::code_hash(&req));
+
+    let mut executed = false;
+    let result = match idempotency_key {
+        Some(key) => match executor
+            .idempotency
+            .slot(&key, requester_id.as_deref(), &req)
+        {
+            Ok(slot) => slot
+                .get_or_init(|| {
+                    executed = true;
+                    executor.execute_for_tenant(req, requester_id.as_deref(), tenant_id.as_deref())
+                })
+                .await
+                .clone(),
+            Err(e) => {
+                return Ok(warp::reply::with_header(
+                    warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "error": e.message() })),
+                        warp::http::StatusCode::UNPROCESSABLE_ENTITY,
+                    ),
+                    "idempotent-replayed",
+                    "false",
+                ));
+            }
+        },
+        None => {
+            executed = true;
+            executor
+                .execute_for_tenant(req, requester_id.as_deref(), tenant_id.as_deref())
+                .await
+        }
+    };
+    let status = match result.status {
+        ExecutionStatus::QuotaExceeded => warp::http::StatusCode::TOO_MANY_REQUESTS,
+        ExecutionStatus::Capacity => warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        _ => warp::http::StatusCode::OK,
+    };
+    let reply = warp::reply::with_header(
+        warp::reply::with_status(warp::reply::json(&result), status),
+        "idempotent-replayed",
+        if executed { "false" } else { "true" },
+    );
+
+    // Recorded in the background so a slow database never delays the response
+    if let (true, Some(history), Some(code_hash)) = (executed, executor.history.clone(), code_hash)
+    {
+        tokio::spawn(async move {
+            if let Err(e) = history
+                .record(&code_hash, requester_id.as_deref(), &result)
+                .await
+            {
+                eprintln!("Failed to record execution: {}", e);
+            }
+        });
+    }
+    Ok(reply)
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs",
+    request_body = CodeExecutionRequest,
+    params(
+        ("X-Requester-Id" = Option<String>, Header, description = "Recorded as the job's submitter"),
+        ("X-Requester-Role" = Option<String>, Header, description = "Checked against high_priority_roles when priority is \"high\"; only honoured with the admin token"),
+        ("Authorization" = Option<String>, Header, description = "Bearer <ADMIN_TOKEN>, sent by a backend vouching for X-Requester-Role. Without it priority \"high\" is queued as normal.")
+    ),
+    responses(
+        (status = 202, description = "Job queued; the body has its jobId and the priority it was queued at, and with closeStdin: false the stdinToken for POST /jobs/{id}/stdin"),
+        (status = 400, description = "Unknown priority", body = ErrorResponse),
+        (status = 403, description = "The role may not use this priority", body = ErrorResponse),
+        (status = 404, description = "Job queue is not enabled", body = ErrorResponse),
+        (status = 503, body = ErrorResponse)
+    )
+)]
+async fn submit_job(
+    req: CodeExecutionRequest,
+    requester_id: Option<String>,
+    requester_role: Option<String>,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let Some(queue) = &executor.job_queue else {
+        return Ok(job_queue_disabled());
+    };
+    let role = executor.authenticated_role(authorization.as_deref(), requester_role.as_deref());
+    let priority = match executor.job_priority(req.priority.as_deref(), role.as_deref()) {
+        Ok(priority) => priority,
+        Err(e) => {
+            let status = match e {
+                PriorityError::Invalid(_) => warp::http::StatusCode::BAD_REQUEST,
+                PriorityError::NotAllowed(_) => warp::http::StatusCode::FORBIDDEN,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": e.message() })),
+                status,
+            ));
+        }
+    };
+    let reply = match queue.enqueue(&req, priority, requester_id.as_deref()).await {
+        Ok((job_id, stdin_token)) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "jobId": job_id,
+                "status": "queued",
+                "priority": priority.as_str(),
+                "stdinToken": stdin_token,
+            })),
+            warp::http::StatusCode::ACCEPTED,
+        ),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    params(
+        JobListQuery,
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, body = JobListResponse),
+        (status = 400, description = "Unknown status", body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, description = "Job queue is not enabled", body = ErrorResponse),
+        (status = 503, body = ErrorResponse)
+    )
+)]
+async fn list_jobs(
+    query: JobListQuery,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // Every submitter's jobs: for backends and admins holding the admin token
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let Some(queue) = &executor.job_queue else {
+        return Ok(job_queue_disabled());
+    };
+    if let Err(e) = query.validate() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+    let reply = match queue.list(&query).await {
+        Ok(jobs) => warp::reply::with_status(warp::reply::json(&jobs), warp::http::StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    params(("id" = String, Path, description = "Job id returned by POST /jobs")),
+    responses(
+        (status = 200, body = JobStatusResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 503, body = ErrorResponse)
+    )
+)]
+async fn job_status(
+    job_id: String,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let Some(queue) = &executor.job_queue else {
+        return Ok(job_queue_disabled());
+    };
+    let reply = match queue.status(&job_id).await {
+        Ok(Some(job)) => {
+            warp::reply::with_status(warp::reply::json(&job), warp::http::StatusCode::OK)
+        }
+        Ok(None) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "Job not found" })),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}/stdin",
+    request_body = JobStdinRequest,
+    params(
+        ("id" = String, Path, description = "Job id returned by POST /jobs"),
+        ("Authorization" = String, Header, description = "Bearer <stdinToken> as returned by POST /jobs, or Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 202, description = "Input queued for the program"),
+        (status = 401, description = "Missing or wrong stdin token", body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 409, description = "The job has finished, already got EOF, or was not submitted with closeStdin: false", body = ErrorResponse),
+        (status = 503, body = ErrorResponse)
+    )
+)]
+async fn write_job_stdin(
+    job_id: String,
+    input: JobStdinRequest,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let Some(queue) = &executor.job_queue else {
+        return Ok(job_queue_disabled());
+    };
+    // Only the submitter got the job's token; the admin token writes to any job
+    let token = match executor.authorize_admin(authorization.as_deref()) {
+        Ok(()) => None,
+        Err(_) => Some(
+            authorization
+                .as_deref()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .unwrap_or("")
+                .trim(),
+        ),
+    };
+    let (status, body) = match queue.write_stdin(&job_id, token, input).await {
+        Ok(()) => (
+            warp::http::StatusCode::ACCEPTED,
+            serde_json::json!({ "jobId": job_id }),
+        ),
+        Err(StdinError::NotFound) => (
+            warp::http::StatusCode::NOT_FOUND,
+            serde_json::json!({ "error": "Job not found" }),
+        ),
+        Err(StdinError::Unauthorized) => (
+            warp::http::StatusCode::UNAUTHORIZED,
+            serde_json::json!({ "error": "Invalid or missing stdin token" }),
+        ),
+        Err(StdinError::Closed(e)) => (
+            warp::http::StatusCode::CONFLICT,
+            serde_json::json!({ "error": e }),
+        ),
+        Err(StdinError::Unavailable(e)) => (
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({ "error": e }),
+        ),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&body), status))
+}
+
+fn job_queue_disabled() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": "Job queue is not enabled" })),
+        warp::http::StatusCode::NOT_FOUND,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/executions",
+    params(
+        ExecutionHistoryQuery,
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, description = "Matching executions, newest first, under `executions`", body = [ExecutionRecord]),
+        (status = 400, description = "since is not an RFC 3339 timestamp", body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, description = "Execution history is not enabled", body = ErrorResponse),
+        (status = 502, body = ErrorResponse)
+    )
+)]
+async fn list_executions(
+    query: ExecutionHistoryQuery,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // Stored code and output of every user: admins only, as /admin/audit
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    if let Err(e) = query.validate() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+    let reply = match &executor.history {
+        Some(history) => match history.query(query).await {
+            Ok(executions) => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "executions": executions })),
+                warp::http::StatusCode::OK,
+            ),
+            Err(e) => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": e })),
+                warp::http::StatusCode::BAD_GATEWAY,
+            ),
+        },
+        None => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "Execution history is not enabled" })),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    post,
+    path = "/validate",
+    request_body = CodeValidationRequest,
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of an earlier response for the same code")
+    ),
+    responses(
+        (status = 200, body = CodeValidationResponse, headers(("ETag" = String, description = "Identifies the result; absent when the check failed without diagnostics, e.g. it timed out"))),
+        (status = 304, description = "The code is unchanged since the response with this ETag")
+    )
+)]
+async fn validate(
+    req: CodeValidationRequest,
+    if_none_match: Option<String>,
+    executor: RustExecutor,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    // Results only depend on the code and the toolchain, so a matching ETag
+    // is answered without a check, cached or not
+    let etag = executor.validation_etag(&req.code);
+    if if_none_match.is_some_and(|tags| validation_cache::matches(&tags, &etag)) {
+        let reply = warp::reply::with_status(warp::reply(), warp::http::StatusCode::NOT_MODIFIED);
+        return Ok(warp::reply::with_header(reply, "etag", etag).into_response());
+    }
+    let (result, etag) = executor.validate_cached(req.code).await;
+    let reply = warp::reply::json(&result);
+    Ok(match etag {
+        Some(etag) => warp::reply::with_header(reply, "etag", etag).into_response(),
+        None => reply.into_response(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/coverage",
+    request_body = CodeCoverageRequest,
+    responses((status = 200, body = CodeCoverageResponse))
+)]
+async fn coverage(
+    req: CodeCoverageRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.collect_coverage(req).await;
+    Ok(warp::reply::json(&result))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mutation",
+    request_body = MutationTestRequest,
+    responses((status = 200, body = MutationTestResponse))
+)]
+async fn mutation(
+    req: MutationTestRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.run_mutation_tests(req).await;
+    Ok(warp::reply::json(&result))
+}
+
+#[utoipa::path(
+    post,
+    path = "/sessions",
+    responses(
+        (status = 201, body = ReplSessionResponse),
+        (status = 503, description = "Too many active sessions", body = ErrorResponse)
+    )
+)]
+async fn create_session(executor: RustExecutor) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = match executor.create_repl_session() {
+        Ok(session) => {
+            warp::reply::with_status(warp::reply::json(&session), warp::http::StatusCode::CREATED)
+        }
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    post,
+    path = "/sessions/{id}/eval",
+    request_body = ReplEvalRequest,
+    params(("id" = String, Path, description = "Session id")),
+    responses(
+        (status = 200, body = CodeExecutionResponse),
+        (status = 404, body = ErrorResponse)
+    )
+)]
+async fn eval_in_session(
+    session_id: String,
+    req: ReplEvalRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = match executor.eval_in_repl_session(&session_id, req).await {
+        Some(result) => {
+            warp::reply::with_status(warp::reply::json(&result), warp::http::StatusCode::OK)
+        }
+        None => session_not_found(),
+    };
+    Ok(reply)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    params(("id" = String, Path, description = "Session id")),
+    responses(
+        (status = 200, description = "Session deleted"),
+        (status = 404, body = ErrorResponse)
+    )
+)]
+async fn delete_session(
+    session_id: String,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = if executor.delete_repl_session(&session_id) {
+        warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "deleted": true })),
+            warp::http::StatusCode::OK,
+        )
+    } else {
+        session_not_found()
+    };
+    Ok(reply)
+}
+
+fn session_not_found() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": "Session not found" })),
+        warp::http::StatusCode::NOT_FOUND,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Toolchain available, warm-up compilation done and capacity left"),
+        (status = 503, description = "Up but unable to take executions")
+    )
+)]
+async fn ready(executor: RustExecutor) -> Result<impl warp::Reply, warp::Rejection> {
+    let readiness = executor.readiness().await;
+    let status = if readiness.is_ready() {
+        warp::http::StatusCode::OK
+    } else {
+        warp::http::StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&readiness),
+        status,
+    ))
+}
+
+#[utoipa::path(get, path = "/info", responses((status = 200, description = "Toolchain, current limits and enabled features")))]
+async fn info(executor: RustExecutor) -> Result<impl warp::Reply, warp::Rejection> {
+    let config = executor.config();
+    let toolchain = &executor.toolchain;
+    let grpc_enabled = env::var("GRPC_PORT")
+        .ok()
+        .and_then(|port| port.parse::<u16>().ok())
+        .is_some();
+    let mut info = serde_json::json!({
+        "service": "rust-executor",
+        "language": "rust",
+        // Recorded on the jobs this replica accepts and runs
+        "instanceId": queue::instance_id(),
+        // Release of the rustc found at startup; null when it was missing
+        "version": toolchain.rustc_release(),
+        "rustcVersion": toolchain.rustc,
+        "cargoVersion": toolchain.cargo,
+        "rustAnalyzerVersion": toolchain.rust_analyzer,
+        "clippyVersion": toolchain.clippy,
+        "maxExecutionTime": config.max_execution_time,
+        "maxRequestTimeout": config.max_request_timeout,
+        "maxMemoryMB": config.max_memory_mb,
+        "maxCodeSizeKB": config.max_code_size_kb,
+        "maxSourceFiles": config.max_source_files,
+        "maxDataFilesKB": config.max_data_files_kb,
+        "maxOutputKB": config.max_output_kb,
+        "maxOutputFilesKB": config.max_output_files_kb,
+        "maxOutputLines": config.max_output_lines,
+        "compileTimeout": config.compile_timeout,
+        "maxCompileTimeout": config.max_compile_timeout,
+        "maxActiveExecutions": config.max_active_executions,
+        "maxLoadPerCpu": config.max_load_per_cpu,
+        "minFreeMemoryMB": config.min_free_memory_mb,
+        "minFreeDiskMB": config.min_free_disk_mb,
+        "forbidUnsafeCode": config.forbid_unsafe_code,
+        "allowedEnvVars": config.allowed_env_vars,
+        "languages": executor.executors.languages(),
+        "availableLibraries": ["std", "core", "alloc"],
+        // Crates a request may list in `dependencies`, with their versions
+        "dependencies": config.allowed_crates,
+        // "process-group": programs run as ordinary processes in their own
+        // process group, with an empty environment and an RLIMIT_CPU when
+        // requested; "bwrap": the same, inside bubblewrap
+        "sandbox": config.sandbox,
+        "features": {
+            "jobQueue": executor.job_queue.is_some(),
+            "executionHistory": executor.history.is_some(),
+            "auditLog": executor.audit_log.is_some(),
+            "artifactStorage": executor.artifacts.has_object_storage(),
+            "admin": executor.admin_token.is_some(),
+            "grpc": grpc_enabled,
+            "tls": config.tls_cert_path.is_some(),
+            "startupWarmup": warmup::enabled(),
+            "completion": toolchain.rust_analyzer.is_some(),
+            "syscallAudit": toolchain.strace.is_some(),
+            "clippyLints": toolchain.clippy.is_some(),
+        },
+    });
+
+    // What submissions build with, which PINNED_RUST_TOOLCHAIN can make
+    // differ from rustcVersion (one json! call this size exceeds the macro
+    // recursion limit)
+    info["pinnedToolchain"] = toolchain_pin::pinned().into();
+    info["projectRustcVersion"] = toolchain.project_rustc.clone().into();
+    info["toolchainDrift"] = toolchain.drift.clone().into();
+    // Unstable features submissions may enable; only in nightly mode
+    info["nightlyMode"] = executor.nightly_mode().into();
+    info["allowedFeatures"] = config.allowed_features.clone().into();
+    // The uid programs run as when SANDBOX_USER drops their privileges
+    info["sandboxUid"] = sandbox_user::configured()
+        .ok()
+        .flatten()
+        .map(|user| user.uid)
+        .into();
+
+    Ok(warp::reply::json(&info))
+}
+
+/// The HTTP API as a warp filter, ready for [`serve_http`], [`serve_tls`] or
+/// `warp::serve`. Every route shares `executor`.
+pub fn routes(
+    executor: &RustExecutor,
+) -> impl Filter<Extract = impl warp::Reply, Error = std::convert::Infallible>
+       + Clone
+       + Send
+       + Sync
+       + 'static {
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_headers(vec![
+            "content-type",
+            "authorization",
+            "x-requester-id",
+            "x-tenant-id",
+            "x-requester-role",
+            "idempotency-key",
+            "if-none-match",
+        ])
+        .allow_methods(vec!["GET", "POST", "DELETE"])
+        .expose_headers(vec!["idempotent-replayed", "etag"]);
+
+    let openapi_route = warp::path!("openapi.json")
+        .and(warp::get())
+        .map(|| warp::reply::json(&openapi::ApiDoc::openapi()));
+
+    let executor_health = executor.clone();
+    let executor_ready = executor.clone();
+    let executor_execute = executor.clone();
+    let executor_validate = executor.clone();
+    let executor_fix = executor.clone();
+    let executor_compare = executor.clone();
+    let executor_diff = executor.clone();
+    let executor_judge = executor.clone();
+    let executor_dry_run = executor.clone();
+    let executor_stress = executor.clone();
+    let executor_compile = executor.clone();
+    let executor_run = executor.clone();
+    let executor_info = executor.clone();
+    let executor_explain = executor.clone();
+    let executor_similarity = executor.clone();
+    let executor_properties = executor.clone();
+    let executor_library_tests = executor.clone();
+    let executor_code_metrics = executor.clone();
+    let executor_std_usage = executor.clone();
+    let executor_complete = executor.clone();
+    let executor_metrics = executor.clone();
+    let executor_coverage = executor.clone();
+    let executor_mutation = executor.clone();
+    let executor_interactive = executor.clone();
+    let executor_sessions = executor.clone();
+    let executor_history = executor.clone();
+    let executor_jobs = executor.clone();
+    let executor_admin = executor.clone();
+    // Every route that compiles or runs code except /execute, which meters
+    // its usage itself
+    let executor_quota = executor.clone();
+
+    let execute_route = warp::path("execute")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::header::optional::<String>("x-requester-id"))
+        .and(warp::header::optional::<String>("x-tenant-id"))
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(warp::any().map(move || executor_execute.clone()))
+        .and_then(execute);
+
+    let validate_route = warp::path("validate")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(warp::any().map(move || executor_validate.clone()))
+        .and_then(validate);
+
+    let fix_route = warp::path("fix")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::any().map(move || executor_fix.clone()))
+        .and_then(fix::fix);
+
+    let compile_route = warp::path("compile")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::header::optional::<String>("x-requester-id"))
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_compile.clone()))
+        .and_then(artifacts::compile);
+
+    let run_route = warp::path!("run" / String)
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_run.clone()))
+        .and_then(artifacts::run);
+
+    let health_route = warp::path("health")
+        .and(warp::get())
+        .and(warp::any().map(move || executor_health.clone()))
+        .and_then(health::health);
+    let ready_route = warp::path("ready")
+        .and(warp::get())
+        .and(warp::any().map(move || executor_ready.clone()))
+        .and_then(ready);
+
+    let info_route = warp::path("info")
+        .and(warp::get())
+        .and(warp::any().map(move || executor_info.clone()))
+        .and_then(info);
+
+    let explain_route = warp::path!("explain" / String)
+        .and(warp::get())
+        .and(warp::any().map(move || executor_explain.clone()))
+        .and_then(explain::explain);
+
+    let similarity_route = warp::path("similarity")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::any().map(move || executor_similarity.clone()))
+        .and_then(similarity::similarity);
+
+    let properties_route = warp::path("property-tests")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_properties.clone()))
+        .and_then(properties::property_tests);
+
+    let library_tests_route = warp::path("library-tests")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_library_tests.clone()))
+        .and_then(library_tests::library_tests);
+
+    let generate_inputs_route = warp::path("generate-inputs")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and_then(input_generator::generate);
+
+    let code_metrics_route = warp::path!("metrics" / "code")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::any().map(move || executor_code_metrics.clone()))
+        .and_then(code_metrics::code_metrics);
+
+    let std_usage_route = warp::path("std-usage")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::any().map(move || executor_std_usage.clone()))
+        .and_then(std_usage::std_usage);
+
+    let compare_route = warp::path("compare")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_compare.clone()))
+        .and_then(compare::compare);
+
+    let diff_route = warp::path("diff")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_diff.clone()))
+        .and_then(behavior_diff::diff);
+
+    let dry_run_route = warp::path!("assignments" / "dryrun")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_dry_run.clone()))
+        .and_then(assignments::dry_run);
+
+    let stress_route = warp::path("stress")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_stress.clone()))
+        .and_then(stress::stress);
+
+    let judge_route = warp::path("judge")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_judge.clone()))
+        .and_then(judge::judge);
+
+    let complete_route = warp::path("complete")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::any().map(move || executor_complete.clone()))
+        .and_then(completion::complete);
+
+    let metrics_route = warp::path!("metrics")
+        .and(warp::get())
+        .and(warp::any().map(move || executor_metrics.clone()))
+        .and_then(metrics::metrics);
+
+    let coverage_route = warp::path("coverage")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_coverage.clone()))
+        .and_then(coverage);
+
+    let mutation_route = warp::path("mutation")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_mutation.clone()))
+        .and_then(mutation);
+
+    let interactive_route = warp::path("interactive")
+        .and(warp::ws())
+        .and(quota::tenant_admission(executor_quota.clone()))
+        .and(warp::any().map(move || executor_interactive.clone()))
+        .map(|ws: warp::ws::Ws, executor: RustExecutor| {
+            ws.on_upgrade(move |socket| executor.interactive_session(socket))
+        });
+
+    let with_sessions = warp::any().map(move || executor_sessions.clone());
+    let create_session_route = warp::path!("sessions")
+        .and(warp::post())
+        .and(with_sessions.clone())
+        .and_then(create_session);
+    let eval_session_route = warp::path!("sessions" / String / "eval")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(quota::tenant_admission(executor_quota))
+        .and(with_sessions.clone())
+        .and_then(eval_in_session);
+    let delete_session_route = warp::path!("sessions" / String)
+        .and(warp::delete())
+        .and(with_sessions)
+        .and_then(delete_session);
+
+    let executions_route = warp::path!("executions")
+        .and(warp::get())
+        .and(warp::query::<ExecutionHistoryQuery>())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::any().map(move || executor_history.clone()))
+        .and_then(list_executions);
+
+    let with_jobs = warp::any().map(move || executor_jobs.clone());
+    let submit_job_route = warp::path!("jobs")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::header::optional::<String>("x-requester-id"))
+        .and(warp::header::optional::<String>("x-requester-role"))
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_jobs.clone())
+        .and_then(submit_job);
+    let list_jobs_route = warp::path!("jobs")
+        .and(warp::get())
+        .and(warp::query::<JobListQuery>())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_jobs.clone())
+        .and_then(list_jobs);
+    let job_status_route = warp::path!("jobs" / String)
+        .and(warp::get())
+        .and(with_jobs.clone())
+        .and_then(job_status);
+    let job_stdin_route = warp::path!("jobs" / String / "stdin")
+        .and(warp::post())
+        .and(rejection::json_body())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_jobs)
+        .and_then(write_job_stdin);
+
+    let with_admin = warp::any().map(move || executor_admin.clone());
+    let reload_route = warp::path!("admin" / "reload")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(admin::reload_config);
+    let tenant_usage_route = warp::path!("admin" / "tenants" / String / "usage")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(admin::tenant_usage);
+    let running_executions_route = warp::path!("admin" / "executions")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(admin::running_executions);
+    let kill_execution_route = warp::path!("admin" / "executions" / u64 / "kill")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(admin::kill_execution);
+    let kill_all_route = warp::path!("admin" / "kill-all")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(admin::kill_all_executions);
+    let audit_route = warp::path!("admin" / "audit")
+        .and(warp::get())
+        .and(warp::query::<audit::AuditLogQuery>())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(audit::query_audit_log);
+    let delete_user_data_route = warp::path!("users" / String / "data")
+        .and(warp::delete())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin.clone())
+        .and_then(user_data::delete_user_data);
+    let selftest_route = warp::path!("selftest")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin)
+        .and_then(selftest::selftest);
+
+    // Boxed in groups so the nested route futures live on the heap; as one
+    // unboxed chain they overflow the stack of debug builds
+    let service_routes = health_route
+        .or(ready_route)
+        .or(openapi_route)
+        .or(info_route)
+        .or(metrics_route)
+        .boxed();
+    let execution_routes = execute_route
+        .or(validate_route)
+        .or(fix_route)
+        .or(compile_route)
+        .or(run_route)
+        .or(interactive_route)
+        .or(create_session_route)
+        .or(eval_session_route)
+        .or(delete_session_route)
+        .boxed();
+    let analysis_routes = explain_route
+        .or(similarity_route)
+        .or(properties_route)
+        .or(library_tests_route)
+        .or(code_metrics_route)
+        .or(std_usage_route)
+        .or(complete_route)
+        .or(coverage_route)
+        .or(mutation_route)
+        .or(compare_route)
+        .or(diff_route)
+        .or(judge_route)
+        .or(dry_run_route)
+        .or(generate_inputs_route)
+        .or(stress_route)
+        .boxed();
+    let job_routes = executions_route
+        .or(submit_job_route)
+        .or(list_jobs_route)
+        .or(job_status_route)
+        .or(job_stdin_route)
+        .boxed();
+    let admin_routes = reload_route
+        .or(tenant_usage_route)
+        .or(running_executions_route)
+        .or(kill_execution_route)
+        .or(kill_all_route)
+        .or(audit_route)
+        .or(delete_user_data_route)
+        .or(selftest_route)
+        .boxed();
+
+    let api_routes = service_routes
+        .or(execution_routes)
+        .or(analysis_routes)
+        .or(job_routes)
+        .or(admin_routes)
+        .boxed();
+
+    // /v2 is the current API. /v1, and the unprefixed paths existing
+    // integrations call, serve the same handlers through the v1 shim.
+    let v2_routes = warp::path("v2").and(api_routes.clone());
+    let v1_routes = warp::path("v1")
+        .and(api_routes.clone())
+        .or(api_routes)
+        .unify()
+        .and_then(v1::downgrade);
+
+    v2_routes
+        .map(warp::Reply::into_response)
+        .or(v1_routes)
+        .unify()
+        .boxed()
+        .recover(rejection::handle_rejection)
+        .with(cors)
+        // Preflight requests the CORS filter refuses
+        .recover(rejection::handle_rejection)
+}
+
+impl Clone for RustExecutor {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            admin_token: self.admin_token.clone(),
+            repl_sessions: self.repl_sessions.clone(),
+            analyzer_sessions: self.analyzer_sessions.clone(),
+            history: self.history.clone(),
+            audit_log: self.audit_log.clone(),
+            idempotency: self.idempotency.clone(),
+            explanations: self.explanations.clone(),
+            validations: self.validations.clone(),
+            artifacts: self.artifacts.clone(),
+            job_queue: self.job_queue.clone(),
+            running_executions: self.running_executions.clone(),
+            tenant_usage: self.tenant_usage.clone(),
+            metrics: self.metrics.clone(),
+            phase_successes: self.phase_successes.clone(),
+            warmup: self.warmup.clone(),
+            toolchain: self.toolchain.clone(),
+            load_shedder: self.load_shedder.clone(),
+            executors: self.executors.clone(),
+        }
+    }
+}
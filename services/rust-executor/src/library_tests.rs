@@ -0,0 +1,334 @@
+use crate::project::{build_executable, write_project};
+use crate::properties::mentions_ident;
+use crate::readiness::KILLED_MESSAGE;
+use crate::reaper::temp_dir;
+use crate::signatures;
+use crate::status::StatusError;
+use crate::wrapping;
+use crate::{ChildSetup, ExecutionStatus, RunLimits, RustExecutor, SourceFile};
+use proc_macro2::TokenStream;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+// The harness reaches the submission as `submission::...`
+const LIBRARY_CARGO_TOML: &str = r#"[package]
+name = "submission"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+path = "src/lib.rs"
+
+[[test]]
+name = "harness"
+path = "tests/harness.rs"
+"#;
+
+// Lines of a failing test's captured output kept as its message
+const MAX_MESSAGE_LINES: usize = 20;
+
+// Would let the submission compile the harness into itself and print the
+// tests
+const INCLUDE_MACROS: &[&str] = &["include", "include_str", "include_bytes"];
+
+#[derive(Deserialize, ToSchema)]
+pub struct LibraryTestRequest {
+    // The student's src/lib.rs, compiled as submitted: items the harness
+    // uses must be `pub`
+    code: String,
+    // Further modules of the library, e.g. src/parser.rs
+    files: Option<Vec<SourceFile>>,
+    // The teacher's integration test file, built as tests/harness.rs with
+    // `use submission::*;` or `use submission::parser::...` to reach the
+    // student's code
+    harness: String,
+    // Functions src/lib.rs must define, e.g. "pub fn sort(v: &mut Vec<i32>)",
+    // checked before the harness is compiled
+    #[serde(rename = "requiredSignatures")]
+    required_signatures: Option<Vec<String>>,
+    // Only run tests whose names contain this, as with `cargo test <filter>`
+    filter: Option<String>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LibraryTestResult {
+    name: String,
+    // "passed", "failed" or "ignored"
+    status: String,
+    // The failing test's panic message and anything else it printed
+    message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LibraryTestResponse {
+    tests: Vec<LibraryTestResult>,
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    // Raw test-runner output
+    output: String,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    // The test run's status; runtime_error when a test failed
+    status: ExecutionStatus,
+}
+
+impl LibraryTestResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            tests: Vec::new(),
+            passed: 0,
+            failed: 0,
+            ignored: 0,
+            output: String::new(),
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+// libtest prints "test <name> ... ok|FAILED|ignored" per test, then the
+// captured output of each failure under "---- <name> stdout ----"
+fn parse_test_output(output: &str) -> Vec<LibraryTestResult> {
+    let mut results: Vec<LibraryTestResult> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("test "))
+        .filter_map(|line| line.rsplit_once(" ... "))
+        .map(|(name, result)| LibraryTestResult {
+            name: name.to_string(),
+            status: match result {
+                "ok" => "passed",
+                _ if result.starts_with("ignored") => "ignored",
+                _ => "failed",
+            }
+            .to_string(),
+            message: None,
+        })
+        .collect();
+
+    let mut current: Option<usize> = None;
+    for line in output.lines() {
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            current = results.iter().position(|result| result.name == name);
+            continue;
+        }
+        // The summary that follows the last captured output
+        if line == "failures:" {
+            current = None;
+        }
+        let Some(result) = current.map(|index| &mut results[index]) else {
+            continue;
+        };
+        let message = result.message.get_or_insert_with(String::new);
+        if message.lines().count() < MAX_MESSAGE_LINES {
+            if !message.is_empty() {
+                message.push('\n');
+            }
+            message.push_str(line);
+        }
+    }
+    for result in &mut results {
+        if let Some(message) = &mut result.message {
+            *message = message.trim().to_string();
+        }
+    }
+    results
+}
+
+impl RustExecutor {
+    // Builds the submission as a library with the teacher's harness as its
+    // integration test and runs it with the libtest runner
+    pub async fn run_library_tests(&self, req: LibraryTestRequest) -> LibraryTestResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("library-tests", None) {
+            Ok(active) => active,
+            Err(e) => return LibraryTestResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        let files = req.files.unwrap_or_default();
+        if let Err(e) = Self::validate_source_files(&config, &files) {
+            return LibraryTestResponse::error(e, start_time);
+        }
+        if files.iter().any(|file| file.path == "src/lib.rs") {
+            return LibraryTestResponse::error(
+                "The library root is `code`; src/lib.rs cannot also be given in `files`"
+                    .to_string(),
+                start_time,
+            );
+        }
+        let total_size = req.code.len()
+            + req.harness.len()
+            + files.iter().map(|f| f.content.len()).sum::<usize>();
+        let code_size_kb = total_size as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return LibraryTestResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) = self.check_unstable_features(
+            std::iter::once(req.code.as_str())
+                .chain(std::iter::once(req.harness.as_str()))
+                .chain(files.iter().map(|f| f.content.as_str())),
+        ) {
+            return LibraryTestResponse::error(e, start_time);
+        }
+        let forbid_unsafe = match self.forbids_unsafe(
+            std::iter::once(req.code.as_str())
+                .chain(std::iter::once(req.harness.as_str()))
+                .chain(files.iter().map(|f| f.content.as_str())),
+        ) {
+            Ok(forbid_unsafe) => forbid_unsafe,
+            Err(e) => return LibraryTestResponse::error(e, start_time),
+        };
+        for source in std::iter::once(&req.code).chain(files.iter().map(|f| &f.content)) {
+            let tokens = match source.parse::<TokenStream>() {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    return LibraryTestResponse::error(
+                        format!("Failed to parse code: {}", e),
+                        start_time,
+                    );
+                }
+            };
+            if let Some(name) = INCLUDE_MACROS
+                .iter()
+                .find(|name| mentions_ident(tokens.clone(), name))
+            {
+                return LibraryTestResponse::error(
+                    StatusError::new(
+                        ExecutionStatus::SecurityViolation,
+                        format!("{}! is not allowed in library submissions", name),
+                    ),
+                    start_time,
+                );
+            }
+        }
+
+        if let Err(e) = signatures::verify(&req.code, &req.required_signatures.unwrap_or_default())
+        {
+            return LibraryTestResponse::error(
+                StatusError::new(ExecutionStatus::CompileError, e),
+                start_time,
+            );
+        }
+
+        let temp_dir = match temp_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                return LibraryTestResponse::error(
+                    StatusError::internal(format!("Failed to create temp directory: {}", e)),
+                    start_time,
+                );
+            }
+        };
+        let project_path = temp_dir.path();
+        let lib_rs = if forbid_unsafe {
+            wrapping::forbid_unsafe(&req.code)
+        } else {
+            req.code.clone()
+        };
+        let mut sources = vec![
+            ("src/lib.rs", lib_rs.as_str()),
+            ("tests/harness.rs", req.harness.as_str()),
+        ];
+        sources.extend(files.iter().map(|f| (f.path.as_str(), f.content.as_str())));
+        if let Err(e) = write_project(project_path, LIBRARY_CARGO_TOML, &sources) {
+            return LibraryTestResponse::error(StatusError::internal(e), start_time);
+        }
+
+        active.set_phase("compiling");
+        let built = active
+            .until_killed(build_executable(
+                project_path,
+                &["test", "--no-run", "--test", "harness"],
+                None,
+                Duration::from_secs(config.compile_timeout),
+            ))
+            .await;
+        let test_executable = match built {
+            Some(Ok(path)) => path,
+            Some(Err(e)) => return LibraryTestResponse::error(e, start_time),
+            None => return LibraryTestResponse::killed(start_time),
+        };
+
+        let args: Vec<String> = req.filter.into_iter().collect();
+        let setup = ChildSetup {
+            working_dir: Some(project_path),
+            trace_log: None,
+            keep_stdin_open: false,
+        };
+        let outcome = self
+            .run_executable(
+                &test_executable,
+                setup,
+                &args,
+                None,
+                RunLimits::wall_clock(execution_timeout),
+                &[],
+                &active,
+            )
+            .await;
+        if active.is_killed() {
+            return LibraryTestResponse::killed(start_time);
+        }
+
+        let output = outcome.stdout_text();
+        let tests = parse_test_output(&output);
+        let count = |status: &str| tests.iter().filter(|test| test.status == status).count();
+        let (passed, failed, ignored) = (count("passed"), count("failed"), count("ignored"));
+        // A run without a single test, e.g. a filter that matched none
+        let status = if tests.is_empty() && outcome.status.is_success() {
+            ExecutionStatus::InvalidRequest
+        } else {
+            outcome.status
+        };
+        LibraryTestResponse {
+            tests,
+            passed,
+            failed,
+            ignored,
+            output,
+            error: outcome.stderr,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/library-tests",
+    request_body = LibraryTestRequest,
+    responses((status = 200, body = LibraryTestResponse))
+)]
+pub async fn library_tests(
+    req: LibraryTestRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.run_library_tests(req).await;
+    Ok(warp::reply::json(&result))
+}
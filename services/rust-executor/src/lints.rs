@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use utoipa::ToSchema;
+
+const MAX_LINTS: usize = 200;
+
+// Lint levels for one execution, e.g. deny ["clippy::needless_range_loop"]
+// and warn ["missing_docs"]. They apply to a lint pass over the user's code
+// before it is built, not to the build itself, so wrapper code is never
+// held to them.
+#[derive(Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct LintConfig {
+    // rustc lints such as "unused_variables", lint groups such as
+    // "warnings", and clippy lints as "clippy::<name>" or "clippy::pedantic"
+    pub allow: Option<Vec<String>>,
+    pub warn: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+    // clippy.toml values, e.g. {"too-many-lines-threshold": 40}
+    #[serde(rename = "clippyConfig")]
+    #[schema(value_type = Option<Object>)]
+    pub clippy_config: Option<HashMap<String, serde_json::Value>>,
+}
+
+// Lint names go into RUSTFLAGS, so only plain names are accepted
+fn is_lint_name(name: &str) -> bool {
+    let name = name.strip_prefix("clippy::").unwrap_or(name);
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl LintConfig {
+    fn levels(&self) -> [(&'static str, &[String]); 3] {
+        // Later flags override earlier ones for the same lint
+        [
+            ("-A", self.allow.as_deref().unwrap_or_default()),
+            ("-W", self.warn.as_deref().unwrap_or_default()),
+            ("-D", self.deny.as_deref().unwrap_or_default()),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let names: Vec<&String> = self.levels().into_iter().flat_map(|(_, n)| n).collect();
+        if names.len() > MAX_LINTS {
+            return Err(format!("At most {} lints can be configured", MAX_LINTS));
+        }
+        if let Some(name) = names.iter().find(|name| !is_lint_name(name)) {
+            return Err(format!(
+                "Invalid lint name '{}': expected e.g. unused_variables or clippy::needless_range_loop",
+                name
+            ));
+        }
+        Ok(())
+    }
+
+    // The lint pass runs clippy instead of `cargo check` for these
+    pub fn needs_clippy(&self) -> bool {
+        self.clippy_config.is_some()
+            || self
+                .levels()
+                .into_iter()
+                .flat_map(|(_, names)| names)
+                .any(|name| name.starts_with("clippy::"))
+    }
+
+    // e.g. "-A dead_code -D clippy::unwrap_used"
+    pub fn rustflags(&self) -> String {
+        self.levels()
+            .into_iter()
+            .flat_map(|(flag, names)| names.iter().map(move |name| format!("{} {}", flag, name)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // clippy reads clippy.toml from the package directory
+    pub fn write_clippy_toml(&self, project_path: &Path) -> Result<(), String> {
+        let Some(values) = &self.clippy_config else {
+            return Ok(());
+        };
+        let table =
+            toml::Value::try_from(values).map_err(|e| format!("Invalid clippyConfig: {}", e))?;
+        let content =
+            toml::to_string(&table).map_err(|e| format!("Invalid clippyConfig: {}", e))?;
+        fs::write(project_path.join("clippy.toml"), content)
+            .map_err(|e| format!("Failed to write clippy.toml: {}", e))
+    }
+}
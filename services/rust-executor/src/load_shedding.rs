@@ -0,0 +1,155 @@
+use crate::config::ExecutorConfig;
+use crate::readiness::ActiveExecution;
+use crate::status::{ExecutionStatus, StatusError};
+use crate::{filesystem, RustExecutor};
+use std::fs;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long a reading is reused before the host is sampled again
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// Once a threshold is crossed, executions are turned away for at least this
+// long, so an instance on the edge does not flap between accepting and
+// rejecting work
+const SHED_COOLDOWN: Duration = Duration::from_secs(10);
+
+// What the host looks like right now; a reading that could not be taken is
+// None and never triggers shedding
+#[derive(Default)]
+pub struct HostSample {
+    // 1-minute load average divided by the number of CPUs
+    pub load_per_cpu: Option<f64>,
+    // MemAvailable from /proc/meminfo
+    pub free_memory_mb: Option<u64>,
+    // Free space on the volume holding the temp directories
+    pub free_disk_mb: Option<u64>,
+}
+
+fn load_per_cpu() -> Option<f64> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let load: f64 = loadavg.split_whitespace().next()?.parse().ok()?;
+    let cpus = std::thread::available_parallelism().ok()?.get();
+    Some(load / cpus as f64)
+}
+
+fn free_memory_mb() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
+}
+
+impl HostSample {
+    pub fn take() -> Self {
+        Self {
+            load_per_cpu: load_per_cpu(),
+            free_memory_mb: free_memory_mb(),
+            free_disk_mb: filesystem::free_mb(&std::env::temp_dir()),
+        }
+    }
+
+    // Why the host should not take more work, or None when it can. A
+    // threshold of zero disables that check.
+    pub fn pressure(&self, config: &ExecutorConfig) -> Option<String> {
+        let mut reasons = Vec::new();
+        if let Some(load) = self.load_per_cpu {
+            if config.max_load_per_cpu > 0.0 && load > config.max_load_per_cpu {
+                reasons.push(format!(
+                    "load average {:.2} per CPU exceeds {:.2}",
+                    load, config.max_load_per_cpu
+                ));
+            }
+        }
+        if let Some(free) = self.free_memory_mb {
+            if free < config.min_free_memory_mb {
+                reasons.push(format!(
+                    "{}MB of memory free, below {}MB",
+                    free, config.min_free_memory_mb
+                ));
+            }
+        }
+        if let Some(free) = self.free_disk_mb {
+            if free < config.min_free_disk_mb {
+                reasons.push(format!(
+                    "{}MB of disk free, below {}MB",
+                    free, config.min_free_disk_mb
+                ));
+            }
+        }
+        (!reasons.is_empty()).then(|| reasons.join("; "))
+    }
+}
+
+struct Verdict {
+    decided_at: Instant,
+    // Set while executions are being shed
+    pressure: Option<String>,
+}
+
+// Decides whether new executions are accepted, sampling the host at most
+// once per SAMPLE_INTERVAL
+#[derive(Default)]
+pub struct LoadShedder {
+    verdict: Mutex<Option<Verdict>>,
+}
+
+impl LoadShedder {
+    pub fn check(&self, config: &ExecutorConfig) -> Result<(), String> {
+        let mut verdict = self.verdict.lock().unwrap();
+        let expired = verdict.as_ref().is_none_or(|verdict| {
+            let valid_for = if verdict.pressure.is_some() {
+                SHED_COOLDOWN
+            } else {
+                SAMPLE_INTERVAL
+            };
+            verdict.decided_at.elapsed() >= valid_for
+        });
+        if expired {
+            let pressure = HostSample::take().pressure(config);
+            if let Some(reason) = &pressure {
+                eprintln!("Shedding load: {}", reason);
+            }
+            *verdict = Some(Verdict {
+                decided_at: Instant::now(),
+                pressure,
+            });
+        }
+        match verdict
+            .as_ref()
+            .and_then(|verdict| verdict.pressure.as_ref())
+        {
+            Some(reason) => Err(format!(
+                "The executor is over capacity ({}); try again shortly",
+                reason
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl RustExecutor {
+    // Called before an execution starts; an Err is returned to the caller
+    // with status "capacity" instead of accepting work likely to time out
+    pub fn admit_execution(&self) -> Result<(), String> {
+        let admitted = self.load_shedder.check(&self.config());
+        if admitted.is_err() {
+            self.metrics.executions_shed.fetch_add(1, Ordering::Relaxed);
+        }
+        admitted
+    }
+
+    // admit_execution then track_execution, for everything that compiles or
+    // runs submitted code
+    pub fn start_execution(
+        &self,
+        kind: &'static str,
+        requester_id: Option<&str>,
+    ) -> Result<ActiveExecution, StatusError> {
+        self.admit_execution()
+            .map_err(|e| StatusError::new(ExecutionStatus::Capacity, e))?;
+        Ok(self.track_execution(kind, requester_id))
+    }
+}
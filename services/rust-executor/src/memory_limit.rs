@@ -0,0 +1,68 @@
+use std::fs;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+// Rust's default allocation error handler prints this before aborting
+const ALLOCATION_FAILED: &str = "memory allocation of ";
+
+// RLIMIT_DATA for a user program: its heap, anonymous mappings and thread
+// stacks, unlike RLIMIT_AS not the address space malloc merely reserves.
+// An allocation past it fails, which Rust turns into an abort.
+pub fn rlimit(max_memory_mb: u32) -> libc::rlimit {
+    let bytes = max_memory_mb as libc::rlim_t * 1024 * 1024;
+    libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    }
+}
+
+// oom_kill count of the executor's cgroup (v2), which the kernel raises
+// when it kills a process in it for exceeding the cgroup's memory limit;
+// None outside a cgroup v2 hierarchy
+fn oom_kill_count() -> Option<u64> {
+    let cgroup = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let events = fs::read_to_string(format!("/sys/fs/cgroup{}/memory.events", path)).ok()?;
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+// Taken before a program starts, to tell afterwards whether the OOM killer
+// fired while it ran. The cgroup is shared by concurrent executions, so
+// only a program that was itself SIGKILLed is attributed the kill.
+pub struct OomWatch {
+    oom_kills: Option<u64>,
+}
+
+impl OomWatch {
+    pub fn start() -> Self {
+        Self {
+            oom_kills: oom_kill_count(),
+        }
+    }
+
+    // A failed allocation under RLIMIT_DATA aborts with Rust's message,
+    // which streamed runs do not capture; an OOM kill is a SIGKILL the
+    // executor did not send, so timeouts and kills must be ruled out first
+    pub fn exceeded(&self, status: ExitStatus, stderr: Option<&str>) -> bool {
+        match status.signal() {
+            Some(libc::SIGABRT) => stderr.is_some_and(|stderr| stderr.contains(ALLOCATION_FAILED)),
+            Some(libc::SIGKILL) => self
+                .oom_kills
+                .zip(oom_kill_count())
+                .is_some_and(|(before, after)| after > before),
+            _ => false,
+        }
+    }
+}
+
+pub fn message(max_memory_mb: u32) -> String {
+    format!(
+        "Memory limit of {} MB exceeded: the program tried to use more memory than it is allowed",
+        max_memory_mb
+    )
+}
@@ -0,0 +1,183 @@
+use crate::artifacts::ArtifactCacheStats;
+use crate::RustExecutor;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Counters exported at /metrics
+#[derive(Default)]
+pub struct ServiceMetrics {
+    pub reaper_runs: AtomicU64,
+    pub temp_dirs_reclaimed: AtomicU64,
+    pub temp_bytes_reclaimed: AtomicU64,
+    pub executions_shed: AtomicU64,
+    pub stray_processes_killed: AtomicU64,
+    pub zombies_reaped: AtomicU64,
+    pub gc_runs: AtomicU64,
+    pub artifacts_reclaimed: AtomicU64,
+    pub artifact_bytes_reclaimed: AtomicU64,
+    pub history_rows_deleted: AtomicU64,
+    pub job_index_entries_pruned: AtomicU64,
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+impl ServiceMetrics {
+    // Prometheus text exposition format
+    fn render(
+        &self,
+        running_executions: usize,
+        toolchain_drift: bool,
+        artifact_cache: &ArtifactCacheStats,
+    ) -> String {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "rust_executor_running_executions",
+            "gauge",
+            "Executions currently compiling or running",
+            running_executions as u64,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_toolchain_drift",
+            "gauge",
+            "1 when generated projects build with another toolchain than PINNED_RUST_TOOLCHAIN",
+            toolchain_drift as u64,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_temp_reaper_runs_total",
+            "counter",
+            "Completed scans for orphaned temp directories",
+            self.reaper_runs.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_temp_dirs_reclaimed_total",
+            "counter",
+            "Orphaned temp directories removed",
+            self.temp_dirs_reclaimed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_temp_bytes_reclaimed_total",
+            "counter",
+            "Bytes freed by removing orphaned temp directories",
+            self.temp_bytes_reclaimed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_executions_shed_total",
+            "counter",
+            "Executions rejected with status \"capacity\" because the host was overloaded",
+            self.executions_shed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_stray_processes_killed_total",
+            "counter",
+            "Processes left running after their execution ended, killed by the reaper",
+            self.stray_processes_killed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_zombies_reaped_total",
+            "counter",
+            "Exited child processes nothing else waited on, reaped by the reaper",
+            self.zombies_reaped.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_gc_runs_total",
+            "counter",
+            "Completed garbage collection passes over persisted data",
+            self.gc_runs.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_gc_artifacts_reclaimed_total",
+            "counter",
+            "Expired compiled artifacts removed from memory and object storage",
+            self.artifacts_reclaimed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_gc_artifact_bytes_reclaimed_total",
+            "counter",
+            "Bytes freed by removing expired artifacts and their run output",
+            self.artifact_bytes_reclaimed.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_gc_history_rows_deleted_total",
+            "counter",
+            "Executions deleted from the history after HISTORY_RETENTION_DAYS",
+            self.history_rows_deleted.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_gc_job_index_entries_pruned_total",
+            "counter",
+            "Entries of expired jobs removed from the job listing index",
+            self.job_index_entries_pruned.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_artifact_cache_hits_total",
+            "counter",
+            "Artifact lookups answered from the local cache",
+            artifact_cache.hits,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_artifact_cache_misses_total",
+            "counter",
+            "Artifact lookups not in the local cache, loaded from object storage or not found",
+            artifact_cache.misses,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_artifact_cache_evictions_total",
+            "counter",
+            "Artifacts dropped from the local cache to stay within MAX_ARTIFACTS and ARTIFACT_CACHE_MAX_MB",
+            artifact_cache.evictions,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_artifact_cache_entries",
+            "gauge",
+            "Artifacts in the local cache",
+            artifact_cache.entries,
+        );
+        write_metric(
+            &mut out,
+            "rust_executor_artifact_cache_bytes",
+            "gauge",
+            "Disk used by the binaries in the local artifact cache",
+            artifact_cache.bytes,
+        );
+        out
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses((status = 200, description = "Prometheus text exposition format", body = String, content_type = "text/plain"))
+)]
+pub async fn metrics(executor: RustExecutor) -> Result<impl warp::Reply, warp::Rejection> {
+    let running_executions = executor.running_executions.lock().unwrap().len();
+    Ok(warp::reply::with_header(
+        executor.metrics.render(
+            running_executions,
+            executor.toolchain.drift.is_some(),
+            &executor.artifacts.stats(),
+        ),
+        "content-type",
+        "text/plain; version=0.0.4",
+    ))
+}
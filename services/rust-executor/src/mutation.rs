@@ -0,0 +1,485 @@
+use crate::project::{build_executable, write_project};
+use crate::readiness::{ActiveExecution, KILLED_MESSAGE};
+use crate::reaper::temp_dir;
+use crate::status::StatusError;
+use crate::wrapping;
+use crate::{ChildSetup, ExecutionStatus, RunLimits, RustExecutor};
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use syn::visit_mut::{self, VisitMut};
+use syn::{BinOp, Expr, ExprLit, Lit, LitBool, LitInt};
+use utoipa::ToSchema;
+
+const MUTATION_CARGO_TOML: &str = r#"[package]
+name = "rust_mutation"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+path = "src/lib.rs"
+"#;
+
+const DEFAULT_MAX_MUTANTS: usize = 50;
+
+#[derive(Deserialize, ToSchema)]
+pub struct MutationTestRequest {
+    #[serde(rename = "referenceCode")]
+    reference_code: String,
+    tests: String,
+    #[serde(rename = "maxMutants")]
+    max_mutants: Option<usize>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MutantResult {
+    id: usize,
+    line: usize,
+    description: String,
+    // killed | survived | timeout | invalid
+    status: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MutationTestResponse {
+    mutants: Vec<MutantResult>,
+    killed: usize,
+    survived: usize,
+    invalid: usize,
+    #[serde(rename = "killRate")]
+    kill_rate: f64,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    status: ExecutionStatus,
+}
+
+impl MutationTestResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            mutants: vec![],
+            killed: 0,
+            survived: 0,
+            invalid: 0,
+            kill_rate: 0.0,
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+struct MutationSite {
+    line: usize,
+    description: String,
+}
+
+// Walks the AST in a fixed order, numbering every possible mutation. With
+// `target` unset it only records the sites; with `target` set it applies the
+// mutation carrying that number and leaves the rest of the tree untouched.
+struct Mutator {
+    target: Option<usize>,
+    next_id: usize,
+    sites: Vec<MutationSite>,
+}
+
+impl Mutator {
+    fn new(target: Option<usize>) -> Self {
+        Self {
+            target,
+            next_id: 0,
+            sites: Vec::new(),
+        }
+    }
+
+    // Registers a candidate and reports whether it is the one to apply
+    fn candidate(&mut self, line: usize, description: String) -> bool {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.sites.push(MutationSite { line, description });
+        self.target == Some(id)
+    }
+}
+
+fn swapped_operator(op: &BinOp) -> Option<(BinOp, &'static str)> {
+    let swapped = match op {
+        BinOp::Add(_) => (BinOp::Sub(Default::default()), "replace + with -"),
+        BinOp::Sub(_) => (BinOp::Add(Default::default()), "replace - with +"),
+        BinOp::Mul(_) => (BinOp::Div(Default::default()), "replace * with /"),
+        BinOp::Div(_) => (BinOp::Mul(Default::default()), "replace / with *"),
+        BinOp::Rem(_) => (BinOp::Mul(Default::default()), "replace % with *"),
+        BinOp::Lt(_) => (BinOp::Le(Default::default()), "replace < with <="),
+        BinOp::Le(_) => (BinOp::Lt(Default::default()), "replace <= with <"),
+        BinOp::Gt(_) => (BinOp::Ge(Default::default()), "replace > with >="),
+        BinOp::Ge(_) => (BinOp::Gt(Default::default()), "replace >= with >"),
+        BinOp::Eq(_) => (BinOp::Ne(Default::default()), "replace == with !="),
+        BinOp::Ne(_) => (BinOp::Eq(Default::default()), "replace != with =="),
+        BinOp::And(_) => (BinOp::Or(Default::default()), "replace && with ||"),
+        BinOp::Or(_) => (BinOp::And(Default::default()), "replace || with &&"),
+        BinOp::AddAssign(_) => (BinOp::SubAssign(Default::default()), "replace += with -="),
+        BinOp::SubAssign(_) => (BinOp::AddAssign(Default::default()), "replace -= with +="),
+        _ => return None,
+    };
+    Some(swapped)
+}
+
+impl VisitMut for Mutator {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Binary(binary) => {
+                if let Some((replacement, description)) = swapped_operator(&binary.op) {
+                    let line = binary
+                        .op
+                        .to_token_stream()
+                        .into_iter()
+                        .next()
+                        .map_or(0, |t| t.span().start().line);
+                    if self.candidate(line, description.to_string()) {
+                        binary.op = replacement;
+                    }
+                }
+            }
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(int), ..
+            }) => {
+                if let Ok(value) = int.base10_parse::<u128>() {
+                    let line = int.span().start().line;
+                    let mut replacements = vec![value + 1];
+                    if value > 0 {
+                        replacements.push(value - 1);
+                    }
+                    for replacement in replacements {
+                        if self.candidate(line, format!("replace {} with {}", value, replacement)) {
+                            *int = LitInt::new(
+                                &format!("{}{}", replacement, int.suffix()),
+                                int.span(),
+                            );
+                        }
+                    }
+                }
+            }
+            Expr::Lit(ExprLit {
+                lit: Lit::Bool(boolean),
+                ..
+            }) => {
+                let line = boolean.span.start().line;
+                let description = format!("replace {} with {}", boolean.value, !boolean.value);
+                if self.candidate(line, description) {
+                    *boolean = LitBool::new(!boolean.value, boolean.span);
+                }
+            }
+            _ => {}
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+
+    // Array lengths and const generics must stay valid, so only mutate
+    // expressions evaluated at runtime
+    fn visit_item_const_mut(&mut self, _item: &mut syn::ItemConst) {}
+
+    fn visit_type_mut(&mut self, _ty: &mut syn::Type) {}
+}
+
+// syn's syntax tree is not Send, so all mutants are rendered to source up
+// front instead of being held across the awaits in the request handler
+fn generate_mutants(
+    reference_code: &str,
+    max_mutants: usize,
+) -> Result<Vec<(MutationSite, String)>, String> {
+    let reference = syn::parse_file(reference_code)
+        .map_err(|e| format!("Failed to parse reference implementation: {}", e))?;
+
+    let mut collector = Mutator::new(None);
+    collector.visit_file_mut(&mut reference.clone());
+
+    Ok(collector
+        .sites
+        .into_iter()
+        .take(max_mutants)
+        .enumerate()
+        .map(|(id, site)| {
+            let mut mutated = reference.clone();
+            Mutator::new(Some(id)).visit_file_mut(&mut mutated);
+            (site, mutated.to_token_stream().to_string())
+        })
+        .collect())
+}
+
+impl RustExecutor {
+    pub async fn run_mutation_tests(&self, req: MutationTestRequest) -> MutationTestResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("mutation", None) {
+            Ok(active) => active,
+            Err(e) => return MutationTestResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+        let max_mutants = req.max_mutants.unwrap_or(DEFAULT_MAX_MUTANTS).min(200);
+
+        let code_size_kb = (req.reference_code.len() + req.tests.len()) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return MutationTestResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) = self
+            .check_unstable_features([req.reference_code.as_str(), req.tests.as_str()].into_iter())
+        {
+            return MutationTestResponse::error(e, start_time);
+        }
+        let forbid_unsafe = match self
+            .forbids_unsafe([req.reference_code.as_str(), req.tests.as_str()].into_iter())
+        {
+            Ok(forbid_unsafe) => forbid_unsafe,
+            Err(e) => return MutationTestResponse::error(e, start_time),
+        };
+
+        let mutant_sources = match generate_mutants(&req.reference_code, max_mutants) {
+            Ok(mutants) => mutants,
+            Err(e) => return MutationTestResponse::error(e, start_time),
+        };
+
+        let temp_dir = match temp_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                return MutationTestResponse::error(
+                    StatusError::internal(format!("Failed to create temp directory: {}", e)),
+                    start_time,
+                );
+            }
+        };
+        let project_path = temp_dir.path();
+
+        // The unmutated reference must pass the student's tests, otherwise
+        // every mutant would count as killed
+        let baseline = self
+            .test_mutant(
+                project_path,
+                &req.reference_code,
+                &req.tests,
+                forbid_unsafe,
+                execution_timeout,
+                &active,
+            )
+            .await;
+        match baseline {
+            MutantOutcome::Passed => {}
+            MutantOutcome::Failed => {
+                return MutationTestResponse::error(
+                    StatusError::new(
+                        ExecutionStatus::RuntimeError,
+                        "Tests fail against the unmodified reference implementation",
+                    ),
+                    start_time,
+                );
+            }
+            MutantOutcome::TimedOut => {
+                return MutationTestResponse::error(
+                    StatusError::new(
+                        ExecutionStatus::Timeout,
+                        "Tests timed out against the unmodified reference implementation",
+                    ),
+                    start_time,
+                );
+            }
+            MutantOutcome::CompileError(e) => {
+                return MutationTestResponse::error(e, start_time);
+            }
+            MutantOutcome::Aborted => return MutationTestResponse::killed(start_time),
+        }
+
+        let mut mutants = Vec::new();
+        for (id, (site, source)) in mutant_sources.into_iter().enumerate() {
+            let status = match self
+                .test_mutant(
+                    project_path,
+                    &source,
+                    &req.tests,
+                    forbid_unsafe,
+                    execution_timeout,
+                    &active,
+                )
+                .await
+            {
+                MutantOutcome::Failed => "killed",
+                MutantOutcome::TimedOut => "timeout",
+                MutantOutcome::Passed => "survived",
+                MutantOutcome::CompileError(_) => "invalid",
+                MutantOutcome::Aborted => return MutationTestResponse::killed(start_time),
+            };
+            mutants.push(MutantResult {
+                id,
+                line: site.line,
+                description: site.description,
+                status: status.to_string(),
+            });
+        }
+
+        let killed = mutants
+            .iter()
+            .filter(|m| m.status == "killed" || m.status == "timeout")
+            .count();
+        let survived = mutants.iter().filter(|m| m.status == "survived").count();
+        let invalid = mutants.iter().filter(|m| m.status == "invalid").count();
+        let kill_rate = if killed + survived == 0 {
+            0.0
+        } else {
+            killed as f64 / (killed + survived) as f64
+        };
+
+        MutationTestResponse {
+            mutants,
+            killed,
+            survived,
+            invalid,
+            kill_rate,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: ExecutionStatus::Success,
+        }
+    }
+
+    // Every mutant reuses the same project directory so cargo only has to
+    // recompile the library itself between runs
+    async fn test_mutant(
+        &self,
+        project_path: &std::path::Path,
+        library: &str,
+        tests: &str,
+        forbid_unsafe: bool,
+        timeout_seconds: u64,
+        active: &ActiveExecution,
+    ) -> MutantOutcome {
+        let mut lib_rs = format!(
+            "{}\n\n#[cfg(test)]\nmod student_tests {{\n    use super::*;\n\n{}\n}}\n",
+            library, tests
+        );
+        if forbid_unsafe {
+            lib_rs = wrapping::forbid_unsafe(&lib_rs);
+        }
+        if let Err(e) = write_project(
+            project_path,
+            MUTATION_CARGO_TOML,
+            &[("src/lib.rs", &lib_rs)],
+        ) {
+            return MutantOutcome::CompileError(StatusError::internal(e));
+        }
+
+        active.set_phase("compiling");
+        let built = active
+            .until_killed(build_executable(
+                project_path,
+                &["test", "--no-run", "--lib"],
+                None,
+                Duration::from_secs(self.config().compile_timeout),
+            ))
+            .await;
+        let executable = match built {
+            Some(Ok(path)) => path,
+            Some(Err(e)) => return MutantOutcome::CompileError(e),
+            None => return MutantOutcome::Aborted,
+        };
+
+        let outcome = self
+            .run_executable(
+                &executable,
+                ChildSetup::default(),
+                &[],
+                None,
+                RunLimits::wall_clock(timeout_seconds),
+                &[],
+                active,
+            )
+            .await;
+        match outcome.status {
+            ExecutionStatus::Success => MutantOutcome::Passed,
+            ExecutionStatus::Timeout => MutantOutcome::TimedOut,
+            ExecutionStatus::Killed => MutantOutcome::Aborted,
+            _ => MutantOutcome::Failed,
+        }
+    }
+}
+
+enum MutantOutcome {
+    Passed,
+    Failed,
+    TimedOut,
+    CompileError(StatusError),
+    // The whole mutation run was killed through the admin endpoints
+    Aborted,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "const N: usize = 3;\n\
+                        fn f(a: i32, b: i32) -> bool {\n\
+                        a + 1 < b\n\
+                        }\n";
+
+    fn descriptions(mutants: &[(MutationSite, String)]) -> Vec<(usize, &str)> {
+        mutants
+            .iter()
+            .map(|(site, _)| (site.line, site.description.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn sites_are_numbered_in_source_order() {
+        let mutants = generate_mutants(CODE, 100).unwrap();
+        assert_eq!(
+            descriptions(&mutants),
+            vec![
+                (3, "replace < with <="),
+                (3, "replace + with -"),
+                (3, "replace 1 with 2"),
+                (3, "replace 1 with 0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn each_mutant_applies_only_its_own_site() {
+        let mutants = generate_mutants(CODE, 100).unwrap();
+        let sources: Vec<&str> = mutants.iter().map(|(_, source)| source.as_str()).collect();
+        assert!(sources[0].contains("a + 1 <= b"));
+        assert!(sources[1].contains("a - 1 < b"));
+        assert!(sources[2].contains("a + 2 < b"));
+        assert!(sources[3].contains("a + 0 < b"));
+        // Constants must stay valid wherever they are used as lengths
+        assert!(sources
+            .iter()
+            .all(|source| source.contains("N : usize = 3")));
+    }
+
+    #[test]
+    fn max_mutants_keeps_the_first_sites() {
+        let mutants = generate_mutants(CODE, 2).unwrap();
+        assert_eq!(
+            descriptions(&mutants),
+            vec![(3, "replace < with <="), (3, "replace + with -")]
+        );
+    }
+
+    #[test]
+    fn unparsable_reference_is_an_error() {
+        assert!(generate_mutants("fn f( {", 10).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::artifacts::{CompileResponse, RunArtifactRequest};
+use crate::assignments::{AssignmentBundle, AssignmentLimits, DryRunProblem, DryRunResponse};
+use crate::audit::AuditRecord;
+use crate::behavior_diff::{
+    BehaviorDiffRequest, BehaviorDiffResponse, CaseDiff, CaseRun, DiffLine,
+};
+use crate::code_metrics::{CodeMetricsRequest, CodeMetricsResponse, FunctionMetrics};
+use crate::compare::{CompareRequest, CompareResponse, InputComparison, TimingStats};
+use crate::completion::{CompletionItem, CompletionRequest, CompletionResponse};
+use crate::coverage::{CodeCoverageRequest, CodeCoverageResponse, CoverageRun, LineCoverage};
+use crate::diagnostics::{Diagnostic, Suggestion};
+use crate::explain::ExplanationResponse;
+use crate::fix::{AppliedFix, FixRequest, FixResponse};
+use crate::health::{HealthResponse, LastSuccess};
+use crate::history::ExecutionRecord;
+use crate::input_generator::{GenerateInputsRequest, GenerateInputsResponse, LineSpec, ValueRange};
+use crate::judge::{
+    GroupResult, JudgeRequest, JudgeResponse, OutputComparison, TestCase, TestGroup, TestResult,
+};
+use crate::library_tests::{LibraryTestRequest, LibraryTestResponse, LibraryTestResult};
+use crate::lints::LintConfig;
+use crate::mutation::{MutantResult, MutationTestRequest, MutationTestResponse};
+use crate::output_events::OutputEvent;
+use crate::output_files::OutputFile;
+use crate::panic_report::{PanicFrame, PanicReport};
+use crate::properties::{PropertyResult, PropertyTestRequest, PropertyTestResponse};
+use crate::queue::{JobListResponse, JobStatusResponse, JobStdinRequest};
+use crate::repl::{ReplEvalRequest, ReplSessionResponse};
+use crate::rules::ConstructRules;
+use crate::selftest::{SelfTestResponse, SelfTestTimings};
+use crate::similarity::{
+    SimilarityPair, SimilarityRequest, SimilarityResponse, Submission, SubmissionFingerprint,
+};
+use crate::std_usage::{StdItem, StdUsageRequest, StdUsageResponse};
+use crate::stress::{Counterexample, StressRequest, StressResponse};
+use crate::syscall_audit::{FileAccess, SyscallAudit};
+use crate::user_data::DeletionReport;
+use crate::{
+    CodeExecutionRequest, CodeExecutionResponse, CodeValidationRequest, CodeValidationResponse,
+    DataFile, ExecutionStatus, OptLevel, SourceFile,
+};
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+
+// Body of every non-200 JSON reply
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    error: String,
+    // Set when the request was rejected before reaching a handler, e.g.
+    // "invalid_body", "payload_too_large" or "not_found"
+    code: Option<String>,
+}
+
+// Served at /openapi.json. Schemas are derived from the serde structs, so
+// field names always match what the handlers accept and return.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Rust executor",
+        description = "Compiles and runs Rust submissions"
+    ),
+    servers(
+        (url = "/v2", description = "Current API"),
+        (url = "/v1", description = "Compatibility: failure statuses other than timeout, memory_limit, stack_overflow, killed, capacity and quota_exceeded are reported as error, and diagnostics are omitted")
+    ),
+    paths(
+        crate::health::health,
+        crate::ready,
+        crate::execute,
+        crate::validate,
+        crate::fix::fix,
+        crate::artifacts::compile,
+        crate::artifacts::run,
+        crate::info,
+        crate::explain::explain,
+        crate::similarity::similarity,
+        crate::code_metrics::code_metrics,
+        crate::std_usage::std_usage,
+        crate::completion::complete,
+        crate::metrics::metrics,
+        crate::coverage,
+        crate::mutation,
+        crate::compare::compare,
+        crate::behavior_diff::diff,
+        crate::judge::judge,
+        crate::assignments::dry_run,
+        crate::input_generator::generate,
+        crate::stress::stress,
+        crate::properties::property_tests,
+        crate::library_tests::library_tests,
+        crate::create_session,
+        crate::eval_in_session,
+        crate::delete_session,
+        crate::list_executions,
+        crate::submit_job,
+        crate::list_jobs,
+        crate::job_status,
+        crate::write_job_stdin,
+        crate::admin::reload_config,
+        crate::admin::tenant_usage,
+        crate::admin::running_executions,
+        crate::admin::kill_execution,
+        crate::admin::kill_all_executions,
+        crate::audit::query_audit_log,
+        crate::user_data::delete_user_data,
+        crate::selftest::selftest,
+    ),
+    components(schemas(
+        HealthResponse,
+        LastSuccess,
+        SourceFile,
+        DataFile,
+        ConstructRules,
+        LintConfig,
+        CodeExecutionRequest,
+        OptLevel,
+        CodeExecutionResponse,
+        ExecutionStatus,
+        PanicReport,
+        PanicFrame,
+        OutputFile,
+        OutputEvent,
+        SyscallAudit,
+        FileAccess,
+        CodeValidationRequest,
+        CodeValidationResponse,
+        Diagnostic,
+        Suggestion,
+        FixRequest,
+        FixResponse,
+        AppliedFix,
+        CompileResponse,
+        RunArtifactRequest,
+        ExplanationResponse,
+        Submission,
+        SimilarityRequest,
+        SimilarityResponse,
+        SubmissionFingerprint,
+        SimilarityPair,
+        CodeMetricsRequest,
+        CodeMetricsResponse,
+        FunctionMetrics,
+        StdUsageRequest,
+        StdUsageResponse,
+        StdItem,
+        CompletionRequest,
+        CompletionResponse,
+        CompletionItem,
+        CodeCoverageRequest,
+        CodeCoverageResponse,
+        LineCoverage,
+        CoverageRun,
+        MutationTestRequest,
+        MutationTestResponse,
+        MutantResult,
+        CompareRequest,
+        CompareResponse,
+        InputComparison,
+        TimingStats,
+        BehaviorDiffRequest,
+        BehaviorDiffResponse,
+        CaseDiff,
+        CaseRun,
+        DiffLine,
+        JudgeRequest,
+        JudgeResponse,
+        OutputComparison,
+        AssignmentBundle,
+        AssignmentLimits,
+        DryRunProblem,
+        DryRunResponse,
+        GenerateInputsRequest,
+        GenerateInputsResponse,
+        LineSpec,
+        ValueRange,
+        StressRequest,
+        StressResponse,
+        Counterexample,
+        TestCase,
+        TestGroup,
+        TestResult,
+        GroupResult,
+        PropertyTestRequest,
+        PropertyTestResponse,
+        PropertyResult,
+        LibraryTestRequest,
+        LibraryTestResponse,
+        LibraryTestResult,
+        ReplEvalRequest,
+        ReplSessionResponse,
+        ExecutionRecord,
+        AuditRecord,
+        DeletionReport,
+        SelfTestResponse,
+        SelfTestTimings,
+        JobStatusResponse,
+        JobListResponse,
+        JobStdinRequest,
+        ErrorResponse,
+    ))
+)]
+pub struct ApiDoc;
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+// Lines recorded per stream; later output is still in `output` and `error`
+const MAX_EVENTS_PER_STREAM: usize = 5000;
+
+// One line the program wrote, so stdout and stderr can be shown in the
+// order they were produced
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct OutputEvent {
+    // "stdout" or "stderr"
+    pub stream: String,
+    // Milliseconds since the program started
+    pub timestamp: f64,
+    // Without the trailing newline; the last line may not have had one
+    pub text: String,
+}
+
+// Splits one stream into lines, each stamped with the time its newline
+// arrived
+pub struct LineRecorder {
+    stream: &'static str,
+    start: Instant,
+    partial: Vec<u8>,
+    events: Vec<OutputEvent>,
+}
+
+impl LineRecorder {
+    pub fn new(stream: &'static str, start: Instant) -> Self {
+        Self {
+            stream,
+            start,
+            partial: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        let mut rest = chunk;
+        while let Some(pos) = rest.iter().position(|&b| b == b'\n') {
+            self.partial.extend_from_slice(&rest[..pos]);
+            self.record();
+            rest = &rest[pos + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+    }
+
+    pub fn finish(mut self) -> Vec<OutputEvent> {
+        if !self.partial.is_empty() {
+            self.record();
+        }
+        self.events
+    }
+
+    fn record(&mut self) {
+        let line = std::mem::take(&mut self.partial);
+        if self.events.len() >= MAX_EVENTS_PER_STREAM {
+            return;
+        }
+        self.events.push(OutputEvent {
+            stream: self.stream.to_string(),
+            timestamp: self.start.elapsed().as_secs_f64() * 1000.0,
+            text: String::from_utf8_lossy(&line).into_owned(),
+        });
+    }
+}
+
+// Both streams in one list, ordered by time. The streams arrive through
+// separate pipes, so lines written within a few milliseconds of each other
+// on different streams can still end up out of order.
+pub fn interleave(stdout: Vec<OutputEvent>, stderr: Vec<OutputEvent>) -> Vec<OutputEvent> {
+    let mut events = stdout;
+    events.extend(stderr);
+    events.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    events
+}
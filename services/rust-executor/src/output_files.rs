@@ -0,0 +1,93 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use utoipa::ToSchema;
+
+// Only these are returned; build products and anything else the program
+// leaves behind are ignored
+const ALLOWED_EXTENSIONS: &[&str] = &[
+    "txt", "csv", "tsv", "json", "md", "log", "out", "xml", "html", "svg", "ppm", "pgm", "pbm",
+    "png", "bmp",
+];
+const MAX_OUTPUT_FILES: usize = 32;
+// Directory entries looked at before giving up, for programs that create
+// huge trees
+const MAX_SCANNED_ENTRIES: usize = 1000;
+// Written before the program runs
+const PROJECT_ENTRIES: &[&str] = &["Cargo.toml", "Cargo.lock", "src", "target"];
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct OutputFile {
+    // Relative to the program's working directory, e.g. "out/result.csv"
+    pub name: String,
+    pub size: u64,
+    // null when returning the file would exceed max_output_files_kb
+    #[serde(rename = "contentBase64")]
+    pub content_base64: Option<String>,
+}
+
+// Files with an allowed extension the program wrote into its working
+// directory, sorted by name. `data_files` are the request's own input files,
+// which are not returned even when the program rewrote them.
+pub fn collect(project_path: &Path, data_files: &[String], max_total_kb: u32) -> Vec<OutputFile> {
+    let mut found = Vec::new();
+    let mut scanned = 0;
+    let mut pending = vec![project_path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            scanned += 1;
+            if scanned > MAX_SCANNED_ENTRIES {
+                break;
+            }
+            let path = entry.path();
+            let Ok(relative) = path.strip_prefix(project_path) else {
+                continue;
+            };
+            let name = relative.to_string_lossy().into_owned();
+            if PROJECT_ENTRIES.contains(&name.as_str()) || data_files.contains(&name) {
+                continue;
+            }
+            // Symlinks are never followed, so a program cannot hand back
+            // files from outside its directory
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file()
+                && path.extension().is_some_and(|ext| {
+                    ALLOWED_EXTENSIONS
+                        .contains(&ext.to_string_lossy().to_ascii_lowercase().as_str())
+                })
+            {
+                found.push((name, path, metadata.len()));
+            }
+        }
+    }
+
+    found.sort();
+    found.truncate(MAX_OUTPUT_FILES);
+    let mut budget = max_total_kb as u64 * 1024;
+    found
+        .into_iter()
+        .map(|(name, path, size)| {
+            let content = (size <= budget)
+                .then(|| fs::read(&path).ok())
+                .flatten()
+                .filter(|content| content.len() as u64 <= budget);
+            if let Some(content) = &content {
+                budget -= content.len() as u64;
+            }
+            OutputFile {
+                name,
+                size,
+                content_base64: content.map(|content| BASE64.encode(content)),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,186 @@
+use crate::output_events::OutputEvent;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Where the user's code sits inside the generated src/main.rs
+#[derive(Clone, Copy)]
+pub struct UserLines {
+    // Generated lines before the user's code
+    pub offset: usize,
+    pub count: usize,
+}
+
+impl UserLines {
+    pub fn new(generated: &str, user_code: &str) -> Self {
+        Self {
+            offset: crate::RustExecutor::user_line_offset(generated, user_code),
+            count: user_code.lines().count().max(1),
+        }
+    }
+
+    // Maps a location reported by rustc or a backtrace to the user's code.
+    // Lines of src/main.rs outside it belong to the wrapper and give None;
+    // other files under src/ are written unchanged.
+    fn map(&self, file: &str, line: usize) -> Option<usize> {
+        let file = file.trim_start_matches("./");
+        if file != "src/main.rs" {
+            return file.starts_with("src/").then_some(line);
+        }
+        line.checked_sub(self.offset)
+            .filter(|&line| line >= 1 && line <= self.count)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PanicFrame {
+    function: String,
+    file: String,
+    line: usize,
+    column: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PanicReport {
+    message: String,
+    // Location in the user's code, e.g. "src/main.rs", 4, 9; missing when
+    // the panic happened outside it
+    file: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+    // Innermost first, only frames in the user's code
+    backtrace: Vec<PanicFrame>,
+}
+
+// "src/main.rs:12:5" -> ("src/main.rs", 12, Some(5))
+fn parse_location(location: &str) -> Option<(&str, usize, Option<usize>)> {
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next()?.parse().ok()?;
+    let middle = parts.next()?;
+    match (middle.parse().ok(), parts.next()) {
+        (Some(line), Some(file)) => Some((file, line, Some(last))),
+        _ => Some((location.rsplit_once(':')?.0, last, None)),
+    }
+}
+
+fn is_backtrace_frame(line: &str) -> Option<&str> {
+    let (index, function) = line.trim_start().split_once(": ")?;
+    index.parse::<usize>().ok()?;
+    Some(function)
+}
+
+// First panic in a program's stderr, as printed by the default hook with
+// RUST_BACKTRACE=1:
+//   thread 'main' (123) panicked at src/main.rs:12:5:
+//   <message>
+//   stack backtrace:
+//      0: <function>
+//                at ./src/main.rs:12:5
+pub fn parse(stderr: &str, user_lines: UserLines) -> Option<PanicReport> {
+    let mut lines = stderr.lines();
+    let location = lines.find_map(|line| {
+        let rest = line.strip_prefix("thread '")?;
+        let (_, location) = rest.split_once(" panicked at ")?;
+        Some(location.strip_suffix(':').unwrap_or(location))
+    })?;
+
+    let mut message = Vec::new();
+    let mut in_backtrace = false;
+    let mut backtrace = Vec::new();
+    let mut function: Option<String> = None;
+    for line in lines {
+        if !in_backtrace {
+            if line == "stack backtrace:" {
+                in_backtrace = true;
+            } else if line.starts_with("note: run with `RUST_BACKTRACE") {
+                break;
+            } else {
+                message.push(line);
+            }
+            continue;
+        }
+        if let Some(name) = is_backtrace_frame(line) {
+            function = Some(name.to_string());
+        } else if let Some(frame_location) = line.trim_start().strip_prefix("at ") {
+            let (Some(name), Some((file, line, column))) =
+                (function.take(), parse_location(frame_location))
+            else {
+                continue;
+            };
+            if let Some(line) = user_lines.map(file, line) {
+                backtrace.push(PanicFrame {
+                    function: name,
+                    file: file.trim_start_matches("./").to_string(),
+                    line,
+                    column,
+                });
+            }
+        } else {
+            break;
+        }
+    }
+
+    let (file, line, column) = match parse_location(location)
+        .and_then(|(file, line, column)| Some((file, user_lines.map(file, line)?, column)))
+    {
+        Some((file, line, column)) => (Some(file.to_string()), Some(line), column),
+        None => match backtrace.first() {
+            Some(frame) => (Some(frame.file.clone()), Some(frame.line), frame.column),
+            None => (None, None, None),
+        },
+    };
+
+    Some(PanicReport {
+        message: message.join("\n"),
+        file,
+        line,
+        column,
+        backtrace,
+    })
+}
+
+// Tells backtrace lines apart from the rest of stderr, one line at a time
+#[derive(Default)]
+struct BacktraceFilter {
+    in_backtrace: bool,
+}
+
+impl BacktraceFilter {
+    fn keep(&mut self, line: &str) -> bool {
+        if line == "stack backtrace:" {
+            self.in_backtrace = true;
+            return false;
+        }
+        if self.in_backtrace {
+            if line.starts_with("note: Some details are omitted") {
+                self.in_backtrace = false;
+                return false;
+            }
+            let trimmed = line.trim_start();
+            if is_backtrace_frame(line).is_some() || trimmed.starts_with("at ") {
+                return false;
+            }
+            self.in_backtrace = false;
+        }
+        true
+    }
+}
+
+// Removes the backtraces the service asked for, leaving the panic messages
+pub fn strip_backtraces(stderr: &str) -> String {
+    let mut filter = BacktraceFilter::default();
+    let kept: Vec<&str> = stderr.lines().filter(|line| filter.keep(line)).collect();
+    let mut stripped = kept.join("\n");
+    if stderr.ends_with('\n') {
+        stripped.push('\n');
+    }
+    stripped
+}
+
+// The same for the stderr lines of an output event stream
+pub fn strip_backtrace_events(events: Vec<OutputEvent>) -> Vec<OutputEvent> {
+    let mut filter = BacktraceFilter::default();
+    events
+        .into_iter()
+        .filter(|event| event.stream != "stderr" || filter.keep(&event.text))
+        .collect()
+}
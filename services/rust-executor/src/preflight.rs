@@ -0,0 +1,203 @@
+use crate::{bwrap, filesystem, reaper, sandbox_user, toolchain_pin, RustExecutor};
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// Generated projects use edition 2021
+const MIN_RUSTC_VERSION: (u32, u32, u32) = (1, 56, 0);
+
+// "1.82.0" out of "cargo 1.82.0 (8f40fc59f 2024-08-21)"
+fn release(version: &str) -> Option<&str> {
+    version.split_whitespace().nth(1)
+}
+
+fn parse_release(release: &str) -> Option<(u32, u32, u32)> {
+    // Pre-release suffixes such as "-nightly" do not matter here
+    let mut parts = release.split('-').next()?.split('.');
+    Some((
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next().unwrap_or("0").parse().ok()?,
+    ))
+}
+
+fn check_toolchain(rustc: Option<&str>, cargo: Option<&str>) -> Result<(), String> {
+    let rustc = rustc.ok_or("rustc not found on PATH")?;
+    let cargo = cargo.ok_or("cargo not found on PATH")?;
+    let rustc_release = release(rustc).unwrap_or_default();
+    let version = parse_release(rustc_release)
+        .ok_or_else(|| format!("Unrecognized rustc version '{}'", rustc))?;
+    if version < MIN_RUSTC_VERSION {
+        let (major, minor, patch) = MIN_RUSTC_VERSION;
+        return Err(format!(
+            "rustc {} is too old, {}.{}.{} or later is required",
+            rustc_release, major, minor, patch
+        ));
+    }
+    // cargo and rustc from different toolchains build with the wrong
+    // standard library or reject flags the other understands
+    let cargo_release = release(cargo).unwrap_or_default();
+    if cargo_release != rustc_release {
+        return Err(format!(
+            "cargo {} does not match rustc {}; both must come from the same toolchain",
+            cargo_release, rustc_release
+        ));
+    }
+    Ok(())
+}
+
+fn mounted_noexec(path: &Path) -> bool {
+    filesystem::stat(path).is_some_and(|stat| stat.f_flag & libc::ST_NOEXEC != 0)
+}
+
+// Every submission is written, built and run under the temp dir
+fn check_temp_dir() -> Result<(), String> {
+    let root = env::temp_dir();
+    let dir = reaper::temp_dir()
+        .map_err(|e| format!("Cannot create directories in {}: {}", root.display(), e))?;
+    fs::write(dir.path().join("preflight"), b"ok")
+        .map_err(|e| format!("Cannot write to {}: {}", root.display(), e))?;
+    if mounted_noexec(dir.path()) {
+        return Err(format!(
+            "{} is mounted noexec, so compiled programs cannot run; set TMPDIR to another volume",
+            root.display()
+        ));
+    }
+    Ok(())
+}
+
+// A cgroup v2 hierarchy whose memory controller is enabled for the
+// executor's cgroup and that the executor can manage (delegated to it)
+fn check_cgroups() -> Result<(), String> {
+    let membership = fs::read_to_string("/proc/self/cgroup")
+        .map_err(|e| format!("Cannot read /proc/self/cgroup: {}", e))?;
+    let path = membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .filter(|_| Path::new("/sys/fs/cgroup/cgroup.controllers").exists())
+        .ok_or("cgroup v2 is not mounted at /sys/fs/cgroup")?;
+    let cgroup = Path::new("/sys/fs/cgroup").join(path.trim_start_matches('/'));
+    let controllers = fs::read_to_string(cgroup.join("cgroup.controllers")).unwrap_or_default();
+    if !controllers.split_whitespace().any(|name| name == "memory") {
+        return Err(format!(
+            "the memory controller is not enabled for cgroup {}",
+            cgroup.display()
+        ));
+    }
+    let subtree_control =
+        CString::new(cgroup.join("cgroup.subtree_control").as_os_str().as_bytes())
+            .map_err(|e| e.to_string())?;
+    // SAFETY: access only reads the path
+    if unsafe { libc::access(subtree_control.as_ptr(), libc::W_OK) } != 0 {
+        return Err(format!(
+            "cgroup {} is not delegated to this user",
+            cgroup.display()
+        ));
+    }
+    Ok(())
+}
+
+fn check_user_namespaces() -> Result<(), String> {
+    let max = fs::read_to_string("/proc/sys/user/max_user_namespaces")
+        .ok()
+        .and_then(|max| max.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    if max == 0 {
+        return Err("user namespaces are disabled (user.max_user_namespaces is 0)".to_string());
+    }
+    // Debian and Ubuntu kernels can restrict them to root
+    let unprivileged = fs::read_to_string("/proc/sys/kernel/unprivileged_userns_clone")
+        .map(|value| value.trim() != "0")
+        .unwrap_or(true);
+    // SAFETY: geteuid cannot fail
+    if !unprivileged && unsafe { libc::geteuid() } != 0 {
+        return Err(
+            "unprivileged user namespaces are disabled (kernel.unprivileged_userns_clone is 0)"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+type SandboxCheck = fn() -> Result<(), String>;
+
+// By the names REQUIRED_SANDBOX_FEATURES lists them under
+const SANDBOX_FEATURES: [(&str, SandboxCheck); 2] = [
+    ("cgroups", check_cgroups),
+    ("userns", check_user_namespaces),
+];
+
+impl RustExecutor {
+    // Checked once at startup, after detect_toolchain, so a misconfigured
+    // host fails to boot instead of failing every execution. The toolchain
+    // and temp dir are always required. The sandbox features the host must
+    // provide are listed in REQUIRED_SANDBOX_FEATURES ("cgroups",
+    // "userns", comma-separated); the others are only warned about.
+    // Returns every failed check.
+    pub fn preflight(&self) -> Result<(), Vec<String>> {
+        let mut failures = Vec::new();
+        if let Err(e) = check_toolchain(
+            self.toolchain.rustc.as_deref(),
+            self.toolchain.cargo.as_deref(),
+        ) {
+            failures.push(e);
+        }
+        if let (Some(channel), None) = (toolchain_pin::pinned(), &self.toolchain.project_rustc) {
+            failures.push(format!(
+                "PINNED_RUST_TOOLCHAIN {} is not installed (rustup toolchain install {})",
+                channel, channel
+            ));
+        }
+        if let Err(e) = check_temp_dir() {
+            failures.push(e);
+        }
+        match sandbox_user::configured() {
+            Ok(Some(_)) => {
+                let missing = sandbox_user::missing_capabilities();
+                if !missing.is_empty() {
+                    failures.push(format!(
+                        "SANDBOX_USER is set but the service lacks {}",
+                        missing.join(", ")
+                    ));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => failures.push(e),
+        }
+        if self.config().sandbox == bwrap::SANDBOX_BWRAP {
+            if let Err(e) = bwrap::check() {
+                failures.push(e);
+            }
+        }
+
+        let required = env::var("REQUIRED_SANDBOX_FEATURES").unwrap_or_default();
+        let required: Vec<&str> = required
+            .split(',')
+            .map(str::trim)
+            .filter(|feature| !feature.is_empty())
+            .collect();
+        for feature in &required {
+            if !SANDBOX_FEATURES.iter().any(|(name, _)| name == feature) {
+                failures.push(format!(
+                    "Unknown sandbox feature '{}' in REQUIRED_SANDBOX_FEATURES, expected cgroups or userns",
+                    feature
+                ));
+            }
+        }
+        for (name, check) in SANDBOX_FEATURES {
+            match check() {
+                Ok(()) => {}
+                Err(e) if required.contains(&name) => failures.push(e),
+                Err(e) => eprintln!("Sandbox feature {} unavailable: {}", name, e),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
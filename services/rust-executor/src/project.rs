@@ -0,0 +1,106 @@
+use crate::compile_timeout_message;
+use crate::status::{ExecutionStatus, StatusError};
+use crate::toolchain_pin;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::timeout;
+
+// Writes a minimal Cargo project, pinned to PINNED_RUST_TOOLCHAIN. `sources` are paths relative to the
+// project root (e.g. "src/main.rs") with their contents.
+pub fn write_project(
+    project_path: &Path,
+    cargo_toml: &str,
+    sources: &[(&str, &str)],
+) -> Result<(), String> {
+    fs::write(project_path.join("Cargo.toml"), cargo_toml)
+        .map_err(|e| format!("Failed to create Cargo.toml: {}", e))?;
+    toolchain_pin::write(project_path)?;
+    write_sources(project_path, sources)
+}
+
+// Points cargo at vendored crates instead of crates.io and keeps it
+// offline, as if every command in the project were run with --offline
+pub fn write_offline_config(project_path: &Path, vendor_dir: &Path) -> Result<(), String> {
+    let cargo_dir = project_path.join(".cargo");
+    fs::create_dir_all(&cargo_dir)
+        .map_err(|e| format!("Failed to create {}: {}", cargo_dir.display(), e))?;
+    let config = format!(
+        "[source.crates-io]\nreplace-with = \"vendored\"\n\n\
+         [source.vendored]\ndirectory = \"{}\"\n\n\
+         [net]\noffline = true\n",
+        vendor_dir.display()
+    );
+    fs::write(cargo_dir.join("config.toml"), config)
+        .map_err(|e| format!("Failed to write cargo config: {}", e))
+}
+
+// Also used for data files, which need not be UTF-8
+pub fn write_sources<C: AsRef<[u8]>>(
+    project_path: &Path,
+    sources: &[(&str, C)],
+) -> Result<(), String> {
+    for (relative_path, content) in sources {
+        let path = project_path.join(relative_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", relative_path, e))?;
+    }
+    Ok(())
+}
+
+// Runs a cargo build-like command (`build`, `test --no-run`, ...) with JSON
+// messages and returns the executable it produced
+pub async fn build_executable(
+    project_path: &Path,
+    cargo_args: &[&str],
+    rustflags: Option<&str>,
+    compile_timeout: Duration,
+) -> Result<PathBuf, StatusError> {
+    let mut cmd = tokio::process::Command::new("cargo");
+    cmd.args(cargo_args)
+        .arg("--message-format=json")
+        .current_dir(project_path)
+        .env("CARGO_TARGET_DIR", project_path.join("target"))
+        .kill_on_drop(true);
+    if let Some(flags) = rustflags {
+        cmd.env("RUSTFLAGS", flags);
+    }
+
+    let output = match timeout(compile_timeout, cmd.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Err(StatusError::internal(format!(
+                "Failed to execute cargo: {}",
+                e
+            )))
+        }
+        Err(_) => {
+            return Err(StatusError::new(
+                ExecutionStatus::Timeout,
+                compile_timeout_message(compile_timeout.as_secs()),
+            ))
+        }
+    };
+
+    if !output.status.success() {
+        return Err(StatusError::new(
+            ExecutionStatus::CompileError,
+            format!(
+                "Compilation error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|msg| msg["reason"] == "compiler-artifact")
+        .filter_map(|msg| msg["executable"].as_str().map(PathBuf::from))
+        .next_back()
+        .ok_or_else(|| StatusError::internal("Compiled executable not found in cargo output"))
+}
@@ -0,0 +1,319 @@
+use crate::project::{build_executable, write_offline_config, write_project};
+use crate::readiness::KILLED_MESSAGE;
+use crate::reaper::temp_dir;
+use crate::status::StatusError;
+use crate::wrapping::{self, WrapMode};
+use crate::{ChildSetup, ExecutionStatus, RunLimits, RustExecutor};
+use proc_macro2::{TokenStream, TokenTree};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+const DEFAULT_HARNESS_VENDOR_DIR: &str = "/opt/harness-vendor";
+
+// proptest is a dev-dependency, so only the `#[cfg(test)]` harness module
+// can use it
+const PROPERTY_CARGO_TOML: &str = r#"[package]
+name = "rust_properties"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+
+[dev-dependencies]
+proptest = "1"
+"#;
+
+#[derive(Deserialize, ToSchema)]
+pub struct PropertyTestRequest {
+    code: String,
+    // Body of the harness module: `proptest! { ... }` blocks and helper
+    // strategies. Student items are in scope through `use super::*` and
+    // `proptest::prelude::*` is imported.
+    properties: String,
+    // Cases generated per property (PROPTEST_CASES); proptest's default is
+    // 256
+    cases: Option<u32>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PropertyResult {
+    name: String,
+    passed: bool,
+    // Why the minimal failing case failed
+    message: Option<String>,
+    // The shrunken input, as printed by proptest, e.g. "v = [1, 0]"
+    counterexample: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PropertyTestResponse {
+    properties: Vec<PropertyResult>,
+    passed: usize,
+    failed: usize,
+    // Raw test-runner output
+    output: String,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    // The test run's status; runtime_error when a property failed
+    status: ExecutionStatus,
+}
+
+impl PropertyTestResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            properties: vec![],
+            passed: 0,
+            failed: 0,
+            output: String::new(),
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+pub fn mentions_ident(tokens: TokenStream, name: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == name,
+        TokenTree::Group(group) => mentions_ident(group.stream(), name),
+        _ => false,
+    })
+}
+
+// Results of the libtest run. A failing property's captured output holds
+// proptest's panic message:
+//   Test failed: <reason>.
+//   minimal failing input: <input>
+fn parse_test_output(output: &str) -> Vec<PropertyResult> {
+    let mut results: Vec<PropertyResult> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("test "))
+        .filter_map(|line| line.rsplit_once(" ... "))
+        .map(|(name, result)| PropertyResult {
+            name: name.trim_start_matches("properties::").to_string(),
+            passed: result == "ok",
+            message: None,
+            counterexample: None,
+        })
+        .collect();
+
+    let mut current: Option<usize> = None;
+    // Inputs are pretty-printed with {:#?}, so they may span several lines,
+    // up to proptest's tab-indented statistics
+    let mut in_counterexample = false;
+    for line in output.lines() {
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            let name = name.trim_start_matches("properties::");
+            current = results.iter().position(|result| result.name == name);
+            in_counterexample = false;
+            continue;
+        }
+        let Some(result) = current.map(|index| &mut results[index]) else {
+            continue;
+        };
+        if in_counterexample {
+            match &mut result.counterexample {
+                Some(input) if !line.is_empty() && !line.starts_with('\t') => {
+                    input.push('\n');
+                    input.push_str(line);
+                }
+                _ => in_counterexample = false,
+            }
+        } else if let Some(input) = line.trim().strip_prefix("minimal failing input: ") {
+            result.counterexample = Some(input.to_string());
+            in_counterexample = true;
+        } else if let Some(reason) = line.trim().strip_prefix("Test failed: ") {
+            result.message = Some(reason.trim_end_matches('.').to_string());
+        } else if result.message.is_none() && line.starts_with("thread '") {
+            // A panic outside proptest!, e.g. in a plain #[test]
+            result.message = Some(line.to_string());
+        }
+    }
+    results
+}
+
+impl RustExecutor {
+    pub async fn run_property_tests(&self, req: PropertyTestRequest) -> PropertyTestResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("properties", None) {
+            Ok(active) => active,
+            Err(e) => return PropertyTestResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        let code_size_kb = (req.code.len() + req.properties.len()) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return PropertyTestResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) =
+            self.check_unstable_features([req.code.as_str(), req.properties.as_str()].into_iter())
+        {
+            return PropertyTestResponse::error(e, start_time);
+        }
+        // The student's own code only gets std, as in /execute
+        let tokens = match req.code.parse::<TokenStream>() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                return PropertyTestResponse::error(
+                    format!("Failed to parse code: {}", e),
+                    start_time,
+                );
+            }
+        };
+        if mentions_ident(tokens, "proptest") {
+            return PropertyTestResponse::error(
+                "proptest is only available to the property harness".to_string(),
+                start_time,
+            );
+        }
+
+        let vendor_dir = PathBuf::from(
+            env::var("HARNESS_VENDOR_DIR").unwrap_or_else(|_| DEFAULT_HARNESS_VENDOR_DIR.into()),
+        );
+        if !vendor_dir.join("proptest").is_dir() {
+            return PropertyTestResponse::error(
+                StatusError::internal(format!(
+                    "Property testing is unavailable: proptest is not vendored in {}",
+                    vendor_dir.display()
+                )),
+                start_time,
+            );
+        }
+
+        let temp_dir = match temp_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                return PropertyTestResponse::error(
+                    StatusError::internal(format!("Failed to create temp directory: {}", e)),
+                    start_time,
+                );
+            }
+        };
+        let project_path = temp_dir.path();
+
+        let forbid_unsafe =
+            match self.forbids_unsafe([req.code.as_str(), req.properties.as_str()].into_iter()) {
+                Ok(forbid_unsafe) => forbid_unsafe,
+                Err(e) => return PropertyTestResponse::error(e, start_time),
+            };
+        let mut main_rs = wrapping::wrap(&req.code, WrapMode::Auto);
+        if forbid_unsafe {
+            main_rs = wrapping::forbid_unsafe(&main_rs);
+        }
+        main_rs.push_str(&format!(
+            "\n\n#[cfg(test)]\nmod properties {{\n    use super::*;\n    use proptest::prelude::*;\n\n{}\n}}\n",
+            req.properties
+        ));
+        if let Err(e) = write_project(
+            project_path,
+            PROPERTY_CARGO_TOML,
+            &[("src/main.rs", &main_rs)],
+        )
+        .and_then(|()| write_offline_config(project_path, &vendor_dir))
+        {
+            return PropertyTestResponse::error(StatusError::internal(e), start_time);
+        }
+
+        active.set_phase("compiling");
+        let built = active
+            .until_killed(build_executable(
+                project_path,
+                &["test", "--no-run", "--bin", "main"],
+                None,
+                Duration::from_secs(config.compile_timeout),
+            ))
+            .await;
+        let test_executable = match built {
+            Some(Ok(path)) => path,
+            Some(Err(e)) => return PropertyTestResponse::error(e, start_time),
+            None => return PropertyTestResponse::killed(start_time),
+        };
+
+        // Regression files would be written relative to the service's
+        // working directory
+        let mut envs = vec![(
+            "PROPTEST_DISABLE_FAILURE_PERSISTENCE".to_string(),
+            "true".to_string(),
+        )];
+        if let Some(cases) = req.cases {
+            envs.push(("PROPTEST_CASES".to_string(), cases.to_string()));
+        }
+        let outcome = self
+            .run_executable(
+                &test_executable,
+                ChildSetup::default(),
+                &[],
+                None,
+                RunLimits::wall_clock(execution_timeout),
+                &envs,
+                &active,
+            )
+            .await;
+        if active.is_killed() {
+            return PropertyTestResponse::killed(start_time);
+        }
+
+        let output = outcome.stdout_text();
+        let properties = parse_test_output(&output);
+        let passed = properties.iter().filter(|result| result.passed).count();
+        let failed = properties.len() - passed;
+        // A harness that ran without a single property was not what the
+        // request meant
+        let status = if properties.is_empty() && outcome.status.is_success() {
+            ExecutionStatus::InvalidRequest
+        } else {
+            outcome.status
+        };
+        PropertyTestResponse {
+            properties,
+            passed,
+            failed,
+            output,
+            error: outcome.stderr,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/property-tests",
+    request_body = PropertyTestRequest,
+    responses((status = 200, body = PropertyTestResponse))
+)]
+pub async fn property_tests(
+    req: PropertyTestRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = executor.run_property_tests(req).await;
+    Ok(warp::reply::json(&result))
+}
@@ -0,0 +1,897 @@
+use crate::admin::tokens_match;
+use crate::job_spool::{JobSpool, SpooledJob};
+use crate::readiness::StdinFeed;
+use crate::telemetry;
+use crate::{CodeExecutionRequest, CodeExecutionResponse, ExecutionStatus, RustExecutor};
+use opentelemetry::trace::FutureExt;
+use opentelemetry::KeyValue;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use utoipa::{IntoParams, ToSchema};
+
+// Normal priority jobs keep the original key so jobs queued before
+// priorities existed are still picked up
+const JOB_QUEUE_KEY: &str = "scriptsmith:rust:jobs";
+const HIGH_PRIORITY_QUEUE_KEY: &str = "scriptsmith:rust:jobs:high";
+const LOW_PRIORITY_QUEUE_KEY: &str = "scriptsmith:rust:jobs:low";
+const JOB_KEY_PREFIX: &str = "scriptsmith:rust:job:";
+// Sorted set of job ids scored by queuedAt, for GET /jobs. Jobs queued
+// before it existed are not listed.
+const JOB_INDEX_KEY: &str = "scriptsmith:rust:jobs:index";
+// Jobs and their results are kept this long for GET /jobs/{id}, counted
+// from when they were queued and again from when they finished; set with
+// JOB_RESULT_TTL_SECONDS
+const DEFAULT_JOB_TTL_SECONDS: i64 = 24 * 60 * 60;
+// How long a worker blocks on an empty queue before polling again
+const WORKER_POLL_TIMEOUT_SECONDS: f64 = 5.0;
+const WORKER_RETRY_DELAY: Duration = Duration::from_secs(2);
+// How long the worker waits on a job's stdin list before checking that the
+// program is still running
+const STDIN_POLL_TIMEOUT_SECONDS: f64 = 1.0;
+// An empty entry in a job's stdin list marks EOF
+const STDIN_EOF: &[u8] = b"";
+// Runs a job may get cut short in before it is marked interrupted, so a
+// submission that takes its worker down cannot do so forever
+const MAX_JOB_ATTEMPTS: i64 = 2;
+const JOB_STATES: [&str; 4] = ["queued", "running", "completed", "interrupted"];
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+// Job hashes fetched per round trip while filtering the index
+const LIST_BATCH_SIZE: isize = 200;
+
+// Jobs shared through Redis. HTTP instances enqueue submissions with
+// POST /jobs and any number of worker processes (EXECUTOR_MODE=worker) pop
+// and execute them, writing the result back to the job's hash. Nothing about
+// a job is kept in the process, so any replica can answer GET /jobs/{id}
+// and replicas can come and go behind a load balancer without sticky
+// sessions. Jobs not yet popped survive a Redis restart with Redis
+// persistence (AOF) enabled; jobs a worker was running when it stopped are
+// recovered from its job spool when it starts again.
+#[derive(Clone)]
+pub struct JobQueue {
+    client: redis::Client,
+    connection: ConnectionManager,
+    ttl_seconds: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JobStatusResponse {
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+    // "queued", "running", "completed" or "interrupted" (its worker stopped
+    // mid-run too many times; result holds the error)
+    pub status: String,
+    pub result: Option<CodeExecutionResponse>,
+    // Instance ids (see /info) of the replica that accepted the job and the
+    // one that ran it; null for jobs queued before they were recorded, and
+    // the worker until the job starts
+    #[serde(rename = "submittedBy")]
+    pub submitted_by: Option<String>,
+    pub worker: Option<String>,
+    // X-Requester-Id of the submission, if it had one
+    pub submitter: Option<String>,
+    // Unix milliseconds
+    #[serde(rename = "queuedAt")]
+    pub queued_at: Option<u64>,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<u64>,
+    #[serde(rename = "completedAt")]
+    pub completed_at: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct JobListQuery {
+    // "queued", "running", "completed" or "interrupted"
+    pub status: Option<String>,
+    // X-Requester-Id the jobs were submitted with
+    pub submitter: Option<String>,
+    // 1-based, default 1
+    pub page: Option<usize>,
+    // Default 50, at most 200
+    #[serde(rename = "pageSize")]
+    #[param(rename = "pageSize")]
+    pub page_size: Option<usize>,
+}
+
+impl JobListQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.status {
+            Some(status) if !JOB_STATES.contains(&status.as_str()) => Err(format!(
+                "Unsupported status '{}', expected one of {}",
+                status,
+                JOB_STATES.join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, job: &JobStatusResponse) -> bool {
+        self.status
+            .as_ref()
+            .is_none_or(|status| *status == job.status)
+            && self
+                .submitter
+                .as_ref()
+                .is_none_or(|submitter| job.submitter.as_ref() == Some(submitter))
+    }
+}
+
+// Response of GET /jobs
+#[derive(Serialize, ToSchema)]
+pub struct JobListResponse {
+    // Newest first
+    pub jobs: Vec<JobStatusResponse>,
+    pub page: usize,
+    #[serde(rename = "pageSize")]
+    pub page_size: usize,
+    // Whether a later page has more matching jobs
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+// Body of POST /jobs/{id}/stdin
+#[derive(Deserialize, ToSchema)]
+pub struct JobStdinRequest {
+    // Text written to the program's stdin
+    #[serde(default)]
+    pub data: String,
+    // Closes stdin after `data`; nothing more can be sent afterwards
+    #[serde(default)]
+    pub eof: bool,
+}
+
+struct PoppedJob {
+    job_id: String,
+    // The queue it came from
+    queue_key: String,
+    request: String,
+    // See telemetry::current_context
+    trace_context: Option<String>,
+    queued_at: Option<u64>,
+}
+
+pub enum StdinError {
+    NotFound,
+    // Neither the job's stdin token nor the admin token was given
+    Unauthorized,
+    // The job finished, already got EOF, or closes stdin on its own
+    Closed(String),
+    Unavailable(String),
+}
+
+#[derive(Clone, Copy)]
+pub enum JobPriority {
+    High,
+    Normal,
+    Low,
+}
+
+pub enum PriorityError {
+    Invalid(String),
+    NotAllowed(String),
+}
+
+impl PriorityError {
+    pub fn message(&self) -> &str {
+        match self {
+            PriorityError::Invalid(message) | PriorityError::NotAllowed(message) => message,
+        }
+    }
+}
+
+impl JobPriority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobPriority::High => "high",
+            JobPriority::Normal => "normal",
+            JobPriority::Low => "low",
+        }
+    }
+
+    fn queue_key(self) -> &'static str {
+        match self {
+            JobPriority::High => HIGH_PRIORITY_QUEUE_KEY,
+            JobPriority::Normal => JOB_QUEUE_KEY,
+            JobPriority::Low => LOW_PRIORITY_QUEUE_KEY,
+        }
+    }
+}
+
+// BRPOP takes from the first non-empty list, so this order is the
+// scheduling order
+const QUEUE_KEYS_BY_PRIORITY: [&str; 3] = [
+    HIGH_PRIORITY_QUEUE_KEY,
+    JOB_QUEUE_KEY,
+    LOW_PRIORITY_QUEUE_KEY,
+];
+
+fn job_key(job_id: &str) -> String {
+    format!("{}{}", JOB_KEY_PREFIX, job_id)
+}
+
+// Input sent to a running job with closeStdin: false, drained by its worker
+fn stdin_key(job_id: &str) -> String {
+    format!("{}{}:stdin", JOB_KEY_PREFIX, job_id)
+}
+
+fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// Only the hash is stored, so reading the job hash does not give the token
+fn stdin_token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn unix_millis() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis())
+        .to_string()
+}
+
+fn job_from_fields(job_id: &str, fields: &HashMap<String, String>) -> Option<JobStatusResponse> {
+    let time = |field: &str| fields.get(field).and_then(|time| time.parse().ok());
+    Some(JobStatusResponse {
+        job_id: job_id.to_string(),
+        status: fields.get("status")?.clone(),
+        result: fields
+            .get("result")
+            .and_then(|result| serde_json::from_str(result).ok()),
+        submitted_by: fields.get("submittedBy").cloned(),
+        worker: fields.get("worker").cloned(),
+        submitter: fields.get("submitter").cloned(),
+        queued_at: time("queuedAt"),
+        started_at: time("startedAt"),
+        completed_at: time("completedAt"),
+    })
+}
+
+// Names this replica in job records and /info: INSTANCE_ID, else the host
+// name, which is the pod name under Kubernetes
+pub fn instance_id() -> &'static str {
+    static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    INSTANCE_ID.get_or_init(|| {
+        env::var("INSTANCE_ID")
+            .or_else(|_| env::var("HOSTNAME"))
+            .or_else(|_| fs::read_to_string("/etc/hostname"))
+            .map(|id| id.trim().to_string())
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| "unknown".to_string())
+    })
+}
+
+impl JobQueue {
+    pub async fn connect(redis_url: &str) -> Result<Self, String> {
+        let client =
+            redis::Client::open(redis_url).map_err(|e| format!("Invalid REDIS_URL: {}", e))?;
+        let connection = ConnectionManager::new(client.clone())
+            .await
+            .map_err(|e| format!("Failed to connect to Redis: {}", e))?;
+        let ttl_seconds = env::var("JOB_RESULT_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .filter(|&ttl| ttl > 0)
+            .unwrap_or(DEFAULT_JOB_TTL_SECONDS);
+        Ok(Self {
+            client,
+            connection,
+            ttl_seconds,
+        })
+    }
+
+    // A blocking pop stalls every other command on a multiplexed connection,
+    // so each worker loop gets a connection of its own
+    async fn dedicated(&self) -> Result<Self, String> {
+        let connection = ConnectionManager::new(self.client.clone())
+            .await
+            .map_err(|e| format!("Failed to connect to Redis: {}", e))?;
+        Ok(Self {
+            client: self.client.clone(),
+            connection,
+            ttl_seconds: self.ttl_seconds,
+        })
+    }
+
+    // Returns the job id, and for a job with closeStdin: false the token that
+    // POST /jobs/{id}/stdin requires
+    pub async fn enqueue(
+        &self,
+        req: &CodeExecutionRequest,
+        priority: JobPriority,
+        submitter: Option<&str>,
+    ) -> Result<(String, Option<String>), String> {
+        let request =
+            serde_json::to_string(req).map_err(|e| format!("Failed to encode job: {}", e))?;
+        let job_id = random_token(24);
+        let key = job_key(&job_id);
+        let queued_at = unix_millis();
+        let mut fields = vec![
+            ("status", "queued"),
+            ("request", request.as_str()),
+            ("submittedBy", instance_id()),
+            ("queuedAt", queued_at.as_str()),
+        ];
+        if let Some(submitter) = submitter {
+            fields.push(("submitter", submitter));
+        }
+        let stdin_token = (req.close_stdin == Some(false)).then(|| random_token(32));
+        let token_hash = stdin_token.as_deref().map(stdin_token_hash);
+        if let Some(token_hash) = &token_hash {
+            fields.push(("stdinTokenHash", token_hash));
+        }
+        // The worker continues the submitter's trace
+        let trace_context = telemetry::current_context();
+        if let Some(trace_context) = &trace_context {
+            fields.push(("traceContext", trace_context));
+        }
+
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .atomic()
+            .hset_multiple(&key, &fields)
+            .ignore()
+            .expire(&key, self.ttl_seconds)
+            .ignore()
+            .zadd(JOB_INDEX_KEY, &job_id, &queued_at)
+            .ignore()
+            .lpush(priority.queue_key(), &job_id)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(|e| format!("Failed to enqueue job: {}", e))?;
+        Ok((job_id, stdin_token))
+    }
+
+    // Jobs waiting to be popped, across priorities
+    pub async fn depth(&self) -> Result<u64, String> {
+        let mut pipe = redis::pipe();
+        for key in QUEUE_KEYS_BY_PRIORITY {
+            pipe.llen(key);
+        }
+        let mut connection = self.connection.clone();
+        let lengths: Vec<u64> = pipe
+            .query_async(&mut connection)
+            .await
+            .map_err(|e| format!("Failed to read queue depth: {}", e))?;
+        Ok(lengths.iter().sum())
+    }
+
+    // Drops index entries old enough that their jobs have expired: a job's
+    // hash outlives its queuing by at most its wait in the queue (under the
+    // TTL, or it expires unrun), its run and the TTL again. Returns how many
+    // were removed.
+    pub async fn prune_index(&self) -> Result<u64, String> {
+        let cutoff = unix_millis().parse::<i64>().unwrap_or_default() - 2 * self.ttl_seconds * 1000;
+        let mut connection = self.connection.clone();
+        connection
+            .zrembyscore(JOB_INDEX_KEY, "-inf", cutoff)
+            .await
+            .map_err(|e| format!("Failed to prune job index: {}", e))
+    }
+
+    pub async fn status(&self, job_id: &str) -> Result<Option<JobStatusResponse>, String> {
+        let mut connection = self.connection.clone();
+        let fields: HashMap<String, String> = connection
+            .hgetall(job_key(job_id))
+            .await
+            .map_err(|e| format!("Failed to read job: {}", e))?;
+        Ok(job_from_fields(job_id, &fields))
+    }
+
+    // Walks the index newest first, fetching job hashes in batches and
+    // filtering them here, until the page is full and one more match shows
+    // there is a next page. Ids of expired jobs are dropped on the way.
+    pub async fn list(&self, query: &JobListQuery) -> Result<JobListResponse, String> {
+        let page = query.page.unwrap_or(1).max(1);
+        let page_size = query
+            .page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let mut skip = (page - 1).saturating_mul(page_size);
+        let mut jobs = Vec::new();
+        let mut has_more = false;
+
+        let mut connection = self.connection.clone();
+        let mut start = 0;
+        'index: loop {
+            let job_ids: Vec<String> = connection
+                .zrevrange(JOB_INDEX_KEY, start, start + LIST_BATCH_SIZE - 1)
+                .await
+                .map_err(|e| format!("Failed to list jobs: {}", e))?;
+            if job_ids.is_empty() {
+                break;
+            }
+            start += job_ids.len() as isize;
+            let mut pipe = redis::pipe();
+            for job_id in &job_ids {
+                pipe.hgetall(job_key(job_id));
+            }
+            let records: Vec<HashMap<String, String>> = pipe
+                .query_async(&mut connection)
+                .await
+                .map_err(|e| format!("Failed to read jobs: {}", e))?;
+
+            let mut expired = Vec::new();
+            for (job_id, fields) in job_ids.iter().zip(&records) {
+                let Some(job) = job_from_fields(job_id, fields) else {
+                    expired.push(job_id);
+                    continue;
+                };
+                if !query.matches(&job) {
+                    continue;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                } else if jobs.len() < page_size {
+                    jobs.push(job);
+                } else {
+                    has_more = true;
+                    break 'index;
+                }
+            }
+            if !expired.is_empty() {
+                // Later entries move up by as many ranks
+                start -= expired.len() as isize;
+                connection
+                    .zrem::<_, _, ()>(JOB_INDEX_KEY, expired)
+                    .await
+                    .map_err(|e| format!("Failed to list jobs: {}", e))?;
+            }
+        }
+        Ok(JobListResponse {
+            jobs,
+            page,
+            page_size,
+            has_more,
+        })
+    }
+
+    // Deletes every job submitted under this requester id, with its queued
+    // input and its index and queue entries, for DELETE /users/{id}/data.
+    // Returns how many jobs were deleted.
+    pub async fn delete_submitted_by(&self, submitter: &str) -> Result<u64, String> {
+        let mut connection = self.connection.clone();
+        let mut job_ids = Vec::new();
+        let mut start = 0;
+        loop {
+            let batch: Vec<String> = connection
+                .zrange(JOB_INDEX_KEY, start, start + LIST_BATCH_SIZE - 1)
+                .await
+                .map_err(|e| format!("Failed to list jobs: {}", e))?;
+            if batch.is_empty() {
+                break;
+            }
+            start += batch.len() as isize;
+            let mut pipe = redis::pipe();
+            for job_id in &batch {
+                pipe.hget(job_key(job_id), "submitter");
+            }
+            let submitters: Vec<Option<String>> = pipe
+                .query_async(&mut connection)
+                .await
+                .map_err(|e| format!("Failed to read jobs: {}", e))?;
+            job_ids.extend(
+                batch
+                    .into_iter()
+                    .zip(submitters)
+                    .filter(|(_, job_submitter)| job_submitter.as_deref() == Some(submitter))
+                    .map(|(job_id, _)| job_id),
+            );
+        }
+        if job_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for job_id in &job_ids {
+            pipe.del(job_key(job_id)).ignore();
+            pipe.del(stdin_key(job_id)).ignore();
+            for queue_key in QUEUE_KEYS_BY_PRIORITY {
+                pipe.lrem(queue_key, 0, job_id).ignore();
+            }
+        }
+        pipe.zrem(JOB_INDEX_KEY, &job_ids).ignore();
+        pipe.query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(|e| format!("Failed to delete jobs: {}", e))?;
+        Ok(job_ids.len() as u64)
+    }
+
+    // Queues input for a job submitted with closeStdin: false. It may be sent
+    // while the job is still queued; the worker writes it once the program
+    // starts. `token` is the stdin token enqueue returned, None when the
+    // caller holds the admin token.
+    pub async fn write_stdin(
+        &self,
+        job_id: &str,
+        token: Option<&str>,
+        input: JobStdinRequest,
+    ) -> Result<(), StdinError> {
+        let key = job_key(job_id);
+        let mut connection = self.connection.clone();
+        let (status, request, stdin_closed, token_hash): (
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+        ) = connection
+            .hget(
+                &key,
+                &["status", "request", "stdinClosed", "stdinTokenHash"],
+            )
+            .await
+            .map_err(|e| StdinError::Unavailable(format!("Failed to read job: {}", e)))?;
+        let (Some(status), Some(request)) = (status, request) else {
+            return Err(StdinError::NotFound);
+        };
+        if let Some(token) = token {
+            let authorized = token_hash
+                .is_some_and(|expected| tokens_match(&expected, &stdin_token_hash(token)));
+            if !authorized {
+                return Err(StdinError::Unauthorized);
+            }
+        }
+        if status == "completed" || status == "interrupted" {
+            return Err(StdinError::Closed("The job has finished".to_string()));
+        }
+        if stdin_closed.is_some() {
+            return Err(StdinError::Closed("EOF was already sent".to_string()));
+        }
+        let keeps_stdin_open = serde_json::from_str::<CodeExecutionRequest>(&request)
+            .is_ok_and(|req| req.close_stdin == Some(false));
+        if !keeps_stdin_open {
+            return Err(StdinError::Closed(
+                "The job was not submitted with closeStdin: false".to_string(),
+            ));
+        }
+
+        let stdin = stdin_key(job_id);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if !input.data.is_empty() {
+            pipe.rpush(&stdin, input.data.as_bytes()).ignore();
+        }
+        if input.eof {
+            pipe.rpush(&stdin, STDIN_EOF)
+                .ignore()
+                .hset(&key, "stdinClosed", 1)
+                .ignore();
+        }
+        pipe.expire(&stdin, self.ttl_seconds)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(|e| StdinError::Unavailable(format!("Failed to write stdin: {}", e)))
+    }
+
+    // Moves a job's queued input into its running program until EOF, or
+    // until the program is gone and the feed is dropped
+    async fn forward_stdin(self, job_id: String, feed: mpsc::Sender<Vec<u8>>) {
+        let queue = match self.dedicated().await {
+            Ok(queue) => queue,
+            Err(e) => {
+                eprintln!("Failed to forward stdin of job {}: {}", job_id, e);
+                return;
+            }
+        };
+        let key = stdin_key(&job_id);
+        let mut connection = queue.connection;
+        while !feed.is_closed() {
+            let popped: Result<Option<(String, Vec<u8>)>, _> =
+                connection.blpop(&key, STDIN_POLL_TIMEOUT_SECONDS).await;
+            match popped {
+                Ok(Some((_, chunk))) if chunk == STDIN_EOF => return,
+                Ok(Some((_, chunk))) => {
+                    if feed.send(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Failed to read stdin of job {}: {}", job_id, e);
+                    tokio::time::sleep(WORKER_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    // Pops the next job, waiting up to the poll timeout
+    async fn next_job(&self) -> Result<Option<PoppedJob>, redis::RedisError> {
+        let mut connection = self.connection.clone();
+        let popped: Option<(String, String)> = connection
+            .brpop(&QUEUE_KEYS_BY_PRIORITY, WORKER_POLL_TIMEOUT_SECONDS)
+            .await?;
+        let Some((queue_key, job_id)) = popped else {
+            return Ok(None);
+        };
+        let key = job_key(&job_id);
+        let (request, trace_context, queued_at): (Option<String>, Option<String>, Option<u64>) =
+            connection
+                .hget(&key, &["request", "traceContext", "queuedAt"])
+                .await?;
+        match request {
+            Some(request) => {
+                connection
+                    .hset_multiple::<_, _, _, ()>(
+                        &key,
+                        &[
+                            ("status", "running"),
+                            ("worker", instance_id()),
+                            ("startedAt", &unix_millis()),
+                        ],
+                    )
+                    .await?;
+                Ok(Some(PoppedJob {
+                    job_id,
+                    queue_key,
+                    request,
+                    trace_context,
+                    queued_at,
+                }))
+            }
+            // Expired before a worker got to it
+            None => Ok(None),
+        }
+    }
+
+    async fn complete_job(
+        &self,
+        job_id: &str,
+        result: &CodeExecutionResponse,
+    ) -> Result<(), redis::RedisError> {
+        let result = serde_json::to_string(result).unwrap_or_default();
+        let key = job_key(job_id);
+        let mut connection = self.connection.clone();
+        // Erased by DELETE /users/{id}/data while it ran; writing the result
+        // would store it again
+        if !connection.exists::<_, bool>(&key).await? {
+            return Ok(());
+        }
+        redis::pipe()
+            .atomic()
+            .hset_multiple(
+                &key,
+                &[
+                    ("status", "completed"),
+                    ("result", &result),
+                    ("completedAt", &unix_millis()),
+                ],
+            )
+            .ignore()
+            .expire(&key, self.ttl_seconds)
+            .ignore()
+            .del(stdin_key(job_id))
+            .ignore()
+            .query_async(&mut connection)
+            .await
+    }
+
+    // Puts a job this instance was running when it stopped back at the
+    // front of its queue, or gives up on it after MAX_JOB_ATTEMPTS runs.
+    // Jobs that finished or expired in the meantime are left alone.
+    async fn recover_job(&self, job: &SpooledJob) -> Result<(), redis::RedisError> {
+        let key = job_key(&job.job_id);
+        let mut connection = self.connection.clone();
+        let status: Option<String> = connection.hget(&key, "status").await?;
+        if status.as_deref().is_none_or(|status| status == "completed") {
+            return Ok(());
+        }
+        let attempts: i64 = connection.hincr(&key, "attempts", 1).await?;
+        if attempts <= MAX_JOB_ATTEMPTS {
+            println!("Requeueing interrupted job {}", job.job_id);
+            return redis::pipe()
+                .atomic()
+                .hset(&key, "status", "queued")
+                .ignore()
+                .hdel(&key, &["worker", "startedAt"])
+                .ignore()
+                // BRPOP takes from the right, so this job runs next
+                .rpush(&job.queue_key, &job.job_id)
+                .ignore()
+                .query_async(&mut connection)
+                .await;
+        }
+        println!(
+            "Giving up on job {} after {} attempts",
+            job.job_id, attempts
+        );
+        let result = CodeExecutionResponse::error(
+            ExecutionStatus::InternalError,
+            "The worker running this job stopped before it finished".to_string(),
+            0.0,
+        );
+        let result = serde_json::to_string(&result).unwrap_or_default();
+        redis::pipe()
+            .atomic()
+            .hset_multiple(
+                &key,
+                &[
+                    ("status", "interrupted"),
+                    ("result", &result),
+                    ("completedAt", &unix_millis()),
+                ],
+            )
+            .ignore()
+            .expire(&key, self.ttl_seconds)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+    }
+}
+
+impl RustExecutor {
+    // Anyone may queue at normal or low priority; high priority is reserved
+    // for the roles in high_priority_roles, so batch submitters cannot
+    // crowd out interactive runs. `role` is the authenticated one (see
+    // authenticated_role): without it "high" falls back to normal.
+    pub fn job_priority(
+        &self,
+        priority: Option<&str>,
+        role: Option<&str>,
+    ) -> Result<JobPriority, PriorityError> {
+        match priority.unwrap_or("normal") {
+            "normal" => Ok(JobPriority::Normal),
+            "low" => Ok(JobPriority::Low),
+            "high" => match role {
+                None => Ok(JobPriority::Normal),
+                Some(role)
+                    if self
+                        .config()
+                        .high_priority_roles
+                        .iter()
+                        .any(|allowed| allowed == role) =>
+                {
+                    Ok(JobPriority::High)
+                }
+                Some(role) => Err(PriorityError::NotAllowed(format!(
+                    "Role '{}' may not queue high priority jobs",
+                    role
+                ))),
+            },
+            other => Err(PriorityError::Invalid(format!(
+                "Unsupported priority '{}', expected \"high\", \"normal\" or \"low\"",
+                other
+            ))),
+        }
+    }
+
+    // Worker mode: executes queued jobs instead of serving HTTP. Runs
+    // WORKER_CONCURRENCY loops in parallel (default 1).
+    pub async fn run_worker(self, queue: JobQueue) {
+        let concurrency = env::var("WORKER_CONCURRENCY")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&value| value > 0)
+            .unwrap_or(1);
+        println!(
+            "Rust executor worker consuming {} with concurrency {}",
+            QUEUE_KEYS_BY_PRIORITY.join(", "),
+            concurrency
+        );
+
+        // The spool is per instance, so a stable INSTANCE_ID is what lets a
+        // restarted worker find the jobs it was running
+        let spool = JobSpool::open(instance_id()).map(Arc::new);
+        if let Some(spool) = &spool {
+            for job in spool.leftover() {
+                match queue.recover_job(&job).await {
+                    Ok(()) => spool.remove(&job.job_id),
+                    Err(e) => eprintln!("Failed to recover job {}: {}", job.job_id, e),
+                }
+            }
+        }
+
+        let mut workers = Vec::new();
+        for _ in 0..concurrency {
+            match queue.dedicated().await {
+                Ok(connection) => workers.push(tokio::spawn(
+                    self.clone().worker_loop(connection, spool.clone()),
+                )),
+                Err(e) => eprintln!("Failed to start worker: {}", e),
+            }
+        }
+        for worker in workers {
+            let _ = worker.await;
+        }
+    }
+
+    async fn worker_loop(self, queue: JobQueue, spool: Option<Arc<JobSpool>>) {
+        loop {
+            // Jobs stay queued while the host is overloaded, for this or a
+            // less busy worker to pick up later
+            if self.load_shedder.check(&self.config()).is_err() {
+                tokio::time::sleep(WORKER_RETRY_DELAY).await;
+                continue;
+            }
+            let PoppedJob {
+                job_id,
+                queue_key,
+                request,
+                trace_context,
+                queued_at,
+            } = match queue.next_job().await {
+                Ok(Some(job)) => job,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Failed to fetch job: {}", e);
+                    tokio::time::sleep(WORKER_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            let waited_ms = queued_at.map_or(0, |queued_at| {
+                unix_millis()
+                    .parse::<u64>()
+                    .unwrap_or_default()
+                    .saturating_sub(queued_at)
+            });
+            let cx = telemetry::consumer_context(
+                "job",
+                trace_context.as_deref(),
+                vec![
+                    KeyValue::new("job.id", job_id.clone()),
+                    KeyValue::new("job.queue", queue_key.clone()),
+                    KeyValue::new("job.wait_ms", waited_ms as i64),
+                ],
+            );
+
+            // Recorded before running, so a crash mid-job leaves it behind
+            let spooled = spool.as_ref().filter(|spool| {
+                let job = SpooledJob {
+                    job_id: job_id.clone(),
+                    queue_key,
+                };
+                spool.record(&job).map_err(|e| eprintln!("{}", e)).is_ok()
+            });
+
+            let result = match serde_json::from_str::<CodeExecutionRequest>(&request) {
+                Ok(req) => {
+                    // Input sent through POST /jobs/{id}/stdin
+                    let stdin = (req.close_stdin == Some(false)).then(|| {
+                        let (sender, feed): (_, StdinFeed) = mpsc::channel(16);
+                        tokio::spawn(queue.clone().forward_stdin(job_id.clone(), sender));
+                        feed
+                    });
+                    self.execute_code_metered(req, None, stdin)
+                        .with_context(cx.clone())
+                        .await
+                        .0
+                }
+                Err(e) => CodeExecutionResponse::error(
+                    ExecutionStatus::InvalidRequest,
+                    format!("Invalid job request: {}", e),
+                    0.0,
+                ),
+            };
+            match queue.complete_job(&job_id, &result).await {
+                Ok(()) => {
+                    if let Some(spool) = spooled {
+                        spool.remove(&job_id);
+                    }
+                }
+                Err(e) => eprintln!("Failed to store result of job {}: {}", job_id, e),
+            }
+        }
+    }
+}
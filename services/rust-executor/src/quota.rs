@@ -0,0 +1,274 @@
+use crate::{CodeExecutionRequest, CodeExecutionResponse, ExecutionStatus, RustExecutor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use warp::{Filter, Rejection};
+
+// Limits for one period. A missing limit means unlimited.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaLimits {
+    pub executions: Option<u64>,
+    pub cpu_seconds: Option<f64>,
+    pub compile_seconds: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantQuota {
+    pub daily: QuotaLimits,
+    pub monthly: QuotaLimits,
+}
+
+// `[tenant_quotas.default]` applies to every tenant without an entry under
+// `[tenant_quotas.tenants."<id>"]`; an entry replaces the default entirely.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantQuotaConfig {
+    pub default: TenantQuota,
+    pub tenants: HashMap<String, TenantQuota>,
+}
+
+impl TenantQuotaConfig {
+    fn for_tenant(&self, tenant_id: &str) -> &TenantQuota {
+        self.tenants.get(tenant_id).unwrap_or(&self.default)
+    }
+}
+
+// What a single execution consumed
+#[derive(Clone, Copy, Default)]
+pub struct ExecutionCost {
+    pub compile_seconds: f64,
+    pub cpu_seconds: f64,
+}
+
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct UsageTotals {
+    executions: u64,
+    #[serde(rename = "cpuSeconds")]
+    cpu_seconds: f64,
+    #[serde(rename = "compileSeconds")]
+    compile_seconds: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, cost: ExecutionCost) {
+        self.cpu_seconds += cost.cpu_seconds;
+        self.compile_seconds += cost.compile_seconds;
+    }
+
+    // Describes the first limit that has been reached, if any
+    fn exhausted(&self, limits: &QuotaLimits) -> Option<String> {
+        if let Some(limit) = limits.executions.filter(|&limit| self.executions >= limit) {
+            return Some(format!("executions ({}/{})", self.executions, limit));
+        }
+        if let Some(limit) = limits
+            .cpu_seconds
+            .filter(|&limit| self.cpu_seconds >= limit)
+        {
+            return Some(format!("CPU seconds ({:.1}/{})", self.cpu_seconds, limit));
+        }
+        if let Some(limit) = limits
+            .compile_seconds
+            .filter(|&limit| self.compile_seconds >= limit)
+        {
+            return Some(format!(
+                "compile seconds ({:.1}/{})",
+                self.compile_seconds, limit
+            ));
+        }
+        None
+    }
+}
+
+// Usage of one tenant in the current UTC day and month. Counters reset when
+// the period changes.
+#[derive(Default)]
+pub struct TenantUsage {
+    day: i64,
+    daily: UsageTotals,
+    month: i64,
+    monthly: UsageTotals,
+}
+
+impl TenantUsage {
+    fn roll_over(&mut self, (day, month): (i64, i64)) {
+        if self.day != day {
+            self.day = day;
+            self.daily = UsageTotals::default();
+        }
+        if self.month != month {
+            self.month = month;
+            self.monthly = UsageTotals::default();
+        }
+    }
+}
+
+// Per-process usage counters keyed by tenant id
+pub type TenantUsageTable = Arc<Mutex<HashMap<String, TenantUsage>>>;
+
+// QuotaLimits with the API's field names; the config keeps TOML's snake_case
+#[derive(Serialize)]
+pub struct PeriodLimits {
+    executions: Option<u64>,
+    #[serde(rename = "cpuSeconds")]
+    cpu_seconds: Option<f64>,
+    #[serde(rename = "compileSeconds")]
+    compile_seconds: Option<f64>,
+}
+
+impl From<&QuotaLimits> for PeriodLimits {
+    fn from(limits: &QuotaLimits) -> Self {
+        Self {
+            executions: limits.executions,
+            cpu_seconds: limits.cpu_seconds,
+            compile_seconds: limits.compile_seconds,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PeriodUsage {
+    used: UsageTotals,
+    limits: PeriodLimits,
+}
+
+#[derive(Serialize)]
+pub struct TenantUsageResponse {
+    #[serde(rename = "tenantId")]
+    tenant_id: String,
+    daily: PeriodUsage,
+    monthly: PeriodUsage,
+}
+
+// Current UTC day number and month number (year * 12 + month), both counted
+// from the Unix epoch
+fn current_periods() -> (i64, i64) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (days, year * 12 + month - 1)
+}
+
+// Rejection for a request whose tenant has used up a quota; answered with
+// 429 and code "quota_exceeded"
+#[derive(Debug)]
+pub struct QuotaExceeded(pub String);
+
+impl warp::reject::Reject for QuotaExceeded {}
+
+// Counts a request against the quota of its tenant (the X-Tenant-Id header)
+// before the route runs it, rejecting it with QuotaExceeded once the quota
+// is used up. For the routes other than /execute only executions are
+// counted: their CPU and compile time is not metered.
+pub fn tenant_admission(
+    executor: RustExecutor,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-tenant-id")
+        .and_then(move |tenant_id: Option<String>| {
+            let admitted = match tenant_id {
+                Some(tenant_id) => executor
+                    .reserve_tenant_execution(&tenant_id)
+                    .map_err(|message| warp::reject::custom(QuotaExceeded(message))),
+                None => Ok(()),
+            };
+            async move { admitted }
+        })
+        .untuple_one()
+}
+
+impl RustExecutor {
+    // Checks the tenant's quotas and counts the execution in one critical
+    // section, so concurrent requests cannot all pass at limit - 1
+    pub fn reserve_tenant_execution(&self, tenant_id: &str) -> Result<(), String> {
+        let config = self.config();
+        let quota = config.tenant_quotas.for_tenant(tenant_id);
+        let mut usage = self.tenant_usage.lock().unwrap();
+        let usage = usage.entry(tenant_id.to_string()).or_default();
+        usage.roll_over(current_periods());
+
+        if let Some(limit) = usage.daily.exhausted(&quota.daily) {
+            return Err(format!(
+                "Daily quota for tenant '{}' exhausted: {}",
+                tenant_id, limit
+            ));
+        }
+        if let Some(limit) = usage.monthly.exhausted(&quota.monthly) {
+            return Err(format!(
+                "Monthly quota for tenant '{}' exhausted: {}",
+                tenant_id, limit
+            ));
+        }
+        usage.daily.executions += 1;
+        usage.monthly.executions += 1;
+        Ok(())
+    }
+
+    // Adds the time a reserved execution used once it has finished
+    fn record_tenant_usage(&self, tenant_id: &str, cost: ExecutionCost) {
+        let mut usage = self.tenant_usage.lock().unwrap();
+        let usage = usage.entry(tenant_id.to_string()).or_default();
+        usage.roll_over(current_periods());
+        usage.daily.add(cost);
+        usage.monthly.add(cost);
+    }
+
+    // Executes on behalf of a tenant (the X-Tenant-Id header), refusing with
+    // status "quota_exceeded" once any of its quotas for the current day or
+    // month is used up
+    pub async fn execute_for_tenant(
+        &self,
+        req: CodeExecutionRequest,
+        requester_id: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> CodeExecutionResponse {
+        let Some(tenant_id) = tenant_id else {
+            return self.execute_code(req, requester_id).await;
+        };
+        if let Err(message) = self.reserve_tenant_execution(tenant_id) {
+            return CodeExecutionResponse::error(ExecutionStatus::QuotaExceeded, message, 0.0);
+        }
+        let (result, cost) = self.execute_code_metered(req, requester_id, None).await;
+        self.record_tenant_usage(tenant_id, cost);
+        result
+    }
+
+    pub fn tenant_usage(&self, tenant_id: &str) -> TenantUsageResponse {
+        let config = self.config();
+        let quota = config.tenant_quotas.for_tenant(tenant_id);
+        let (daily, monthly) = match self.tenant_usage.lock().unwrap().get_mut(tenant_id) {
+            Some(usage) => {
+                usage.roll_over(current_periods());
+                (usage.daily, usage.monthly)
+            }
+            None => Default::default(),
+        };
+        TenantUsageResponse {
+            tenant_id: tenant_id.to_string(),
+            daily: PeriodUsage {
+                used: daily,
+                limits: (&quota.daily).into(),
+            },
+            monthly: PeriodUsage {
+                used: monthly,
+                limits: (&quota.monthly).into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::load_shedding::HostSample;
+use crate::rusage::{self, ResourceUsage};
+use crate::{toolchain_pin, RustExecutor};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::time::timeout;
+
+const TOOLCHAIN_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Error reported by an execution stopped through the admin kill endpoints;
+// its status is "killed"
+pub const KILLED_MESSAGE: &str = "Execution was killed by an administrator";
+
+static NEXT_EXECUTION_ID: AtomicU64 = AtomicU64::new(1);
+
+// An execution that is currently compiling or running
+pub struct RunningExecution {
+    id: u64,
+    // Which endpoint started it: "execute", "interactive", "repl", ...
+    kind: &'static str,
+    requester_id: Option<String>,
+    started_at: Instant,
+    phase: Mutex<&'static str>,
+    // The user program's process while one is running
+    pid: Mutex<Option<u32>>,
+    // Set by the admin kill endpoints
+    killed: watch::Sender<bool>,
+}
+
+impl RunningExecution {
+    fn kill(&self) {
+        self.killed.send_replace(true);
+        if let Some(pid) = *self.pid.lock().unwrap() {
+            rusage::kill(pid);
+        }
+    }
+}
+
+// Executions in flight keyed by id; /ready compares its size with
+// max_active_executions
+pub type ExecutionRegistry = Arc<Mutex<HashMap<u64, Arc<RunningExecution>>>>;
+
+// Input for the program that arrives while it runs, written after the
+// request's inputData; the sender going away is EOF
+pub type StdinFeed = mpsc::Receiver<Vec<u8>>;
+
+// Keeps an execution listed as in flight for as long as it is alive
+pub struct ActiveExecution {
+    registry: ExecutionRegistry,
+    execution: Arc<RunningExecution>,
+    stdin: Mutex<Option<StdinFeed>>,
+}
+
+impl ActiveExecution {
+    pub fn id(&self) -> u64 {
+        self.execution.id
+    }
+
+    pub fn phase(&self) -> &'static str {
+        *self.execution.phase.lock().unwrap()
+    }
+
+    pub fn set_phase(&self, phase: &'static str) {
+        *self.execution.phase.lock().unwrap() = phase;
+    }
+
+    // A program started after the execution was killed is killed right away
+    pub fn set_pid(&self, pid: Option<u32>) {
+        let mut current = self.execution.pid.lock().unwrap();
+        *current = pid;
+        if let (Some(pid), true) = (pid, self.is_killed()) {
+            rusage::kill(pid);
+        }
+    }
+
+    pub fn attach_stdin(&self, feed: StdinFeed) {
+        *self.stdin.lock().unwrap() = Some(feed);
+    }
+
+    // Taken by whatever starts the program
+    pub fn take_stdin(&self) -> Option<StdinFeed> {
+        self.stdin.lock().unwrap().take()
+    }
+
+    pub fn is_killed(&self) -> bool {
+        *self.execution.killed.borrow()
+    }
+
+    // Runs `work` unless the execution is killed first, in which case the
+    // future is dropped (so kill_on_drop processes such as cargo are
+    // stopped) and None is returned
+    pub async fn until_killed<T>(&self, work: impl Future<Output = T>) -> Option<T> {
+        let mut killed = self.execution.killed.subscribe();
+        tokio::select! {
+            output = work => Some(output),
+            _ = killed.wait_for(|&killed| killed) => None,
+        }
+    }
+}
+
+impl Drop for ActiveExecution {
+    fn drop(&mut self) {
+        self.registry.lock().unwrap().remove(&self.execution.id);
+    }
+}
+
+#[derive(Serialize)]
+pub struct RunningExecutionInfo {
+    id: u64,
+    kind: &'static str,
+    #[serde(rename = "requesterId")]
+    requester_id: Option<String>,
+    // "preparing", "compiling" or "running"
+    phase: &'static str,
+    #[serde(rename = "elapsedSeconds")]
+    elapsed_seconds: f64,
+    pid: Option<u32>,
+    // Usage of the running program so far; null while nothing is running
+    usage: Option<ResourceUsage>,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessCheck {
+    ok: bool,
+    detail: String,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    // "ready" or "not_ready"
+    status: String,
+    checks: BTreeMap<&'static str, ReadinessCheck>,
+}
+
+impl ReadinessResponse {
+    pub fn is_ready(&self) -> bool {
+        self.checks.values().all(|check| check.ok)
+    }
+}
+
+async fn tool_version(tool: &str) -> Result<String, String> {
+    let output = timeout(
+        TOOLCHAIN_CHECK_TIMEOUT,
+        tokio::process::Command::new(tool)
+            .arg("--version")
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| format!("{} --version timed out", tool))?
+    .map_err(|e| format!("{} not available: {}", tool, e))?;
+    if !output.status.success() {
+        return Err(format!("{} --version failed", tool));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Queried once at startup for /info; /ready re-checks the toolchain on
+// every call
+#[derive(Default)]
+pub struct ToolchainVersions {
+    pub rustc: Option<String>,
+    pub cargo: Option<String>,
+    // Optional; /complete is unavailable without it
+    pub rust_analyzer: Option<String>,
+    // Optional; needed for auditSyscalls
+    pub strace: Option<String>,
+    // Optional; needed for clippy lints in a request's `lints`
+    pub clippy: Option<String>,
+    // What generated projects build with, PINNED_RUST_TOOLCHAIN applied;
+    // returned as toolchainVersion with every execution. None when the
+    // pinned toolchain is not installed.
+    pub project_rustc: Option<String>,
+    // Set when project_rustc is not the pinned toolchain
+    pub drift: Option<String>,
+}
+
+impl ToolchainVersions {
+    pub async fn detect() -> Self {
+        let (rustc, cargo) = (tool_version("rustc").await, tool_version("cargo").await);
+        for e in [&rustc, &cargo]
+            .into_iter()
+            .filter_map(|v| v.as_ref().err())
+        {
+            eprintln!("Toolchain check failed: {}", e);
+        }
+        let project_rustc = match toolchain_pin::pinned() {
+            Some(channel) => toolchain_pin::project_rustc()
+                .await
+                .map_err(|e| eprintln!("Pinned toolchain {} unavailable: {}", channel, e))
+                .ok(),
+            None => rustc.clone().ok(),
+        };
+        let drift = project_rustc.as_deref().and_then(toolchain_pin::drift);
+        if let Some(drift) = &drift {
+            eprintln!("Toolchain drift: {}", drift);
+        }
+        Self {
+            rustc: rustc.ok(),
+            cargo: cargo.ok(),
+            rust_analyzer: tool_version("rust-analyzer").await.ok(),
+            strace: tool_version("strace").await.ok(),
+            clippy: tool_version("cargo-clippy").await.ok(),
+            project_rustc,
+            drift,
+        }
+    }
+
+    // "1.82.0" out of "rustc 1.82.0 (f6e511eec 2024-10-15)"
+    pub fn rustc_release(&self) -> Option<&str> {
+        self.rustc.as_deref()?.split_whitespace().nth(1)
+    }
+}
+
+impl RustExecutor {
+    pub fn track_execution(
+        &self,
+        kind: &'static str,
+        requester_id: Option<&str>,
+    ) -> ActiveExecution {
+        let execution = Arc::new(RunningExecution {
+            id: NEXT_EXECUTION_ID.fetch_add(1, Ordering::Relaxed),
+            kind,
+            requester_id: requester_id.map(str::to_string),
+            started_at: Instant::now(),
+            phase: Mutex::new("preparing"),
+            pid: Mutex::new(None),
+            killed: watch::Sender::new(false),
+        });
+        self.running_executions
+            .lock()
+            .unwrap()
+            .insert(execution.id, execution.clone());
+        ActiveExecution {
+            registry: self.running_executions.clone(),
+            execution,
+            stdin: Mutex::new(None),
+        }
+    }
+
+    // Returns false when no execution has this id
+    pub fn kill_execution(&self, id: u64) -> bool {
+        let execution = self.running_executions.lock().unwrap().get(&id).cloned();
+        match execution {
+            Some(execution) => {
+                execution.kill();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns the ids of the executions that were killed
+    pub fn kill_all_executions(&self) -> Vec<u64> {
+        let executions: Vec<Arc<RunningExecution>> = self
+            .running_executions
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        executions
+            .iter()
+            .map(|execution| {
+                execution.kill();
+                execution.id
+            })
+            .collect()
+    }
+
+    // Oldest first
+    pub fn running_executions(&self) -> Vec<RunningExecutionInfo> {
+        let mut executions: Vec<Arc<RunningExecution>> = self
+            .running_executions
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        executions.sort_by_key(|execution| execution.id);
+        executions
+            .iter()
+            .map(|execution| {
+                let pid = *execution.pid.lock().unwrap();
+                RunningExecutionInfo {
+                    id: execution.id,
+                    kind: execution.kind,
+                    requester_id: execution.requester_id.clone(),
+                    phase: *execution.phase.lock().unwrap(),
+                    elapsed_seconds: execution.started_at.elapsed().as_secs_f64(),
+                    pid,
+                    usage: pid.and_then(rusage::live_usage),
+                }
+            })
+            .collect()
+    }
+
+    // Whether this instance can take executions right now, as opposed to
+    // /health which only reports that the process is up
+    pub async fn readiness(&self) -> ReadinessResponse {
+        let mut checks = BTreeMap::new();
+
+        let toolchain = match (tool_version("cargo").await, tool_version("rustc").await) {
+            (Ok(cargo), Ok(rustc)) => ReadinessCheck {
+                ok: true,
+                detail: format!("{}; {}", cargo, rustc),
+            },
+            (Err(e), _) | (_, Err(e)) => ReadinessCheck {
+                ok: false,
+                detail: e,
+            },
+        };
+        checks.insert("toolchain", toolchain);
+
+        let (ok, detail) = self.warmup.read().unwrap().check();
+        checks.insert("warmup", ReadinessCheck { ok, detail });
+
+        let active = self.running_executions.lock().unwrap().len();
+        let max_active = self.config().max_active_executions;
+        checks.insert(
+            "capacity",
+            ReadinessCheck {
+                ok: active < max_active,
+                detail: format!("{}/{} executions in flight", active, max_active),
+            },
+        );
+
+        let config = self.config();
+        let pressure = HostSample::take().pressure(&config);
+        checks.insert(
+            "host",
+            ReadinessCheck {
+                ok: pressure.is_none(),
+                detail: pressure
+                    .unwrap_or_else(|| "within load, memory and disk limits".to_string()),
+            },
+        );
+
+        let mut response = ReadinessResponse {
+            status: String::new(),
+            checks,
+        };
+        response.status = if response.is_ready() {
+            "ready".to_string()
+        } else {
+            "not_ready".to_string()
+        };
+        response
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{rusage, RustExecutor};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const TEMP_DIR_PREFIX: &str = "rust-executor-";
+const DEFAULT_REAPER_INTERVAL_SECONDS: u64 = 600;
+const DEFAULT_TEMP_DIR_MAX_AGE_SECONDS: u64 = 21600;
+const DEFAULT_PROCESS_REAPER_INTERVAL_SECONDS: u64 = 30;
+// Younger processes are left alone: a user program is only registered as a
+// live group once spawn has returned
+const STRAY_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+// Distinguishes this process's temp directories from those left behind by
+// earlier processes, which are the only ones the reaper touches
+fn instance_prefix() -> &'static str {
+    static PREFIX: OnceLock<String> = OnceLock::new();
+    PREFIX.get_or_init(|| {
+        let instance: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        format!("{}{}-", TEMP_DIR_PREFIX, instance)
+    })
+}
+
+// Every project, artifact and session directory is created through this so
+// the reaper can recognize it
+pub fn temp_dir() -> io::Result<TempDir> {
+    tempfile::Builder::new().prefix(instance_prefix()).tempdir()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+// Removes directories of other executor processes (crashed, or killed with
+// SIGKILL before TempDir could clean up) untouched for longer than max_age.
+// Returns the number of directories and bytes reclaimed.
+fn reap_orphaned_temp_dirs(max_age: Duration) -> (u64, u64) {
+    let Ok(entries) = fs::read_dir(env::temp_dir()) else {
+        return (0, 0);
+    };
+    let (mut dirs, mut bytes) = (0, 0);
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(TEMP_DIR_PREFIX) || name.starts_with(instance_prefix()) {
+            continue;
+        }
+        // DirEntry::metadata does not follow symlinks, so a link planted
+        // under our prefix is skipped rather than its target removed
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let age = metadata.modified().ok().and_then(|m| m.elapsed().ok());
+        if !metadata.is_dir() || age.is_none_or(|age| age < max_age) {
+            continue;
+        }
+        let size = dir_size(&entry.path());
+        match fs::remove_dir_all(entry.path()) {
+            Ok(()) => {
+                dirs += 1;
+                bytes += size;
+            }
+            Err(e) => eprintln!("Failed to remove {}: {}", entry.path().display(), e),
+        }
+    }
+    (dirs, bytes)
+}
+
+fn seconds_from_env(name: &str, default: u64) -> Duration {
+    let seconds = env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(default);
+    Duration::from_secs(seconds)
+}
+
+// Scans every TEMP_REAPER_INTERVAL_SECONDS. When several executors share a
+// temp directory, TEMP_DIR_MAX_AGE_SECONDS must exceed the lifetime of
+// their live directories (artifacts, REPL sessions).
+pub async fn reap_temp_dirs_periodically(executor: RustExecutor) {
+    let interval = seconds_from_env(
+        "TEMP_REAPER_INTERVAL_SECONDS",
+        DEFAULT_REAPER_INTERVAL_SECONDS,
+    );
+    let max_age = seconds_from_env("TEMP_DIR_MAX_AGE_SECONDS", DEFAULT_TEMP_DIR_MAX_AGE_SECONDS);
+
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        let reaped = tokio::task::spawn_blocking(move || reap_orphaned_temp_dirs(max_age)).await;
+        let Ok((dirs, bytes)) = reaped else {
+            continue;
+        };
+        let metrics = &executor.metrics;
+        metrics.reaper_runs.fetch_add(1, Ordering::Relaxed);
+        metrics
+            .temp_dirs_reclaimed
+            .fetch_add(dirs, Ordering::Relaxed);
+        metrics
+            .temp_bytes_reclaimed
+            .fetch_add(bytes, Ordering::Relaxed);
+        if dirs > 0 {
+            println!(
+                "Removed {} orphaned temp directories ({} bytes)",
+                dirs, bytes
+            );
+        }
+    }
+}
+
+// A child of the executor, from /proc/<pid>/stat
+struct ChildProcess {
+    pid: u32,
+    zombie: bool,
+    pgid: u32,
+    age: Duration,
+}
+
+fn read_child(pid: u32, parent: u32, uptime: f64, ticks_per_second: f64) -> Option<ChildProcess> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Counted from the closing parenthesis of the command name: state,
+    // ppid and pgrp are fields 3 to 5, starttime (in clock ticks since
+    // boot) field 22
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    if fields.get(1)?.parse::<u32>().ok()? != parent {
+        return None;
+    }
+    let started = fields.get(19)?.parse::<f64>().ok()? / ticks_per_second;
+    Some(ChildProcess {
+        pid,
+        zombie: *fields.first()? == "Z",
+        pgid: fields.get(2)?.parse().ok()?,
+        age: Duration::from_secs_f64((uptime - started).max(0.0)),
+    })
+}
+
+// Becomes the parent of every orphan a user program leaves behind, rather
+// than init, so the scan below can find and reap them. The executor is
+// usually PID 1 in its container, where it is already.
+pub fn adopt_orphans() {
+    // SAFETY: prctl with PR_SET_CHILD_SUBREAPER only sets a flag on this
+    // process
+    if unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) } == -1 {
+        eprintln!(
+            "Failed to become a child subreaper: {}",
+            io::Error::last_os_error()
+        );
+    }
+}
+
+// Kills children outside the executor's own process group that no running
+// execution accounts for, such as processes a program detached with setsid
+// before its group was killed. Dead children are reaped once they were
+// already zombies at the previous scan: anything still waiting on them
+// would have collected them by then. Returns the number killed and reaped,
+// and the zombies left for the next scan.
+fn reap_stray_processes(previous_zombies: &HashSet<u32>) -> (u64, u64, HashSet<u32>) {
+    let mut zombies = HashSet::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return (0, 0, zombies);
+    };
+    let uptime = fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|uptime| uptime.split_whitespace().next()?.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    // SAFETY: sysconf and getpgrp have no memory-safety requirements
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let own_group = unsafe { libc::getpgrp() } as u32;
+    let own_pid = std::process::id();
+
+    let (mut killed, mut reaped) = (0, 0);
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        let Some(child) = read_child(pid, own_pid, uptime, ticks_per_second) else {
+            continue;
+        };
+        if rusage::is_live_group(child.pgid) {
+            continue;
+        }
+        if child.zombie {
+            if !previous_zombies.contains(&child.pid) {
+                zombies.insert(child.pid);
+                continue;
+            }
+            // SAFETY: waitpid only writes to the status pointer, which is
+            // valid
+            let mut status = 0;
+            if unsafe { libc::waitpid(child.pid as libc::pid_t, &mut status, libc::WNOHANG) } > 0 {
+                reaped += 1;
+            }
+        } else if child.pgid != own_group && child.age >= STRAY_GRACE_PERIOD {
+            // cargo, rust-analyzer and the like stay in the executor's group
+            rusage::kill(child.pgid);
+            // SAFETY: sending a signal has no memory-safety requirements
+            unsafe {
+                libc::kill(child.pid as libc::pid_t, libc::SIGKILL);
+            }
+            killed += 1;
+        }
+    }
+    (killed, reaped, zombies)
+}
+
+// Scans every PROCESS_REAPER_INTERVAL_SECONDS; killed strays are reaped on
+// the next scan
+pub async fn reap_stray_processes_periodically(executor: RustExecutor) {
+    let interval = seconds_from_env(
+        "PROCESS_REAPER_INTERVAL_SECONDS",
+        DEFAULT_PROCESS_REAPER_INTERVAL_SECONDS,
+    );
+    let mut ticks = tokio::time::interval(interval);
+    let mut zombies = HashSet::new();
+    loop {
+        ticks.tick().await;
+        let previous = std::mem::take(&mut zombies);
+        let scanned = tokio::task::spawn_blocking(move || reap_stray_processes(&previous)).await;
+        let Ok((killed, reaped, remaining)) = scanned else {
+            continue;
+        };
+        zombies = remaining;
+        let metrics = &executor.metrics;
+        metrics
+            .stray_processes_killed
+            .fetch_add(killed, Ordering::Relaxed);
+        metrics.zombies_reaped.fetch_add(reaped, Ordering::Relaxed);
+        if killed > 0 {
+            println!("Killed {} stray processes", killed);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::quota::QuotaExceeded;
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+use std::env;
+use warp::http::StatusCode;
+use warp::reject::{
+    InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader, PayloadTooLarge,
+    UnsupportedMediaType,
+};
+use warp::{Filter, Rejection};
+
+const DEFAULT_MAX_REQUEST_BODY_KB: u64 = 1024;
+
+// JSON request body of at most MAX_REQUEST_BODY_KB
+pub fn json_body<T: DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    let max_body_kb = env::var("MAX_REQUEST_BODY_KB")
+        .ok()
+        .and_then(|kb| kb.parse().ok())
+        .filter(|&kb| kb > 0)
+        .unwrap_or(DEFAULT_MAX_REQUEST_BODY_KB);
+    warp::body::content_length_limit(max_body_kb * 1024).and(warp::body::json())
+}
+
+fn describe(err: &Rejection) -> (StatusCode, &'static str, String) {
+    if err.is_not_found() {
+        return (StatusCode::NOT_FOUND, "not_found", "Not found".to_string());
+    }
+    if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        return (StatusCode::BAD_REQUEST, "invalid_body", e.to_string());
+    }
+    if let Some(e) = err.find::<MissingHeader>() {
+        return (StatusCode::BAD_REQUEST, "missing_header", e.to_string());
+    }
+    if let Some(e) = err.find::<InvalidHeader>() {
+        return (StatusCode::BAD_REQUEST, "invalid_header", e.to_string());
+    }
+    if let Some(e) = err.find::<InvalidQuery>() {
+        return (StatusCode::BAD_REQUEST, "invalid_query", e.to_string());
+    }
+    if let Some(e) = err.find::<warp::ws::MissingConnectionUpgrade>() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "websocket_upgrade_required",
+            e.to_string(),
+        );
+    }
+    if let Some(e) = err.find::<LengthRequired>() {
+        return (
+            StatusCode::LENGTH_REQUIRED,
+            "length_required",
+            e.to_string(),
+        );
+    }
+    if let Some(e) = err.find::<PayloadTooLarge>() {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            e.to_string(),
+        );
+    }
+    if let Some(e) = err.find::<UnsupportedMediaType>() {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            e.to_string(),
+        );
+    }
+    if let Some(e) = err.find::<QuotaExceeded>() {
+        return (StatusCode::TOO_MANY_REQUESTS, "quota_exceeded", e.0.clone());
+    }
+    if let Some(e) = err.find::<warp::cors::CorsForbidden>() {
+        return (StatusCode::FORBIDDEN, "cors_forbidden", e.to_string());
+    }
+    // Checked last: warp reports it for every route whose path matched but
+    // whose method did not, even when another route failed for a better
+    // reason
+    if let Some(e) = err.find::<MethodNotAllowed>() {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            e.to_string(),
+        );
+    }
+    eprintln!("Unhandled rejection: {:?}", err);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal_error",
+        "Internal server error".to_string(),
+    )
+}
+
+// Replaces warp's plain-text rejections with the JSON error body every
+// handler uses, plus a machine-readable code
+pub async fn handle_rejection(err: Rejection) -> Result<impl warp::Reply, Infallible> {
+    let (status, code, message) = describe(&err);
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": message, "code": code })),
+        status,
+    ))
+}
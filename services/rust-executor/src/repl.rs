@@ -0,0 +1,411 @@
+use crate::project::write_project;
+use crate::reaper::temp_dir;
+use crate::{
+    wrapping, BuildSettings, CodeExecutionResponse, ExecutionStatus, LimitExceeded, RunLimits,
+    RustExecutor,
+};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use syn::spanned::Spanned;
+use syn::{Item, Stmt};
+use tempfile::TempDir;
+use utoipa::ToSchema;
+
+const MAX_REPL_SESSIONS: usize = 50;
+const REPL_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+// Printed around the evaluation of the trailing expression so the parts of
+// stdout can be told apart
+const VALUE_MARKER: &str = "\u{0}__scriptsmith_repl_value__\u{0}";
+
+const REPL_CARGO_TOML: &str = r#"[package]
+name = "rust_repl"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "main"
+path = "src/main.rs"
+"#;
+
+const REPL_PRELUDE: &str = r#"#![allow(unused)]
+use std::io;
+use std::io::prelude::*;
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque};
+use std::time::{Duration, Instant};
+use std::thread;
+
+// Prints the value of a trailing expression when it implements Debug and
+// silently skips it otherwise (autoref specialization)
+struct __ReplValue<T>(T);
+trait __ReplShowDebug {
+    fn __repl_show(&self);
+}
+impl<T: std::fmt::Debug> __ReplShowDebug for __ReplValue<T> {
+    fn __repl_show(&self) {
+        if std::any::type_name::<T>() != "()" {
+            println!("{:?}", self.0);
+        }
+    }
+}
+trait __ReplShowNothing {
+    fn __repl_show(&self);
+}
+impl<T> __ReplShowNothing for &__ReplValue<T> {
+    fn __repl_show(&self) {}
+}
+"#;
+
+pub type ReplSessions = Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<ReplSession>>>>>;
+
+// Definitions and statements accumulated by a session. Every evaluation
+// recompiles the whole program and replays the earlier statements with their
+// output hidden, so snippets with side effects outside stdout (time,
+// randomness) are observed again on each evaluation.
+pub struct ReplSession {
+    project_dir: TempDir,
+    items: Vec<(Option<String>, String)>,
+    statements: Vec<String>,
+    replayed_output_len: usize,
+    last_used: Instant,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReplEvalRequest {
+    code: String,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReplSessionResponse {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+// A snippet split into the parts the session keeps
+struct ParsedSnippet {
+    items: Vec<(Option<String>, String)>,
+    statements: Vec<String>,
+    trailing_expr: Option<String>,
+}
+
+pub fn item_name(item: &Item) -> Option<String> {
+    let ident = match item {
+        Item::Fn(item) => &item.sig.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::Trait(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        Item::Const(item) => &item.ident,
+        Item::Static(item) => &item.ident,
+        Item::Mod(item) => &item.ident,
+        Item::Union(item) => &item.ident,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+fn parse_snippet(code: &str) -> Result<ParsedSnippet, String> {
+    // Parsed as the body of a block so both items and statements are allowed
+    let wrapped = format!("{{\n{}\n}}", code);
+    let block: syn::Block =
+        syn::parse_str(&wrapped).map_err(|e| format!("Failed to parse snippet: {}", e))?;
+    let source_of = |node: &dyn Spanned| -> String {
+        let range = node.span().byte_range();
+        wrapped.get(range).map(str::to_string).unwrap_or_default()
+    };
+
+    let mut snippet = ParsedSnippet {
+        items: Vec::new(),
+        statements: Vec::new(),
+        trailing_expr: None,
+    };
+    let count = block.stmts.len();
+    for (index, stmt) in block.stmts.iter().enumerate() {
+        match stmt {
+            Stmt::Item(item) => snippet.items.push((item_name(item), source_of(item))),
+            Stmt::Expr(expr, None) if index + 1 == count => {
+                snippet.trailing_expr = Some(source_of(expr));
+            }
+            // More statements follow when it is replayed, so it needs its `;`
+            Stmt::Macro(mac) if mac.semi_token.is_none() => {
+                snippet.statements.push(format!("{};", source_of(stmt)));
+            }
+            _ => snippet.statements.push(source_of(stmt)),
+        }
+    }
+    Ok(snippet)
+}
+
+fn render_program(
+    items: &[(Option<String>, String)],
+    statements: &[String],
+    trailing_expr: Option<&str>,
+) -> String {
+    let mut program = String::from(REPL_PRELUDE);
+    for (_, item) in items {
+        program.push_str(item);
+        program.push('\n');
+    }
+    program.push_str("\nfn main() {\n");
+    for statement in statements {
+        program.push_str(statement);
+        program.push('\n');
+    }
+    let print_marker = format!("print!(\"{}\");\n", VALUE_MARKER.escape_default());
+    program.push_str(&print_marker);
+    match trailing_expr {
+        Some(expr) => {
+            program.push_str(&format!(
+                "let __repl_value = __ReplValue({{\n{}\n}});\n",
+                expr
+            ));
+            program.push_str(&print_marker);
+            program.push_str("(&__repl_value).__repl_show();\n");
+        }
+        None => program.push_str(&print_marker),
+    }
+    program.push_str("}\n");
+    program
+}
+
+impl RustExecutor {
+    pub fn create_repl_session(&self) -> Result<ReplSessionResponse, String> {
+        let project_dir =
+            temp_dir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+
+        let mut sessions = self.repl_sessions.lock().unwrap();
+        // Sessions currently evaluating are locked and are never reclaimed
+        sessions.retain(|_, session| match session.try_lock() {
+            Ok(session) => session.last_used.elapsed() < REPL_IDLE_TIMEOUT,
+            Err(_) => true,
+        });
+        if sessions.len() >= MAX_REPL_SESSIONS {
+            return Err(format!(
+                "Too many active REPL sessions (limit {})",
+                MAX_REPL_SESSIONS
+            ));
+        }
+
+        let session_id: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(24)
+            .map(char::from)
+            .collect();
+        sessions.insert(
+            session_id.clone(),
+            Arc::new(tokio::sync::Mutex::new(ReplSession {
+                project_dir,
+                items: Vec::new(),
+                statements: Vec::new(),
+                replayed_output_len: 0,
+                last_used: Instant::now(),
+            })),
+        );
+        Ok(ReplSessionResponse { session_id })
+    }
+
+    pub fn delete_repl_session(&self, session_id: &str) -> bool {
+        self.repl_sessions
+            .lock()
+            .unwrap()
+            .remove(session_id)
+            .is_some()
+    }
+
+    // Returns None when the session does not exist
+    pub async fn eval_in_repl_session(
+        &self,
+        session_id: &str,
+        req: ReplEvalRequest,
+    ) -> Option<CodeExecutionResponse> {
+        let session = self
+            .repl_sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()?;
+        let mut session = session.lock().await;
+        session.last_used = Instant::now();
+        let start_time = Instant::now();
+        let active = match self.start_execution("repl", None) {
+            Ok(active) => active,
+            Err(e) => {
+                return Some(CodeExecutionResponse::error(
+                    e.status,
+                    e.message,
+                    start_time.elapsed().as_secs_f64(),
+                ))
+            }
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+
+        // Earlier cells were checked when they were evaluated
+        if let Err(e) = self.check_unstable_features(std::iter::once(req.code.as_str())) {
+            return Some(CodeExecutionResponse::error(
+                e.status,
+                e.message,
+                start_time.elapsed().as_secs_f64(),
+            ));
+        }
+        let forbid_unsafe = match self.forbids_unsafe(std::iter::once(req.code.as_str())) {
+            Ok(forbid_unsafe) => forbid_unsafe,
+            Err(e) => {
+                return Some(CodeExecutionResponse::error(
+                    e.status,
+                    e.message,
+                    start_time.elapsed().as_secs_f64(),
+                ))
+            }
+        };
+        let snippet = match parse_snippet(&req.code) {
+            Ok(snippet) => snippet,
+            Err(e) => {
+                return Some(CodeExecutionResponse::error(
+                    ExecutionStatus::CompileError,
+                    e,
+                    start_time.elapsed().as_secs_f64(),
+                ))
+            }
+        };
+
+        // Redefining an item replaces the earlier definition with that name
+        let mut items = session.items.clone();
+        for (name, source) in snippet.items {
+            match name
+                .as_ref()
+                .and_then(|name| items.iter().position(|(n, _)| n.as_ref() == Some(name)))
+            {
+                Some(index) => items[index] = (name, source),
+                None => items.push((name, source)),
+            }
+        }
+        let mut statements = session.statements.clone();
+        statements.extend(snippet.statements);
+
+        let program_size_kb = items
+            .iter()
+            .map(|(_, source)| source.len())
+            .chain(statements.iter().map(String::len))
+            .sum::<usize>() as f64
+            / 1024.0;
+        if program_size_kb > config.max_code_size_kb as f64 {
+            return Some(CodeExecutionResponse::error(
+                ExecutionStatus::InvalidRequest,
+                format!(
+                    "Session code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    program_size_kb, config.max_code_size_kb
+                ),
+                start_time.elapsed().as_secs_f64(),
+            ));
+        }
+
+        let mut program = render_program(&items, &statements, snippet.trailing_expr.as_deref());
+        if forbid_unsafe {
+            program = wrapping::forbid_unsafe(&program);
+        }
+        let project_path = session.project_dir.path();
+        if let Err(e) = write_project(project_path, REPL_CARGO_TOML, &[("src/main.rs", &program)]) {
+            return Some(CodeExecutionResponse::error(
+                ExecutionStatus::InternalError,
+                e,
+                start_time.elapsed().as_secs_f64(),
+            ));
+        }
+
+        let mut result = self
+            .compile_and_run(
+                project_path,
+                None,
+                None,
+                RunLimits::wall_clock(execution_timeout),
+                &[],
+                BuildSettings::default(),
+                &active,
+            )
+            .await;
+
+        // stdout is: replayed output, marker, output of evaluating the
+        // trailing expression, marker, the expression's displayed value
+        let marker = VALUE_MARKER.as_bytes();
+        let find_marker = |from: usize| {
+            result.stdout[from.min(result.stdout.len())..]
+                .windows(marker.len())
+                .position(|window| window == marker)
+                .map(|pos| pos + from)
+        };
+        let before_expr = find_marker(0);
+        let before_value = before_expr.and_then(|pos| find_marker(pos + marker.len()));
+
+        // Only the output produced by this snippet is returned; the replayed
+        // prefix was already shown by earlier evaluations
+        let mut output = Vec::new();
+        let mut next_replayed_len = 0;
+        match (before_expr, before_value) {
+            (Some(first), Some(second)) => {
+                let replayed = session.replayed_output_len.min(first);
+                output.extend_from_slice(&result.stdout[replayed..first]);
+                output.extend_from_slice(&result.stdout[first + marker.len()..second]);
+                output.extend_from_slice(&result.stdout[second + marker.len()..]);
+                next_replayed_len = second - marker.len();
+            }
+            _ => {
+                let end = before_expr.unwrap_or(result.stdout.len());
+                let replayed = session.replayed_output_len.min(end);
+                output.extend_from_slice(&result.stdout[replayed..end]);
+            }
+        }
+        result.stdout = output;
+
+        if result.status.is_success() {
+            session.items = items;
+            session.statements = statements;
+            // Keep side effects of a trailing expression for later snippets
+            // without moving the value
+            if let Some(expr) = snippet.trailing_expr {
+                session.statements.push(format!("let _ = &({});", expr));
+            }
+            session.replayed_output_len = next_replayed_len;
+        }
+
+        Some(CodeExecutionResponse {
+            output: result.stdout_text(),
+            output_encoding: "utf8".to_string(),
+            error: result.stderr,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: result.status,
+            compile_time_ms: Some(result.compile_time.as_secs_f64() * 1000.0),
+            run_time_ms: result
+                .run_time
+                .map(|run_time| run_time.as_secs_f64() * 1000.0),
+            peak_memory_kb: result.usage.map(|usage| usage.peak_memory_kb),
+            user_cpu_ms: result.usage.map(|usage| usage.user_cpu_ms),
+            system_cpu_ms: result.usage.map(|usage| usage.system_cpu_ms),
+            exit_code: result.exit_code,
+            output_url: None,
+            limit_exceeded: result
+                .limit_exceeded
+                .map(|limit| limit.as_str().to_string()),
+            memory_limit_kb: result
+                .limit_exceeded
+                .and_then(LimitExceeded::memory_limit_kb),
+            truncated_by_timeout: result
+                .limit_exceeded
+                .is_some_and(LimitExceeded::stopped_program),
+            panic: None,
+            output_files: Vec::new(),
+            // The program's raw output includes the replayed snippets
+            output_events: Vec::new(),
+            syscall_audit: None,
+            diagnostics: Vec::new(),
+            toolchain_version: self.toolchain.project_rustc.clone(),
+        })
+    }
+}
@@ -0,0 +1,271 @@
+use crate::std_usage;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::Expr;
+use utoipa::ToSchema;
+
+const LOOP_KINDS: &[&str] = &["for", "while", "loop"];
+
+// Constructs an assignment forbids or requires, checked against the
+// submission's syntax tree before it is compiled
+#[derive(Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct ConstructRules {
+    // "for", "while" and/or "loop"
+    #[serde(rename = "forbiddenLoops")]
+    pub forbidden_loops: Option<Vec<String>>,
+    // Method names, e.g. "clone" or "unwrap". Receiver types are not known,
+    // so every call of a method with that name counts.
+    #[serde(rename = "forbiddenMethods")]
+    pub forbidden_methods: Option<Vec<String>>,
+    // Macro names without the `!`, e.g. "vec"
+    #[serde(rename = "forbiddenMacros")]
+    pub forbidden_macros: Option<Vec<String>>,
+    // std items and everything under them, e.g. "std::collections::HashMap"
+    // or "std::collections"
+    #[serde(rename = "forbiddenPaths")]
+    pub forbidden_paths: Option<Vec<String>>,
+    // Some function must call itself, directly or through other functions
+    #[serde(rename = "requireRecursion")]
+    pub require_recursion: Option<bool>,
+}
+
+impl ConstructRules {
+    fn is_empty(&self) -> bool {
+        let empty = |list: &Option<Vec<String>>| list.as_ref().is_none_or(Vec::is_empty);
+        empty(&self.forbidden_loops)
+            && empty(&self.forbidden_methods)
+            && empty(&self.forbidden_macros)
+            && empty(&self.forbidden_paths)
+            && !self.require_recursion.unwrap_or(false)
+    }
+}
+
+#[derive(Default)]
+struct LoopFinder {
+    // (keyword, line)
+    loops: Vec<(&'static str, usize)>,
+}
+
+impl<'ast> Visit<'ast> for LoopFinder {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        let keyword = match expr {
+            Expr::ForLoop(_) => Some("for"),
+            Expr::While(_) => Some("while"),
+            Expr::Loop(_) => Some("loop"),
+            _ => None,
+        };
+        if let Some(keyword) = keyword {
+            self.loops.push((keyword, expr.span().start().line));
+        }
+        visit::visit_expr(self, expr);
+    }
+}
+
+// Which functions each function calls, by name. Methods of different types
+// with the same name share a node, which only errs towards finding recursion.
+#[derive(Default)]
+struct CallGraph {
+    calls: HashMap<String, HashSet<String>>,
+    current: Option<String>,
+}
+
+impl CallGraph {
+    fn within(&mut self, name: String, visit_body: impl FnOnce(&mut Self)) {
+        self.calls.entry(name.clone()).or_default();
+        let outer = self.current.replace(name);
+        visit_body(self);
+        self.current = outer;
+    }
+
+    fn call(&mut self, callee: String) {
+        if let Some(caller) = &self.current {
+            self.calls.entry(caller.clone()).or_default().insert(callee);
+        }
+    }
+
+    fn has_cycle(&self) -> bool {
+        // 1: on the current path, 2: fully explored
+        fn visit(
+            graph: &HashMap<String, HashSet<String>>,
+            name: &str,
+            state: &mut HashMap<String, u8>,
+        ) -> bool {
+            match state.get(name) {
+                Some(1) => return true,
+                Some(_) => return false,
+                None => {}
+            }
+            state.insert(name.to_string(), 1);
+            let cycle = graph.get(name).is_some_and(|callees| {
+                callees
+                    .iter()
+                    .filter(|callee| graph.contains_key(*callee))
+                    .any(|callee| visit(graph, callee, state))
+            });
+            state.insert(name.to_string(), 2);
+            cycle
+        }
+        let mut state = HashMap::new();
+        self.calls
+            .keys()
+            .any(|name| visit(&self.calls, name, &mut state))
+    }
+}
+
+impl<'ast> Visit<'ast> for CallGraph {
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.within(item.sig.ident.to_string(), |graph| {
+            visit::visit_item_fn(graph, item)
+        });
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.within(item.sig.ident.to_string(), |graph| {
+            visit::visit_impl_item_fn(graph, item)
+        });
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        self.within(item.sig.ident.to_string(), |graph| {
+            visit::visit_trait_item_fn(graph, item)
+        });
+    }
+
+    // `f(..)`, `Self::f(..)` and `Type::f(..)`
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let Expr::Path(path) = &*call.func {
+            if let Some(segment) = path.path.segments.last() {
+                self.call(segment.ident.to_string());
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        self.call(call.method.to_string());
+        visit::visit_expr_method_call(self, call);
+    }
+}
+
+fn is_under(name: &str, forbidden: &str) -> bool {
+    name == forbidden
+        || name
+            .strip_prefix(forbidden)
+            .is_some_and(|rest| rest.starts_with("::"))
+        // A glob import could bring the forbidden item into scope unnamed
+        || name.strip_suffix("::*").is_some_and(|module| {
+            forbidden
+                .strip_prefix(module)
+                .is_some_and(|rest| rest.starts_with("::"))
+        })
+}
+
+fn lines(lines: &[usize]) -> String {
+    let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+    if lines.len() == 1 {
+        format!("line {}", lines[0])
+    } else {
+        format!("lines {}", lines.join(", "))
+    }
+}
+
+// Checks the submission against an assignment's rules, returning every
+// violation found, one per line
+pub fn check(code: &str, rules: &ConstructRules) -> Result<(), String> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let forbidden_loops = rules.forbidden_loops.as_deref().unwrap_or_default();
+    if let Some(unknown) = forbidden_loops
+        .iter()
+        .find(|kind| !LOOP_KINDS.contains(&kind.as_str()))
+    {
+        return Err(format!(
+            "Unknown loop kind '{}' in forbiddenLoops: expected for, while or loop",
+            unknown
+        ));
+    }
+    // Snippets are checked as the body of main, the way /execute runs them.
+    // Code that does not parse is left to the compiler.
+    let (file, line_offset) = match syn::parse_file(code) {
+        Ok(file) => (file, 0),
+        Err(_) => match syn::parse_file(&format!("fn main() {{\n{}\n}}", code)) {
+            Ok(file) => (file, 1),
+            Err(_) => return Ok(()),
+        },
+    };
+    let Ok(usage) = std_usage::collect_usage(code) else {
+        return Ok(());
+    };
+
+    let mut violations = Vec::new();
+    let mut loop_finder = LoopFinder::default();
+    loop_finder.visit_file(&file);
+    for kind in LOOP_KINDS.iter().filter(|kind| {
+        forbidden_loops
+            .iter()
+            .any(|forbidden| forbidden.as_str() == **kind)
+    }) {
+        let found: Vec<usize> = loop_finder
+            .loops
+            .iter()
+            .filter(|(keyword, _)| keyword == kind)
+            .map(|(_, line)| line.saturating_sub(line_offset).max(1))
+            .collect();
+        if !found.is_empty() {
+            violations.push(format!(
+                "`{}` loops are not allowed ({})",
+                kind,
+                lines(&found)
+            ));
+        }
+    }
+
+    let forbidden = [
+        ("method", &rules.forbidden_methods),
+        ("macro", &rules.forbidden_macros),
+        ("path", &rules.forbidden_paths),
+    ];
+    for (kind, names) in forbidden {
+        for name in names.as_deref().unwrap_or_default() {
+            let mut found: Vec<usize> = usage
+                .items
+                .iter()
+                .filter(|item| item.kind == kind)
+                .filter(|item| match kind {
+                    "path" => is_under(&item.name, name),
+                    _ => &item.name == name,
+                })
+                .flat_map(|item| item.lines.iter().copied())
+                .collect();
+            found.sort_unstable();
+            found.dedup();
+            if found.is_empty() {
+                continue;
+            }
+            let shown = match kind {
+                "method" => format!("`.{}()`", name),
+                "macro" => format!("`{}!`", name),
+                _ => format!("`{}`", name),
+            };
+            violations.push(format!("{} is not allowed ({})", shown, lines(&found)));
+        }
+    }
+
+    if rules.require_recursion.unwrap_or(false) {
+        let mut graph = CallGraph::default();
+        graph.visit_file(&file);
+        if !graph.has_cycle() {
+            violations
+                .push("The solution must use recursion, but no function calls itself".to_string());
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations.join("\n"))
+    }
+}
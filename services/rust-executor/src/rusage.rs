@@ -0,0 +1,200 @@
+use crate::bwrap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Resources used by a finished child process, as reported by wait4
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    #[serde(rename = "userCpuMs")]
+    pub user_cpu_ms: u64,
+    #[serde(rename = "systemCpuMs")]
+    pub system_cpu_ms: u64,
+    #[serde(rename = "peakMemoryKB")]
+    pub peak_memory_kb: u64,
+}
+
+impl ResourceUsage {
+    pub fn cpu_seconds(&self) -> f64 {
+        (self.user_cpu_ms + self.system_cpu_ms) as f64 / 1000.0
+    }
+}
+
+fn timeval_ms(time: libc::timeval) -> u64 {
+    (time.tv_sec as u64) * 1000 + (time.tv_usec as u64) / 1000
+}
+
+// Blocks until the process exits, leaving it unreaped so its pid, and
+// with it the process group id, cannot be reused yet
+fn wait_for_exit_blocking(pid: u32) -> io::Result<()> {
+    loop {
+        // SAFETY: siginfo_t is plain old data and waitid only writes to it
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let result = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if result == -1 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        return Ok(());
+    }
+}
+
+fn wait4_blocking(pid: u32) -> io::Result<(ExitStatus, ResourceUsage)> {
+    loop {
+        let mut status = 0;
+        // SAFETY: rusage is plain old data, and wait4 only writes to the two
+        // out-pointers, which are valid for the duration of the call
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut rusage) };
+        if result == -1 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        return Ok((
+            ExitStatus::from_raw(status),
+            ResourceUsage {
+                user_cpu_ms: timeval_ms(rusage.ru_utime),
+                system_cpu_ms: timeval_ms(rusage.ru_stime),
+                // ru_maxrss is already in kilobytes on Linux
+                peak_memory_kb: rusage.ru_maxrss.max(0) as u64,
+            },
+        ));
+    }
+}
+
+// Process groups of user programs whose leader has not been reaped yet.
+// Any other group among the executor's children is a stray (see reaper).
+fn live_groups() -> &'static Mutex<HashSet<u32>> {
+    static LIVE_GROUPS: OnceLock<Mutex<HashSet<u32>>> = OnceLock::new();
+    LIVE_GROUPS.get_or_init(Mutex::default)
+}
+
+pub fn is_live_group(pgid: u32) -> bool {
+    live_groups().lock().unwrap().contains(&pgid)
+}
+
+// A user program spawned as the leader of its own process group (see
+// RustExecutor::child_command). Killing it kills everything the program
+// started, and dropping the handle before the program was reaped kills the
+// group too, so a cancelled request never leaves processes behind.
+pub struct ProcessGroup {
+    pid: u32,
+    // Set once the leader is reaped; from then on its pid may be reused and
+    // the group must not be signalled
+    reaped: Arc<Mutex<bool>>,
+    // The leader is bwrap, whose exit status stands for the program's
+    in_bwrap: bool,
+}
+
+impl ProcessGroup {
+    pub fn new(pid: u32) -> Self {
+        live_groups().lock().unwrap().insert(pid);
+        Self {
+            pid,
+            reaped: Arc::new(Mutex::new(false)),
+            in_bwrap: false,
+        }
+    }
+
+    pub fn in_bwrap(mut self, in_bwrap: bool) -> Self {
+        self.in_bwrap = in_bwrap;
+        self
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn kill(&self) {
+        if !*self.reaped.lock().unwrap() {
+            kill(self.pid);
+        }
+    }
+
+    // Waits for the leader to exit, kills whatever it left running in the
+    // group, then reaps it and returns its resource usage. tokio's Child
+    // reaps internally and discards the rusage, so programs are spawned
+    // through std and waited on here.
+    pub async fn wait(&self) -> io::Result<(ExitStatus, ResourceUsage)> {
+        let (pid, reaped, in_bwrap) = (self.pid, self.reaped.clone(), self.in_bwrap);
+        tokio::task::spawn_blocking(move || {
+            wait_for_exit_blocking(pid)?;
+            let mut reaped = reaped.lock().unwrap();
+            kill(pid);
+            let (status, usage) = wait4_blocking(pid)?;
+            *reaped = true;
+            live_groups().lock().unwrap().remove(&pid);
+            let status = if in_bwrap {
+                bwrap::program_status(status)
+            } else {
+                status
+            };
+            Ok((status, usage))
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+impl Drop for ProcessGroup {
+    // A leader killed here is left for the reaper to collect
+    fn drop(&mut self) {
+        self.kill();
+        live_groups().lock().unwrap().remove(&self.pid);
+    }
+}
+
+// Usage so far of a process that is still running, read from /proc
+pub fn live_usage(pid: u32) -> Option<ResourceUsage> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name can contain spaces, so fields are counted from the
+    // closing parenthesis; utime and stime are fields 14 and 15
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let ticks_to_ms = |field: &str| -> Option<u64> {
+        // SAFETY: sysconf has no memory-safety requirements
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+        Some(field.parse::<u64>().ok()? * 1000 / ticks_per_second)
+    };
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let peak_memory_kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap_or(0);
+    Some(ResourceUsage {
+        user_cpu_ms: ticks_to_ms(fields.get(11)?)?,
+        system_cpu_ms: ticks_to_ms(fields.get(12)?)?,
+        peak_memory_kb,
+    })
+}
+
+// Kills the process group led by pid
+pub fn kill(pid: u32) {
+    // A pid of 0 would signal the executor's own group
+    if pid == 0 {
+        return;
+    }
+    // SAFETY: sending a signal has no memory-safety requirements. Callers
+    // only kill children whose wait has not completed, so the pid has not
+    // been recycled.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
@@ -0,0 +1,174 @@
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+// Capabilities the service needs to hand programs to another user, wait on
+// and kill them, and clean up what they wrote: CAP_CHOWN, CAP_DAC_OVERRIDE,
+// CAP_KILL, CAP_SETGID and CAP_SETUID
+const REQUIRED_CAPABILITIES: [(u32, &str); 5] = [
+    (0, "CAP_CHOWN"),
+    (1, "CAP_DAC_OVERRIDE"),
+    (5, "CAP_KILL"),
+    (6, "CAP_SETGID"),
+    (7, "CAP_SETUID"),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct SandboxUser {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+fn lookup(name: &str) -> Result<SandboxUser, String> {
+    let c_name = CString::new(name).map_err(|e| e.to_string())?;
+    // SAFETY: getpwnam's result is copied out before anything else can
+    // call it; this runs once, from configured()
+    let passwd = unsafe { libc::getpwnam(c_name.as_ptr()) };
+    if passwd.is_null() {
+        return Err(format!("SANDBOX_USER {} does not exist", name));
+    }
+    let (uid, gid) = unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) };
+    Ok(SandboxUser { uid, gid })
+}
+
+fn parse(spec: &str) -> Result<SandboxUser, String> {
+    let user = match spec.split_once(':') {
+        Some((uid, gid)) => SandboxUser {
+            uid: uid
+                .parse()
+                .map_err(|_| format!("Invalid SANDBOX_USER uid '{}'", uid))?,
+            gid: gid
+                .parse()
+                .map_err(|_| format!("Invalid SANDBOX_USER gid '{}'", gid))?,
+        },
+        None => lookup(spec)?,
+    };
+    if user.uid == 0 || user.gid == 0 {
+        return Err(format!(
+            "SANDBOX_USER {} is root; programs must run unprivileged",
+            spec
+        ));
+    }
+    Ok(user)
+}
+
+// SANDBOX_USER, a user name or "uid:gid": the unprivileged account compiled
+// programs run as instead of the service's. Unset, programs run as the
+// service. Every program shares the account, so it keeps programs away
+// from the service and the executor's files, not from each other.
+pub fn configured() -> Result<Option<SandboxUser>, String> {
+    static USER: OnceLock<Result<Option<SandboxUser>, String>> = OnceLock::new();
+    USER.get_or_init(|| {
+        match env::var("SANDBOX_USER")
+            .ok()
+            .map(|spec| spec.trim().to_string())
+            .filter(|spec| !spec.is_empty())
+        {
+            Some(spec) => parse(&spec).map(Some),
+            None => Ok(None),
+        }
+    })
+    .clone()
+}
+
+// Which of REQUIRED_CAPABILITIES the service lacks, from CapEff in
+// /proc/self/status
+pub fn missing_capabilities() -> Vec<&'static str> {
+    let effective = fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))
+                .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        })
+        .unwrap_or(0);
+    REQUIRED_CAPABILITIES
+        .iter()
+        .filter(|(bit, _)| effective & (1 << bit) == 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+// Lets the sandbox user reach the binary: it owns the working directory,
+// where it may write output files, and may traverse (not list) the
+// directories leading to a binary stored elsewhere, such as an artifact's
+fn grant_access(
+    user: SandboxUser,
+    executable_path: &Path,
+    working_dir: Option<&Path>,
+) -> io::Result<()> {
+    if let Some(working_dir) = working_dir {
+        chown(working_dir, Some(user.uid), Some(user.gid))?;
+        if executable_path.starts_with(working_dir) {
+            return Ok(());
+        }
+    }
+    let temp_root = env::temp_dir();
+    for dir in executable_path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(&temp_root) && *dir != temp_root)
+    {
+        let mut permissions = fs::metadata(dir)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o001);
+        fs::set_permissions(dir, permissions)?;
+    }
+    // Readable too, for the symbols in panic backtraces
+    let mut permissions = fs::metadata(executable_path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o005);
+    fs::set_permissions(executable_path, permissions)
+}
+
+// Makes `cmd` drop to the sandbox user, if one is configured, right before
+// it executes. A misconfigured or failed hand-over fails the spawn rather
+// than running the program with the service's privileges.
+pub fn apply(cmd: &mut Command, executable_path: &Path, working_dir: Option<&Path>) {
+    let user = match configured() {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            // SAFETY: only builds an error value
+            unsafe {
+                cmd.pre_exec(move || Err(io::Error::other(e.clone())));
+            }
+            return;
+        }
+    };
+    if let Err(e) = grant_access(user, executable_path, working_dir) {
+        let message = format!("Failed to hand the program to SANDBOX_USER: {}", e);
+        // SAFETY: as above
+        unsafe {
+            cmd.pre_exec(move || Err(io::Error::other(message.clone())));
+        }
+        return;
+    }
+    if working_dir.is_none() {
+        // Rather than the service's working directory
+        cmd.current_dir("/");
+    }
+    // SAFETY: setgroups, setgid, setuid and prctl are async-signal-safe and
+    // touch no memory shared with the parent
+    unsafe {
+        cmd.pre_exec(move || {
+            // Groups first, while still allowed to change them; setuid last
+            if libc::setgroups(0, std::ptr::null()) == -1
+                || libc::setgid(user.gid) == -1
+                || libc::setuid(user.uid) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            // setuid binaries in the image cannot win the privileges back
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{CodeExecutionRequest, ExecutionStatus, RustExecutor};
+use serde::Serialize;
+use std::time::Instant;
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const SELFTEST_PROGRAM: &str = r#"fn main() {
+    let greeting = ["Hello", "world"].join(", ");
+    println!("{}!", greeting);
+}"#;
+const SELFTEST_OUTPUT: &str = "Hello, world!";
+
+#[derive(Serialize, ToSchema)]
+pub struct SelfTestTimings {
+    // From the start of the request to the response, admission included
+    #[serde(rename = "totalMs")]
+    total_ms: f64,
+    // null when the pipeline failed before reaching the step
+    #[serde(rename = "compileMs")]
+    compile_ms: Option<f64>,
+    #[serde(rename = "runMs")]
+    run_ms: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SelfTestResponse {
+    // Whether the program built, ran and printed what it should
+    passed: bool,
+    status: ExecutionStatus,
+    // Why it did not pass
+    error: Option<String>,
+    #[serde(rename = "rustcVersion")]
+    rustc_version: Option<String>,
+    timings: SelfTestTimings,
+}
+
+impl RustExecutor {
+    // Runs a canned program through the same path as /execute (admission,
+    // tracking, the backend) without recording it in the history, audit log
+    // or quotas
+    pub async fn self_test(&self) -> SelfTestResponse {
+        let start = Instant::now();
+        let request = CodeExecutionRequest {
+            code: SELFTEST_PROGRAM.to_string(),
+            ..Default::default()
+        };
+        let (response, _) = self.run_execution(request, Some("selftest"), None).await;
+        let error = if !response.status.is_success() {
+            Some(response.error)
+        } else if response.output.trim_end() != SELFTEST_OUTPUT {
+            Some(format!(
+                "Expected output {:?}, got {:?}",
+                SELFTEST_OUTPUT, response.output
+            ))
+        } else {
+            None
+        };
+        SelfTestResponse {
+            passed: error.is_none(),
+            status: response.status,
+            error,
+            rustc_version: self.toolchain.rustc.clone(),
+            timings: SelfTestTimings {
+                total_ms: start.elapsed().as_secs_f64() * 1000.0,
+                compile_ms: response.compile_time_ms,
+                run_ms: response.run_time_ms,
+            },
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/selftest",
+    params(("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")),
+    responses(
+        (status = 200, description = "The canned program built, ran and printed the expected output", body = SelfTestResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 503, description = "The self-test failed; see error", body = SelfTestResponse)
+    )
+)]
+pub async fn selftest(
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let result = executor.self_test().await;
+    if result.passed {
+        println!("Self-test passed in {:.0}ms", result.timings.total_ms);
+    } else {
+        eprintln!(
+            "Self-test failed: {}",
+            result.error.as_deref().unwrap_or_default()
+        );
+    }
+    let status = if result.passed {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&result), status))
+}
@@ -0,0 +1,189 @@
+use quote::ToTokens;
+use syn::{FnArg, Item, ItemFn, Signature, Visibility};
+
+const MAX_REQUIRED_SIGNATURES: usize = 50;
+
+// Tokens printed the way rustc prints them in messages, e.g. `&mut Vec<i32>`
+fn render(tokens: &impl ToTokens) -> String {
+    tokens
+        .to_token_stream()
+        .to_string()
+        .replace(" < ", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace("& ", "&")
+        .replace(" ,", ",")
+        .replace(" (", "(")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(" :: ", "::")
+        .replace(" : ", ": ")
+        .replace(" ;", ";")
+}
+
+fn describe(vis: &Visibility, sig: &Signature) -> String {
+    let vis = render(vis);
+    let sig = render(sig);
+    if vis.is_empty() {
+        sig
+    } else {
+        format!("{} {}", vis, sig)
+    }
+}
+
+// Parameter types only: the student may name parameters as they like
+fn parameter_types(sig: &Signature) -> Vec<String> {
+    sig.inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Receiver(receiver) => render(receiver),
+            FnArg::Typed(typed) => render(&typed.ty),
+        })
+        .collect()
+}
+
+// "pub fn sort(v: &mut Vec<i32>)" as a function with an empty body
+fn parse_required(required: &str) -> Result<ItemFn, String> {
+    let required = required.trim().trim_end_matches(';');
+    syn::parse_str::<ItemFn>(&format!("{} {{}}", required)).map_err(|_| {
+        format!(
+            "Invalid required signature '{}': expected a function signature such as `pub fn sort(v: &mut Vec<i32>)`",
+            required
+        )
+    })
+}
+
+// What is wrong with `found` compared to `expected`; empty when it matches
+fn mismatches(expected: &ItemFn, found: &ItemFn) -> Vec<String> {
+    let (expected_sig, found_sig) = (&expected.sig, &found.sig);
+    let mut problems = Vec::new();
+    if matches!(expected.vis, Visibility::Public(_)) && !matches!(found.vis, Visibility::Public(_))
+    {
+        problems.push("it must be `pub`".to_string());
+    }
+    for (keyword, wanted, present) in [
+        (
+            "async",
+            expected_sig.asyncness.is_some(),
+            found_sig.asyncness.is_some(),
+        ),
+        (
+            "unsafe",
+            expected_sig.unsafety.is_some(),
+            found_sig.unsafety.is_some(),
+        ),
+        (
+            "const",
+            expected_sig.constness.is_some(),
+            found_sig.constness.is_some(),
+        ),
+    ] {
+        if wanted && !present {
+            problems.push(format!("it must be `{}`", keyword));
+        } else if present && !wanted {
+            problems.push(format!("it must not be `{}`", keyword));
+        }
+    }
+    if render(&expected_sig.generics) != render(&found_sig.generics)
+        || render(&expected_sig.generics.where_clause) != render(&found_sig.generics.where_clause)
+    {
+        problems.push(format!(
+            "generic parameters are `{}`, expected `{}`",
+            render(&found_sig.generics),
+            render(&expected_sig.generics)
+        ));
+    }
+    let (expected_params, found_params) =
+        (parameter_types(expected_sig), parameter_types(found_sig));
+    if expected_params.len() != found_params.len() {
+        problems.push(format!(
+            "it takes {} parameter(s), expected {}",
+            found_params.len(),
+            expected_params.len()
+        ));
+    } else {
+        for (index, (expected, found)) in expected_params.iter().zip(&found_params).enumerate() {
+            if expected != found {
+                problems.push(format!(
+                    "parameter {} is `{}`, expected `{}`",
+                    index + 1,
+                    found,
+                    expected
+                ));
+            }
+        }
+    }
+    if render(&expected_sig.output) != render(&found_sig.output) {
+        let shown = |output: &syn::ReturnType| match output {
+            syn::ReturnType::Default => "()".to_string(),
+            syn::ReturnType::Type(_, ty) => render(ty),
+        };
+        problems.push(format!(
+            "it returns `{}`, expected `{}`",
+            shown(&found_sig.output),
+            shown(&expected_sig.output)
+        ));
+    }
+    problems
+}
+
+// Checks that the top-level functions an assignment requires exist in the
+// student's code with the given signatures, so a missing or misnamed
+// function is reported as such instead of as compile errors in the harness
+// that calls it. Returns every problem found, one per line.
+pub fn verify(code: &str, required: &[String]) -> Result<(), String> {
+    if required.is_empty() {
+        return Ok(());
+    }
+    if required.len() > MAX_REQUIRED_SIGNATURES {
+        return Err(format!(
+            "At most {} required signatures are allowed",
+            MAX_REQUIRED_SIGNATURES
+        ));
+    }
+    let required = required
+        .iter()
+        .map(|required| parse_required(required))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Snippets without items are left to the compiler
+    let Ok(file) = syn::parse_file(code) else {
+        return Ok(());
+    };
+    let functions: Vec<&ItemFn> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Fn(function) => Some(function),
+            _ => None,
+        })
+        .collect();
+
+    let mut problems = Vec::new();
+    for expected in &required {
+        let name = &expected.sig.ident;
+        let wanted = describe(&expected.vis, &expected.sig);
+        match functions.iter().find(|found| &found.sig.ident == name) {
+            None => problems.push(format!(
+                "Missing function `{}`: expected `{}`",
+                name, wanted
+            )),
+            Some(found) => {
+                let wrong = mismatches(expected, found);
+                if !wrong.is_empty() {
+                    problems.push(format!(
+                        "Wrong signature for `{}`: expected `{}`, found `{}` ({})",
+                        name,
+                        wanted,
+                        describe(&found.vis, &found.sig),
+                        wrong.join("; ")
+                    ));
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
@@ -0,0 +1,373 @@
+use crate::RustExecutor;
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use syn::visit_mut::VisitMut;
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const DEFAULT_KGRAM_SIZE: usize = 5;
+const DEFAULT_WINDOW_SIZE: usize = 4;
+const MAX_SUBMISSIONS: usize = 200;
+
+#[derive(Deserialize, ToSchema)]
+pub struct Submission {
+    id: String,
+    code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SimilarityRequest {
+    submissions: Vec<Submission>,
+    // Tokens per fingerprinted k-gram; smaller values find shorter matches
+    #[serde(rename = "kgramSize")]
+    kgram_size: Option<usize>,
+    // Winnowing window, in k-grams; a match of kgramSize + windowSize - 1
+    // tokens is always detected
+    #[serde(rename = "windowSize")]
+    window_size: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SubmissionFingerprint {
+    id: String,
+    tokens: usize,
+    fingerprints: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SimilarityPair {
+    a: String,
+    b: String,
+    // Shared fingerprints over the fingerprints of both submissions (Jaccard)
+    similarity: f64,
+    // Share of a's fingerprints also found in b, and the other way round
+    #[serde(rename = "aInB")]
+    a_in_b: f64,
+    #[serde(rename = "bInA")]
+    b_in_a: f64,
+    #[serde(rename = "sharedFingerprints")]
+    shared_fingerprints: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SimilarityResponse {
+    submissions: Vec<SubmissionFingerprint>,
+    // Every pair of submissions, most similar first
+    pairs: Vec<SimilarityPair>,
+}
+
+// Macro arguments are not parsed, so identifiers inside them are found on
+// the raw tokens; syn refuses to parse keywords as identifiers
+fn erase_token_identifiers(stream: TokenStream) -> TokenStream {
+    stream
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Group(group) => {
+                let mut erased = proc_macro2::Group::new(
+                    group.delimiter(),
+                    erase_token_identifiers(group.stream()),
+                );
+                erased.set_span(group.span());
+                TokenTree::Group(erased)
+            }
+            TokenTree::Ident(ident) if syn::parse2::<Ident>(ident.to_token_stream()).is_ok() => {
+                TokenTree::Ident(Ident::new("v", ident.span()))
+            }
+            other => other,
+        })
+        .collect()
+}
+
+// Renames every identifier to the same placeholder, so consistently
+// renaming variables or functions does not hide a copy
+struct IdentifierEraser;
+
+impl VisitMut for IdentifierEraser {
+    fn visit_ident_mut(&mut self, ident: &mut Ident) {
+        *ident = Ident::new("v", ident.span());
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        syn::visit_mut::visit_macro_mut(self, mac);
+        mac.tokens = erase_token_identifiers(std::mem::take(&mut mac.tokens));
+    }
+}
+
+fn flatten_tokens(stream: TokenStream, tokens: &mut Vec<String>) {
+    for tree in stream {
+        match tree {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    proc_macro2::Delimiter::Parenthesis => ("(", ")"),
+                    proc_macro2::Delimiter::Brace => ("{", "}"),
+                    proc_macro2::Delimiter::Bracket => ("[", "]"),
+                    proc_macro2::Delimiter::None => ("", ""),
+                };
+                tokens.push(open.to_string());
+                flatten_tokens(group.stream(), tokens);
+                tokens.push(close.to_string());
+            }
+            other => tokens.push(other.to_string()),
+        }
+    }
+}
+
+// Comments and layout disappear when the code is parsed; identifiers are
+// erased through the syntax tree. Snippets without items are parsed as the
+// body of main, the same way /execute wraps them.
+fn normalized_tokens(code: &str) -> Result<Vec<String>, String> {
+    let mut file = syn::parse_file(code)
+        .or_else(|_| syn::parse_file(&format!("fn main() {{\n{}\n}}", code)))
+        .map_err(|e| e.to_string())?;
+    IdentifierEraser.visit_file_mut(&mut file);
+
+    let mut tokens = Vec::new();
+    flatten_tokens(file.to_token_stream(), &mut tokens);
+    tokens.retain(|token| !token.is_empty());
+    Ok(tokens)
+}
+
+fn hash_kgram(kgram: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    kgram.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Winnowing (Schleimer, Wilkerson and Aiken): from every window of k-gram
+// hashes keep the minimum
+fn winnow(tokens: &[String], kgram_size: usize, window_size: usize) -> HashSet<u64> {
+    let hashes: Vec<u64> = tokens.windows(kgram_size).map(hash_kgram).collect();
+    if hashes.len() <= window_size {
+        return hashes.into_iter().collect();
+    }
+    hashes
+        .windows(window_size)
+        .filter_map(|window| window.iter().min().copied())
+        .collect()
+}
+
+fn ratio(shared: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
+
+fn compare_submissions(
+    req: SimilarityRequest,
+    max_code_size_kb: u32,
+) -> Result<SimilarityResponse, String> {
+    if req.submissions.len() < 2 {
+        return Err("At least two submissions are required".to_string());
+    }
+    if req.submissions.len() > MAX_SUBMISSIONS {
+        return Err(format!(
+            "Too many submissions ({}), at most {} are allowed",
+            req.submissions.len(),
+            MAX_SUBMISSIONS
+        ));
+    }
+    let kgram_size = req.kgram_size.unwrap_or(DEFAULT_KGRAM_SIZE).clamp(1, 50);
+    let window_size = req.window_size.unwrap_or(DEFAULT_WINDOW_SIZE).clamp(1, 50);
+
+    let mut fingerprinted = Vec::new();
+    for submission in req.submissions {
+        let code_size_kb = submission.code.len() as f64 / 1024.0;
+        if code_size_kb > max_code_size_kb as f64 {
+            return Err(format!(
+                "Submission '{}' ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                submission.id, code_size_kb, max_code_size_kb
+            ));
+        }
+        let tokens = normalized_tokens(&submission.code)
+            .map_err(|e| format!("Failed to parse submission '{}': {}", submission.id, e))?;
+        let fingerprints = winnow(&tokens, kgram_size, window_size);
+        fingerprinted.push((submission.id, tokens.len(), fingerprints));
+    }
+
+    let mut pairs = Vec::new();
+    for (i, (a, _, a_prints)) in fingerprinted.iter().enumerate() {
+        for (b, _, b_prints) in &fingerprinted[i + 1..] {
+            let shared = a_prints.intersection(b_prints).count();
+            pairs.push(SimilarityPair {
+                a: a.clone(),
+                b: b.clone(),
+                similarity: ratio(shared, a_prints.len() + b_prints.len() - shared),
+                a_in_b: ratio(shared, a_prints.len()),
+                b_in_a: ratio(shared, b_prints.len()),
+                shared_fingerprints: shared,
+            });
+        }
+    }
+    pairs.sort_by(|x, y| y.similarity.total_cmp(&x.similarity));
+
+    Ok(SimilarityResponse {
+        submissions: fingerprinted
+            .into_iter()
+            .map(|(id, tokens, fingerprints)| SubmissionFingerprint {
+                id,
+                tokens,
+                fingerprints: fingerprints.len(),
+            })
+            .collect(),
+        pairs,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/similarity",
+    request_body = SimilarityRequest,
+    responses(
+        (status = 200, body = SimilarityResponse),
+        (status = 400, description = "Too few submissions or one failed to parse", body = ErrorResponse)
+    )
+)]
+pub async fn similarity(
+    req: SimilarityRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let max_code_size_kb = executor.config().max_code_size_kb;
+    // Pairwise comparison of many submissions is CPU-bound
+    let result = tokio::task::spawn_blocking(move || compare_submissions(req, max_code_size_kb))
+        .await
+        .unwrap_or_else(|e| Err(format!("Similarity check failed: {}", e)));
+    let reply = match result {
+        Ok(response) => warp::reply::with_status(warp::reply::json(&response), StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            StatusCode::BAD_REQUEST,
+        ),
+    };
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(code: &str) -> Vec<String> {
+        normalized_tokens(code).unwrap()
+    }
+
+    fn request(codes: &[&str]) -> SimilarityRequest {
+        SimilarityRequest {
+            submissions: codes
+                .iter()
+                .enumerate()
+                .map(|(i, code)| Submission {
+                    id: i.to_string(),
+                    code: code.to_string(),
+                })
+                .collect(),
+            kgram_size: None,
+            window_size: None,
+        }
+    }
+
+    #[test]
+    fn renaming_identifiers_gives_the_same_tokens() {
+        assert_eq!(
+            tokens("fn add(a: i32, b: i32) -> i32 { a + b }"),
+            tokens("fn sum(left: i32, right: i32) -> i32 { left + right }")
+        );
+    }
+
+    #[test]
+    fn comments_and_layout_are_ignored() {
+        assert_eq!(
+            tokens("fn main() { let x = 1; }"),
+            tokens("// entry point\nfn main() {\n    /* one */\n    let x =\n        1;\n}\n")
+        );
+    }
+
+    #[test]
+    fn macro_arguments_are_erased_but_not_keywords() {
+        let erased = tokens("fn main() { let total = 2; println!(\"{}\", total as u8); }");
+        assert!(!erased.iter().any(|token| token == "total"));
+        assert!(erased.iter().any(|token| token == "as"));
+        assert_eq!(
+            erased,
+            tokens("fn main() { let count = 2; println!(\"{}\", count as u8); }")
+        );
+    }
+
+    #[test]
+    fn literals_are_kept() {
+        assert_ne!(tokens("fn main() { f(1); }"), tokens("fn main() { f(2); }"));
+    }
+
+    #[test]
+    fn snippets_are_parsed_as_the_body_of_main() {
+        assert_eq!(tokens("let x = 1;"), tokens("fn main() { let y = 1; }"));
+    }
+
+    #[test]
+    fn unparsable_code_is_an_error() {
+        assert!(normalized_tokens("fn main( {").is_err());
+    }
+
+    #[test]
+    fn winnowing_keeps_one_hash_per_window() {
+        let tokens: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let fingerprints = winnow(&tokens, 5, 4);
+        // 16 k-grams, so 13 windows, each keeping one of its hashes
+        assert!(!fingerprints.is_empty() && fingerprints.len() <= 13);
+        let hashes: HashSet<u64> = tokens.windows(5).map(hash_kgram).collect();
+        assert!(fingerprints.is_subset(&hashes));
+    }
+
+    #[test]
+    fn inputs_shorter_than_a_window_keep_every_kgram() {
+        let tokens: Vec<String> = ["a", "b", "c", "d", "e", "f"].map(String::from).to_vec();
+        assert_eq!(winnow(&tokens, 5, 4).len(), 2);
+        assert!(winnow(&tokens[..4], 5, 4).is_empty());
+    }
+
+    #[test]
+    fn a_shared_run_of_k_plus_w_minus_1_tokens_is_detected() {
+        let shared: Vec<String> = (0..8).map(|i| format!("s{}", i)).collect();
+        let a: Vec<String> = ["x", "y", "z"]
+            .map(String::from)
+            .into_iter()
+            .chain(shared.iter().cloned())
+            .collect();
+        let b: Vec<String> = shared
+            .iter()
+            .cloned()
+            .chain(["p", "q", "r", "t"].map(String::from))
+            .collect();
+        let (a, b) = (winnow(&a, 5, 4), winnow(&b, 5, 4));
+        assert!(a.intersection(&b).next().is_some());
+    }
+
+    #[test]
+    fn a_renamed_copy_is_fully_similar() {
+        let response = compare_submissions(
+            request(&[
+                "fn main() { let mut total = 0; for i in 0..10 { total += i * i; } println!(\"{}\", total); }",
+                "fn main() { let mut acc = 0; for n in 0..10 { acc += n * n; } println!(\"{}\", acc); }",
+                "fn main() { let v: Vec<u8> = Vec::new(); if v.is_empty() { return; } }",
+            ]),
+            64,
+        )
+        .unwrap();
+        let top = &response.pairs[0];
+        assert_eq!((top.a.as_str(), top.b.as_str()), ("0", "1"));
+        assert_eq!(top.similarity, 1.0);
+        assert!(response.pairs[1].similarity < 1.0);
+    }
+
+    #[test]
+    fn requests_are_checked() {
+        assert!(compare_submissions(request(&["fn main() {}"]), 64).is_err());
+        assert!(compare_submissions(request(&["fn main() {}", "fn main( {"]), 64).is_err());
+        let large = "fn main() {}".repeat(200);
+        assert!(compare_submissions(request(&[&large, "fn main() {}"]), 1).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// How an execution ended. Clients classify failures by this rather than by
+/// the text of `error`.
+///
+/// - `success`: compiled, ran and exited with code 0
+/// - `compile_error`: the build or the lint pass failed, or the code broke
+///   the request's signatures or rules
+/// - `runtime_error`: the program exited with a non-zero code or a signal
+/// - `timeout`: a compile, wall-clock or CPU time limit was hit; see
+///   `limitExceeded`
+/// - `memory_limit`: the program ran past max_memory_mb
+/// - `output_limit`: the program wrote more than max_output_kb to stdout or
+///   stderr and was stopped
+/// - `stack_overflow`: the program used up its stack
+/// - `security_violation`: the request or code tried something the service
+///   forbids, e.g. unsafe code under forbidUnsafe or a disallowed
+///   environment variable
+/// - `invalid_request`: the request itself was malformed or over a limit
+/// - `killed`: an operator stopped the execution
+/// - `capacity`: the host was overloaded and turned the execution away
+/// - `quota_exceeded`: the tenant's quota was used up
+/// - `internal_error`: the service failed, e.g. it could not spawn the
+///   program or write its sources
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    Success,
+    CompileError,
+    RuntimeError,
+    Timeout,
+    MemoryLimit,
+    OutputLimit,
+    StackOverflow,
+    SecurityViolation,
+    InvalidRequest,
+    Killed,
+    Capacity,
+    QuotaExceeded,
+    InternalError,
+}
+
+impl ExecutionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExecutionStatus::Success => "success",
+            ExecutionStatus::CompileError => "compile_error",
+            ExecutionStatus::RuntimeError => "runtime_error",
+            ExecutionStatus::Timeout => "timeout",
+            ExecutionStatus::MemoryLimit => "memory_limit",
+            ExecutionStatus::OutputLimit => "output_limit",
+            ExecutionStatus::StackOverflow => "stack_overflow",
+            ExecutionStatus::SecurityViolation => "security_violation",
+            ExecutionStatus::InvalidRequest => "invalid_request",
+            ExecutionStatus::Killed => "killed",
+            ExecutionStatus::Capacity => "capacity",
+            ExecutionStatus::QuotaExceeded => "quota_exceeded",
+            ExecutionStatus::InternalError => "internal_error",
+        }
+    }
+
+    pub fn is_success(self) -> bool {
+        self == ExecutionStatus::Success
+    }
+
+    // What the v1 API reported, before failures were told apart
+    pub fn v1_name(self) -> &'static str {
+        match self {
+            ExecutionStatus::CompileError
+            | ExecutionStatus::RuntimeError
+            | ExecutionStatus::OutputLimit
+            | ExecutionStatus::SecurityViolation
+            | ExecutionStatus::InvalidRequest
+            | ExecutionStatus::InternalError => "error",
+            status => status.as_str(),
+        }
+    }
+}
+
+impl std::fmt::Display for ExecutionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// A failure outside the program's own run, such as a rejected request or a
+// failed build, with the status it is reported under. Plain messages are
+// validation errors.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: ExecutionStatus,
+    pub message: String,
+}
+
+impl StatusError {
+    pub fn new(status: ExecutionStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ExecutionStatus::InternalError, message)
+    }
+}
+
+impl From<String> for StatusError {
+    fn from(message: String) -> Self {
+        Self::new(ExecutionStatus::InvalidRequest, message)
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -0,0 +1,342 @@
+use crate::RustExecutor;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Expr, Token, UseTree};
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+const STD_ROOTS: &[&str] = &["std", "core", "alloc"];
+
+// Types in scope without a `use`, resolved to where they live
+const PRELUDE_TYPES: &[(&str, &str)] = &[
+    ("Vec", "std::vec::Vec"),
+    ("String", "std::string::String"),
+    ("Box", "std::boxed::Box"),
+    ("Option", "std::option::Option"),
+    ("Result", "std::result::Result"),
+];
+
+const STD_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "concat",
+    "dbg",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "env",
+    "eprint",
+    "eprintln",
+    "format",
+    "format_args",
+    "include",
+    "include_bytes",
+    "include_str",
+    "matches",
+    "panic",
+    "print",
+    "println",
+    "stringify",
+    "thread_local",
+    "todo",
+    "unimplemented",
+    "unreachable",
+    "vec",
+    "write",
+    "writeln",
+];
+
+#[derive(Deserialize, ToSchema)]
+pub struct StdUsageRequest {
+    code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StdItem {
+    // "std::collections::HashMap::new", "sort" or "println"
+    pub name: String,
+    // "path" for items reached through a path or a `use`, "method" for
+    // method calls and "macro"
+    pub kind: String,
+    count: usize,
+    // Lines of the first uses
+    pub lines: Vec<usize>,
+    // A method the submission also defines, so some of these calls may be
+    // to its own
+    #[serde(rename = "definedLocally")]
+    defined_locally: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StdUsageResponse {
+    // Sorted by kind, then name
+    pub items: Vec<StdItem>,
+}
+
+const MAX_LINES_PER_ITEM: usize = 20;
+
+// Names the submission defines itself, which shadow the prelude and std
+// macros
+#[derive(Default)]
+struct LocalNames {
+    items: HashSet<String>,
+    methods: HashSet<String>,
+    macros: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for LocalNames {
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        let name = match item {
+            syn::Item::Struct(item) => Some(&item.ident),
+            syn::Item::Enum(item) => Some(&item.ident),
+            syn::Item::Type(item) => Some(&item.ident),
+            syn::Item::Trait(item) => Some(&item.ident),
+            syn::Item::Fn(item) => Some(&item.sig.ident),
+            syn::Item::Macro(item) => {
+                if let Some(name) = &item.ident {
+                    self.macros.insert(name.to_string());
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(name) = name {
+            self.items.insert(name.to_string());
+        }
+        visit::visit_item(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.methods.insert(item.sig.ident.to_string());
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        self.methods.insert(item.sig.ident.to_string());
+        visit::visit_trait_item_fn(self, item);
+    }
+}
+
+struct UsageCollector<'a> {
+    local: &'a LocalNames,
+    // Name brought into scope by a `use` of std, e.g. "HashMap" or an alias
+    imports: HashMap<String, String>,
+    // (kind, name) -> lines
+    uses: BTreeMap<(&'static str, String), Vec<usize>>,
+    // 1 when the code was wrapped in `fn main() {` to parse it
+    line_offset: usize,
+}
+
+impl UsageCollector<'_> {
+    fn record(&mut self, kind: &'static str, name: String, span: proc_macro2::Span) {
+        let line = span.start().line.saturating_sub(self.line_offset).max(1);
+        self.uses.entry((kind, name)).or_default().push(line);
+    }
+
+    fn collect_imports(&mut self, tree: &UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.collect_imports(&path.tree, prefix);
+                prefix.pop();
+            }
+            UseTree::Name(name) => self.import(prefix, &name.ident, &name.ident, tree.span()),
+            UseTree::Rename(rename) => {
+                self.import(prefix, &rename.ident, &rename.rename, tree.span())
+            }
+            UseTree::Glob(glob) => {
+                if prefix
+                    .first()
+                    .is_some_and(|root| STD_ROOTS.contains(&root.as_str()))
+                {
+                    self.record("path", format!("{}::*", prefix.join("::")), glob.span());
+                }
+            }
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.collect_imports(tree, prefix);
+                }
+            }
+        }
+    }
+
+    fn import(
+        &mut self,
+        prefix: &[String],
+        ident: &syn::Ident,
+        alias: &syn::Ident,
+        span: proc_macro2::Span,
+    ) {
+        // `use std::{self}` and `use std::io::{self, Read}`
+        let mut path = prefix.to_vec();
+        if ident != "self" {
+            path.push(ident.to_string());
+        }
+        if !path
+            .first()
+            .is_some_and(|root| STD_ROOTS.contains(&root.as_str()))
+        {
+            return;
+        }
+        let alias = if alias == "self" {
+            path.last().cloned().unwrap_or_default()
+        } else {
+            alias.to_string()
+        };
+        let path = path.join("::");
+        self.imports.insert(alias, path.clone());
+        self.record("path", path, span);
+    }
+
+    // The std path a path in the code refers to, if any
+    fn resolve(&self, path: &syn::Path) -> Option<String> {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let first = segments.first()?;
+        let root = if path.leading_colon.is_none() && !STD_ROOTS.contains(&first.as_str()) {
+            match self.imports.get(first) {
+                Some(imported) => imported.clone(),
+                None if self.local.items.contains(first) => return None,
+                None => PRELUDE_TYPES
+                    .iter()
+                    .find(|(name, _)| name == first)
+                    .map(|(_, path)| path.to_string())?,
+            }
+        } else if STD_ROOTS.contains(&first.as_str()) {
+            first.clone()
+        } else {
+            return None;
+        };
+        Some(
+            std::iter::once(root)
+                .chain(segments.into_iter().skip(1))
+                .collect::<Vec<_>>()
+                .join("::"),
+        )
+    }
+}
+
+impl<'ast> Visit<'ast> for UsageCollector<'_> {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        self.collect_imports(&item.tree, &mut Vec::new());
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        if let Some(resolved) = self.resolve(path) {
+            self.record("path", resolved, path.span());
+        }
+        visit::visit_path(self, path);
+    }
+
+    // Without type information the receiver is unknown, so method calls are
+    // reported by name
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        self.record("method", call.method.to_string(), call.method.span());
+        visit::visit_expr_method_call(self, call);
+    }
+
+    // Macro arguments are plain tokens to syn; the comma-separated ones of
+    // println!, format!, vec! and friends are parsed as expressions so the
+    // calls inside them are seen too
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let name = mac
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default();
+        if STD_MACROS.contains(&name.as_str()) && !self.local.macros.contains(&name) {
+            self.record("macro", name, mac.path.span());
+        }
+        if let Ok(args) = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        }
+    }
+}
+
+pub fn collect_usage(code: &str) -> Result<StdUsageResponse, String> {
+    // Snippets are analysed as the body of main, the way /execute runs them
+    let (file, line_offset) = match syn::parse_file(code) {
+        Ok(file) => (file, 0),
+        Err(e) => (
+            syn::parse_file(&format!("fn main() {{\n{}\n}}", code))
+                .map_err(|_| format!("Failed to parse code: {}", e))?,
+            1,
+        ),
+    };
+
+    let mut local = LocalNames::default();
+    local.visit_file(&file);
+    let mut collector = UsageCollector {
+        local: &local,
+        imports: HashMap::new(),
+        uses: BTreeMap::new(),
+        line_offset,
+    };
+    // Imports first, so a `use` below the code that relies on it still
+    // resolves
+    for item in &file.items {
+        if let syn::Item::Use(item) = item {
+            collector.collect_imports(&item.tree, &mut Vec::new());
+        }
+    }
+    collector.uses.clear();
+    collector.visit_file(&file);
+
+    let items = collector
+        .uses
+        .into_iter()
+        .map(|((kind, name), mut lines)| {
+            let count = lines.len();
+            lines.sort_unstable();
+            lines.dedup();
+            lines.truncate(MAX_LINES_PER_ITEM);
+            StdItem {
+                defined_locally: kind == "method" && local.methods.contains(&name),
+                name,
+                kind: kind.to_string(),
+                count,
+                lines,
+            }
+        })
+        .collect();
+    Ok(StdUsageResponse { items })
+}
+
+#[utoipa::path(
+    post,
+    path = "/std-usage",
+    request_body = StdUsageRequest,
+    responses(
+        (status = 200, body = StdUsageResponse),
+        (status = 400, description = "Code too large or not parseable", body = ErrorResponse)
+    )
+)]
+pub async fn std_usage(
+    req: StdUsageRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let max_code_size_kb = executor.config().max_code_size_kb;
+    let code_size_kb = req.code.len() as f64 / 1024.0;
+    let result = if code_size_kb > max_code_size_kb as f64 {
+        Err(format!(
+            "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+            code_size_kb, max_code_size_kb
+        ))
+    } else {
+        collect_usage(&req.code)
+    };
+    let reply = match result {
+        Ok(usage) => warp::reply::with_status(warp::reply::json(&usage), StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            StatusCode::BAD_REQUEST,
+        ),
+    };
+    Ok(reply)
+}
@@ -0,0 +1,142 @@
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use std::env;
+use std::time::Duration;
+
+const STORAGE_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// S3-compatible object storage (AWS S3, MinIO, ...) configured through
+// S3_BUCKET, S3_REGION, S3_ENDPOINT, S3_ACCESS_KEY_ID, S3_SECRET_ACCESS_KEY
+// and S3_PREFIX. A custom endpoint is addressed path-style, as MinIO
+// expects.
+pub struct ObjectStorage {
+    bucket: Box<Bucket>,
+    prefix: String,
+}
+
+fn storage_error(e: impl std::fmt::Display) -> String {
+    format!("Object storage error: {}", e)
+}
+
+impl ObjectStorage {
+    // None when S3_BUCKET is not set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(bucket_name) = env::var("S3_BUCKET").ok().filter(|name| !name.is_empty()) else {
+            return Ok(None);
+        };
+        let region_name = env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = env::var("S3_ENDPOINT").ok().filter(|url| !url.is_empty());
+        let region = match &endpoint {
+            Some(endpoint) => Region::Custom {
+                region: region_name,
+                endpoint: endpoint.clone(),
+            },
+            None => region_name.parse().map_err(storage_error)?,
+        };
+        let credentials = Credentials::new(
+            env::var("S3_ACCESS_KEY_ID").ok().as_deref(),
+            env::var("S3_SECRET_ACCESS_KEY").ok().as_deref(),
+            None,
+            None,
+            None,
+        )
+        .map_err(storage_error)?;
+
+        let mut bucket = Bucket::new(&bucket_name, region, credentials).map_err(storage_error)?;
+        if endpoint.is_some() {
+            bucket = bucket.with_path_style();
+        }
+        bucket.set_request_timeout(Some(STORAGE_REQUEST_TIMEOUT));
+
+        let mut prefix = env::var("S3_PREFIX").unwrap_or_else(|_| "rust-executor/".to_string());
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        Ok(Some(Self { bucket, prefix }))
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    pub async fn put(&self, key: &str, content: &[u8]) -> Result<(), String> {
+        let response = self
+            .bucket
+            .put_object(self.path(key), content)
+            .await
+            .map_err(storage_error)?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => Err(storage_error(format!(
+                "PUT {} returned HTTP {}",
+                key, status
+            ))),
+        }
+    }
+
+    // None when the object does not exist
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let response = self
+            .bucket
+            .get_object(self.path(key))
+            .await
+            .map_err(storage_error)?;
+        match response.status_code() {
+            200..=299 => Ok(Some(response.to_vec())),
+            404 => Ok(None),
+            status => Err(storage_error(format!(
+                "GET {} returned HTTP {}",
+                key, status
+            ))),
+        }
+    }
+
+    // Keys under `prefix`, relative to S3_PREFIX like the other methods
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let objects = self.list_sizes(prefix).await?;
+        Ok(objects.into_iter().map(|(key, _)| key).collect())
+    }
+
+    // Like list, with each object's size in bytes
+    pub async fn list_sizes(&self, prefix: &str) -> Result<Vec<(String, u64)>, String> {
+        let pages = self
+            .bucket
+            .list(self.path(prefix), None)
+            .await
+            .map_err(storage_error)?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .filter_map(|object| {
+                let key = object.key.strip_prefix(&self.prefix)?.to_string();
+                Some((key, object.size))
+            })
+            .collect())
+    }
+
+    // Deleting an object that does not exist succeeds
+    pub async fn delete(&self, key: &str) -> Result<(), String> {
+        let response = self
+            .bucket
+            .delete_object(self.path(key))
+            .await
+            .map_err(storage_error)?;
+        match response.status_code() {
+            200..=299 | 404 => Ok(()),
+            status => Err(storage_error(format!(
+                "DELETE {} returned HTTP {}",
+                key, status
+            ))),
+        }
+    }
+
+    // A URL anyone can download the object from until it expires
+    pub async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, String> {
+        // Presigned URLs are valid for at most a week
+        let expiry_seconds = expires_in.as_secs().clamp(1, 604_800) as u32;
+        self.bucket
+            .presign_get(self.path(key), expiry_seconds, None)
+            .await
+            .map_err(storage_error)
+    }
+}
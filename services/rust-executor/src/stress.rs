@@ -0,0 +1,325 @@
+use crate::input_generator::{self, LineSpec};
+use crate::judge::{Comparison, OutputComparison};
+use crate::readiness::KILLED_MESSAGE;
+use crate::status::StatusError;
+use crate::{
+    check_cpu_time_limit, omit_middle_lines, BuildProfile, BuildSettings, ChildSetup,
+    ExecutionStatus, LimitExceeded, RunLimits, RustExecutor,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+const DEFAULT_TIME_BUDGET: u64 = 10;
+const MAX_TIME_BUDGET: u64 = 60;
+const DEFAULT_MAX_ITERATIONS: u64 = 1000;
+const MAX_ITERATIONS: u64 = 100_000;
+// Further inputs tried after the first mismatch, looking for a shorter one
+const SHRINK_ITERATIONS: u64 = 200;
+
+#[derive(Deserialize, ToSchema)]
+pub struct StressRequest {
+    code: String,
+    // Known to be correct; its output is the expected one
+    #[serde(rename = "referenceSolution")]
+    reference_solution: String,
+    // How each input is generated, as in /generate-inputs
+    lines: Vec<LineSpec>,
+    // Input i is generated from seed + i; random when absent
+    seed: Option<u64>,
+    // Seconds to spend running inputs, default 10, at most 60
+    #[serde(rename = "timeBudget")]
+    time_budget: Option<u64>,
+    // Inputs to try at most, default 1000
+    #[serde(rename = "maxIterations")]
+    max_iterations: Option<u64>,
+    // Wall-clock seconds per run
+    timeout: Option<u64>,
+    // CPU seconds per run, as for /execute
+    #[serde(rename = "cpuTimeLimit")]
+    cpu_time_limit: Option<u64>,
+    // As in /judge
+    comparison: Option<OutputComparison>,
+}
+
+// The shortest input found on which the code and the reference disagree
+#[derive(Serialize, ToSchema)]
+pub struct Counterexample {
+    input: String,
+    // /generate-inputs with this seed and count 1 gives the input again
+    seed: u64,
+    // The reference solution's output
+    #[serde(rename = "expectedOutput")]
+    expected_output: String,
+    output: String,
+    error: String,
+    // The code's run; a mismatch with a successful run is a wrong answer
+    status: ExecutionStatus,
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StressResponse {
+    // A mismatch was found
+    found: bool,
+    counterexample: Option<Counterexample>,
+    // Inputs generated
+    iterations: u64,
+    // Inputs that showed a mismatch. Once one is found, longer inputs are
+    // skipped rather than run.
+    mismatches: u64,
+    // Seed of the first input
+    seed: u64,
+    // The search ended at timeBudget rather than maxIterations or after
+    // shrinking. A run cut short by it counts in iterations but as neither
+    // a match nor a mismatch.
+    #[serde(rename = "timeBudgetExhausted")]
+    time_budget_exhausted: bool,
+    error: String,
+    #[serde(rename = "executionTime")]
+    execution_time: f64,
+    status: ExecutionStatus,
+}
+
+impl StressResponse {
+    fn error(error: impl Into<StatusError>, start_time: Instant) -> Self {
+        let error = error.into();
+        Self {
+            found: false,
+            counterexample: None,
+            iterations: 0,
+            mismatches: 0,
+            seed: 0,
+            time_budget_exhausted: false,
+            error: error.message,
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: error.status,
+        }
+    }
+
+    fn killed(start_time: Instant) -> Self {
+        Self {
+            status: ExecutionStatus::Killed,
+            ..Self::error(KILLED_MESSAGE.to_string(), start_time)
+        }
+    }
+}
+
+impl RustExecutor {
+    // Runs the code and the reference solution on generated inputs until
+    // they disagree or the budget is spent. After the first mismatch it
+    // keeps going for a while and reports the shortest failing input, which
+    // is the easiest to debug by hand.
+    pub async fn stress_test(&self, req: StressRequest) -> StressResponse {
+        let start_time = Instant::now();
+        let active = match self.start_execution("stress", None) {
+            Ok(active) => active,
+            Err(e) => return StressResponse::error(e, start_time),
+        };
+        let config = self.config();
+        let execution_timeout = req
+            .timeout
+            .filter(|&t| t <= config.max_request_timeout)
+            .unwrap_or(config.max_execution_time);
+        if let Err(e) = check_cpu_time_limit(&config, req.cpu_time_limit) {
+            return StressResponse::error(e, start_time);
+        }
+        // The memory limit applies to every run as it does elsewhere
+        let limits = RunLimits {
+            wall_seconds: execution_timeout,
+            cpu_seconds: req.cpu_time_limit,
+            deadline: None,
+        };
+
+        let code_size_kb = (req.code.len() + req.reference_solution.len()) as f64 / 1024.0;
+        if code_size_kb > config.max_code_size_kb as f64 {
+            return StressResponse::error(
+                format!(
+                    "Code size ({:.1}KB) exceeds maximum allowed size ({}KB)",
+                    code_size_kb, config.max_code_size_kb
+                ),
+                start_time,
+            );
+        }
+        if let Err(e) = self.check_unstable_features(
+            [req.code.as_str(), req.reference_solution.as_str()].into_iter(),
+        ) {
+            return StressResponse::error(e, start_time);
+        }
+        let time_budget = req.time_budget.unwrap_or(DEFAULT_TIME_BUDGET);
+        if time_budget == 0 || time_budget > MAX_TIME_BUDGET {
+            return StressResponse::error(
+                format!("timeBudget must be between 1 and {}", MAX_TIME_BUDGET),
+                start_time,
+            );
+        }
+        let max_iterations = req.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS);
+        if max_iterations == 0 || max_iterations > MAX_ITERATIONS {
+            return StressResponse::error(
+                format!("maxIterations must be between 1 and {}", MAX_ITERATIONS),
+                start_time,
+            );
+        }
+        let comparison = match Comparison::parse(req.comparison.as_ref()) {
+            Ok(comparison) => comparison,
+            Err(e) => return StressResponse::error(e, start_time),
+        };
+        let seed = req.seed.unwrap_or_else(rand::random);
+        // A spec that cannot generate anything is reported before building
+        if let Err(e) = input_generator::generate_one(seed, &req.lines) {
+            return StressResponse::error(e, start_time);
+        }
+
+        // Many short runs: worth optimizing for
+        let build = BuildSettings {
+            profile: BuildProfile::Release,
+            ..BuildSettings::default()
+        };
+        active.set_phase("compiling");
+        let built = active
+            .until_killed(async {
+                let code = self.build_for_comparison("code", &req.code, build).await?;
+                let reference = self
+                    .build_for_comparison("referenceSolution", &req.reference_solution, build)
+                    .await?;
+                Ok::<_, StatusError>((code, reference))
+            })
+            .await;
+        let ((code_dir, code_executable), (reference_dir, reference_executable)) = match built {
+            Some(Ok(built)) => built,
+            Some(Err(e)) => return StressResponse::error(e, start_time),
+            None => return StressResponse::killed(start_time),
+        };
+
+        active.set_phase("running");
+        let deadline = Instant::now() + Duration::from_secs(time_budget);
+        // Each run is also killed at the deadline; one cut short by it ends
+        // the search rather than counting against either program
+        let limits = RunLimits {
+            deadline: Some(tokio::time::Instant::from_std(deadline)),
+            ..limits
+        };
+        let mut time_budget_exhausted = false;
+        let max_output_lines = config.max_output_lines;
+        let mut iterations = 0;
+        let mut mismatches = 0;
+        let mut first_mismatch_at = None;
+        let mut counterexample: Option<Counterexample> = None;
+        while iterations < max_iterations
+            && first_mismatch_at.is_none_or(|at| iterations < at + SHRINK_ITERATIONS)
+        {
+            if Instant::now() >= deadline {
+                time_budget_exhausted = true;
+                break;
+            }
+            let input_seed = seed.wrapping_add(iterations);
+            let input = match input_generator::generate_one(input_seed, &req.lines) {
+                Ok(input) => input,
+                Err(e) => return StressResponse::error(e, start_time),
+            };
+            iterations += 1;
+            // Only a shorter input could replace the one already found
+            if counterexample
+                .as_ref()
+                .is_some_and(|found| input.len() >= found.input.len())
+            {
+                continue;
+            }
+
+            let setup = |dir| ChildSetup {
+                working_dir: Some(dir),
+                trace_log: None,
+                keep_stdin_open: false,
+            };
+            let reference = self
+                .run_executable(
+                    &reference_executable,
+                    setup(reference_dir.path()),
+                    &[],
+                    Some(input.as_bytes()),
+                    limits,
+                    &[],
+                    &active,
+                )
+                .await;
+            if active.is_killed() {
+                return StressResponse::killed(start_time);
+            }
+            if reference.limit_exceeded == Some(LimitExceeded::TimeBudget) {
+                time_budget_exhausted = true;
+                break;
+            }
+            if !reference.status.is_success() {
+                return StressResponse::error(
+                    format!(
+                        "The reference solution fails with status {} on the input of seed {}: {}",
+                        reference.status.as_str(),
+                        input_seed,
+                        reference.stderr
+                    ),
+                    start_time,
+                );
+            }
+            let outcome = self
+                .run_executable(
+                    &code_executable,
+                    setup(code_dir.path()),
+                    &[],
+                    Some(input.as_bytes()),
+                    limits,
+                    &[],
+                    &active,
+                )
+                .await;
+            if active.is_killed() {
+                return StressResponse::killed(start_time);
+            }
+            if outcome.limit_exceeded == Some(LimitExceeded::TimeBudget) {
+                time_budget_exhausted = true;
+                break;
+            }
+            let expected_output = String::from_utf8_lossy(&reference.stdout).into_owned();
+            let output = String::from_utf8_lossy(&outcome.stdout).into_owned();
+            if outcome.status.is_success() && comparison.matches(&output, &expected_output) {
+                continue;
+            }
+            mismatches += 1;
+            first_mismatch_at.get_or_insert(iterations);
+            counterexample = Some(Counterexample {
+                input,
+                seed: input_seed,
+                expected_output: omit_middle_lines(&expected_output, max_output_lines),
+                output: omit_middle_lines(&output, max_output_lines),
+                error: omit_middle_lines(&outcome.stderr, max_output_lines),
+                status: outcome.status,
+                exit_code: outcome.exit_code,
+            });
+        }
+
+        StressResponse {
+            found: counterexample.is_some(),
+            counterexample,
+            iterations,
+            mismatches,
+            seed,
+            time_budget_exhausted,
+            error: String::new(),
+            execution_time: start_time.elapsed().as_secs_f64(),
+            status: ExecutionStatus::Success,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/stress",
+    request_body = StressRequest,
+    responses((status = 200, description = "The shortest failing input found, if any", body = StressResponse))
+)]
+pub async fn stress(
+    req: StressRequest,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&executor.stress_test(req).await))
+}
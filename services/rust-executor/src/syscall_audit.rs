@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use utoipa::ToSchema;
+
+// Under target/, so it is never mistaken for an output file
+pub const TRACE_LOG: &str = "target/syscalls.strace";
+
+const MAX_LISTED: usize = 100;
+
+const NETWORK_SYSCALLS: &[&str] = &[
+    "socket",
+    "socketpair",
+    "connect",
+    "bind",
+    "listen",
+    "accept",
+    "accept4",
+    "sendto",
+    "recvfrom",
+    "sendmsg",
+    "recvmsg",
+    "sendmmsg",
+    "recvmmsg",
+    "getsockname",
+    "getpeername",
+    "setsockopt",
+    "getsockopt",
+    "shutdown",
+];
+
+const PROCESS_SYSCALLS: &[&str] = &[
+    "clone",
+    "clone3",
+    "fork",
+    "vfork",
+    "execve",
+    "execveat",
+    "wait4",
+    "waitid",
+    "exit",
+    "exit_group",
+    "kill",
+    "tkill",
+    "tgkill",
+];
+
+// Read by the dynamic loader and std before main; not something the
+// student wrote
+const STARTUP_PATH_PREFIXES: &[&str] = &[
+    "/etc/ld.so.",
+    "/lib/",
+    "/lib64/",
+    "/usr/lib/",
+    "/usr/lib64/",
+    "/proc/self/",
+    "/sys/devices/system/cpu",
+];
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct FileAccess {
+    pub path: String,
+    // e.g. ["openat", "unlink"]
+    pub syscalls: Vec<String>,
+}
+
+// What the program asked the kernel for, as seen by strace
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct SyscallAudit {
+    // Traced calls by category: "file", "network" and "process"
+    pub counts: BTreeMap<String, usize>,
+    // Paths the program opened, inspected, created or removed, in the order
+    // first used; loader and runtime startup reads are left out
+    pub files: Vec<FileAccess>,
+    // Network calls with their arguments, e.g.
+    // "connect(3, {sa_family=AF_INET, sin_port=htons(80), ...}, 16) = -1 EACCES"
+    pub network: Vec<String>,
+    // Programs started through execve, the submission itself excluded
+    pub executed: Vec<String>,
+}
+
+// Wraps a command so the program runs under strace, which follows every
+// process it starts and writes the trace to `log`
+pub fn traced_command(executable_path: &Path, log: &Path) -> Command {
+    let mut cmd = Command::new("strace");
+    cmd.args([
+        "-f",
+        "-qq",
+        "-s",
+        "4096",
+        "-e",
+        "trace=file,network,process",
+        "-o",
+    ])
+    .arg(log)
+    .arg("--")
+    .arg(executable_path);
+    cmd
+}
+
+// "1234 openat(AT_FDCWD, \"x\", O_RDONLY) = 3" -> ("openat", "(AT_FDCWD, ...")
+fn split_call(line: &str) -> Option<(&str, &str)> {
+    let line = line
+        .split_once(' ')
+        .filter(|(pid, _)| pid.chars().all(|c| c.is_ascii_digit()))
+        .map_or(line, |(_, rest)| rest)
+        .trim_start();
+    if let Some(resumed) = line.strip_prefix("<... ") {
+        let (name, rest) = resumed.split_once(" resumed>")?;
+        return Some((name, rest));
+    }
+    let open = line.find('(')?;
+    let name = &line[..open];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((name, &line[open..]))
+}
+
+// First quoted argument, the path for file syscalls
+fn first_string(args: &str) -> Option<&str> {
+    let start = args.find('"')? + 1;
+    let end = start + args[start..].find('"')?;
+    Some(&args[start..end])
+}
+
+pub fn summarize(log: &Path) -> Option<SyscallAudit> {
+    let trace = fs::read_to_string(log).ok()?;
+    let mut counts = BTreeMap::new();
+    let mut files: Vec<FileAccess> = Vec::new();
+    let mut network = Vec::new();
+    let mut seen_network = BTreeSet::new();
+    let mut executed = Vec::new();
+    let mut skipped_submission = false;
+
+    for line in trace.lines() {
+        let Some((name, args)) = split_call(line) else {
+            continue;
+        };
+        // Counted once, when the call starts
+        if line.contains("<... ") {
+            continue;
+        }
+        let category = if NETWORK_SYSCALLS.contains(&name) {
+            "network"
+        } else if PROCESS_SYSCALLS.contains(&name) {
+            "process"
+        } else {
+            "file"
+        };
+        *counts.entry(category.to_string()).or_insert(0) += 1;
+
+        match category {
+            "network" => {
+                let call = format!("{}{}", name, args);
+                if network.len() < MAX_LISTED && seen_network.insert(call.clone()) {
+                    network.push(call);
+                }
+            }
+            "process" if name.starts_with("execve") => {
+                // strace's own exec of the submission comes first
+                if !skipped_submission {
+                    skipped_submission = true;
+                    continue;
+                }
+                if let Some(path) = first_string(args) {
+                    if executed.len() < MAX_LISTED {
+                        executed.push(path.to_string());
+                    }
+                }
+            }
+            "file" => {
+                // fd-based calls like fstat pass an empty path
+                let Some(path) = first_string(args).filter(|path| !path.is_empty()) else {
+                    continue;
+                };
+                if STARTUP_PATH_PREFIXES
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix))
+                {
+                    continue;
+                }
+                let listed = files.len();
+                match files.iter_mut().find(|file| file.path == path) {
+                    Some(file) if !file.syscalls.iter().any(|syscall| syscall == name) => {
+                        file.syscalls.push(name.to_string());
+                    }
+                    Some(_) => {}
+                    None if listed < MAX_LISTED => files.push(FileAccess {
+                        path: path.to_string(),
+                        syscalls: vec![name.to_string()],
+                    }),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(SyscallAudit {
+        counts,
+        files,
+        network,
+        executed,
+    })
+}
@@ -0,0 +1,193 @@
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::{runtime, trace as sdktrace, Resource};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_stream::Stream;
+use warp::http::{HeaderMap, Request, Response};
+use warp::hyper::server::accept;
+use warp::hyper::service::{make_service_fn, service_fn, Service};
+use warp::hyper::{Body, Server};
+use warp::Filter;
+
+const TRACER_NAME: &str = "rust-executor";
+
+/// Exports spans over OTLP (gRPC) when OTEL_EXPORTER_OTLP_ENDPOINT or
+/// OTEL_EXPORTER_OTLP_TRACES_ENDPOINT is set; the exporter reads its other
+/// OTEL_* settings itself. W3C trace context (`traceparent`) is accepted on
+/// requests either way. Must be called inside the Tokio runtime.
+pub fn init() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let configured = [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|name| env::var(name).is_ok_and(|value| !value.is_empty()));
+    if !configured {
+        return;
+    }
+    let service_name =
+        env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "rust-executor".to_string());
+    let installed = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_trace_config(
+            sdktrace::Config::default()
+                .with_resource(Resource::new([KeyValue::new("service.name", service_name)])),
+        )
+        .install_batch(runtime::Tokio);
+    match installed {
+        Ok(provider) => {
+            global::set_tracer_provider(provider);
+            println!("Exporting traces over OTLP");
+        }
+        Err(e) => eprintln!("Trace export disabled: {}", e),
+    }
+}
+
+/// Exports the spans still buffered
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+// Serves a request in a server span, continuing the trace its headers
+// carry. The handler runs inside the span's context, so the spans it opens
+// become children of it.
+async fn traced<S>(mut service: S, req: Request<Body>) -> Result<Response<Body>, Infallible>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(format!("{} {}", req.method(), req.uri().path()))
+        .with_kind(SpanKind::Server)
+        .with_attributes([
+            KeyValue::new("http.request.method", req.method().to_string()),
+            KeyValue::new("url.path", req.uri().path().to_string()),
+        ])
+        .start_with_context(&tracer, &parent);
+    let cx = parent.with_span(span);
+
+    poll_fn(|task| service.poll_ready(task)).await?;
+    let response = service.call(req).with_context(cx.clone()).await?;
+    let span = cx.span();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        response.status().as_u16() as i64,
+    ));
+    if response.status().is_server_error() {
+        span.set_status(Status::error(response.status().to_string()));
+    }
+    Ok(response)
+}
+
+/// Serves the routes over HTTP like `warp::serve`, with a span per request
+pub async fn serve<F>(routes: F, addr: SocketAddr)
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+{
+    let service = warp::service(routes);
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| traced(service.clone(), req))) }
+    });
+    if let Err(e) = Server::bind(&addr).serve(make_service).await {
+        eprintln!("HTTP server failed: {}", e);
+    }
+}
+
+/// Like `serve`, over connections accepted elsewhere
+pub async fn serve_incoming<F, S, C>(routes: F, incoming: S)
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+    S: Stream<Item = io::Result<C>> + Send,
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = warp::service(routes);
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| traced(service.clone(), req))) }
+    });
+    if let Err(e) = Server::builder(accept::from_stream(incoming))
+        .serve(make_service)
+        .await
+    {
+        eprintln!("HTTP server failed: {}", e);
+    }
+}
+
+/// Runs `future` in a span named `name`, a child of the current one.
+/// Returns the span's context too, for attributes known only afterwards.
+pub async fn in_span<T>(name: &'static str, future: impl Future<Output = T>) -> (T, Context) {
+    let tracer = global::tracer(TRACER_NAME);
+    let cx = Context::current_with_span(tracer.start(name));
+    let output = future.with_context(cx.clone()).await;
+    (output, cx)
+}
+
+// Id of the trace the current span belongs to, if any
+pub fn current_trace_id() -> Option<String> {
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// The current trace context in W3C headers, as JSON, for handing to
+/// another process. None outside a trace.
+pub fn current_context() -> Option<String> {
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut headers)
+    });
+    (!headers.is_empty())
+        .then(|| serde_json::to_string(&headers).ok())
+        .flatten()
+}
+
+/// Context of a consumer span continuing the trace `context` came from
+/// (see `current_context`)
+pub fn consumer_context(
+    name: &'static str,
+    context: Option<&str>,
+    attributes: Vec<KeyValue>,
+) -> Context {
+    let headers: HashMap<String, String> = context
+        .and_then(|context| serde_json::from_str(context).ok())
+        .unwrap_or_default();
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&headers));
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(SpanKind::Consumer)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+    parent.with_span(span)
+}
@@ -0,0 +1,159 @@
+use crate::{telemetry, RustExecutor};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use warp::Filter;
+
+const DEFAULT_TLS_RELOAD_INTERVAL_SECONDS: u64 = 30;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, String> {
+    let open = |path: &str| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))
+    };
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate {}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", cert_path));
+    }
+    let key = rustls_pemfile::private_key(&mut open(key_path)?)
+        .map_err(|e| format!("Invalid private key {}: {}", key_path, e))?
+        .ok_or_else(|| format!("No private key found in {}", key_path))?;
+    let signing_key = ring::sign::any_supported_type(&key)
+        .map_err(|e| format!("Unsupported private key {}: {}", key_path, e))?;
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified
+        .keys_match()
+        .map_err(|e| format!("{} does not match {}: {}", key_path, cert_path, e))?;
+    Ok(certified)
+}
+
+// Hands every handshake the most recently loaded certificate, so a rotation
+// applies to new connections while open ones keep theirs
+#[derive(Debug)]
+struct ReloadingCert {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+// Paths and modification times of the files the current certificate was
+// loaded from
+type LoadedFrom = (String, String, Option<SystemTime>, Option<SystemTime>);
+
+fn current_files(executor: &RustExecutor) -> Option<LoadedFrom> {
+    let config = executor.config();
+    let cert_path = config.tls_cert_path.clone()?;
+    let key_path = config.tls_key_path.clone()?;
+    let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let (cert_modified, key_modified) = (modified(&cert_path), modified(&key_path));
+    Some((cert_path, key_path, cert_modified, key_modified))
+}
+
+// Polls every TLS_RELOAD_INTERVAL_SECONDS for replaced files or changed
+// paths (after a config reload). A pair that fails to load, e.g. the key
+// written before the certificate, keeps the previous one until the next
+// poll.
+async fn reload_periodically(
+    executor: RustExecutor,
+    resolver: Arc<ReloadingCert>,
+    mut loaded_from: LoadedFrom,
+) {
+    let interval = env::var("TLS_RELOAD_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(DEFAULT_TLS_RELOAD_INTERVAL_SECONDS);
+    let mut ticks = tokio::time::interval(Duration::from_secs(interval));
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let Some(files) = current_files(&executor) else {
+            continue;
+        };
+        if files == loaded_from {
+            continue;
+        }
+        match load_certified_key(&files.0, &files.1) {
+            Ok(certified) => {
+                *resolver.current.write().unwrap() = Arc::new(certified);
+                println!("TLS certificate reloaded from {}", files.0);
+                loaded_from = files;
+            }
+            Err(e) => eprintln!("TLS certificate reload failed, keeping current one: {}", e),
+        }
+    }
+}
+
+// Serves the routes over HTTPS. Handshakes run on their own tasks so a
+// slow or stalled client cannot hold up accepting other connections.
+pub async fn serve<F>(routes: F, addr: SocketAddr, executor: RustExecutor) -> Result<(), String>
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+{
+    let files = current_files(&executor).ok_or("TLS certificate paths are not set")?;
+    let certified = load_certified_key(&files.0, &files.1)?;
+    let resolver = Arc::new(ReloadingCert {
+        current: RwLock::new(Arc::new(certified)),
+    });
+    let mut tls_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Invalid TLS configuration: {}", e))?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    tls_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+    tokio::spawn(reload_periodically(executor, resolver, files));
+
+    let (connections, incoming) = mpsc::channel::<io::Result<TlsStream<TcpStream>>>(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let connections = connections.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = connections.send(Ok(stream)).await;
+                    }
+                    Ok(Err(e)) => eprintln!("TLS handshake failed: {}", e),
+                    Err(_) => eprintln!("TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    telemetry::serve_incoming(routes, ReceiverStream::new(incoming)).await;
+    Ok(())
+}
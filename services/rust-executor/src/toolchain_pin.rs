@@ -0,0 +1,83 @@
+use crate::reaper;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const TOOLCHAIN_FILE: &str = "rust-toolchain.toml";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// PINNED_RUST_TOOLCHAIN, a rustup channel such as "1.82.0". Every
+// generated project then carries a rust-toolchain.toml selecting it, so
+// replicas grade with the same compiler whichever toolchain their image
+// defaults to. The toolchain must be installed in the image; unset builds
+// with the host default.
+pub fn pinned() -> Option<&'static str> {
+    static PINNED: OnceLock<Option<String>> = OnceLock::new();
+    PINNED
+        .get_or_init(|| {
+            env::var("PINNED_RUST_TOOLCHAIN")
+                .ok()
+                .map(|channel| channel.trim().to_string())
+                .filter(|channel| !channel.is_empty())
+        })
+        .as_deref()
+}
+
+// Written next to the Cargo.toml of every generated project
+pub fn write(project_path: &Path) -> Result<(), String> {
+    let Some(channel) = pinned() else {
+        return Ok(());
+    };
+    fs::write(
+        project_path.join(TOOLCHAIN_FILE),
+        format!("[toolchain]\nchannel = \"{}\"\n", channel),
+    )
+    .map_err(|e| format!("Failed to create {}: {}", TOOLCHAIN_FILE, e))
+}
+
+// `rustc --version` as a generated project sees it, pin applied. rustup is
+// kept from downloading a missing pinned toolchain, which would only hide
+// that the image lacks it.
+pub async fn project_rustc() -> Result<String, String> {
+    let dir = reaper::temp_dir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    write(dir.path())?;
+    let output = timeout(
+        PROBE_TIMEOUT,
+        tokio::process::Command::new("rustc")
+            .arg("--version")
+            .current_dir(dir.path())
+            .env("RUSTUP_AUTO_INSTALL", "0")
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| "rustc --version timed out".to_string())?
+    .map_err(|e| format!("rustc not available: {}", e))?;
+    if !output.status.success() {
+        // rustup's first line says what is wrong; a backtrace may follow
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or_default().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Why the compiler projects get is not the pinned one, if it is not. Only
+// version channels ("1.82.0", "1.82") can be compared; "stable" or a
+// nightly date cannot drift by definition.
+pub fn drift(project_rustc: &str) -> Option<String> {
+    let channel = pinned()?;
+    if !channel.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let release = project_rustc.split_whitespace().nth(1).unwrap_or_default();
+    let matches = release == channel || release.starts_with(&format!("{}.", channel));
+    (!matches).then(|| {
+        format!(
+            "projects build with {} instead of the pinned {}",
+            project_rustc, channel
+        )
+    })
+}
@@ -0,0 +1,180 @@
+use crate::status::{ExecutionStatus, StatusError};
+use crate::RustExecutor;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use std::collections::BTreeSet;
+use std::env;
+
+// Splits an attribute's arguments at its top-level commas
+fn split_args(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut args = vec![Vec::new()];
+    for token in tokens {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => args.push(Vec::new()),
+            _ => args.last_mut().unwrap().push(token),
+        }
+    }
+    args.retain(|arg| !arg.is_empty());
+    args
+}
+
+// The features an attribute body (what is between `#![` and `]`) enables,
+// looking through `cfg_attr(predicate, ...)` whatever its predicate
+fn attribute_features(body: &[TokenTree], features: &mut BTreeSet<String>) {
+    let [TokenTree::Ident(name), TokenTree::Group(args)] = body else {
+        return;
+    };
+    if args.delimiter() != Delimiter::Parenthesis {
+        return;
+    }
+    if name == "feature" {
+        for arg in split_args(args.stream()) {
+            features.extend(arg.iter().map(|token| token.to_string()));
+        }
+    } else if name == "cfg_attr" {
+        for attribute in split_args(args.stream()).iter().skip(1) {
+            attribute_features(attribute, features);
+        }
+    }
+}
+
+// Inner attributes anywhere in the source count, not only at the crate
+// root; rustc ignores the others, so rejecting them costs nothing
+fn collect_features(tokens: TokenStream, features: &mut BTreeSet<String>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenTree::Group(group) = token else {
+            continue;
+        };
+        let inner_attribute = group.delimiter() == Delimiter::Bracket
+            && i >= 2
+            && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '!')
+            && matches!(&tokens[i - 2], TokenTree::Punct(p) if p.as_char() == '#');
+        if inner_attribute {
+            let body: Vec<TokenTree> = group.stream().into_iter().collect();
+            attribute_features(&body, features);
+        } else {
+            collect_features(group.stream(), features);
+        }
+    }
+}
+
+// The `#![feature(...)]` names the sources enable. Sources that do not
+// tokenize are left to the compiler.
+pub fn requested_features<'a>(sources: impl Iterator<Item = &'a str>) -> BTreeSet<String> {
+    let mut features = BTreeSet::new();
+    for source in sources {
+        if let Ok(tokens) = source.parse::<TokenStream>() {
+            collect_features(tokens, &mut features);
+        }
+    }
+    features
+}
+
+impl RustExecutor {
+    // Whether submissions can enable unstable features: projects build with
+    // a nightly (PINNED_RUST_TOOLCHAIN or the host default), or
+    // RUSTC_BOOTSTRAP makes a stable compiler act as one
+    pub fn nightly_mode(&self) -> bool {
+        env::var_os("RUSTC_BOOTSTRAP").is_some_and(|value| value != "0")
+            || self
+                .toolchain
+                .project_rustc
+                .as_deref()
+                .is_some_and(|rustc| rustc.contains("-nightly") || rustc.contains("-dev"))
+    }
+
+    // In nightly mode only the features in allowed_features may be enabled;
+    // the others (asm, lang_items, ...) reach past what the sandbox and the
+    // other checks assume a program can do. A stable compiler rejects every
+    // `#![feature]` itself.
+    pub fn check_unstable_features<'a>(
+        &self,
+        sources: impl Iterator<Item = &'a str>,
+    ) -> Result<(), StatusError> {
+        if !self.nightly_mode() {
+            return Ok(());
+        }
+        let config = self.config();
+        let denied: Vec<String> = requested_features(sources)
+            .into_iter()
+            .filter(|feature| !config.allowed_features.contains(feature))
+            .collect();
+        if denied.is_empty() {
+            return Ok(());
+        }
+        let message = format!(
+            "Unstable feature{} not allowed: {}; {}",
+            if denied.len() == 1 { " is" } else { "s are" },
+            denied.join(", "),
+            if config.allowed_features.is_empty() {
+                "no unstable features are allowed".to_string()
+            } else {
+                format!("allowed features: {}", config.allowed_features.join(", "))
+            }
+        );
+        Err(StatusError::new(
+            ExecutionStatus::SecurityViolation,
+            message,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(source: &str) -> Vec<String> {
+        requested_features(std::iter::once(source))
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn feature_attributes_are_collected() {
+        assert_eq!(
+            features("#![feature(never_type, box_patterns)]\n#![feature(asm)]\nfn main() {}"),
+            vec!["asm", "box_patterns", "never_type"]
+        );
+    }
+
+    #[test]
+    fn cfg_attr_is_looked_through_whatever_its_predicate() {
+        assert_eq!(features("#![cfg_attr(test, feature(test))]"), vec!["test"]);
+        assert_eq!(
+            features("#![cfg_attr(all(unix, not(miri)), feature(a), feature(b, c))]"),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            features("#![cfg_attr(any(), cfg_attr(debug_assertions, feature(nested)))]"),
+            vec!["nested"]
+        );
+    }
+
+    #[test]
+    fn inner_attributes_below_the_crate_root_count() {
+        assert_eq!(
+            features("mod m { #![feature(lang_items)] }\nfn main() {}"),
+            vec!["lang_items"]
+        );
+    }
+
+    #[test]
+    fn other_attributes_strings_and_comments_are_ignored() {
+        let source = "#![allow(dead_code)]\n\
+                      #[cfg_attr(test, feature(outer))]\n\
+                      // #![feature(commented)]\n\
+                      fn main() { println!(\"#![feature(quoted)]\"); }";
+        assert!(features(source).is_empty());
+    }
+
+    #[test]
+    fn sources_that_do_not_tokenize_are_skipped() {
+        let sources = ["fn main() { \"unterminated }", "#![feature(rest)]"];
+        assert_eq!(
+            requested_features(sources.into_iter())
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["rest"]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::RustExecutor;
+use serde::Serialize;
+use utoipa::ToSchema;
+use warp::http::StatusCode;
+
+// What DELETE /users/{id}/data removed. A count is null when that store is
+// not enabled on this instance.
+#[derive(Serialize, ToSchema)]
+pub struct DeletionReport {
+    #[serde(rename = "userId")]
+    user_id: String,
+    // Rows of the execution history (/executions)
+    executions: Option<u64>,
+    // Compiled binaries and their stored run output
+    artifacts: Option<usize>,
+    #[serde(rename = "auditEntries")]
+    audit_entries: Option<u64>,
+    // Jobs submitted through POST /jobs, with their code and results
+    jobs: Option<u64>,
+    // Idempotency-Key results held in memory
+    #[serde(rename = "cachedResults")]
+    cached_results: usize,
+    // Stores that could not be purged; the request can be repeated
+    errors: Vec<String>,
+}
+
+fn collect<T>(result: Result<T, String>, errors: &mut Vec<String>) -> Option<T> {
+    result.map_err(|e| errors.push(e)).ok()
+}
+
+impl RustExecutor {
+    // Erases everything stored under a requester id (the X-Requester-Id
+    // header), for GDPR and FERPA erasure requests
+    pub async fn delete_user_data(&self, user_id: &str) -> DeletionReport {
+        let mut errors = Vec::new();
+        let executions = match &self.history {
+            Some(history) => collect(history.delete_for(user_id).await, &mut errors),
+            None => None,
+        };
+        let audit_entries = match &self.audit_log {
+            Some(audit_log) => collect(audit_log.delete_for(user_id).await, &mut errors),
+            None => None,
+        };
+        let artifacts = collect(self.artifacts.delete_owned_by(user_id).await, &mut errors);
+        let jobs = match &self.job_queue {
+            Some(queue) => collect(queue.delete_submitted_by(user_id).await, &mut errors),
+            None => None,
+        };
+        DeletionReport {
+            user_id: user_id.to_string(),
+            executions,
+            artifacts,
+            audit_entries,
+            jobs,
+            cached_results: self.idempotency.forget(user_id),
+            errors,
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/data",
+    params(
+        ("id" = String, Path, description = "Requester id as sent in X-Requester-Id"),
+        ("Authorization" = String, Header, description = "Bearer <ADMIN_TOKEN>")
+    ),
+    responses(
+        (status = 200, description = "Everything stored for the user was deleted", body = DeletionReport),
+        (status = 401, body = ErrorResponse),
+        (status = 502, description = "Some stores could not be purged; see `errors`", body = DeletionReport)
+    )
+)]
+pub async fn delete_user_data(
+    user_id: String,
+    authorization: Option<String>,
+    executor: RustExecutor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = executor.authorize_admin(authorization.as_deref()) {
+        return Ok(reply);
+    }
+    let report = executor.delete_user_data(&user_id).await;
+    println!(
+        "Data of user {} deleted by an administrator ({} errors)",
+        user_id,
+        report.errors.len()
+    );
+    let status = if report.errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&report), status))
+}
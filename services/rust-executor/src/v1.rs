@@ -0,0 +1,55 @@
+use crate::ExecutionStatus;
+use serde_json::Value;
+use warp::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use warp::hyper::body::{self, Body};
+use warp::reply::Response;
+use warp::Reply;
+
+// The v1 compatibility shim. /v1 and the unprefixed paths are served by the
+// current handlers, and their JSON replies are rewritten into what v1
+// clients were written against: statuses the taxonomy split out of "error"
+// are reported as "error" again, and the structured `diagnostics` are left
+// out.
+pub async fn downgrade<R: Reply>(reply: R) -> Result<Response, warp::Rejection> {
+    let response = reply.into_response();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/json");
+    if !is_json {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = body::to_bytes(body).await.unwrap_or_default();
+    let downgraded = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut value) => {
+            downgrade_value(&mut value);
+            serde_json::to_vec(&value).unwrap_or_default()
+        }
+        Err(_) => bytes.to_vec(),
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, Body::from(downgraded)))
+}
+
+// Statuses nest, e.g. in job results and judge test results; other
+// "status" fields (job states, test verdicts) are not ExecutionStatus
+// values and stay as they are
+fn downgrade_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.remove("diagnostics");
+            for (key, field) in fields.iter_mut() {
+                let status = (key == "status")
+                    .then(|| serde_json::from_value::<ExecutionStatus>(field.clone()).ok())
+                    .flatten();
+                match status {
+                    Some(status) => *field = Value::from(status.v1_name()),
+                    None => downgrade_value(field),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(downgrade_value),
+        _ => {}
+    }
+}
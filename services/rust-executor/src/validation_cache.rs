@@ -0,0 +1,131 @@
+use crate::{CodeValidationResponse, RustExecutor};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+const DEFAULT_VALIDATION_CACHE_TTL_SECONDS: u64 = 600;
+const DEFAULT_VALIDATION_CACHE_SIZE: usize = 1000;
+
+type CachedValidation = Arc<OnceCell<CodeValidationResponse>>;
+
+struct Entry {
+    created: Instant,
+    result: CachedValidation,
+}
+
+// /validate results keyed by their ETag, a hash of the code and the rustc
+// that checked it. Editors revalidate unchanged code on every pause in
+// typing; those requests are answered from here, and a revalidation that
+// arrives while the first check is still running waits for it. Entries
+// expire after VALIDATION_CACHE_TTL_SECONDS; beyond VALIDATION_CACHE_SIZE
+// the oldest is dropped.
+pub struct ValidationCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+// A failure cargo reported without diagnostics (a timeout, a broken
+// toolchain) says nothing about the code and may not happen again
+fn cacheable(result: &CodeValidationResponse) -> bool {
+    result.is_valid || !result.diagnostics.is_empty()
+}
+
+impl ValidationCache {
+    pub fn new() -> Self {
+        let ttl_seconds = env::var("VALIDATION_CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_VALIDATION_CACHE_TTL_SECONDS);
+        let max_entries = env::var("VALIDATION_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(DEFAULT_VALIDATION_CACHE_SIZE);
+        Self {
+            ttl: Duration::from_secs(ttl_seconds),
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn slot(&self, etag: &str) -> CachedValidation {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.created.elapsed() < self.ttl);
+        if !entries.contains_key(etag) {
+            while entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.created)
+                    .map(|(etag, _)| etag.clone());
+                match oldest {
+                    Some(etag) => entries.remove(&etag),
+                    None => break,
+                };
+            }
+        }
+        entries
+            .entry(etag.to_string())
+            .or_insert_with(|| Entry {
+                created: Instant::now(),
+                result: CachedValidation::default(),
+            })
+            .result
+            .clone()
+    }
+
+    fn forget(&self, etag: &str) {
+        self.entries.lock().unwrap().remove(etag);
+    }
+}
+
+impl RustExecutor {
+    // Quoted, as the ETag header carries it
+    pub fn validation_etag(&self, code: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            self.toolchain
+                .project_rustc
+                .as_deref()
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        hasher.update([0]);
+        hasher.update(code.as_bytes());
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("\"{}\"", hash)
+    }
+
+    // validate_syntax through the cache. Returns the result with its ETag,
+    // None when the result is not cached and must not be reused.
+    pub async fn validate_cached(&self, code: String) -> (CodeValidationResponse, Option<String>) {
+        let etag = self.validation_etag(&code);
+        let slot = self.validations.slot(&etag);
+        let result = slot
+            .get_or_init(|| self.validate_syntax(code))
+            .await
+            .clone();
+        if cacheable(&result) {
+            (result, Some(etag))
+        } else {
+            self.validations.forget(&etag);
+            (result, None)
+        }
+    }
+}
+
+// Whether an If-None-Match header lists `etag`. "*" is not honored: it
+// would answer 304 for code that was never checked.
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate.strip_prefix("W/").unwrap_or(candidate) == etag)
+}
@@ -0,0 +1,119 @@
+use crate::{CodeExecutionRequest, RustExecutor};
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+const WARMUP_PROGRAM: &str = r#"fn main() {
+    println!("warm-up");
+}"#;
+
+#[derive(Clone)]
+pub enum WarmupStatus {
+    Running,
+    Completed { seconds: f64 },
+    Failed(String),
+    // STARTUP_WARMUP=false
+    Skipped,
+}
+
+pub type WarmupState = Arc<RwLock<WarmupStatus>>;
+
+pub fn enabled() -> bool {
+    !matches!(
+        env::var("STARTUP_WARMUP").as_deref(),
+        Ok("0" | "false" | "no")
+    )
+}
+
+impl WarmupStatus {
+    // The readiness check for the warm-up: only a completed one is ok, so a
+    // broken toolchain keeps the instance out of rotation
+    pub fn check(&self) -> (bool, String) {
+        match self {
+            WarmupStatus::Running => (false, "warm-up compilation in progress".to_string()),
+            WarmupStatus::Completed { seconds } => {
+                (true, format!("warm-up compilation took {:.1}s", seconds))
+            }
+            WarmupStatus::Failed(e) => (false, format!("warm-up compilation failed: {}", e)),
+            WarmupStatus::Skipped => (true, "warm-up disabled".to_string()),
+        }
+    }
+}
+
+impl RustExecutor {
+    // Compiles and runs a hello-world through the same path as /execute, so
+    // the first real submission after a deploy does not pay for loading the
+    // toolchain and filling the disk cache
+    pub async fn warm_up(&self) {
+        if !enabled() {
+            *self.warmup.write().unwrap() = WarmupStatus::Skipped;
+            return;
+        }
+        println!("Running warm-up compilation");
+        let start = Instant::now();
+        let active = self.track_execution("warmup", None);
+        let request = CodeExecutionRequest {
+            language: None,
+            code: WARMUP_PROGRAM.to_string(),
+            files: None,
+            dependencies: None,
+            input_data: None,
+            timeout: None,
+            cpu_time_limit: None,
+            deterministic: None,
+            seed: None,
+            env: None,
+            input_encoding: None,
+            output_encoding: None,
+            priority: None,
+            profile: None,
+            opt_level: None,
+            overflow_checks: None,
+            wrap_mode: None,
+            template: None,
+            required_signatures: None,
+            rules: None,
+            forbid_unsafe: None,
+            strict: None,
+            lints: None,
+            data_files: None,
+            ansi: None,
+            audit_syscalls: None,
+            close_stdin: None,
+            compile_timeout: None,
+        };
+        let outcome = match self.prepare_execution(request) {
+            Ok(prepared) => {
+                let outcome = self
+                    .compile_and_run(
+                        prepared.temp_dir.path(),
+                        None,
+                        None,
+                        prepared.limits(),
+                        &prepared.env,
+                        prepared.build,
+                        &active,
+                    )
+                    .await;
+                if outcome.status.is_success() {
+                    Ok(())
+                } else {
+                    Err(outcome.stderr)
+                }
+            }
+            Err(e) => Err(e.message),
+        };
+        let status = match outcome {
+            Ok(()) => {
+                let seconds = start.elapsed().as_secs_f64();
+                println!("Warm-up compilation finished in {:.1}s", seconds);
+                WarmupStatus::Completed { seconds }
+            }
+            Err(e) => {
+                eprintln!("Warm-up compilation failed: {}", e);
+                WarmupStatus::Failed(e.trim().to_string())
+            }
+        };
+        *self.warmup.write().unwrap() = status;
+    }
+}
@@ -0,0 +1,419 @@
+use crate::properties::mentions_ident;
+use crate::repl::item_name;
+use crate::status::{ExecutionStatus, StatusError};
+use crate::RustExecutor;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::HashSet;
+use std::ops::Range;
+use syn::visit::{self, Visit};
+use syn::{Item, UseTree};
+
+// Imported for every submission unless the code binds the same name itself
+const PRELUDE_IMPORTS: &[&str] = &[
+    "std::io",
+    "std::io::prelude::*",
+    "std::collections::HashMap",
+    "std::collections::HashSet",
+    "std::collections::BTreeMap",
+    "std::collections::BTreeSet",
+    "std::collections::VecDeque",
+    "std::time::Duration",
+    "std::time::Instant",
+    "std::thread",
+];
+
+// Submissions build without external crates, so async code runs on this
+// single-threaded executor in place of #[tokio::main]
+const BLOCK_ON: &str = r#"
+fn __scriptsmith_block_on<F: std::future::Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    let mut future = std::pin::pin!(future);
+    let waker = std::sync::Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = std::task::Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(output) => return output,
+            std::task::Poll::Pending => std::thread::park(),
+        }
+    }
+}
+"#;
+
+// The user's `async fn main` is renamed to this; same length as `main` so
+// columns and byte offsets in the rest of the code stay put
+const ASYNC_MAIN: &str = "_am_";
+
+// How a submission is turned into src/main.rs
+#[derive(Clone, Copy, Default)]
+pub enum WrapMode {
+    // Decided from the code: programs keep their own `main`, items without
+    // one (libraries, test-only files) get an empty one and statements are
+    // wrapped into one
+    #[default]
+    Auto,
+    // Always placed inside a generated `main`
+    Wrap,
+    // Compiled exactly as submitted, without added imports
+    Raw,
+}
+
+impl WrapMode {
+    pub fn parse(mode: Option<&str>) -> Result<Self, String> {
+        match mode {
+            None | Some("auto") => Ok(WrapMode::Auto),
+            Some("wrap") => Ok(WrapMode::Wrap),
+            Some("raw") => Ok(WrapMode::Raw),
+            Some(other) => Err(format!("Unsupported wrapMode '{}'", other)),
+        }
+    }
+}
+
+enum Shape {
+    // A file of items with a `main`
+    Program,
+    // `async fn main`, possibly under #[tokio::main] or #[async_std::main]
+    AsyncProgram(AsyncMain),
+    // A file of items without `main`
+    Items,
+    // Statements and expressions; `awaits` when they use `.await`
+    Snippet { awaits: bool },
+}
+
+// Byte ranges of an `async fn main` to rewrite
+struct AsyncMain {
+    ident: Range<usize>,
+    // Runtime attributes like #[tokio::main], blanked out
+    attributes: Vec<Range<usize>>,
+    // e.g. "-> Result<(), String>"; empty for `()`
+    output: String,
+}
+
+impl AsyncMain {
+    fn new(main: &syn::ItemFn) -> Self {
+        let attributes = main
+            .attrs
+            .iter()
+            .filter(|attr| {
+                attr.path()
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "main")
+            })
+            .map(|attr| {
+                attr.pound_token.span.byte_range().start
+                    ..attr.bracket_token.span.close().byte_range().end
+            })
+            .collect();
+        Self {
+            ident: main.sig.ident.span().byte_range(),
+            attributes,
+            output: main.sig.output.to_token_stream().to_string(),
+        }
+    }
+
+    // The code with `main` renamed and runtime attributes replaced by
+    // spaces, plus a `main` driving it
+    fn rewrite(&self, code: &str) -> Option<String> {
+        let mut rewritten = code.to_string();
+        for range in &self.attributes {
+            let blank = " ".repeat(code.get(range.clone())?.len());
+            rewritten.replace_range(range.clone(), &blank);
+        }
+        rewritten
+            .get(self.ident.clone())
+            .filter(|ident| *ident == "main")?;
+        rewritten.replace_range(self.ident.clone(), ASYNC_MAIN);
+        rewritten.push_str(&format!(
+            "\nfn main() {} {{\n    __scriptsmith_block_on({}())\n}}\n",
+            self.output, ASYNC_MAIN
+        ));
+        Some(rewritten)
+    }
+}
+
+#[derive(Default)]
+struct AwaitFinder {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for AwaitFinder {
+    fn visit_expr_await(&mut self, node: &'ast syn::ExprAwait) {
+        self.found = true;
+        visit::visit_expr_await(self, node);
+    }
+}
+
+fn snippet_awaits(code: &str) -> bool {
+    match syn::parse_str::<syn::Block>(&format!("{{\n{}\n}}", code)) {
+        Ok(block) => {
+            let mut finder = AwaitFinder::default();
+            finder.visit_block(&block);
+            finder.found
+        }
+        Err(_) => code.contains(".await"),
+    }
+}
+
+fn shape(code: &str) -> Shape {
+    if let Ok(file) = syn::parse_file(code) {
+        let main = file.items.iter().find_map(|item| match item {
+            Item::Fn(f) if f.sig.ident == "main" => Some(f),
+            _ => None,
+        });
+        if let Some(main) = main.filter(|main| main.sig.asyncness.is_some()) {
+            return Shape::AsyncProgram(AsyncMain::new(main));
+        }
+        let has_main = main.is_some();
+        // `println!("hi");` parses as a macro item but is meant as a statement
+        let has_statement_macro = file.items.iter().any(|item| {
+            matches!(item, Item::Macro(m) if m.ident.is_none() && !m.mac.path.is_ident("macro_rules"))
+        });
+        return match (has_main, has_statement_macro) {
+            (true, _) => Shape::Program,
+            (false, false) => Shape::Items,
+            (false, true) => Shape::Snippet {
+                awaits: snippet_awaits(code),
+            },
+        };
+    }
+    // Code with syntax errors is wrapped unless a line declares `main`, so
+    // the compiler still reports the errors where the student expects them
+    let declares_main = code.lines().any(|line| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("pub ").unwrap_or(line);
+        let line = line.strip_prefix("async ").unwrap_or(line);
+        line.starts_with("fn main(") || line.starts_with("fn main (")
+    });
+    if declares_main {
+        Shape::Program
+    } else {
+        Shape::Snippet {
+            awaits: snippet_awaits(code),
+        }
+    }
+}
+
+fn use_tree_names(tree: &UseTree, parent: Option<&syn::Ident>, names: &mut HashSet<String>) {
+    match tree {
+        UseTree::Path(path) => use_tree_names(&path.tree, Some(&path.ident), names),
+        UseTree::Name(name) if name.ident == "self" => {
+            if let Some(parent) = parent {
+                names.insert(parent.to_string());
+            }
+        }
+        UseTree::Name(name) => {
+            names.insert(name.ident.to_string());
+        }
+        UseTree::Rename(rename) => {
+            names.insert(rename.rename.to_string());
+        }
+        UseTree::Glob(_) => {}
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                use_tree_names(tree, parent, names);
+            }
+        }
+    }
+}
+
+// Names the code binds at the top level, through `use` or its own items
+fn top_level_names(code: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let Ok(file) = syn::parse_file(code) else {
+        return names;
+    };
+    for item in &file.items {
+        match item {
+            Item::Use(item) => use_tree_names(&item.tree, None, &mut names),
+            item => names.extend(item_name(item)),
+        }
+    }
+    names
+}
+
+// Prelude imports the code does not already provide. They go after the
+// code: `use` items may appear anywhere in a module, and this keeps inner
+// attributes such as `#![allow(...)]` first and user line numbers unchanged.
+fn appended_imports(code: &str) -> String {
+    let bound = top_level_names(code);
+    let mut imports = String::new();
+    for path in PRELUDE_IMPORTS {
+        let name = path.rsplit("::").next().unwrap_or(path);
+        if name == "*" || !bound.contains(name) {
+            imports.push_str(&format!("#[allow(unused_imports)]\nuse {};\n", path));
+        }
+    }
+    imports
+}
+
+// Statements run inside a generated `main`; their own `use` statements
+// shadow these imports instead of conflicting with them. Code using `.await`
+// runs as an async block on the injected executor.
+fn wrap_in_main(code: &str, awaits: bool) -> String {
+    let (open_async, close_async) = if awaits {
+        ("__scriptsmith_block_on(async {\n", "\n})")
+    } else {
+        ("", "")
+    };
+    let mut wrapped = format!(
+        r#"use std::io;
+use std::io::prelude::*;
+use std::collections::{{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque}};
+use std::time::{{Duration, Instant}};
+use std::thread;
+
+fn main() {{
+    // User code wrapper
+    let result = std::panic::catch_unwind(|| {{
+        // User code starts here
+{}{}{}
+    }});
+
+    match result {{
+        Ok(_) => {{}}
+        Err(e) => {{
+            if let Some(s) = e.downcast_ref::<&str>() {{
+                eprintln!("Error: {{}}", s);
+            }} else if let Some(s) = e.downcast_ref::<String>() {{
+                eprintln!("Error: {{}}", s);
+            }} else {{
+                eprintln!("Error: panic occurred");
+            }}
+            std::process::exit(1);
+        }}
+    }}
+}}"#,
+        open_async, code, close_async
+    );
+    if awaits {
+        wrapped.push_str(BLOCK_ON);
+    }
+    wrapped
+}
+
+// Generated src/main.rs for a submission; the user's code appears in it
+// verbatim, except for an `async fn main` which is renamed in place
+pub fn wrap(code: &str, mode: WrapMode) -> String {
+    let shape = match mode {
+        WrapMode::Raw => return code.to_string(),
+        WrapMode::Wrap => Shape::Snippet {
+            awaits: snippet_awaits(code),
+        },
+        WrapMode::Auto => shape(code),
+    };
+    match shape {
+        Shape::Snippet { awaits } => wrap_in_main(code, awaits),
+        Shape::Program => format!("{}\n{}", code, appended_imports(code)),
+        Shape::AsyncProgram(async_main) => match async_main.rewrite(code) {
+            Some(rewritten) => format!("{}\n{}{}", rewritten, appended_imports(code), BLOCK_ON),
+            None => format!("{}\n{}", code, appended_imports(code)),
+        },
+        Shape::Items => format!("{}\n{}\nfn main() {{}}\n", code, appended_imports(code)),
+    }
+}
+
+// Marks where a request's template takes the student's code
+pub const STUDENT_CODE_PLACEHOLDER: &str = "{{STUDENT_CODE}}";
+
+// Generated src/main.rs for a submission with a request template: the
+// template as given, with the user's code verbatim in place of the
+// placeholder. The template supplies everything else, `main` included.
+pub fn apply_template(template: &str, code: &str) -> Result<String, String> {
+    match template.matches(STUDENT_CODE_PLACEHOLDER).count() {
+        1 => Ok(template.replacen(STUDENT_CODE_PLACEHOLDER, code, 1)),
+        0 => Err(format!(
+            "template must contain the {} placeholder",
+            STUDENT_CODE_PLACEHOLDER
+        )),
+        _ => Err(format!(
+            "template must contain the {} placeholder only once",
+            STUDENT_CODE_PLACEHOLDER
+        )),
+    }
+}
+
+// Attribute that makes any `unsafe` block, fn or impl a compile error
+const FORBID_UNSAFE: &str = "#![forbid(unsafe_code)]";
+
+// Lint levels that would relax the forbid, were rustc to accept them
+const UNSAFE_OVERRIDES: &[&str] = &["allow", "warn", "expect"];
+
+// The generated src/main.rs with unsafe code forbidden crate-wide. It goes
+// on the first line, ahead of the user's own inner attributes; user line
+// numbers are mapped after this.
+pub fn forbid_unsafe(generated: &str) -> String {
+    format!("{}\n{}", FORBID_UNSAFE, generated)
+}
+
+// `#[allow(unsafe_code)]` and the like, anywhere in the token stream,
+// including inside `cfg_attr`
+fn overrides_unsafe_lint(tokens: TokenStream) -> bool {
+    let mut after_hash = false;
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                after_hash = true;
+                continue;
+            }
+            TokenTree::Punct(punct) if after_hash && punct.as_char() == '!' => continue,
+            TokenTree::Group(group) => {
+                let attribute = after_hash && group.delimiter() == Delimiter::Bracket;
+                if attribute
+                    && mentions_ident(group.stream(), "unsafe_code")
+                    && UNSAFE_OVERRIDES
+                        .iter()
+                        .any(|level| mentions_ident(group.stream(), level))
+                {
+                    return true;
+                }
+                if overrides_unsafe_lint(group.stream()) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        after_hash = false;
+    }
+    false
+}
+
+// rustc already refuses to lower a forbidden lint, but its E0453 says
+// little to a student; this rejects the attempt up front with a clear
+// message. Sources that do not tokenize are left to the compiler.
+pub fn check_unsafe_not_allowed<'a>(sources: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    for source in sources {
+        if let Ok(tokens) = source.parse::<TokenStream>() {
+            if overrides_unsafe_lint(tokens) {
+                return Err(
+                    "unsafe code is forbidden for this submission; `unsafe_code` cannot be allowed"
+                        .to_string(),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+impl RustExecutor {
+    // forbid_unsafe_code for the builds that do not go through
+    // prepare_execution (/compare, /coverage, sessions, ...): whether their
+    // crate root gets forbid_unsafe, after rejecting sources that try to
+    // allow unsafe code again
+    pub fn forbids_unsafe<'a>(
+        &self,
+        sources: impl Iterator<Item = &'a str>,
+    ) -> Result<bool, StatusError> {
+        if !self.config().forbid_unsafe_code {
+            return Ok(false);
+        }
+        check_unsafe_not_allowed(sources)
+            .map_err(|e| StatusError::new(ExecutionStatus::SecurityViolation, e))?;
+        Ok(true)
+    }
+}